@@ -2,6 +2,10 @@ use criterion::BenchmarkId;
 use criterion::{criterion_group, criterion_main, Criterion};
 
 use std::fs::File;
+use std::io::Cursor;
+
+use bytes::Bytes;
+use mp4::{AacConfig, Mp4Config, Mp4Reader, Mp4Sample, Mp4Writer, TrackConfig};
 
 fn read_mp4(filename: &str) -> u64 {
     let f = File::open(filename).unwrap();
@@ -10,6 +14,67 @@ fn read_mp4(filename: &str) -> u64 {
     m.size()
 }
 
+fn read_samples(filename: &str) -> u64 {
+    let f = File::open(filename).unwrap();
+    let mut m = mp4::read_mp4(f).unwrap();
+
+    let mut total = 0;
+    for track_id in m.tracks().keys().copied().collect::<Vec<u32>>() {
+        let sample_count = m.sample_count(track_id).unwrap();
+        for sample_id in 1..=sample_count {
+            if let Some(sample) = m.read_sample(track_id, sample_id).unwrap() {
+                total += sample.bytes.len() as u64;
+            }
+        }
+    }
+    total
+}
+
+/// An AAC track whose sample durations alternate, so `stts` has one entry
+/// per sample.
+fn many_stts_entries(sample_count: u32) -> Vec<u8> {
+    let config = Mp4Config {
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        ..Default::default()
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::new()), &config).unwrap();
+    writer
+        .add_track(&TrackConfig::from(AacConfig::default()))
+        .unwrap();
+    let mut start_time = 0;
+    for sample_id in 0..sample_count {
+        let duration = 1024 + sample_id % 2;
+        writer
+            .write_sample(
+                1,
+                &Mp4Sample {
+                    start_time,
+                    duration,
+                    rendering_offset: 0,
+                    is_sync: true,
+                    bytes: Bytes::from_static(&[0; 4]),
+                },
+            )
+            .unwrap();
+        start_time += duration as u64;
+    }
+    writer.write_end().unwrap();
+    writer.into_writer().into_inner()
+}
+
+fn read_sample_times(data: &[u8]) -> u64 {
+    let mut m = Mp4Reader::read_header(Cursor::new(data), data.len() as u64).unwrap();
+
+    let mut total = 0;
+    let sample_count = m.sample_count(1).unwrap();
+    for sample_id in 1..=sample_count {
+        if let Some(sample) = m.read_sample(1, sample_id).unwrap() {
+            total += sample.start_time;
+        }
+    }
+    total
+}
+
 fn criterion_benchmark(c: &mut Criterion) {
     let filename = "tests/samples/minimal.mp4";
 
@@ -20,6 +85,19 @@ fn criterion_benchmark(c: &mut Criterion) {
             b.iter(|| read_mp4(s));
         },
     );
+
+    c.bench_with_input(
+        BenchmarkId::new("read_samples", filename),
+        &filename,
+        |b, &s| {
+            b.iter(|| read_samples(s));
+        },
+    );
+
+    let data = many_stts_entries(10_000);
+    c.bench_function("read_sample_times_10000_stts_entries", |b| {
+        b.iter(|| read_sample_times(&data));
+    });
 }
 
 criterion_group!(benches, criterion_benchmark);
@@ -5,8 +5,8 @@ use std::io::{self, BufReader, BufWriter};
 use std::path::Path;
 
 use mp4::{
-    AacConfig, AvcConfig, HevcConfig, MediaConfig, MediaType, Mp4Config, Result, TrackConfig,
-    TtxtConfig, Vp9Config,
+    AacConfig, Av1Config, AvcConfig, HevcConfig, MediaConfig, MediaType, Mp4Config,
+    RawSampleEntryConfig, Result, TrackConfig, TrackType, TtxtConfig, Vp9Config, WriteBox,
 };
 
 fn main() {
@@ -38,52 +38,99 @@ fn copy<P: AsRef<Path>>(src_filename: &P, dst_filename: &P) -> Result<()> {
             minor_version: mp4_reader.minor_version(),
             compatible_brands: mp4_reader.compatible_brands().to_vec(),
             timescale: mp4_reader.timescale(),
+            rate: mp4_reader.moov.mvhd.rate,
+            write_iods: mp4_reader.moov.iods.is_some(),
+            ..Default::default()
         },
     )?;
 
     // TODO interleaving
     for track in mp4_reader.tracks().values() {
-        let media_conf = match track.media_type()? {
-            MediaType::H264 => MediaConfig::AvcConfig(AvcConfig {
+        let media_conf = if let Some(sample_entry) = track.sample_entry_raw_bytes() {
+            MediaConfig::RawSampleEntryConfig(RawSampleEntryConfig {
+                track_type: track.track_type()?,
+                timescale: track.timescale(),
                 width: track.width(),
                 height: track.height(),
-                seq_param_set: track.sequence_parameter_set()?.to_vec(),
-                pic_param_set: track.picture_parameter_set()?.to_vec(),
-            }),
-            MediaType::H265 => MediaConfig::HevcConfig(HevcConfig {
-                width: track.width(),
-                height: track.height(),
-            }),
-            MediaType::VP9 => MediaConfig::Vp9Config(Vp9Config {
-                width: track.width(),
-                height: track.height(),
-            }),
-            MediaType::AAC => MediaConfig::AacConfig(AacConfig {
-                bitrate: track.bitrate(),
-                profile: track.audio_profile()?,
-                freq_index: track.sample_freq_index()?,
-                chan_conf: track.channel_config()?,
-            }),
-            MediaType::TTXT => MediaConfig::TtxtConfig(TtxtConfig {}),
+                sample_entry: sample_entry.to_vec(),
+            })
+        } else {
+            match track.media_type()? {
+                MediaType::H264 => MediaConfig::AvcConfig(AvcConfig {
+                    width: track.width(),
+                    height: track.height(),
+                    seq_param_set: track.sequence_parameter_set()?.to_vec(),
+                    pic_param_set: track.picture_parameter_set()?.to_vec(),
+                }),
+                MediaType::H265 => MediaConfig::HevcConfig(HevcConfig {
+                    width: track.width(),
+                    height: track.height(),
+                    hdr: track.hdr_metadata(),
+                    video_param_set: first_or_empty(track.hevc_vps()?),
+                    seq_param_set: first_or_empty(track.hevc_sps()?),
+                    pic_param_set: first_or_empty(track.hevc_pps()?),
+                }),
+                MediaType::VP9 => MediaConfig::Vp9Config(Vp9Config {
+                    width: track.width(),
+                    height: track.height(),
+                }),
+                MediaType::AV1 => MediaConfig::Av1Config(Av1Config {
+                    width: track.width(),
+                    height: track.height(),
+                    config_obus: track
+                        .trak
+                        .mdia
+                        .minf
+                        .stbl
+                        .stsd
+                        .av01
+                        .as_ref()
+                        .map_or_else(Vec::new, |av01| av01.av1c.config_obus.clone()),
+                }),
+                MediaType::AAC => MediaConfig::AacConfig(AacConfig {
+                    bitrate: track.bitrate(),
+                    profile: track.audio_profile()?,
+                    freq_index: track.sample_freq_index()?,
+                    chan_conf: track.channel_config()?,
+                }),
+                MediaType::AC3 => MediaConfig::Ac3Config(track.ac3_config()?),
+                MediaType::EAC3 => {
+                    // There is no E-AC-3 track config, so copy the sample entry.
+                    let mut sample_entry = Vec::new();
+                    if let Some(ref eac3) = track.trak.mdia.minf.stbl.stsd.eac3 {
+                        eac3.write_box(&mut sample_entry)?;
+                    }
+                    MediaConfig::RawSampleEntryConfig(RawSampleEntryConfig {
+                        track_type: TrackType::Audio,
+                        timescale: track.timescale(),
+                        width: 0,
+                        height: 0,
+                        sample_entry,
+                    })
+                }
+                MediaType::Opus => MediaConfig::OpusConfig(track.opus_config()?),
+                MediaType::TTXT => MediaConfig::TtxtConfig(TtxtConfig {}),
+            }
         };
 
         let track_conf = TrackConfig {
             track_type: track.track_type()?,
             timescale: track.timescale(),
             language: track.language().to_string(),
+            volume: track.trak.tkhd.volume,
             media_conf,
+            compressor_name: track.compressor_name().unwrap_or_default().to_string(),
+            display_size: None,
+            pixel_aspect_ratio: track.pixel_aspect_ratio(),
+            rotation: track.rotation(),
         };
 
         mp4_writer.add_track(&track_conf)?;
     }
 
     for track_id in mp4_reader.tracks().keys().copied().collect::<Vec<u32>>() {
-        let sample_count = mp4_reader.sample_count(track_id)?;
-        for sample_idx in 0..sample_count {
-            let sample_id = sample_idx + 1;
-            let sample = mp4_reader.read_sample(track_id, sample_id)?.unwrap();
-            mp4_writer.write_sample(track_id, &sample)?;
-            // println!("copy {}:({})", sample_id, sample);
+        for sample in mp4_reader.samples(track_id)? {
+            mp4_writer.write_sample(track_id, &sample?)?;
         }
     }
 
@@ -91,3 +138,7 @@ fn copy<P: AsRef<Path>>(src_filename: &P, dst_filename: &P) -> Result<()> {
 
     Ok(())
 }
+
+fn first_or_empty(nalus: Vec<&[u8]>) -> Vec<u8> {
+    nalus.first().map_or_else(Vec::new, |nalu| nalu.to_vec())
+}
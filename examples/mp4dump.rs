@@ -56,13 +56,18 @@ fn get_boxes(file: File) -> Result<Vec<Box>> {
         if let Some(mehd) = &mvex.mehd {
             boxes.push(build_box(mehd));
         }
-        boxes.push(build_box(&mvex.trex));
+        for trex in mvex.trexs.iter() {
+            boxes.push(build_box(trex));
+        }
     }
 
     // trak.
     for track in mp4.tracks().values() {
         boxes.push(build_box(&track.trak));
         boxes.push(build_box(&track.trak.tkhd));
+        if let Some(ref tref) = track.trak.tref {
+            boxes.push(build_box(tref));
+        }
         if let Some(ref edts) = track.trak.edts {
             boxes.push(build_box(edts));
             if let Some(ref elst) = edts.elst {
@@ -85,6 +90,9 @@ fn get_boxes(file: File) -> Result<Vec<Box>> {
         if let Some(ref smhd) = &minf.smhd {
             boxes.push(build_box(smhd));
         }
+        if let Some(ref hmhd) = &minf.hmhd {
+            boxes.push(build_box(hmhd));
+        }
 
         // trak.mdia.minf.stbl
         let stbl = &track.trak.mdia.minf.stbl;
@@ -96,6 +104,9 @@ fn get_boxes(file: File) -> Result<Vec<Box>> {
         if let Some(ref hev1) = &stbl.stsd.hev1 {
             boxes.push(build_box(hev1));
         }
+        if let Some(ref dvh1) = &stbl.stsd.dvh1 {
+            boxes.push(build_box(dvh1));
+        }
         if let Some(ref mp4a) = &stbl.stsd.mp4a {
             boxes.push(build_box(mp4a));
         }
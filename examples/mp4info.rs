@@ -52,6 +52,7 @@ fn info<P: AsRef<Path>>(filename: &P) -> Result<()> {
             TrackType::Video => video_info(track),
             TrackType::Audio => audio_info(track),
             TrackType::Subtitle => subtitle_info(track),
+            TrackType::Hint => hint_info(track),
         };
 
         println!(
@@ -133,6 +134,18 @@ fn subtitle_info(track: &Mp4Track) -> Result<String> {
     }
 }
 
+fn hint_info(track: &Mp4Track) -> Result<String> {
+    if let Some(ref rtp) = track.trak.mdia.minf.stbl.stsd.rtp {
+        Ok(format!(
+            "({:?}), max packet size {}",
+            track.box_type()?,
+            rtp.max_packet_size
+        ))
+    } else {
+        Err(Error::InvalidData("rtp box not found"))
+    }
+}
+
 fn creation_time(creation_time: u64) -> u64 {
     // convert from MP4 epoch (1904-01-01) to Unix epoch (1970-01-01)
     if creation_time >= 2082844800 {
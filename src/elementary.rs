@@ -0,0 +1,135 @@
+//! Framing of samples as raw elementary streams: Annex B byte streams for
+//! H.264/H.265 and ADTS for AAC.
+
+use std::io::Write;
+
+use crate::*;
+
+const START_CODE: [u8; 4] = [0x00, 0x00, 0x00, 0x01];
+
+/// Largest ADTS frame, as the frame length field is 13 bits.
+const MAX_ADTS_FRAME_SIZE: usize = 0x1FFF;
+
+/// How the samples of a track are framed in its elementary stream.
+pub(crate) enum Framing {
+    AnnexB {
+        nal_length_size: usize,
+        parameter_sets: Vec<Vec<u8>>,
+    },
+    Adts {
+        header: [u8; 7],
+    },
+}
+
+impl Framing {
+    pub(crate) fn new(track: &Mp4Track) -> Result<Self> {
+        let stsd = &track.trak.mdia.minf.stbl.stsd;
+        let hvcc = stsd
+            .hev1
+            .as_ref()
+            .map(|hev1| &hev1.hvcc)
+            .or(stsd.dvh1.as_ref().map(|dvh1| &dvh1.hvcc));
+        if let Some(ref avc1) = stsd.avc1 {
+            let avcc = &avc1.avcc;
+            Ok(Framing::AnnexB {
+                nal_length_size: (avcc.length_size_minus_one & 0x3) as usize + 1,
+                parameter_sets: avcc
+                    .sequence_parameter_sets
+                    .iter()
+                    .chain(avcc.picture_parameter_sets.iter())
+                    .map(|nal| nal.bytes.clone())
+                    .collect(),
+            })
+        } else if let Some(hvcc) = hvcc {
+            Ok(Framing::AnnexB {
+                nal_length_size: (hvcc.length_size_minus_one & 0x3) as usize + 1,
+                parameter_sets: hvcc
+                    .arrays
+                    .iter()
+                    .flat_map(|array| array.nalus.iter())
+                    .map(|nalu| nalu.data.clone())
+                    .collect(),
+            })
+        } else if let Some(ref mp4a) = stsd.mp4a {
+            let esds = mp4a
+                .esds
+                .as_ref()
+                .ok_or(Error::BoxInStblNotFound(track.track_id(), BoxType::EsdsBox))?;
+            let dec_specific = &esds.es_desc.dec_config.dec_specific;
+            if !(1..=4).contains(&dec_specific.profile) {
+                return Err(Error::InvalidData(
+                    "ADTS only carries AAC Main, LC, SSR and LTP",
+                ));
+            }
+            if dec_specific.freq_index > 12 {
+                return Err(Error::InvalidData(
+                    "ADTS can't signal an explicit sampling frequency",
+                ));
+            }
+            let profile = dec_specific.profile - 1;
+            let chan_conf = dec_specific.chan_conf & 0x7;
+            Ok(Framing::Adts {
+                header: [
+                    0xFF,
+                    0xF1, // MPEG-4, layer 0, no CRC
+                    (profile << 6) | (dec_specific.freq_index << 2) | (chan_conf >> 2),
+                    chan_conf << 6,
+                    0x00,
+                    0x1F, // buffer fullness 0x7FF: variable bitrate
+                    0xFC, // one raw data block
+                ],
+            })
+        } else {
+            Err(Error::InvalidData(
+                "elementary streams are only supported for H.264, H.265 and AAC",
+            ))
+        }
+    }
+
+    /// Writes one sample with its framing. With Annex B, the parameter sets
+    /// are repeated before every sync sample so decoding can start there.
+    pub(crate) fn write_sample<W: Write>(&self, writer: &mut W, sample: &Mp4Sample) -> Result<()> {
+        match self {
+            Framing::AnnexB {
+                nal_length_size,
+                parameter_sets,
+            } => {
+                if sample.is_sync {
+                    for parameter_set in parameter_sets {
+                        writer.write_all(&START_CODE)?;
+                        writer.write_all(parameter_set)?;
+                    }
+                }
+                let mut bytes = &sample.bytes[..];
+                while !bytes.is_empty() {
+                    if bytes.len() < *nal_length_size {
+                        return Err(Error::InvalidData("truncated NAL unit length"));
+                    }
+                    let (length, rest) = bytes.split_at(*nal_length_size);
+                    let length = length
+                        .iter()
+                        .fold(0usize, |length, &byte| (length << 8) | byte as usize);
+                    if rest.len() < length {
+                        return Err(Error::InvalidData("NAL unit extends past the sample"));
+                    }
+                    writer.write_all(&START_CODE)?;
+                    writer.write_all(&rest[..length])?;
+                    bytes = &rest[length..];
+                }
+            }
+            Framing::Adts { header } => {
+                let frame_size = header.len() + sample.bytes.len();
+                if frame_size > MAX_ADTS_FRAME_SIZE {
+                    return Err(Error::InvalidData("AAC frame is too large for ADTS"));
+                }
+                let mut header = *header;
+                header[3] |= (frame_size >> 11) as u8;
+                header[4] = (frame_size >> 3) as u8;
+                header[5] |= (frame_size << 5) as u8;
+                writer.write_all(&header)?;
+                writer.write_all(&sample.bytes)?;
+            }
+        }
+        Ok(())
+    }
+}
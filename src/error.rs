@@ -1,6 +1,7 @@
 use thiserror::Error;
 
 use crate::mp4box::BoxType;
+use crate::FourCC;
 
 #[derive(Error, Debug)]
 pub enum Error {
@@ -26,4 +27,26 @@ pub enum Error {
     EntryInTrunNotFound(u32, BoxType, u32),
     #[error("{0} version {1} is not supported")]
     UnsupportedBoxVersion(BoxType, u8),
+    #[error("trak[{0}] sample {1} is in chunk {2} but there are only {3} chunks")]
+    ChunkOutOfRange(u32, u32, u32, u32),
+    #[error("trak[{0}] sample {1} at offset {2} with size {3} is outside every mdat")]
+    SampleOutsideMdat(u32, u32, u64, u32),
+    #[error("trak[{0}] sample {1} at offset {2} with size {3} extends past the end of the file ({4} bytes)")]
+    Truncated(u32, u32, u64, u32, u64),
+    #[error("trak[{0}] sample data is in external file {1} but no data resolver is set")]
+    ExternalDataNotResolved(u32, String),
+    #[error("protection scheme {0} is not supported")]
+    UnsupportedProtectionScheme(FourCC),
+    #[error("trak[{0}] has no decryption key for KID {1:02x?}")]
+    DecryptionKeyNotFound(u32, [u8; 16]),
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum Warning {
+    #[error("trak[{0}].stsd data_reference_index {1} is out of range of dref entry_count {2}")]
+    DataReferenceIndexOutOfRange(u32, u16, u32),
+    #[error("duplicate {0} box at offset {1} was ignored")]
+    DuplicateBox(BoxType, u64),
+    #[error("moov at offset {0} follows mdat at offset {1}, so the file isn't streamable")]
+    MoovAfterMdat(u64, u64),
 }
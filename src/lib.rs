@@ -69,7 +69,7 @@ use std::fs::File;
 use std::io::BufReader;
 
 mod error;
-pub use error::Error;
+pub use error::{Error, Warning};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -82,11 +82,14 @@ pub use mp4box::*;
 mod track;
 pub use track::{Mp4Track, TrackConfig};
 
+mod elementary;
+mod recover;
+
 mod reader;
-pub use reader::Mp4Reader;
+pub use reader::{EditCorrectedSamples, Mp4Reader, ReadSeek, Samples};
 
 mod writer;
-pub use writer::{Mp4Config, Mp4Writer};
+pub use writer::{FragmentConfig, Mp4Config, Mp4FragmentWriter, Mp4Header, Mp4Writer};
 
 pub fn read_mp4(f: File) -> Result<Mp4Reader<BufReader<File>>> {
     let size = f.metadata()?.len();
@@ -0,0 +1,311 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use serde::Serialize;
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::*;
+
+/// AC-3 (Dolby Digital) audio sample entry, as specified in ETSI TS 102 366
+/// Annex F.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Ac3Box {
+    pub data_reference_index: u16,
+    pub channelcount: u16,
+    pub samplesize: u16,
+
+    #[serde(with = "value_u32")]
+    pub samplerate: FixedPointU16,
+    pub dac3: Dac3Box,
+}
+
+impl Default for Ac3Box {
+    fn default() -> Self {
+        Self {
+            data_reference_index: 0,
+            channelcount: 2,
+            samplesize: 16,
+            samplerate: FixedPointU16::new(48000),
+            dac3: Dac3Box::default(),
+        }
+    }
+}
+
+impl Ac3Box {
+    pub fn new(config: &Ac3Config) -> Self {
+        let dac3 = Dac3Box::new(config);
+        Self {
+            data_reference_index: 1,
+            // The channel count is ignored by readers and always 2.
+            channelcount: 2,
+            samplesize: 16,
+            samplerate: FixedPointU16::new(dac3.sample_rate().unwrap_or(0) as u16),
+            dac3,
+        }
+    }
+
+    pub fn get_type(&self) -> BoxType {
+        BoxType::Ac3Box
+    }
+
+    pub fn get_size(&self) -> u64 {
+        HEADER_SIZE + 8 + 20 + self.dac3.box_size()
+    }
+}
+
+impl Mp4Box for Ac3Box {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "channel_count={} sample_size={} sample_rate={}",
+            self.channelcount,
+            self.samplesize,
+            self.samplerate.value()
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for Ac3Box {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        reader.read_u32::<BigEndian>()?; // reserved
+        reader.read_u16::<BigEndian>()?; // reserved
+        let data_reference_index = reader.read_u16::<BigEndian>()?;
+        reader.read_u64::<BigEndian>()?; // reserved
+        let channelcount = reader.read_u16::<BigEndian>()?;
+        let samplesize = reader.read_u16::<BigEndian>()?;
+        reader.read_u32::<BigEndian>()?; // pre-defined, reserved
+        let samplerate = FixedPointU16::new_raw(reader.read_u32::<BigEndian>()?);
+
+        let mut dac3 = None;
+        let end = start + size;
+        let mut current = reader.stream_position()?;
+        while current + HEADER_SIZE <= end {
+            let header = BoxHeader::read(reader)?;
+            let BoxHeader { name, size: s } = header;
+            if s < HEADER_SIZE || current + s > end {
+                return Err(Error::InvalidData(
+                    "ac-3 box contains a box with a larger size than it",
+                ));
+            }
+            if name == BoxType::Dac3Box {
+                dac3 = Some(Dac3Box::read_box(reader, s)?);
+            }
+            skip_bytes_to(reader, current + s)?;
+            current = reader.stream_position()?;
+        }
+        let dac3 = dac3.ok_or(Error::BoxNotFound(BoxType::Dac3Box))?;
+
+        skip_bytes_to(reader, end)?;
+
+        Ok(Ac3Box {
+            data_reference_index,
+            channelcount,
+            samplesize,
+            samplerate,
+            dac3,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for Ac3Box {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        writer.write_u32::<BigEndian>(0)?; // reserved
+        writer.write_u16::<BigEndian>(0)?; // reserved
+        writer.write_u16::<BigEndian>(self.data_reference_index)?;
+
+        writer.write_u64::<BigEndian>(0)?; // reserved
+        writer.write_u16::<BigEndian>(self.channelcount)?;
+        writer.write_u16::<BigEndian>(self.samplesize)?;
+        writer.write_u32::<BigEndian>(0)?; // reserved
+        writer.write_u32::<BigEndian>(self.samplerate.raw_value())?;
+
+        self.dac3.write_box(writer)?;
+
+        Ok(size)
+    }
+}
+
+/// The AC3SpecificBox, repeating the stream parameters of the AC-3
+/// bit stream information header.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct Dac3Box {
+    pub fscod: u8,
+    pub bsid: u8,
+    pub bsmod: u8,
+    pub acmod: u8,
+    pub lfeon: bool,
+    pub bit_rate_code: u8,
+}
+
+impl Dac3Box {
+    pub fn new(config: &Ac3Config) -> Self {
+        Self {
+            fscod: config.fscod,
+            bsid: config.bsid,
+            bsmod: config.bsmod,
+            acmod: config.acmod,
+            lfeon: config.lfeon,
+            bit_rate_code: config.bit_rate_code,
+        }
+    }
+
+    pub fn get_type(&self) -> BoxType {
+        BoxType::Dac3Box
+    }
+
+    pub fn get_size(&self) -> u64 {
+        HEADER_SIZE + 3
+    }
+
+    /// The sampling rate in Hz signalled by `fscod`, if it's a valid code.
+    pub fn sample_rate(&self) -> Option<u32> {
+        match self.fscod {
+            0 => Some(48000),
+            1 => Some(44100),
+            2 => Some(32000),
+            _ => None,
+        }
+    }
+
+    /// The number of channels coded by `acmod`, plus one for the LFE channel.
+    pub fn channel_count(&self) -> u8 {
+        let channels = match self.acmod & 0x7 {
+            0 => 2, // 1+1, dual mono
+            1 => 1,
+            2 => 2,
+            3 | 4 => 3,
+            5 | 6 => 4,
+            _ => 5,
+        };
+        channels + self.lfeon as u8
+    }
+}
+
+impl Mp4Box for Dac3Box {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "fscod={} bsid={} bsmod={} acmod={} lfeon={} bit_rate_code={}",
+            self.fscod, self.bsid, self.bsmod, self.acmod, self.lfeon, self.bit_rate_code
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for Dac3Box {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let bits = reader.read_u24::<BigEndian>()?;
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(Dac3Box {
+            fscod: (bits >> 22) as u8 & 0x3,
+            bsid: (bits >> 17) as u8 & 0x1F,
+            bsmod: (bits >> 14) as u8 & 0x7,
+            acmod: (bits >> 11) as u8 & 0x7,
+            lfeon: (bits >> 10) & 0x1 == 1,
+            bit_rate_code: (bits >> 5) as u8 & 0x1F,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for Dac3Box {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        let bits = (self.fscod as u32 & 0x3) << 22
+            | (self.bsid as u32 & 0x1F) << 17
+            | (self.bsmod as u32 & 0x7) << 14
+            | (self.acmod as u32 & 0x7) << 11
+            | (self.lfeon as u32) << 10
+            | (self.bit_rate_code as u32 & 0x1F) << 5;
+        writer.write_u24::<BigEndian>(bits)?;
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_ac3() {
+        // 5.1 (3/2 + LFE) at 48 kHz and 448 kbit/s.
+        let src_box = Ac3Box::new(&Ac3Config {
+            fscod: 0,
+            bsid: 8,
+            bsmod: 0,
+            acmod: 7,
+            lfeon: true,
+            bit_rate_code: 16,
+        });
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::Ac3Box);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = Ac3Box::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+        assert_eq!(dst_box.samplerate.value(), 48000);
+        assert_eq!(dst_box.dac3.channel_count(), 6);
+    }
+
+    #[test]
+    fn test_dac3() {
+        let src_box = Dac3Box {
+            fscod: 1,
+            bsid: 8,
+            bsmod: 7,
+            acmod: 2,
+            lfeon: false,
+            bit_rate_code: 10,
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::Dac3Box);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = Dac3Box::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+}
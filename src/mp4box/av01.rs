@@ -0,0 +1,605 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use serde::Serialize;
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::*;
+
+/// Written as `dav1` for Dolby Vision streams that aren't meant for plain
+/// AV1 decoders and as `av01` otherwise.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Av01Box {
+    pub dolby_vision_only: bool,
+    pub data_reference_index: u16,
+    pub width: u16,
+    pub height: u16,
+
+    #[serde(with = "value_u32")]
+    pub horizresolution: FixedPointU16,
+
+    #[serde(with = "value_u32")]
+    pub vertresolution: FixedPointU16,
+    pub frame_count: u16,
+    pub compressor_name: String,
+    pub depth: u16,
+    pub av1c: Av1CBox,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dvcc: Option<DvccBox>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pasp: Option<PaspBox>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub colr: Option<ColrBox>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub btrt: Option<BtrtBox>,
+}
+
+impl Default for Av01Box {
+    fn default() -> Self {
+        Av01Box {
+            dolby_vision_only: false,
+            data_reference_index: 0,
+            width: 0,
+            height: 0,
+            horizresolution: FixedPointU16::new(0x48),
+            vertresolution: FixedPointU16::new(0x48),
+            frame_count: 1,
+            compressor_name: String::new(),
+            depth: 0x0018,
+            av1c: Av1CBox::default(),
+            dvcc: None,
+            pasp: None,
+            colr: None,
+            btrt: None,
+        }
+    }
+}
+
+impl Av01Box {
+    pub fn new(config: &Av1Config) -> Self {
+        Av01Box {
+            dolby_vision_only: false,
+            data_reference_index: 1,
+            width: config.width,
+            height: config.height,
+            horizresolution: FixedPointU16::new(0x48),
+            vertresolution: FixedPointU16::new(0x48),
+            frame_count: 1,
+            compressor_name: String::new(),
+            depth: 0x0018,
+            av1c: Av1CBox::new(&config.config_obus),
+            dvcc: None,
+            pasp: None,
+            colr: None,
+            btrt: None,
+        }
+    }
+
+    pub fn get_type(&self) -> BoxType {
+        if self.dolby_vision_only {
+            BoxType::Dav1Box
+        } else {
+            BoxType::Av01Box
+        }
+    }
+
+    pub fn get_size(&self) -> u64 {
+        let mut size = HEADER_SIZE + 8 + 70 + self.av1c.box_size();
+        if let Some(ref dvcc) = self.dvcc {
+            size += dvcc.box_size();
+        }
+        if let Some(ref pasp) = self.pasp {
+            size += pasp.box_size();
+        }
+        if let Some(ref colr) = self.colr {
+            size += colr.box_size();
+        }
+        if let Some(ref btrt) = self.btrt {
+            size += btrt.box_size();
+        }
+        size
+    }
+}
+
+impl Mp4Box for Av01Box {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "data_reference_index={} width={} height={} frame_count={}",
+            self.data_reference_index, self.width, self.height, self.frame_count
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for Av01Box {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        reader.read_u32::<BigEndian>()?; // reserved
+        reader.read_u16::<BigEndian>()?; // reserved
+        let data_reference_index = reader.read_u16::<BigEndian>()?;
+
+        reader.read_u32::<BigEndian>()?; // pre-defined, reserved
+        reader.read_u64::<BigEndian>()?; // pre-defined
+        reader.read_u32::<BigEndian>()?; // pre-defined
+        let width = reader.read_u16::<BigEndian>()?;
+        let height = reader.read_u16::<BigEndian>()?;
+        let horizresolution = FixedPointU16::new_raw(reader.read_u32::<BigEndian>()?);
+        let vertresolution = FixedPointU16::new_raw(reader.read_u32::<BigEndian>()?);
+        reader.read_u32::<BigEndian>()?; // reserved
+        let frame_count = reader.read_u16::<BigEndian>()?;
+        let compressor_name = read_compressor_name(reader)?;
+        let depth = reader.read_u16::<BigEndian>()?;
+        reader.read_i16::<BigEndian>()?; // pre-defined
+
+        let mut av1c = None;
+        let mut dvcc = None;
+        let mut pasp = None;
+        let mut colr = None;
+        let mut btrt = None;
+
+        let mut current = reader.stream_position()?;
+        let end = start + size;
+        while current < end {
+            let header = BoxHeader::read(reader)?;
+            let BoxHeader { name, size: s } = header;
+            if s > size {
+                return Err(Error::InvalidData(
+                    "av01 box contains a box with a larger size than it",
+                ));
+            }
+            match name {
+                BoxType::Av1CBox => {
+                    av1c = Some(Av1CBox::read_box(reader, s)?);
+                }
+                BoxType::DvcCBox | BoxType::DvvCBox => {
+                    let mut entry = DvccBox::read_box(reader, s)?;
+                    entry.dvvc = name == BoxType::DvvCBox;
+                    dvcc = Some(entry);
+                }
+                BoxType::PaspBox => {
+                    pasp = Some(PaspBox::read_box(reader, s)?);
+                }
+                BoxType::ColrBox => {
+                    colr = Some(ColrBox::read_box(reader, s)?);
+                }
+                BoxType::BtrtBox => {
+                    btrt = Some(BtrtBox::read_box(reader, s)?);
+                }
+                _ => {}
+            }
+            skip_bytes_to(reader, current + s)?;
+            current = reader.stream_position()?;
+        }
+
+        let av1c = av1c.ok_or(Error::InvalidData("av1C not found"))?;
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(Av01Box {
+            dolby_vision_only: false,
+            data_reference_index,
+            width,
+            height,
+            horizresolution,
+            vertresolution,
+            frame_count,
+            compressor_name,
+            depth,
+            av1c,
+            dvcc,
+            pasp,
+            colr,
+            btrt,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for Av01Box {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        writer.write_u32::<BigEndian>(0)?; // reserved
+        writer.write_u16::<BigEndian>(0)?; // reserved
+        writer.write_u16::<BigEndian>(self.data_reference_index)?;
+
+        writer.write_u32::<BigEndian>(0)?; // pre-defined, reserved
+        writer.write_u64::<BigEndian>(0)?; // pre-defined
+        writer.write_u32::<BigEndian>(0)?; // pre-defined
+        writer.write_u16::<BigEndian>(self.width)?;
+        writer.write_u16::<BigEndian>(self.height)?;
+        writer.write_u32::<BigEndian>(self.horizresolution.raw_value())?;
+        writer.write_u32::<BigEndian>(self.vertresolution.raw_value())?;
+        writer.write_u32::<BigEndian>(0)?; // reserved
+        writer.write_u16::<BigEndian>(self.frame_count)?;
+        write_compressor_name(writer, &self.compressor_name)?;
+        writer.write_u16::<BigEndian>(self.depth)?;
+        writer.write_i16::<BigEndian>(-1)?; // pre-defined
+
+        self.av1c.write_box(writer)?;
+        if let Some(ref dvcc) = self.dvcc {
+            dvcc.write_box(writer)?;
+        }
+        if let Some(ref pasp) = self.pasp {
+            pasp.write_box(writer)?;
+        }
+        if let Some(ref colr) = self.colr {
+            colr.write_box(writer)?;
+        }
+        if let Some(ref btrt) = self.btrt {
+            btrt.write_box(writer)?;
+        }
+
+        Ok(size)
+    }
+}
+
+/// The AV1CodecConfigurationRecord. The fields mirror the sequence header
+/// OBU, which (with any metadata OBUs) is carried in `config_obus`.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct Av1CBox {
+    pub seq_profile: u8,
+    pub seq_level_idx_0: u8,
+    pub seq_tier_0: bool,
+    pub high_bitdepth: bool,
+    pub twelve_bit: bool,
+    pub monochrome: bool,
+    pub chroma_subsampling_x: bool,
+    pub chroma_subsampling_y: bool,
+    pub chroma_sample_position: u8,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub initial_presentation_delay_minus_one: Option<u8>,
+
+    #[serde(skip_serializing)]
+    pub config_obus: Vec<u8>,
+}
+
+impl Av1CBox {
+    /// Builds the record from `config_obus`, taking the profile, level and
+    /// color configuration from its sequence header OBU. Without one, the
+    /// record describes 8-bit 4:2:0 Main profile with an unconstrained level.
+    pub fn new(config_obus: &[u8]) -> Self {
+        let mut av1c = parse_sequence_header(config_obus).unwrap_or(Av1CBox {
+            seq_level_idx_0: 31,
+            chroma_subsampling_x: true,
+            chroma_subsampling_y: true,
+            ..Default::default()
+        });
+        av1c.config_obus = config_obus.to_vec();
+        av1c
+    }
+
+    pub fn get_type(&self) -> BoxType {
+        BoxType::Av1CBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        HEADER_SIZE + 4 + self.config_obus.len() as u64
+    }
+}
+
+impl Mp4Box for Av1CBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "seq_profile={} seq_level_idx_0={} high_bitdepth={} monochrome={}",
+            self.seq_profile, self.seq_level_idx_0, self.high_bitdepth, self.monochrome
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for Av1CBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let marker_version = reader.read_u8()?;
+        if marker_version != 0x81 {
+            return Err(Error::InvalidData("av1C marker or version is invalid"));
+        }
+        let byte = reader.read_u8()?;
+        let seq_profile = byte >> 5;
+        let seq_level_idx_0 = byte & 0x1F;
+        let byte = reader.read_u8()?;
+        let seq_tier_0 = byte & 0x80 != 0;
+        let high_bitdepth = byte & 0x40 != 0;
+        let twelve_bit = byte & 0x20 != 0;
+        let monochrome = byte & 0x10 != 0;
+        let chroma_subsampling_x = byte & 0x08 != 0;
+        let chroma_subsampling_y = byte & 0x04 != 0;
+        let chroma_sample_position = byte & 0x03;
+        let byte = reader.read_u8()?;
+        let initial_presentation_delay_minus_one = if byte & 0x10 != 0 {
+            Some(byte & 0x0F)
+        } else {
+            None
+        };
+
+        let header_size = HEADER_SIZE + 4;
+        let obus_size = size
+            .checked_sub(header_size)
+            .ok_or(Error::InvalidData("av1C box is too small"))?;
+        let mut config_obus = vec![0u8; obus_size as usize];
+        reader.read_exact(&mut config_obus)?;
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(Av1CBox {
+            seq_profile,
+            seq_level_idx_0,
+            seq_tier_0,
+            high_bitdepth,
+            twelve_bit,
+            monochrome,
+            chroma_subsampling_x,
+            chroma_subsampling_y,
+            chroma_sample_position,
+            initial_presentation_delay_minus_one,
+            config_obus,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for Av1CBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        writer.write_u8(0x81)?; // marker, version 1
+        writer.write_u8((self.seq_profile << 5) | (self.seq_level_idx_0 & 0x1F))?;
+        writer.write_u8(
+            (self.seq_tier_0 as u8) << 7
+                | (self.high_bitdepth as u8) << 6
+                | (self.twelve_bit as u8) << 5
+                | (self.monochrome as u8) << 4
+                | (self.chroma_subsampling_x as u8) << 3
+                | (self.chroma_subsampling_y as u8) << 2
+                | (self.chroma_sample_position & 0x03),
+        )?;
+        match self.initial_presentation_delay_minus_one {
+            Some(delay) => writer.write_u8(0x10 | (delay & 0x0F))?,
+            None => writer.write_u8(0)?,
+        }
+        writer.write_all(&self.config_obus)?;
+
+        Ok(size)
+    }
+}
+
+const OBU_SEQUENCE_HEADER: u8 = 1;
+
+fn read_leb128(bytes: &[u8]) -> Option<(usize, usize)> {
+    let mut value = 0usize;
+    for (i, byte) in bytes.iter().take(8).enumerate() {
+        value |= ((byte & 0x7F) as usize) << (i * 7);
+        if byte & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+/// Finds the sequence header OBU and reads the fields the av1C record
+/// repeats, as specified in section 5.5 of the AV1 bitstream specification.
+fn parse_sequence_header(mut obus: &[u8]) -> Option<Av1CBox> {
+    let payload = loop {
+        let header = *obus.first()?;
+        let obu_type = (header >> 3) & 0xF;
+        let header_size = if header & 0x04 != 0 { 2 } else { 1 };
+        let (payload_size, size_len) = if header & 0x02 != 0 {
+            read_leb128(obus.get(header_size..)?)?
+        } else {
+            (obus.len().checked_sub(header_size)?, 0)
+        };
+        let payload_start = header_size + size_len;
+        let payload = obus.get(payload_start..payload_start + payload_size)?;
+        if obu_type == OBU_SEQUENCE_HEADER {
+            break payload;
+        }
+        obus = &obus[payload_start + payload_size..];
+    };
+
+    let mut r = BitReader::new(payload);
+    let seq_profile = r.read(3)? as u8;
+    r.read_flag()?; // still_picture
+    let reduced_still_picture_header = r.read_flag()?;
+
+    let seq_level_idx_0;
+    let mut seq_tier_0 = false;
+    if reduced_still_picture_header {
+        seq_level_idx_0 = r.read(5)? as u8;
+    } else {
+        let mut buffer_delay_length = 0;
+        let mut decoder_model_info_present = false;
+        if r.read_flag()? {
+            // timing_info
+            r.read(32)?; // num_units_in_display_tick
+            r.read(32)?; // time_scale
+            if r.read_flag()? {
+                r.read_uvlc()?; // num_ticks_per_picture_minus_1
+            }
+            decoder_model_info_present = r.read_flag()?;
+            if decoder_model_info_present {
+                buffer_delay_length = r.read(5)? + 1;
+                r.read(32)?; // num_units_in_decoding_tick
+                r.read(5)?; // buffer_removal_time_length_minus_1
+                r.read(5)?; // frame_presentation_time_length_minus_1
+            }
+        }
+        let initial_display_delay_present = r.read_flag()?;
+        let operating_points_cnt = r.read(5)? + 1;
+        let mut levels = Vec::with_capacity(operating_points_cnt as usize);
+        for _ in 0..operating_points_cnt {
+            r.read(12)?; // operating_point_idc
+            let seq_level_idx = r.read(5)? as u8;
+            let seq_tier = seq_level_idx > 7 && r.read_flag()?;
+            levels.push((seq_level_idx, seq_tier));
+            if decoder_model_info_present && r.read_flag()? {
+                r.read(buffer_delay_length)?; // decoder_buffer_delay
+                r.read(buffer_delay_length)?; // encoder_buffer_delay
+                r.read_flag()?; // low_delay_mode_flag
+            }
+            if initial_display_delay_present && r.read_flag()? {
+                r.read(4)?; // initial_display_delay_minus_1
+            }
+        }
+        seq_level_idx_0 = levels[0].0;
+        seq_tier_0 = levels[0].1;
+    }
+
+    let frame_width_bits = r.read(4)? + 1;
+    let frame_height_bits = r.read(4)? + 1;
+    r.read(frame_width_bits)?; // max_frame_width_minus_1
+    r.read(frame_height_bits)?; // max_frame_height_minus_1
+    if !reduced_still_picture_header && r.read_flag()? {
+        r.read(4)?; // delta_frame_id_length_minus_2
+        r.read(3)?; // additional_frame_id_length_minus_1
+    }
+    r.read(3)?; // use_128x128_superblock, enable_filter_intra, enable_intra_edge_filter
+    if !reduced_still_picture_header {
+        // enable_interintra_compound, enable_masked_compound,
+        // enable_warped_motion, enable_dual_filter
+        r.read(4)?;
+        let enable_order_hint = r.read_flag()?;
+        if enable_order_hint {
+            r.read(2)?; // enable_jnt_comp, enable_ref_frame_mvs
+        }
+        let seq_force_screen_content_tools = if r.read_flag()? {
+            2 // SELECT_SCREEN_CONTENT_TOOLS
+        } else {
+            r.read(1)?
+        };
+        if seq_force_screen_content_tools > 0 && !r.read_flag()? {
+            r.read(1)?; // seq_force_integer_mv
+        }
+        if enable_order_hint {
+            r.read(3)?; // order_hint_bits_minus_1
+        }
+    }
+    r.read(3)?; // enable_superres, enable_cdef, enable_restoration
+
+    // color_config
+    let high_bitdepth = r.read_flag()?;
+    let twelve_bit = seq_profile == 2 && high_bitdepth && r.read_flag()?;
+    let monochrome = seq_profile != 1 && r.read_flag()?;
+    let (mut color_primaries, mut transfer_characteristics, mut matrix_coefficients) = (2, 2, 2);
+    if r.read_flag()? {
+        color_primaries = r.read(8)?;
+        transfer_characteristics = r.read(8)?;
+        matrix_coefficients = r.read(8)?;
+    }
+    let (mut subsampling_x, mut subsampling_y, mut chroma_sample_position) = (true, true, 0);
+    if monochrome {
+        // Nothing further is coded that av1C repeats.
+    } else if color_primaries == 1 && transfer_characteristics == 13 && matrix_coefficients == 0 {
+        subsampling_x = false;
+        subsampling_y = false;
+    } else {
+        r.read_flag()?; // color_range
+        match seq_profile {
+            0 => {}
+            1 => {
+                subsampling_x = false;
+                subsampling_y = false;
+            }
+            _ => {
+                if twelve_bit {
+                    subsampling_x = r.read_flag()?;
+                    subsampling_y = subsampling_x && r.read_flag()?;
+                } else {
+                    subsampling_y = false;
+                }
+            }
+        }
+        if subsampling_x && subsampling_y {
+            chroma_sample_position = r.read(2)? as u8;
+        }
+    }
+
+    Some(Av1CBox {
+        seq_profile,
+        seq_level_idx_0,
+        seq_tier_0,
+        high_bitdepth,
+        twelve_bit,
+        monochrome,
+        chroma_subsampling_x: subsampling_x,
+        chroma_subsampling_y: subsampling_y,
+        chroma_sample_position,
+        initial_presentation_delay_minus_one: None,
+        config_obus: Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    // Sequence header OBU of an 8-bit 4:2:0 Main profile stream at level 4.0
+    // (seq_level_idx 8), 1920x1080.
+    const SEQUENCE_HEADER: [u8; 13] = [
+        0x0A, 0x0B, 0x00, 0x00, 0x00, 0x42, 0xAB, 0xBF, 0xC3, 0x73, 0xFF, 0xE6, 0x02,
+    ];
+
+    #[test]
+    fn test_av1c_from_sequence_header() {
+        let av1c = Av1CBox::new(&SEQUENCE_HEADER);
+        assert_eq!(av1c.seq_profile, 0);
+        assert_eq!(av1c.seq_level_idx_0, 8);
+        assert!(!av1c.seq_tier_0);
+        assert!(!av1c.high_bitdepth);
+        assert!(!av1c.monochrome);
+        assert!(av1c.chroma_subsampling_x);
+        assert!(av1c.chroma_subsampling_y);
+        assert_eq!(av1c.config_obus, SEQUENCE_HEADER);
+    }
+
+    #[test]
+    fn test_av01() {
+        let src_box = Av01Box::new(&Av1Config {
+            width: 1920,
+            height: 1080,
+            config_obus: SEQUENCE_HEADER.to_vec(),
+        });
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::Av01Box);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = Av01Box::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+}
@@ -4,8 +4,13 @@ use std::io::{Read, Seek, Write};
 
 use crate::mp4box::*;
 
+/// H.264 sample entry.
+///
+/// Written as `avc3` when parameter sets may be carried in-band and as
+/// `avc1` otherwise.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct Avc1Box {
+    pub in_band_parameter_sets: bool,
     pub data_reference_index: u16,
     pub width: u16,
     pub height: u16,
@@ -16,21 +21,53 @@ pub struct Avc1Box {
     #[serde(with = "value_u32")]
     pub vertresolution: FixedPointU16,
     pub frame_count: u16,
+    pub compressor_name: String,
     pub depth: u16,
     pub avcc: AvcCBox,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dvcc: Option<DvccBox>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pasp: Option<PaspBox>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub colr: Option<ColrBox>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mdcv: Option<MdcvBox>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clli: Option<ClliBox>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub btrt: Option<BtrtBox>,
+
+    /// Present when the entry is protected and stored as `encv`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sinf: Option<SinfBox>,
 }
 
 impl Default for Avc1Box {
     fn default() -> Self {
         Avc1Box {
+            in_band_parameter_sets: false,
             data_reference_index: 0,
             width: 0,
             height: 0,
             horizresolution: FixedPointU16::new(0x48),
             vertresolution: FixedPointU16::new(0x48),
             frame_count: 1,
+            compressor_name: String::new(),
             depth: 0x0018,
             avcc: AvcCBox::default(),
+            dvcc: None,
+            pasp: None,
+            colr: None,
+            mdcv: None,
+            clli: None,
+            btrt: None,
+            sinf: None,
         }
     }
 }
@@ -38,23 +75,60 @@ impl Default for Avc1Box {
 impl Avc1Box {
     pub fn new(config: &AvcConfig) -> Self {
         Avc1Box {
+            in_band_parameter_sets: false,
             data_reference_index: 1,
             width: config.width,
             height: config.height,
             horizresolution: FixedPointU16::new(0x48),
             vertresolution: FixedPointU16::new(0x48),
             frame_count: 1,
+            compressor_name: String::new(),
             depth: 0x0018,
             avcc: AvcCBox::new(&config.seq_param_set, &config.pic_param_set),
+            dvcc: None,
+            pasp: None,
+            colr: None,
+            mdcv: None,
+            clli: None,
+            btrt: None,
+            sinf: None,
         }
     }
 
     pub fn get_type(&self) -> BoxType {
-        BoxType::Avc1Box
+        if self.sinf.is_some() {
+            BoxType::EncvBox
+        } else if self.in_band_parameter_sets {
+            BoxType::Avc3Box
+        } else {
+            BoxType::Avc1Box
+        }
     }
 
     pub fn get_size(&self) -> u64 {
-        HEADER_SIZE + 8 + 70 + self.avcc.box_size()
+        let mut size = HEADER_SIZE + 8 + 70 + self.avcc.box_size();
+        if let Some(ref dvcc) = self.dvcc {
+            size += dvcc.box_size();
+        }
+        if let Some(ref pasp) = self.pasp {
+            size += pasp.box_size();
+        }
+        if let Some(ref colr) = self.colr {
+            size += colr.box_size();
+        }
+        if let Some(ref mdcv) = self.mdcv {
+            size += mdcv.box_size();
+        }
+        if let Some(ref clli) = self.clli {
+            size += clli.box_size();
+        }
+        if let Some(ref btrt) = self.btrt {
+            size += btrt.box_size();
+        }
+        if let Some(ref sinf) = self.sinf {
+            size += sinf.box_size();
+        }
+        size
     }
 }
 
@@ -97,16 +171,22 @@ impl<R: Read + Seek> ReadBox<&mut R> for Avc1Box {
         let vertresolution = FixedPointU16::new_raw(reader.read_u32::<BigEndian>()?);
         reader.read_u32::<BigEndian>()?; // reserved
         let frame_count = reader.read_u16::<BigEndian>()?;
-        skip_bytes(reader, 32)?; // compressorname
+        let compressor_name = read_compressor_name(reader)?;
         let depth = reader.read_u16::<BigEndian>()?;
         reader.read_i16::<BigEndian>()?; // pre-defined
 
+        let mut avcc = None;
+        let mut dvcc = None;
+        let mut pasp = None;
+        let mut colr = None;
+        let mut mdcv = None;
+        let mut clli = None;
+        let mut btrt = None;
+        let mut sinf = None;
+
+        let mut current = reader.stream_position()?;
         let end = start + size;
-        loop {
-            let current = reader.stream_position()?;
-            if current >= end {
-                return Err(Error::InvalidData("avcc not found"));
-            }
+        while current < end {
             let header = BoxHeader::read(reader)?;
             let BoxHeader { name, size: s } = header;
             if s > size {
@@ -114,25 +194,62 @@ impl<R: Read + Seek> ReadBox<&mut R> for Avc1Box {
                     "avc1 box contains a box with a larger size than it",
                 ));
             }
-            if name == BoxType::AvcCBox {
-                let avcc = AvcCBox::read_box(reader, s)?;
-
-                skip_bytes_to(reader, start + size)?;
-
-                return Ok(Avc1Box {
-                    data_reference_index,
-                    width,
-                    height,
-                    horizresolution,
-                    vertresolution,
-                    frame_count,
-                    depth,
-                    avcc,
-                });
-            } else {
-                skip_bytes_to(reader, current + s)?;
+            match name {
+                BoxType::AvcCBox => {
+                    avcc = Some(AvcCBox::read_box(reader, s)?);
+                }
+                BoxType::DvcCBox | BoxType::DvvCBox => {
+                    let mut entry = DvccBox::read_box(reader, s)?;
+                    entry.dvvc = name == BoxType::DvvCBox;
+                    dvcc = Some(entry);
+                }
+                BoxType::PaspBox => {
+                    pasp = Some(PaspBox::read_box(reader, s)?);
+                }
+                BoxType::ColrBox => {
+                    colr = Some(ColrBox::read_box(reader, s)?);
+                }
+                BoxType::MdcvBox => {
+                    mdcv = Some(MdcvBox::read_box(reader, s)?);
+                }
+                BoxType::ClliBox => {
+                    clli = Some(ClliBox::read_box(reader, s)?);
+                }
+                BoxType::BtrtBox => {
+                    btrt = Some(BtrtBox::read_box(reader, s)?);
+                }
+                BoxType::SinfBox => {
+                    sinf = Some(SinfBox::read_box(reader, s)?);
+                }
+                _ => {}
             }
+            skip_bytes_to(reader, current + s)?;
+            current = reader.stream_position()?;
         }
+
+        let avcc = avcc.ok_or(Error::InvalidData("avcc not found"))?;
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(Avc1Box {
+            in_band_parameter_sets: false,
+            data_reference_index,
+            width,
+            height,
+            horizresolution,
+            vertresolution,
+            frame_count,
+            compressor_name,
+            depth,
+            avcc,
+            dvcc,
+            pasp,
+            colr,
+            mdcv,
+            clli,
+            btrt,
+            sinf,
+        })
     }
 }
 
@@ -154,12 +271,32 @@ impl<W: Write> WriteBox<&mut W> for Avc1Box {
         writer.write_u32::<BigEndian>(self.vertresolution.raw_value())?;
         writer.write_u32::<BigEndian>(0)?; // reserved
         writer.write_u16::<BigEndian>(self.frame_count)?;
-        // skip compressorname
-        write_zeros(writer, 32)?;
+        write_compressor_name(writer, &self.compressor_name)?;
         writer.write_u16::<BigEndian>(self.depth)?;
         writer.write_i16::<BigEndian>(-1)?; // pre-defined
 
         self.avcc.write_box(writer)?;
+        if let Some(ref dvcc) = self.dvcc {
+            dvcc.write_box(writer)?;
+        }
+        if let Some(ref pasp) = self.pasp {
+            pasp.write_box(writer)?;
+        }
+        if let Some(ref colr) = self.colr {
+            colr.write_box(writer)?;
+        }
+        if let Some(ref mdcv) = self.mdcv {
+            mdcv.write_box(writer)?;
+        }
+        if let Some(ref clli) = self.clli {
+            clli.write_box(writer)?;
+        }
+        if let Some(ref btrt) = self.btrt {
+            btrt.write_box(writer)?;
+        }
+        if let Some(ref sinf) = self.sinf {
+            sinf.write_box(writer)?;
+        }
 
         Ok(size)
     }
@@ -315,12 +452,14 @@ mod tests {
     #[test]
     fn test_avc1() {
         let src_box = Avc1Box {
+            in_band_parameter_sets: false,
             data_reference_index: 1,
             width: 320,
             height: 240,
             horizresolution: FixedPointU16::new(0x48),
             vertresolution: FixedPointU16::new(0x48),
             frame_count: 1,
+            compressor_name: String::new(),
             depth: 24,
             avcc: AvcCBox {
                 configuration_version: 1,
@@ -338,6 +477,13 @@ mod tests {
                     bytes: vec![0x68, 0xEB, 0xE3, 0xCB, 0x22, 0xC0],
                 }],
             },
+            dvcc: None,
+            pasp: None,
+            colr: None,
+            mdcv: None,
+            clli: None,
+            btrt: None,
+            sinf: None,
         };
         let mut buf = Vec::new();
         src_box.write_box(&mut buf).unwrap();
@@ -351,4 +497,36 @@ mod tests {
         let dst_box = Avc1Box::read_box(&mut reader, header.size).unwrap();
         assert_eq!(src_box, dst_box);
     }
+
+    #[test]
+    fn test_avc3() {
+        let src_box = Avc1Box {
+            in_band_parameter_sets: true,
+            data_reference_index: 1,
+            width: 320,
+            height: 240,
+            avcc: AvcCBox {
+                configuration_version: 1,
+                avc_profile_indication: 100,
+                profile_compatibility: 0,
+                avc_level_indication: 13,
+                length_size_minus_one: 3,
+                sequence_parameter_sets: vec![],
+                picture_parameter_sets: vec![],
+            },
+            ..Default::default()
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::Avc3Box);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let mut dst_box = Avc1Box::read_box(&mut reader, header.size).unwrap();
+        dst_box.in_band_parameter_sets = header.name == BoxType::Avc3Box;
+        assert_eq!(src_box, dst_box);
+    }
 }
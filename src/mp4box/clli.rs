@@ -0,0 +1,98 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use serde::Serialize;
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::*;
+
+/// Content light level information, in cd/m².
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct ClliBox {
+    pub max_content_light_level: u16,
+    pub max_pic_average_light_level: u16,
+}
+
+impl ClliBox {
+    pub fn get_type(&self) -> BoxType {
+        BoxType::ClliBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        HEADER_SIZE + 4
+    }
+}
+
+impl Mp4Box for ClliBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "max_content_light_level={} max_pic_average_light_level={}",
+            self.max_content_light_level, self.max_pic_average_light_level
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for ClliBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let max_content_light_level = reader.read_u16::<BigEndian>()?;
+        let max_pic_average_light_level = reader.read_u16::<BigEndian>()?;
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(ClliBox {
+            max_content_light_level,
+            max_pic_average_light_level,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for ClliBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        writer.write_u16::<BigEndian>(self.max_content_light_level)?;
+        writer.write_u16::<BigEndian>(self.max_pic_average_light_level)?;
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_clli() {
+        let src_box = ClliBox {
+            max_content_light_level: 1000,
+            max_pic_average_light_level: 400,
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::ClliBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = ClliBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+}
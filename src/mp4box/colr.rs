@@ -0,0 +1,190 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use serde::Serialize;
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::*;
+
+/// Colour information. `nclx` (ISO/IEC 23091-2 code points) and QuickTime's
+/// `nclc` are parsed; ICC profiles (`rICC`, `prof`) are kept verbatim.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ColrBox {
+    pub colour_type: FourCC,
+    pub colour_primaries: u16,
+    pub transfer_characteristics: u16,
+    pub matrix_coefficients: u16,
+    pub full_range_flag: bool,
+
+    #[serde(skip_serializing)]
+    pub icc_profile: Vec<u8>,
+}
+
+impl Default for ColrBox {
+    fn default() -> Self {
+        ColrBox {
+            colour_type: FourCC::from(*b"nclx"),
+            colour_primaries: 2,
+            transfer_characteristics: 2,
+            matrix_coefficients: 2,
+            full_range_flag: false,
+            icc_profile: Vec::new(),
+        }
+    }
+}
+
+impl ColrBox {
+    pub fn get_type(&self) -> BoxType {
+        BoxType::ColrBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        match &self.colour_type.value {
+            b"nclx" => HEADER_SIZE + 4 + 7,
+            b"nclc" => HEADER_SIZE + 4 + 6,
+            _ => HEADER_SIZE + 4 + self.icc_profile.len() as u64,
+        }
+    }
+
+    fn has_code_points(&self) -> bool {
+        matches!(&self.colour_type.value, b"nclx" | b"nclc")
+    }
+}
+
+impl Mp4Box for ColrBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = if self.has_code_points() {
+            format!(
+                "colour_type={} colour_primaries={} transfer_characteristics={} matrix_coefficients={} full_range_flag={}",
+                self.colour_type,
+                self.colour_primaries,
+                self.transfer_characteristics,
+                self.matrix_coefficients,
+                self.full_range_flag
+            )
+        } else {
+            format!(
+                "colour_type={} icc_profile_size={}",
+                self.colour_type,
+                self.icc_profile.len()
+            )
+        };
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for ColrBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        if size < HEADER_SIZE + 4 {
+            return Err(Error::InvalidData("colr box is too small"));
+        }
+        let colour_type = FourCC::from(reader.read_u32::<BigEndian>()?);
+
+        let mut colr = ColrBox {
+            colour_type,
+            ..ColrBox::default()
+        };
+        match &colour_type.value {
+            b"nclx" | b"nclc" => {
+                colr.colour_primaries = reader.read_u16::<BigEndian>()?;
+                colr.transfer_characteristics = reader.read_u16::<BigEndian>()?;
+                colr.matrix_coefficients = reader.read_u16::<BigEndian>()?;
+                if &colour_type.value == b"nclx" && size >= HEADER_SIZE + 4 + 7 {
+                    colr.full_range_flag = reader.read_u8()? & 0x80 != 0;
+                }
+            }
+            _ => {
+                let mut icc_profile = vec![0u8; (size - HEADER_SIZE - 4) as usize];
+                reader.read_exact(&mut icc_profile)?;
+                colr.icc_profile = icc_profile;
+            }
+        }
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(colr)
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for ColrBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        writer.write_u32::<BigEndian>((&self.colour_type).into())?;
+        if self.has_code_points() {
+            writer.write_u16::<BigEndian>(self.colour_primaries)?;
+            writer.write_u16::<BigEndian>(self.transfer_characteristics)?;
+            writer.write_u16::<BigEndian>(self.matrix_coefficients)?;
+            if &self.colour_type.value == b"nclx" {
+                writer.write_u8(if self.full_range_flag { 0x80 } else { 0 })?;
+            }
+        } else {
+            writer.write_all(&self.icc_profile)?;
+        }
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_colr_nclx() {
+        let src_box = ColrBox {
+            colour_type: str::parse("nclx").unwrap(),
+            colour_primaries: 9,
+            transfer_characteristics: 16,
+            matrix_coefficients: 9,
+            full_range_flag: true,
+            icc_profile: Vec::new(),
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::ColrBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = ColrBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+
+    #[test]
+    fn test_colr_icc() {
+        let src_box = ColrBox {
+            colour_type: str::parse("prof").unwrap(),
+            icc_profile: vec![1, 2, 3, 4, 5],
+            ..ColrBox::default()
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::ColrBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = ColrBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+}
@@ -0,0 +1,167 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use serde::Serialize;
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::*;
+
+/// Composition to decode timeline mapping, written alongside a `ctts` with
+/// negative offsets. Fields are stored as 32-bit values in version 0 and
+/// 64-bit values in version 1.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct CslgBox {
+    pub version: u8,
+    pub flags: u32,
+    pub composition_to_dts_shift: i64,
+    pub least_decode_to_display_delta: i64,
+    pub greatest_decode_to_display_delta: i64,
+    pub composition_start_time: i64,
+    pub composition_end_time: i64,
+}
+
+impl CslgBox {
+    pub fn get_type(&self) -> BoxType {
+        BoxType::CslgBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        if self.version == 1 {
+            HEADER_SIZE + HEADER_EXT_SIZE + 40
+        } else {
+            HEADER_SIZE + HEADER_EXT_SIZE + 20
+        }
+    }
+}
+
+impl Mp4Box for CslgBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "composition_to_dts_shift={} least_decode_to_display_delta={} greatest_decode_to_display_delta={}",
+            self.composition_to_dts_shift,
+            self.least_decode_to_display_delta,
+            self.greatest_decode_to_display_delta
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for CslgBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let (version, flags) = read_box_header_ext(reader)?;
+
+        let mut values = [0i64; 5];
+        for value in values.iter_mut() {
+            *value = if version == 1 {
+                reader.read_i64::<BigEndian>()?
+            } else {
+                reader.read_i32::<BigEndian>()? as i64
+            };
+        }
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(CslgBox {
+            version,
+            flags,
+            composition_to_dts_shift: values[0],
+            least_decode_to_display_delta: values[1],
+            greatest_decode_to_display_delta: values[2],
+            composition_start_time: values[3],
+            composition_end_time: values[4],
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for CslgBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        write_box_header_ext(writer, self.version, self.flags)?;
+
+        let values = [
+            self.composition_to_dts_shift,
+            self.least_decode_to_display_delta,
+            self.greatest_decode_to_display_delta,
+            self.composition_start_time,
+            self.composition_end_time,
+        ];
+        for value in values.iter() {
+            if self.version == 1 {
+                writer.write_i64::<BigEndian>(*value)?;
+            } else {
+                writer.write_i32::<BigEndian>(*value as i32)?;
+            }
+        }
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_cslg32() {
+        let src_box = CslgBox {
+            version: 0,
+            flags: 0,
+            composition_to_dts_shift: 1024,
+            least_decode_to_display_delta: -1024,
+            greatest_decode_to_display_delta: 2048,
+            composition_start_time: 0,
+            composition_end_time: 10240,
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::CslgBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = CslgBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+
+    #[test]
+    fn test_cslg64() {
+        let src_box = CslgBox {
+            version: 1,
+            flags: 0,
+            composition_to_dts_shift: 1024,
+            least_decode_to_display_delta: -1024,
+            greatest_decode_to_display_delta: 2048,
+            composition_start_time: 0,
+            composition_end_time: u32::MAX as i64 + 10240,
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::CslgBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = CslgBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+}
@@ -5,7 +5,7 @@ use crate::mp4box::*;
 
 #[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
 pub struct DinfBox {
-    dref: DrefBox,
+    pub dref: DrefBox,
 }
 
 impl DinfBox {
@@ -93,6 +93,7 @@ impl<W: Write> WriteBox<&mut W> for DinfBox {
 pub struct DrefBox {
     pub version: u8,
     pub flags: u32,
+    pub entry_count: u32,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub url: Option<UrlBox>,
@@ -103,6 +104,7 @@ impl Default for DrefBox {
         DrefBox {
             version: 0,
             flags: 0,
+            entry_count: 1,
             url: Some(UrlBox::default()),
         }
     }
@@ -184,6 +186,7 @@ impl<R: Read + Seek> ReadBox<&mut R> for DrefBox {
         Ok(DrefBox {
             version,
             flags,
+            entry_count,
             url,
         })
     }
@@ -196,7 +199,8 @@ impl<W: Write> WriteBox<&mut W> for DrefBox {
 
         write_box_header_ext(writer, self.version, self.flags)?;
 
-        writer.write_u32::<BigEndian>(1)?;
+        // Only the url entry is kept, so that is all there is to count.
+        writer.write_u32::<BigEndian>(self.url.is_some() as u32)?;
 
         if let Some(ref url) = self.url {
             url.write_box(writer)?;
@@ -232,7 +236,7 @@ impl UrlBox {
         let mut size = HEADER_SIZE + HEADER_EXT_SIZE;
 
         if !self.location.is_empty() {
-            size += self.location.bytes().len() as u64 + 1;
+            size += self.location.len() as u64 + 1;
         }
 
         size
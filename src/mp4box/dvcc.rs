@@ -0,0 +1,175 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use serde::Serialize;
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::*;
+
+/// Dolby Vision decoder configuration record.
+///
+/// Stored as `dvcC` for profiles up to 7 and as `dvvC` for profiles 8 to 10.
+/// Written as `dvvC` if `dvvc` is set and as `dvcC` otherwise, so that the
+/// box type read is kept.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct DvccBox {
+    pub dvvc: bool,
+    pub dv_version_major: u8,
+    pub dv_version_minor: u8,
+    pub dv_profile: u8,
+    pub dv_level: u8,
+    pub rpu_present: bool,
+    pub el_present: bool,
+    pub bl_present: bool,
+    pub dv_bl_signal_compatibility_id: u8,
+}
+
+impl DvccBox {
+    pub fn get_type(&self) -> BoxType {
+        if self.dvvc {
+            BoxType::DvvCBox
+        } else {
+            BoxType::DvcCBox
+        }
+    }
+
+    pub fn get_size(&self) -> u64 {
+        HEADER_SIZE + 24
+    }
+}
+
+impl Mp4Box for DvccBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "dv_profile={} dv_level={} rpu_present={} el_present={} bl_present={}",
+            self.dv_profile, self.dv_level, self.rpu_present, self.el_present, self.bl_present
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for DvccBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let dv_version_major = reader.read_u8()?;
+        let dv_version_minor = reader.read_u8()?;
+
+        let params = reader.read_u16::<BigEndian>()?;
+        let dv_profile = ((params & 0b1111_1110_0000_0000) >> 9) as u8;
+        let dv_level = ((params & 0b0000_0001_1111_1000) >> 3) as u8;
+        let rpu_present = (params & 0b0000_0000_0000_0100) > 0;
+        let el_present = (params & 0b0000_0000_0000_0010) > 0;
+        let bl_present = (params & 0b0000_0000_0000_0001) > 0;
+
+        let dv_bl_signal_compatibility_id = (reader.read_u8()? & 0b1111_0000) >> 4;
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(DvccBox {
+            dvvc: false,
+            dv_version_major,
+            dv_version_minor,
+            dv_profile,
+            dv_level,
+            rpu_present,
+            el_present,
+            bl_present,
+            dv_bl_signal_compatibility_id,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for DvccBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        writer.write_u8(self.dv_version_major)?;
+        writer.write_u8(self.dv_version_minor)?;
+
+        let dv_profile = ((self.dv_profile & 0b111_1111) as u16) << 9;
+        let dv_level = ((self.dv_level & 0b11_1111) as u16) << 3;
+        let rpu_present = u16::from(self.rpu_present) << 2;
+        let el_present = u16::from(self.el_present) << 1;
+        let bl_present = u16::from(self.bl_present);
+        writer.write_u16::<BigEndian>(
+            dv_profile | dv_level | rpu_present | el_present | bl_present,
+        )?;
+
+        writer.write_u8((self.dv_bl_signal_compatibility_id & 0b1111) << 4)?;
+        write_zeros(writer, 19)?; // reserved
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_dvcc() {
+        let src_box = DvccBox {
+            dvvc: false,
+            dv_version_major: 1,
+            dv_version_minor: 0,
+            dv_profile: 5,
+            dv_level: 6,
+            rpu_present: true,
+            el_present: false,
+            bl_present: true,
+            dv_bl_signal_compatibility_id: 0,
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::DvcCBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = DvccBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+
+    #[test]
+    fn test_dvvc() {
+        let src_box = DvccBox {
+            dvvc: true,
+            dv_version_major: 1,
+            dv_version_minor: 0,
+            dv_profile: 8,
+            dv_level: 9,
+            rpu_present: true,
+            el_present: false,
+            bl_present: true,
+            dv_bl_signal_compatibility_id: 4,
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::DvvCBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let mut dst_box = DvccBox::read_box(&mut reader, header.size).unwrap();
+        dst_box.dvvc = header.name == BoxType::DvvCBox;
+        assert_eq!(src_box, dst_box);
+    }
+}
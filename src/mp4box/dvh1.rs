@@ -0,0 +1,319 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use serde::Serialize;
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::hev1::HvcCBox;
+use crate::mp4box::*;
+
+/// Dolby Vision HEVC sample entry.
+///
+/// Written as `dvhe` when parameter sets may be carried in-band and as
+/// `dvh1` otherwise.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Dvh1Box {
+    pub in_band_parameter_sets: bool,
+    pub data_reference_index: u16,
+    pub width: u16,
+    pub height: u16,
+
+    #[serde(with = "value_u32")]
+    pub horizresolution: FixedPointU16,
+
+    #[serde(with = "value_u32")]
+    pub vertresolution: FixedPointU16,
+    pub frame_count: u16,
+    pub compressor_name: String,
+    pub depth: u16,
+    pub hvcc: HvcCBox,
+    pub dvcc: DvccBox,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pasp: Option<PaspBox>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub colr: Option<ColrBox>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mdcv: Option<MdcvBox>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clli: Option<ClliBox>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub btrt: Option<BtrtBox>,
+}
+
+impl Default for Dvh1Box {
+    fn default() -> Self {
+        Dvh1Box {
+            in_band_parameter_sets: false,
+            data_reference_index: 0,
+            width: 0,
+            height: 0,
+            horizresolution: FixedPointU16::new(0x48),
+            vertresolution: FixedPointU16::new(0x48),
+            frame_count: 1,
+            compressor_name: String::new(),
+            depth: 0x0018,
+            hvcc: HvcCBox::default(),
+            dvcc: DvccBox::default(),
+            pasp: None,
+            colr: None,
+            mdcv: None,
+            clli: None,
+            btrt: None,
+        }
+    }
+}
+
+impl Dvh1Box {
+    pub fn get_type(&self) -> BoxType {
+        if self.in_band_parameter_sets {
+            BoxType::DvheBox
+        } else {
+            BoxType::Dvh1Box
+        }
+    }
+
+    pub fn get_size(&self) -> u64 {
+        let mut size = HEADER_SIZE + 8 + 70 + self.hvcc.box_size() + self.dvcc.box_size();
+        if let Some(ref pasp) = self.pasp {
+            size += pasp.box_size();
+        }
+        if let Some(ref colr) = self.colr {
+            size += colr.box_size();
+        }
+        if let Some(ref mdcv) = self.mdcv {
+            size += mdcv.box_size();
+        }
+        if let Some(ref clli) = self.clli {
+            size += clli.box_size();
+        }
+        if let Some(ref btrt) = self.btrt {
+            size += btrt.box_size();
+        }
+        size
+    }
+}
+
+impl Mp4Box for Dvh1Box {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "data_reference_index={} width={} height={} frame_count={} dv_profile={} dv_level={}",
+            self.data_reference_index,
+            self.width,
+            self.height,
+            self.frame_count,
+            self.dvcc.dv_profile,
+            self.dvcc.dv_level
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for Dvh1Box {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        reader.read_u32::<BigEndian>()?; // reserved
+        reader.read_u16::<BigEndian>()?; // reserved
+        let data_reference_index = reader.read_u16::<BigEndian>()?;
+
+        reader.read_u32::<BigEndian>()?; // pre-defined, reserved
+        reader.read_u64::<BigEndian>()?; // pre-defined
+        reader.read_u32::<BigEndian>()?; // pre-defined
+        let width = reader.read_u16::<BigEndian>()?;
+        let height = reader.read_u16::<BigEndian>()?;
+        let horizresolution = FixedPointU16::new_raw(reader.read_u32::<BigEndian>()?);
+        let vertresolution = FixedPointU16::new_raw(reader.read_u32::<BigEndian>()?);
+        reader.read_u32::<BigEndian>()?; // reserved
+        let frame_count = reader.read_u16::<BigEndian>()?;
+        let compressor_name = read_compressor_name(reader)?;
+        let depth = reader.read_u16::<BigEndian>()?;
+        reader.read_i16::<BigEndian>()?; // pre-defined
+
+        let mut hvcc = None;
+        let mut dvcc = None;
+        let mut pasp = None;
+        let mut colr = None;
+        let mut mdcv = None;
+        let mut clli = None;
+        let mut btrt = None;
+
+        let mut current = reader.stream_position()?;
+        let end = start + size;
+        while current < end {
+            let header = BoxHeader::read(reader)?;
+            let BoxHeader { name, size: s } = header;
+            if s > size {
+                return Err(Error::InvalidData(
+                    "dvh1 box contains a box with a larger size than it",
+                ));
+            }
+            match name {
+                BoxType::HvcCBox => {
+                    hvcc = Some(HvcCBox::read_box(reader, s)?);
+                }
+                BoxType::DvcCBox | BoxType::DvvCBox => {
+                    let mut entry = DvccBox::read_box(reader, s)?;
+                    entry.dvvc = name == BoxType::DvvCBox;
+                    dvcc = Some(entry);
+                }
+                BoxType::PaspBox => {
+                    pasp = Some(PaspBox::read_box(reader, s)?);
+                }
+                BoxType::ColrBox => {
+                    colr = Some(ColrBox::read_box(reader, s)?);
+                }
+                BoxType::MdcvBox => {
+                    mdcv = Some(MdcvBox::read_box(reader, s)?);
+                }
+                BoxType::ClliBox => {
+                    clli = Some(ClliBox::read_box(reader, s)?);
+                }
+                BoxType::BtrtBox => {
+                    btrt = Some(BtrtBox::read_box(reader, s)?);
+                }
+                _ => {}
+            }
+            skip_bytes_to(reader, current + s)?;
+            current = reader.stream_position()?;
+        }
+
+        let hvcc = hvcc.ok_or(Error::InvalidData("hvcc not found"))?;
+        let dvcc = dvcc.ok_or(Error::Box2NotFound(BoxType::DvcCBox, BoxType::DvvCBox))?;
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(Dvh1Box {
+            in_band_parameter_sets: false,
+            data_reference_index,
+            width,
+            height,
+            horizresolution,
+            vertresolution,
+            frame_count,
+            compressor_name,
+            depth,
+            hvcc,
+            dvcc,
+            pasp,
+            colr,
+            mdcv,
+            clli,
+            btrt,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for Dvh1Box {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        writer.write_u32::<BigEndian>(0)?; // reserved
+        writer.write_u16::<BigEndian>(0)?; // reserved
+        writer.write_u16::<BigEndian>(self.data_reference_index)?;
+
+        writer.write_u32::<BigEndian>(0)?; // pre-defined, reserved
+        writer.write_u64::<BigEndian>(0)?; // pre-defined
+        writer.write_u32::<BigEndian>(0)?; // pre-defined
+        writer.write_u16::<BigEndian>(self.width)?;
+        writer.write_u16::<BigEndian>(self.height)?;
+        writer.write_u32::<BigEndian>(self.horizresolution.raw_value())?;
+        writer.write_u32::<BigEndian>(self.vertresolution.raw_value())?;
+        writer.write_u32::<BigEndian>(0)?; // reserved
+        writer.write_u16::<BigEndian>(self.frame_count)?;
+        write_compressor_name(writer, &self.compressor_name)?;
+        writer.write_u16::<BigEndian>(self.depth)?;
+        writer.write_i16::<BigEndian>(-1)?; // pre-defined
+
+        self.hvcc.write_box(writer)?;
+        self.dvcc.write_box(writer)?;
+        if let Some(ref pasp) = self.pasp {
+            pasp.write_box(writer)?;
+        }
+        if let Some(ref colr) = self.colr {
+            colr.write_box(writer)?;
+        }
+        if let Some(ref mdcv) = self.mdcv {
+            mdcv.write_box(writer)?;
+        }
+        if let Some(ref clli) = self.clli {
+            clli.write_box(writer)?;
+        }
+        if let Some(ref btrt) = self.btrt {
+            btrt.write_box(writer)?;
+        }
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_dvh1() {
+        let src_box = Dvh1Box {
+            in_band_parameter_sets: false,
+            data_reference_index: 1,
+            width: 1920,
+            height: 1080,
+            horizresolution: FixedPointU16::new(0x48),
+            vertresolution: FixedPointU16::new(0x48),
+            frame_count: 1,
+            compressor_name: String::new(),
+            depth: 24,
+            hvcc: HvcCBox {
+                configuration_version: 1,
+                ..Default::default()
+            },
+            dvcc: DvccBox {
+                dvvc: false,
+                dv_version_major: 1,
+                dv_version_minor: 0,
+                dv_profile: 5,
+                dv_level: 9,
+                rpu_present: true,
+                el_present: false,
+                bl_present: true,
+                dv_bl_signal_compatibility_id: 0,
+            },
+            pasp: Some(PaspBox {
+                h_spacing: 4,
+                v_spacing: 3,
+            }),
+            colr: None,
+            mdcv: None,
+            clli: None,
+            btrt: None,
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::Dvh1Box);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = Dvh1Box::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+}
@@ -0,0 +1,352 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use serde::Serialize;
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::*;
+
+/// Enhanced AC-3 (Dolby Digital Plus) audio sample entry, as specified in
+/// ETSI TS 102 366 Annex F.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Eac3Box {
+    pub data_reference_index: u16,
+    pub channelcount: u16,
+    pub samplesize: u16,
+
+    #[serde(with = "value_u32")]
+    pub samplerate: FixedPointU16,
+    pub dec3: Dec3Box,
+}
+
+impl Default for Eac3Box {
+    fn default() -> Self {
+        Self {
+            data_reference_index: 0,
+            channelcount: 2,
+            samplesize: 16,
+            samplerate: FixedPointU16::new(48000),
+            dec3: Dec3Box::default(),
+        }
+    }
+}
+
+impl Eac3Box {
+    pub fn get_type(&self) -> BoxType {
+        BoxType::Eac3Box
+    }
+
+    pub fn get_size(&self) -> u64 {
+        HEADER_SIZE + 8 + 20 + self.dec3.box_size()
+    }
+}
+
+impl Mp4Box for Eac3Box {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "channel_count={} sample_size={} sample_rate={}",
+            self.channelcount,
+            self.samplesize,
+            self.samplerate.value()
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for Eac3Box {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        reader.read_u32::<BigEndian>()?; // reserved
+        reader.read_u16::<BigEndian>()?; // reserved
+        let data_reference_index = reader.read_u16::<BigEndian>()?;
+        reader.read_u64::<BigEndian>()?; // reserved
+        let channelcount = reader.read_u16::<BigEndian>()?;
+        let samplesize = reader.read_u16::<BigEndian>()?;
+        reader.read_u32::<BigEndian>()?; // pre-defined, reserved
+        let samplerate = FixedPointU16::new_raw(reader.read_u32::<BigEndian>()?);
+
+        let mut dec3 = None;
+        let end = start + size;
+        let mut current = reader.stream_position()?;
+        while current + HEADER_SIZE <= end {
+            let header = BoxHeader::read(reader)?;
+            let BoxHeader { name, size: s } = header;
+            if s < HEADER_SIZE || current + s > end {
+                return Err(Error::InvalidData(
+                    "ec-3 box contains a box with a larger size than it",
+                ));
+            }
+            if name == BoxType::Dec3Box {
+                dec3 = Some(Dec3Box::read_box(reader, s)?);
+            }
+            skip_bytes_to(reader, current + s)?;
+            current = reader.stream_position()?;
+        }
+        let dec3 = dec3.ok_or(Error::BoxNotFound(BoxType::Dec3Box))?;
+
+        skip_bytes_to(reader, end)?;
+
+        Ok(Eac3Box {
+            data_reference_index,
+            channelcount,
+            samplesize,
+            samplerate,
+            dec3,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for Eac3Box {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        writer.write_u32::<BigEndian>(0)?; // reserved
+        writer.write_u16::<BigEndian>(0)?; // reserved
+        writer.write_u16::<BigEndian>(self.data_reference_index)?;
+
+        writer.write_u64::<BigEndian>(0)?; // reserved
+        writer.write_u16::<BigEndian>(self.channelcount)?;
+        writer.write_u16::<BigEndian>(self.samplesize)?;
+        writer.write_u32::<BigEndian>(0)?; // reserved
+        writer.write_u32::<BigEndian>(self.samplerate.raw_value())?;
+
+        self.dec3.write_box(writer)?;
+
+        Ok(size)
+    }
+}
+
+/// One independent substream described by the EC3SpecificBox. `chan_loc`
+/// is only stored when the substream has dependent substreams.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct Ec3Substream {
+    pub fscod: u8,
+    pub bsid: u8,
+    pub asvc: bool,
+    pub bsmod: u8,
+    pub acmod: u8,
+    pub lfeon: bool,
+    pub num_dep_sub: u8,
+    pub chan_loc: u16,
+}
+
+impl Ec3Substream {
+    fn size(&self) -> u64 {
+        if self.num_dep_sub > 0 {
+            4
+        } else {
+            3
+        }
+    }
+}
+
+/// The EC3SpecificBox: the bitrate in kbit/s and one entry per independent
+/// substream.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct Dec3Box {
+    pub data_rate: u16,
+    pub substreams: Vec<Ec3Substream>,
+}
+
+impl Dec3Box {
+    pub fn get_type(&self) -> BoxType {
+        BoxType::Dec3Box
+    }
+
+    pub fn get_size(&self) -> u64 {
+        HEADER_SIZE + 2 + self.substreams.iter().map(Ec3Substream::size).sum::<u64>()
+    }
+}
+
+impl Mp4Box for Dec3Box {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "data_rate={} num_ind_sub={}",
+            self.data_rate,
+            self.substreams.len()
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for Dec3Box {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let bits = reader.read_u16::<BigEndian>()?;
+        let data_rate = bits >> 3;
+        let num_ind_sub = (bits & 0x7) + 1;
+
+        let mut substreams = Vec::with_capacity(num_ind_sub as usize);
+        for _ in 0..num_ind_sub {
+            let bits = reader.read_u24::<BigEndian>()?;
+            let num_dep_sub = (bits >> 1) as u8 & 0xF;
+            let chan_loc = if num_dep_sub > 0 {
+                ((bits as u16 & 0x1) << 8) | reader.read_u8()? as u16
+            } else {
+                0
+            };
+            substreams.push(Ec3Substream {
+                fscod: (bits >> 22) as u8 & 0x3,
+                bsid: (bits >> 17) as u8 & 0x1F,
+                asvc: (bits >> 15) & 0x1 == 1,
+                bsmod: (bits >> 12) as u8 & 0x7,
+                acmod: (bits >> 9) as u8 & 0x7,
+                lfeon: (bits >> 8) & 0x1 == 1,
+                num_dep_sub,
+                chan_loc,
+            });
+        }
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(Dec3Box {
+            data_rate,
+            substreams,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for Dec3Box {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        if self.substreams.is_empty() || self.substreams.len() > 8 {
+            return Err(Error::InvalidData(
+                "dec3 must describe between 1 and 8 independent substreams",
+            ));
+        }
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        let num_ind_sub = self.substreams.len() as u16 - 1;
+        writer.write_u16::<BigEndian>((self.data_rate << 3) | num_ind_sub)?;
+        for substream in self.substreams.iter() {
+            let num_dep_sub = substream.num_dep_sub as u32 & 0xF;
+            let mut bits = (substream.fscod as u32 & 0x3) << 22
+                | (substream.bsid as u32 & 0x1F) << 17
+                | (substream.asvc as u32) << 15
+                | (substream.bsmod as u32 & 0x7) << 12
+                | (substream.acmod as u32 & 0x7) << 9
+                | (substream.lfeon as u32) << 8
+                | num_dep_sub << 1;
+            if num_dep_sub > 0 {
+                bits |= (substream.chan_loc as u32 >> 8) & 0x1;
+                writer.write_u24::<BigEndian>(bits)?;
+                writer.write_u8(substream.chan_loc as u8)?;
+            } else {
+                writer.write_u24::<BigEndian>(bits)?;
+            }
+        }
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_eac3() {
+        // 5.1 at 48 kHz and 640 kbit/s, no dependent substreams.
+        let src_box = Eac3Box {
+            data_reference_index: 1,
+            channelcount: 2,
+            samplesize: 16,
+            samplerate: FixedPointU16::new(48000),
+            dec3: Dec3Box {
+                data_rate: 640,
+                substreams: vec![Ec3Substream {
+                    fscod: 0,
+                    bsid: 16,
+                    asvc: false,
+                    bsmod: 0,
+                    acmod: 7,
+                    lfeon: true,
+                    num_dep_sub: 0,
+                    chan_loc: 0,
+                }],
+            },
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::Eac3Box);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = Eac3Box::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+
+    #[test]
+    fn test_dec3_dependent_substreams() {
+        // 7.1: a 5.1 independent substream plus one dependent substream
+        // carrying Lrs/Rrs (chan_loc bit 6), then a second, stereo
+        // independent substream with an associated service.
+        let src_box = Dec3Box {
+            data_rate: 1024,
+            substreams: vec![
+                Ec3Substream {
+                    fscod: 0,
+                    bsid: 16,
+                    asvc: false,
+                    bsmod: 0,
+                    acmod: 7,
+                    lfeon: true,
+                    num_dep_sub: 1,
+                    chan_loc: 0x140,
+                },
+                Ec3Substream {
+                    fscod: 0,
+                    bsid: 16,
+                    asvc: true,
+                    bsmod: 2,
+                    acmod: 2,
+                    lfeon: false,
+                    num_dep_sub: 0,
+                    chan_loc: 0,
+                },
+            ],
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+        assert_eq!(src_box.box_size(), 8 + 2 + 4 + 3);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::Dec3Box);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = Dec3Box::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+}
@@ -0,0 +1,110 @@
+use byteorder::WriteBytesExt;
+use serde::Serialize;
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::*;
+
+/// Extended language tag (BCP 47, e.g. `pt-BR`), overriding the ISO 639-2/T
+/// code in `mdhd`.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct ElngBox {
+    pub version: u8,
+    pub flags: u32,
+    pub extended_language: String,
+}
+
+impl ElngBox {
+    pub fn get_type(&self) -> BoxType {
+        BoxType::ElngBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        HEADER_SIZE + HEADER_EXT_SIZE + self.extended_language.len() as u64 + 1
+    }
+}
+
+impl Mp4Box for ElngBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!("extended_language={}", self.extended_language);
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for ElngBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let (version, flags) = read_box_header_ext(reader)?;
+
+        let buf_size = size
+            .checked_sub(HEADER_SIZE + HEADER_EXT_SIZE)
+            .ok_or(Error::InvalidData("elng size too small"))?;
+        let mut buf = vec![0u8; buf_size as usize];
+        reader.read_exact(&mut buf)?;
+        if let Some(end) = buf.iter().position(|&b| b == b'\0') {
+            buf.truncate(end);
+        }
+        let extended_language = String::from_utf8(buf).unwrap_or_default();
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(ElngBox {
+            version,
+            flags,
+            extended_language,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for ElngBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        write_box_header_ext(writer, self.version, self.flags)?;
+
+        writer.write_all(self.extended_language.as_bytes())?;
+        writer.write_u8(0)?;
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_elng() {
+        let src_box = ElngBox {
+            version: 0,
+            flags: 0,
+            extended_language: String::from("pt-BR"),
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::ElngBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = ElngBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+}
@@ -0,0 +1,225 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use serde::Serialize;
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::*;
+
+/// QuickTime base media information header, used by text and other
+/// non-audiovisual tracks in place of `vmhd`/`smhd`.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct GmhdBox {
+    pub gmin: GminBox,
+
+    /// Display matrix from the `text` media information atom of text tracks.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text_matrix: Option<[i32; 9]>,
+}
+
+impl GmhdBox {
+    pub fn get_type(&self) -> BoxType {
+        BoxType::GmhdBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        let mut size = HEADER_SIZE + self.gmin.box_size();
+        if self.text_matrix.is_some() {
+            size += HEADER_SIZE + 36;
+        }
+        size
+    }
+}
+
+impl Mp4Box for GmhdBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "graphics_mode={} balance={}",
+            self.gmin.graphics_mode, self.gmin.balance
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for GmhdBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let mut gmin = None;
+        let mut text_matrix = None;
+
+        let mut current = reader.stream_position()?;
+        let end = start + size;
+        while current < end {
+            let header = BoxHeader::read(reader)?;
+            let BoxHeader { name, size: s } = header;
+            if s > size {
+                return Err(Error::InvalidData(
+                    "gmhd box contains a box with a larger size than it",
+                ));
+            }
+            match name {
+                BoxType::GminBox => {
+                    gmin = Some(GminBox::read_box(reader, s)?);
+                }
+                BoxType::TextBox if s >= HEADER_SIZE + 36 => {
+                    let mut matrix = [0i32; 9];
+                    for value in matrix.iter_mut() {
+                        *value = reader.read_i32::<BigEndian>()?;
+                    }
+                    text_matrix = Some(matrix);
+                }
+                _ => {}
+            }
+            skip_bytes_to(reader, current + s)?;
+            current = reader.stream_position()?;
+        }
+
+        let gmin = gmin.ok_or(Error::BoxNotFound(BoxType::GminBox))?;
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(GmhdBox { gmin, text_matrix })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for GmhdBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        self.gmin.write_box(writer)?;
+        if let Some(ref matrix) = self.text_matrix {
+            BoxHeader::new(BoxType::TextBox, HEADER_SIZE + 36).write(writer)?;
+            for value in matrix.iter() {
+                writer.write_i32::<BigEndian>(*value)?;
+            }
+        }
+
+        Ok(size)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GminBox {
+    pub version: u8,
+    pub flags: u32,
+    pub graphics_mode: u16,
+    pub opcolor: [u16; 3],
+    pub balance: i16,
+}
+
+impl Default for GminBox {
+    fn default() -> Self {
+        GminBox {
+            version: 0,
+            flags: 0,
+            graphics_mode: 0x40, // dither copy
+            opcolor: [0x8000; 3],
+            balance: 0,
+        }
+    }
+}
+
+impl Mp4Box for GminBox {
+    fn box_type(&self) -> BoxType {
+        BoxType::GminBox
+    }
+
+    fn box_size(&self) -> u64 {
+        HEADER_SIZE + HEADER_EXT_SIZE + 12
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "graphics_mode={} balance={}",
+            self.graphics_mode, self.balance
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for GminBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let (version, flags) = read_box_header_ext(reader)?;
+
+        let graphics_mode = reader.read_u16::<BigEndian>()?;
+        let opcolor = [
+            reader.read_u16::<BigEndian>()?,
+            reader.read_u16::<BigEndian>()?,
+            reader.read_u16::<BigEndian>()?,
+        ];
+        let balance = reader.read_i16::<BigEndian>()?;
+        reader.read_u16::<BigEndian>()?; // reserved
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(GminBox {
+            version,
+            flags,
+            graphics_mode,
+            opcolor,
+            balance,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for GminBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        write_box_header_ext(writer, self.version, self.flags)?;
+
+        writer.write_u16::<BigEndian>(self.graphics_mode)?;
+        for color in self.opcolor.iter() {
+            writer.write_u16::<BigEndian>(*color)?;
+        }
+        writer.write_i16::<BigEndian>(self.balance)?;
+        writer.write_u16::<BigEndian>(0)?; // reserved
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_gmhd() {
+        let src_box = GmhdBox {
+            gmin: GminBox::default(),
+            text_matrix: Some([0x10000, 0, 0, 0, 0x10000, 0, 0, 0, 0x40000000]),
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::GmhdBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = GmhdBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+}
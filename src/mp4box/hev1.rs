@@ -16,8 +16,31 @@ pub struct Hev1Box {
     #[serde(with = "value_u32")]
     pub vertresolution: FixedPointU16,
     pub frame_count: u16,
+    pub compressor_name: String,
     pub depth: u16,
     pub hvcc: HvcCBox,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dvcc: Option<DvccBox>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pasp: Option<PaspBox>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub colr: Option<ColrBox>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mdcv: Option<MdcvBox>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clli: Option<ClliBox>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub btrt: Option<BtrtBox>,
+
+    /// Present when the entry is protected and stored as `encv`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sinf: Option<SinfBox>,
 }
 
 impl Default for Hev1Box {
@@ -29,14 +52,23 @@ impl Default for Hev1Box {
             horizresolution: FixedPointU16::new(0x48),
             vertresolution: FixedPointU16::new(0x48),
             frame_count: 1,
+            compressor_name: String::new(),
             depth: 0x0018,
             hvcc: HvcCBox::default(),
+            dvcc: None,
+            pasp: None,
+            colr: None,
+            mdcv: None,
+            clli: None,
+            btrt: None,
+            sinf: None,
         }
     }
 }
 
 impl Hev1Box {
     pub fn new(config: &HevcConfig) -> Self {
+        let hdr = config.hdr.as_ref();
         Hev1Box {
             data_reference_index: 1,
             width: config.width,
@@ -44,17 +76,55 @@ impl Hev1Box {
             horizresolution: FixedPointU16::new(0x48),
             vertresolution: FixedPointU16::new(0x48),
             frame_count: 1,
+            compressor_name: String::new(),
             depth: 0x0018,
-            hvcc: HvcCBox::new(),
+            hvcc: HvcCBox::from_parameter_sets(
+                &config.video_param_set,
+                &config.seq_param_set,
+                &config.pic_param_set,
+            ),
+            dvcc: None,
+            pasp: None,
+            colr: hdr.and_then(|hdr| hdr.colr.clone()),
+            mdcv: hdr.and_then(|hdr| hdr.mdcv.clone()),
+            clli: hdr.and_then(|hdr| hdr.clli.clone()),
+            btrt: None,
+            sinf: None,
         }
     }
 
     pub fn get_type(&self) -> BoxType {
-        BoxType::Hev1Box
+        if self.sinf.is_some() {
+            BoxType::EncvBox
+        } else {
+            BoxType::Hev1Box
+        }
     }
 
     pub fn get_size(&self) -> u64 {
-        HEADER_SIZE + 8 + 70 + self.hvcc.box_size()
+        let mut size = HEADER_SIZE + 8 + 70 + self.hvcc.box_size();
+        if let Some(ref dvcc) = self.dvcc {
+            size += dvcc.box_size();
+        }
+        if let Some(ref pasp) = self.pasp {
+            size += pasp.box_size();
+        }
+        if let Some(ref colr) = self.colr {
+            size += colr.box_size();
+        }
+        if let Some(ref mdcv) = self.mdcv {
+            size += mdcv.box_size();
+        }
+        if let Some(ref clli) = self.clli {
+            size += clli.box_size();
+        }
+        if let Some(ref btrt) = self.btrt {
+            size += btrt.box_size();
+        }
+        if let Some(ref sinf) = self.sinf {
+            size += sinf.box_size();
+        }
+        size
     }
 }
 
@@ -97,35 +167,84 @@ impl<R: Read + Seek> ReadBox<&mut R> for Hev1Box {
         let vertresolution = FixedPointU16::new_raw(reader.read_u32::<BigEndian>()?);
         reader.read_u32::<BigEndian>()?; // reserved
         let frame_count = reader.read_u16::<BigEndian>()?;
-        skip_bytes(reader, 32)?; // compressorname
+        let compressor_name = read_compressor_name(reader)?;
         let depth = reader.read_u16::<BigEndian>()?;
         reader.read_i16::<BigEndian>()?; // pre-defined
 
-        let header = BoxHeader::read(reader)?;
-        let BoxHeader { name, size: s } = header;
-        if s > size {
-            return Err(Error::InvalidData(
-                "hev1 box contains a box with a larger size than it",
-            ));
-        }
-        if name == BoxType::HvcCBox {
-            let hvcc = HvcCBox::read_box(reader, s)?;
-
-            skip_bytes_to(reader, start + size)?;
-
-            Ok(Hev1Box {
-                data_reference_index,
-                width,
-                height,
-                horizresolution,
-                vertresolution,
-                frame_count,
-                depth,
-                hvcc,
-            })
-        } else {
-            Err(Error::InvalidData("hvcc not found"))
+        let mut hvcc = None;
+        let mut dvcc = None;
+        let mut pasp = None;
+        let mut colr = None;
+        let mut mdcv = None;
+        let mut clli = None;
+        let mut btrt = None;
+        let mut sinf = None;
+
+        let mut current = reader.stream_position()?;
+        let end = start + size;
+        while current < end {
+            let header = BoxHeader::read(reader)?;
+            let BoxHeader { name, size: s } = header;
+            if s > size {
+                return Err(Error::InvalidData(
+                    "hev1 box contains a box with a larger size than it",
+                ));
+            }
+            match name {
+                BoxType::HvcCBox => {
+                    hvcc = Some(HvcCBox::read_box(reader, s)?);
+                }
+                BoxType::DvcCBox | BoxType::DvvCBox => {
+                    let mut entry = DvccBox::read_box(reader, s)?;
+                    entry.dvvc = name == BoxType::DvvCBox;
+                    dvcc = Some(entry);
+                }
+                BoxType::PaspBox => {
+                    pasp = Some(PaspBox::read_box(reader, s)?);
+                }
+                BoxType::ColrBox => {
+                    colr = Some(ColrBox::read_box(reader, s)?);
+                }
+                BoxType::MdcvBox => {
+                    mdcv = Some(MdcvBox::read_box(reader, s)?);
+                }
+                BoxType::ClliBox => {
+                    clli = Some(ClliBox::read_box(reader, s)?);
+                }
+                BoxType::BtrtBox => {
+                    btrt = Some(BtrtBox::read_box(reader, s)?);
+                }
+                BoxType::SinfBox => {
+                    sinf = Some(SinfBox::read_box(reader, s)?);
+                }
+                _ => {}
+            }
+            skip_bytes_to(reader, current + s)?;
+            current = reader.stream_position()?;
         }
+
+        let hvcc = hvcc.ok_or(Error::InvalidData("hvcc not found"))?;
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(Hev1Box {
+            data_reference_index,
+            width,
+            height,
+            horizresolution,
+            vertresolution,
+            frame_count,
+            compressor_name,
+            depth,
+            hvcc,
+            dvcc,
+            pasp,
+            colr,
+            mdcv,
+            clli,
+            btrt,
+            sinf,
+        })
     }
 }
 
@@ -147,12 +266,32 @@ impl<W: Write> WriteBox<&mut W> for Hev1Box {
         writer.write_u32::<BigEndian>(self.vertresolution.raw_value())?;
         writer.write_u32::<BigEndian>(0)?; // reserved
         writer.write_u16::<BigEndian>(self.frame_count)?;
-        // skip compressorname
-        write_zeros(writer, 32)?;
+        write_compressor_name(writer, &self.compressor_name)?;
         writer.write_u16::<BigEndian>(self.depth)?;
         writer.write_i16::<BigEndian>(-1)?; // pre-defined
 
         self.hvcc.write_box(writer)?;
+        if let Some(ref dvcc) = self.dvcc {
+            dvcc.write_box(writer)?;
+        }
+        if let Some(ref pasp) = self.pasp {
+            pasp.write_box(writer)?;
+        }
+        if let Some(ref colr) = self.colr {
+            colr.write_box(writer)?;
+        }
+        if let Some(ref mdcv) = self.mdcv {
+            mdcv.write_box(writer)?;
+        }
+        if let Some(ref clli) = self.clli {
+            clli.write_box(writer)?;
+        }
+        if let Some(ref btrt) = self.btrt {
+            btrt.write_box(writer)?;
+        }
+        if let Some(ref sinf) = self.sinf {
+            sinf.write_box(writer)?;
+        }
 
         Ok(size)
     }
@@ -187,6 +326,119 @@ impl HvcCBox {
             ..Default::default()
         }
     }
+
+    /// Builds the record for 4-byte NAL unit lengths from the VPS, SPS and
+    /// PPS NAL units, each stored in an array unless empty. The profile,
+    /// tier, level, chroma format and bit depths are read from the SPS.
+    pub fn from_parameter_sets(vps: &[u8], sps: &[u8], pps: &[u8]) -> Self {
+        let mut hvcc = match parse_sps(sps) {
+            Some(hvcc) => hvcc,
+            None => Self::new(),
+        };
+        hvcc.length_size_minus_one = 3;
+        for (nal_unit_type, nalu) in [(32, vps), (33, sps), (34, pps)] {
+            if !nalu.is_empty() {
+                hvcc.arrays.push(HvcCArray {
+                    // hev1 may also carry parameter sets in-band.
+                    completeness: false,
+                    nal_unit_type,
+                    nalus: vec![HvcCArrayNalu {
+                        size: nalu.len() as u16,
+                        data: nalu.to_vec(),
+                    }],
+                });
+            }
+        }
+        hvcc
+    }
+}
+
+/// Strips the emulation prevention bytes (the 0x03 in 0x000003) of a NAL
+/// unit, leaving its raw byte sequence payload.
+fn nal_unit_to_rbsp(nalu: &[u8]) -> Vec<u8> {
+    let mut rbsp = Vec::with_capacity(nalu.len());
+    let mut zeros = 0;
+    for &byte in nalu {
+        if zeros >= 2 && byte == 0x03 {
+            zeros = 0;
+            continue;
+        }
+        zeros = if byte == 0 { zeros + 1 } else { 0 };
+        rbsp.push(byte);
+    }
+    rbsp
+}
+
+/// Reads the fields the hvcC record repeats from an SPS NAL unit, as
+/// specified in section 7.3.2.2 of H.265.
+fn parse_sps(sps: &[u8]) -> Option<HvcCBox> {
+    let rbsp = nal_unit_to_rbsp(sps);
+    let mut r = BitReader::new(&rbsp);
+    r.read(16)?; // nal_unit_header
+    r.read(4)?; // sps_video_parameter_set_id
+    let max_sub_layers_minus1 = r.read(3)?;
+    let temporal_id_nested = r.read_flag()?;
+
+    // profile_tier_level(1, sps_max_sub_layers_minus1)
+    let general_profile_space = r.read(2)? as u8;
+    let general_tier_flag = r.read_flag()?;
+    let general_profile_idc = r.read(5)? as u8;
+    let general_profile_compatibility_flags = r.read(32)?;
+    let general_constraint_indicator_flag = (r.read(16)? as u64) << 32 | r.read(32)? as u64;
+    let general_level_idc = r.read(8)? as u8;
+    let mut sub_layers_present = Vec::new();
+    for _ in 0..max_sub_layers_minus1 {
+        let profile_present = r.read_flag()?;
+        let level_present = r.read_flag()?;
+        sub_layers_present.push((profile_present, level_present));
+    }
+    if max_sub_layers_minus1 > 0 {
+        for _ in max_sub_layers_minus1..8 {
+            r.read(2)?; // reserved_zero_2bits
+        }
+    }
+    for (profile_present, level_present) in sub_layers_present {
+        if profile_present {
+            r.read(32)?;
+            r.read(32)?;
+            r.read(24)?;
+        }
+        if level_present {
+            r.read(8)?;
+        }
+    }
+
+    r.read_uvlc()?; // sps_seq_parameter_set_id
+    let chroma_format_idc = r.read_uvlc()?;
+    if chroma_format_idc == 3 {
+        r.read_flag()?; // separate_colour_plane_flag
+    }
+    r.read_uvlc()?; // pic_width_in_luma_samples
+    r.read_uvlc()?; // pic_height_in_luma_samples
+    if r.read_flag()? {
+        // conformance_window offsets
+        for _ in 0..4 {
+            r.read_uvlc()?;
+        }
+    }
+    let bit_depth_luma_minus8 = r.read_uvlc()?;
+    let bit_depth_chroma_minus8 = r.read_uvlc()?;
+
+    Some(HvcCBox {
+        configuration_version: 1,
+        general_profile_space,
+        general_tier_flag,
+        general_profile_idc,
+        general_profile_compatibility_flags,
+        general_constraint_indicator_flag,
+        general_level_idc,
+        chroma_format_idc: chroma_format_idc as u8,
+        bit_depth_luma_minus8: bit_depth_luma_minus8 as u8,
+        bit_depth_chroma_minus8: bit_depth_chroma_minus8 as u8,
+        num_temporal_layers: max_sub_layers_minus1 as u8 + 1,
+        temporal_id_nested,
+        ..Default::default()
+    })
 }
 
 impl Mp4Box for HvcCBox {
@@ -248,8 +500,8 @@ impl<R: Read + Seek> ReadBox<&mut R> for HvcCBox {
     fn read_box(reader: &mut R, _size: u64) -> Result<Self> {
         let configuration_version = reader.read_u8()?;
         let params = reader.read_u8()?;
-        let general_profile_space = params & 0b11000000 >> 6;
-        let general_tier_flag = (params & 0b00100000 >> 5) > 0;
+        let general_profile_space = (params & 0b11000000) >> 6;
+        let general_tier_flag = (params & 0b00100000) > 0;
         let general_profile_idc = params & 0b00011111;
 
         let general_profile_compatibility_flags = reader.read_u32::<BigEndian>()?;
@@ -263,9 +515,9 @@ impl<R: Read + Seek> ReadBox<&mut R> for HvcCBox {
         let avg_frame_rate = reader.read_u16::<BigEndian>()?;
 
         let params = reader.read_u8()?;
-        let constant_frame_rate = params & 0b11000000 >> 6;
-        let num_temporal_layers = params & 0b00111000 >> 3;
-        let temporal_id_nested = (params & 0b00000100 >> 2) > 0;
+        let constant_frame_rate = (params & 0b11000000) >> 6;
+        let num_temporal_layers = (params & 0b00111000) >> 3;
+        let temporal_id_nested = (params & 0b00000100) > 0;
         let length_size_minus_one = params & 0b000011;
 
         let num_of_arrays = reader.read_u8()?;
@@ -374,11 +626,19 @@ mod tests {
             horizresolution: FixedPointU16::new(0x48),
             vertresolution: FixedPointU16::new(0x48),
             frame_count: 1,
+            compressor_name: String::new(),
             depth: 24,
             hvcc: HvcCBox {
                 configuration_version: 1,
                 ..Default::default()
             },
+            dvcc: None,
+            pasp: None,
+            colr: None,
+            mdcv: None,
+            clli: None,
+            btrt: None,
+            sinf: None,
         };
         let mut buf = Vec::new();
         src_box.write_box(&mut buf).unwrap();
@@ -392,4 +652,123 @@ mod tests {
         let dst_box = Hev1Box::read_box(&mut reader, header.size).unwrap();
         assert_eq!(src_box, dst_box);
     }
+
+    #[test]
+    fn test_hev1_dvvc() {
+        let src_box = Hev1Box {
+            data_reference_index: 1,
+            width: 3840,
+            height: 2160,
+            horizresolution: FixedPointU16::new(0x48),
+            vertresolution: FixedPointU16::new(0x48),
+            frame_count: 1,
+            compressor_name: String::new(),
+            depth: 24,
+            hvcc: HvcCBox {
+                configuration_version: 1,
+                ..Default::default()
+            },
+            dvcc: Some(DvccBox {
+                dvvc: true,
+                dv_version_major: 1,
+                dv_version_minor: 0,
+                dv_profile: 8,
+                dv_level: 6,
+                rpu_present: true,
+                el_present: false,
+                bl_present: true,
+                dv_bl_signal_compatibility_id: 1,
+            }),
+            pasp: None,
+            colr: None,
+            mdcv: None,
+            clli: None,
+            btrt: None,
+            sinf: None,
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::Hev1Box);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = Hev1Box::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+
+    #[test]
+    fn test_hvcc_bit_fields() {
+        let src_box = HvcCBox {
+            configuration_version: 1,
+            general_profile_space: 1,
+            general_tier_flag: true,
+            general_profile_idc: 2,
+            constant_frame_rate: 1,
+            num_temporal_layers: 3,
+            temporal_id_nested: true,
+            length_size_minus_one: 3,
+            ..Default::default()
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::HvcCBox);
+        let dst_box = HvcCBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+
+    #[test]
+    fn test_hvcc_from_parameter_sets() {
+        let vps = [
+            0x40, 0x01, 0x0C, 0x01, 0xFF, 0xFF, 0x01, 0x60, 0x00, 0x00, 0x03, 0x00, 0x90, 0x00,
+            0x00, 0x03, 0x00, 0x00, 0x03, 0x00, 0x78, 0x95, 0x98, 0x09,
+        ];
+        // Main profile, level 4, 1920x1080 4:2:0 8-bit, with emulation
+        // prevention bytes in its profile_tier_level.
+        let sps = [
+            0x42, 0x01, 0x01, 0x01, 0x60, 0x00, 0x00, 0x03, 0x00, 0x90, 0x00, 0x00, 0x03, 0x00,
+            0x00, 0x03, 0x00, 0x78, 0xA0, 0x03, 0xC0, 0x80, 0x10, 0xE5, 0x96, 0x56, 0x69, 0x24,
+            0xCA, 0xE0, 0x10, 0x00, 0x00, 0x03, 0x00, 0x10, 0x00, 0x00, 0x03, 0x01, 0xE0, 0x80,
+        ];
+        let pps = [0x44, 0x01, 0xC1, 0x72, 0xB4, 0x62, 0x40];
+
+        let hvcc = HvcCBox::from_parameter_sets(&vps, &sps, &pps);
+        assert_eq!(hvcc.configuration_version, 1);
+        assert_eq!(hvcc.general_profile_space, 0);
+        assert!(!hvcc.general_tier_flag);
+        assert_eq!(hvcc.general_profile_idc, 1);
+        assert_eq!(hvcc.general_profile_compatibility_flags, 0x6000_0000);
+        assert_eq!(hvcc.general_constraint_indicator_flag, 0x9000_0000_0000);
+        assert_eq!(hvcc.general_level_idc, 120);
+        assert_eq!(hvcc.chroma_format_idc, 1);
+        assert_eq!(hvcc.bit_depth_luma_minus8, 0);
+        assert_eq!(hvcc.bit_depth_chroma_minus8, 0);
+        assert_eq!(hvcc.num_temporal_layers, 1);
+        assert!(hvcc.temporal_id_nested);
+        assert_eq!(hvcc.length_size_minus_one, 3);
+        let arrays: Vec<(u8, &[u8])> = hvcc
+            .arrays
+            .iter()
+            .map(|array| (array.nal_unit_type, &array.nalus[0].data[..]))
+            .collect();
+        assert_eq!(arrays, [(32, &vps[..]), (33, &sps[..]), (34, &pps[..])]);
+
+        let mut buf = Vec::new();
+        hvcc.write_box(&mut buf).unwrap();
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        let dst_box = HvcCBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(hvcc, dst_box);
+
+        // Without an SPS only the defaults are set.
+        let hvcc = HvcCBox::from_parameter_sets(&[], &[], &[]);
+        assert_eq!(hvcc.general_profile_idc, 0);
+        assert_eq!(hvcc.length_size_minus_one, 3);
+        assert!(hvcc.arrays.is_empty());
+    }
 }
@@ -0,0 +1,119 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use serde::Serialize;
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::*;
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct HmhdBox {
+    pub version: u8,
+    pub flags: u32,
+    pub max_pdu_size: u16,
+    pub avg_pdu_size: u16,
+    pub max_bitrate: u32,
+    pub avg_bitrate: u32,
+}
+
+impl HmhdBox {
+    pub fn get_type(&self) -> BoxType {
+        BoxType::HmhdBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        HEADER_SIZE + HEADER_EXT_SIZE + 16
+    }
+}
+
+impl Mp4Box for HmhdBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "max_pdu_size={} avg_pdu_size={} max_bitrate={} avg_bitrate={}",
+            self.max_pdu_size, self.avg_pdu_size, self.max_bitrate, self.avg_bitrate
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for HmhdBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let (version, flags) = read_box_header_ext(reader)?;
+
+        let max_pdu_size = reader.read_u16::<BigEndian>()?;
+        let avg_pdu_size = reader.read_u16::<BigEndian>()?;
+        let max_bitrate = reader.read_u32::<BigEndian>()?;
+        let avg_bitrate = reader.read_u32::<BigEndian>()?;
+        reader.read_u32::<BigEndian>()?; // reserved
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(HmhdBox {
+            version,
+            flags,
+            max_pdu_size,
+            avg_pdu_size,
+            max_bitrate,
+            avg_bitrate,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for HmhdBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        write_box_header_ext(writer, self.version, self.flags)?;
+
+        writer.write_u16::<BigEndian>(self.max_pdu_size)?;
+        writer.write_u16::<BigEndian>(self.avg_pdu_size)?;
+        writer.write_u32::<BigEndian>(self.max_bitrate)?;
+        writer.write_u32::<BigEndian>(self.avg_bitrate)?;
+        writer.write_u32::<BigEndian>(0)?; // reserved
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_hmhd() {
+        let src_box = HmhdBox {
+            version: 0,
+            flags: 0,
+            max_pdu_size: 1450,
+            avg_pdu_size: 1200,
+            max_bitrate: 2_000_000,
+            avg_bitrate: 1_500_000,
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::HmhdBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = HmhdBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+}
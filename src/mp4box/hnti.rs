@@ -0,0 +1,181 @@
+use serde::Serialize;
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::*;
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct HntiBox {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sdp: Option<SdpBox>,
+}
+
+impl HntiBox {
+    pub fn get_type(&self) -> BoxType {
+        BoxType::HntiBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        let mut size = HEADER_SIZE;
+        if let Some(ref sdp) = self.sdp {
+            size += sdp.box_size();
+        }
+        size
+    }
+}
+
+impl Mp4Box for HntiBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        Ok(String::new())
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for HntiBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let mut sdp = None;
+
+        let mut current = reader.stream_position()?;
+        let end = start + size;
+        while current < end {
+            // Get box header.
+            let header = BoxHeader::read(reader)?;
+            let BoxHeader { name, size: s } = header;
+            if s > size {
+                return Err(Error::InvalidData(
+                    "hnti box contains a box with a larger size than it",
+                ));
+            }
+
+            match name {
+                BoxType::SdpBox => {
+                    sdp = Some(SdpBox::read_box(reader, s)?);
+                }
+                _ => {
+                    // XXX warn!()
+                    skip_box(reader, s)?;
+                }
+            }
+
+            current = reader.stream_position()?;
+        }
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(HntiBox { sdp })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for HntiBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        if let Some(ref sdp) = self.sdp {
+            sdp.write_box(writer)?;
+        }
+        Ok(size)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct SdpBox {
+    pub sdp_text: String,
+}
+
+impl SdpBox {
+    pub fn get_type(&self) -> BoxType {
+        BoxType::SdpBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        HEADER_SIZE + self.sdp_text.len() as u64
+    }
+}
+
+impl Mp4Box for SdpBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!("sdp_text={}", self.sdp_text);
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for SdpBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let buf_size = size
+            .checked_sub(HEADER_SIZE)
+            .ok_or(Error::InvalidData("sdp size too small"))?;
+
+        let mut buf = vec![0u8; buf_size as usize];
+        reader.read_exact(&mut buf)?;
+        let sdp_text = String::from_utf8(buf).unwrap_or_default();
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(SdpBox { sdp_text })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for SdpBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        writer.write_all(self.sdp_text.as_bytes())?;
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_hnti() {
+        let src_box = HntiBox {
+            sdp: Some(SdpBox {
+                sdp_text: String::from("m=video 0 RTP/AVP 96\r\na=rtpmap:96 H264/90000\r\n"),
+            }),
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::HntiBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = HntiBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+}
@@ -97,7 +97,10 @@ impl<W: Write> WriteBox<&mut W> for IlstBox {
         let size = self.box_size();
         BoxHeader::new(self.box_type(), size).write(writer)?;
 
-        for (key, value) in &self.items {
+        // Written in key order, as the map's iteration order varies between runs.
+        let mut items: Vec<_> = self.items.iter().collect();
+        items.sort_by(|a, b| a.0.cmp(b.0));
+        for (key, value) in items {
             let name = match key {
                 MetadataKey::Title => BoxType::NameBox,
                 MetadataKey::Year => BoxType::DayBox,
@@ -166,7 +169,7 @@ impl<R: Read + Seek> ReadBox<&mut R> for IlstItemBox {
 }
 
 impl<'a> Metadata<'a> for IlstBox {
-    fn title(&self) -> Option<Cow<str>> {
+    fn title(&self) -> Option<Cow<'_, str>> {
         self.items.get(&MetadataKey::Title).map(item_to_str)
     }
 
@@ -178,7 +181,13 @@ impl<'a> Metadata<'a> for IlstBox {
         self.items.get(&MetadataKey::Poster).map(item_to_bytes)
     }
 
-    fn summary(&self) -> Option<Cow<str>> {
+    fn poster_type(&self) -> Option<DataType> {
+        self.items
+            .get(&MetadataKey::Poster)
+            .map(|item| item.data.data_type.clone())
+    }
+
+    fn summary(&self) -> Option<Cow<'_, str>> {
         self.items.get(&MetadataKey::Summary).map(item_to_str)
     }
 }
@@ -187,7 +196,7 @@ fn item_to_bytes(item: &IlstItemBox) -> &[u8] {
     &item.data.data
 }
 
-fn item_to_str(item: &IlstItemBox) -> Cow<str> {
+fn item_to_str(item: &IlstItemBox) -> Cow<'_, str> {
     String::from_utf8_lossy(&item.data.data)
 }
 
@@ -0,0 +1,153 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use serde::Serialize;
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::mp4a::{read_desc, size_of_length, write_desc};
+use crate::mp4box::*;
+
+const MP4_IOD_TAG: u8 = 0x10;
+
+/// Profile/level indication meaning "no capability required".
+pub const IODS_NO_CAPABILITY: u8 = 0xFF;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct IodsBox {
+    pub version: u8,
+    pub flags: u32,
+    pub object_descriptor_id: u16,
+    pub od_profile_level: u8,
+    pub scene_profile_level: u8,
+    pub audio_profile_level: u8,
+    pub visual_profile_level: u8,
+    pub graphics_profile_level: u8,
+}
+
+impl Default for IodsBox {
+    fn default() -> Self {
+        IodsBox {
+            version: 0,
+            flags: 0,
+            object_descriptor_id: 1,
+            od_profile_level: IODS_NO_CAPABILITY,
+            scene_profile_level: IODS_NO_CAPABILITY,
+            audio_profile_level: IODS_NO_CAPABILITY,
+            visual_profile_level: IODS_NO_CAPABILITY,
+            graphics_profile_level: IODS_NO_CAPABILITY,
+        }
+    }
+}
+
+impl IodsBox {
+    pub fn get_type(&self) -> BoxType {
+        BoxType::IodsBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        HEADER_SIZE + HEADER_EXT_SIZE + 1 + size_of_length(7) as u64 + 7
+    }
+}
+
+impl Mp4Box for IodsBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "object_descriptor_id={} audio_profile_level={:#x} visual_profile_level={:#x}",
+            self.object_descriptor_id, self.audio_profile_level, self.visual_profile_level
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for IodsBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let (version, flags) = read_box_header_ext(reader)?;
+
+        let (_tag, _desc_size) = read_desc(reader)?;
+        let id_and_flags = reader.read_u16::<BigEndian>()?;
+        let object_descriptor_id = id_and_flags >> 6;
+        let url_flag = id_and_flags & 0x20 != 0;
+
+        let mut iods = IodsBox {
+            version,
+            flags,
+            object_descriptor_id,
+            ..Default::default()
+        };
+        if !url_flag {
+            iods.od_profile_level = reader.read_u8()?;
+            iods.scene_profile_level = reader.read_u8()?;
+            iods.audio_profile_level = reader.read_u8()?;
+            iods.visual_profile_level = reader.read_u8()?;
+            iods.graphics_profile_level = reader.read_u8()?;
+        }
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(iods)
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for IodsBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        write_box_header_ext(writer, self.version, self.flags)?;
+
+        write_desc(writer, MP4_IOD_TAG, 7)?;
+        // URL_Flag = 0, includeInlineProfileLevelFlag = 0, reserved = 0b1111
+        writer.write_u16::<BigEndian>((self.object_descriptor_id << 6) | 0x0F)?;
+        writer.write_u8(self.od_profile_level)?;
+        writer.write_u8(self.scene_profile_level)?;
+        writer.write_u8(self.audio_profile_level)?;
+        writer.write_u8(self.visual_profile_level)?;
+        writer.write_u8(self.graphics_profile_level)?;
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_iods() {
+        let src_box = IodsBox {
+            version: 0,
+            flags: 0,
+            object_descriptor_id: 1,
+            od_profile_level: IODS_NO_CAPABILITY,
+            scene_profile_level: IODS_NO_CAPABILITY,
+            audio_profile_level: 0x29,
+            visual_profile_level: 0x7F,
+            graphics_profile_level: IODS_NO_CAPABILITY,
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::IodsBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = IodsBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+}
@@ -0,0 +1,147 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use serde::Serialize;
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::*;
+
+/// Key table of QuickTime `mdta` metadata. The `ilst` items of the same
+/// `meta` are named by the 1-based index of their key in `entries`.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct KeysBox {
+    pub version: u8,
+    pub flags: u32,
+    pub entries: Vec<KeysEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct KeysEntry {
+    /// Usually `mdta`, for reverse-DNS keys such as
+    /// `com.apple.quicktime.location.ISO6709`.
+    pub namespace: FourCC,
+    pub key: String,
+}
+
+impl KeysEntry {
+    fn get_size(&self) -> u64 {
+        8 + self.key.len() as u64
+    }
+}
+
+impl KeysBox {
+    pub fn get_type(&self) -> BoxType {
+        BoxType::KeysBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        HEADER_SIZE
+            + HEADER_EXT_SIZE
+            + 4
+            + self.entries.iter().map(KeysEntry::get_size).sum::<u64>()
+    }
+}
+
+impl Mp4Box for KeysBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!("entry_count={}", self.entries.len());
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for KeysBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let (version, flags) = read_box_header_ext(reader)?;
+
+        let entry_count = reader.read_u32::<BigEndian>()?;
+        let end = start + size;
+        let mut entries = Vec::new();
+        for _ in 0..entry_count {
+            let key_size = reader.read_u32::<BigEndian>()? as u64;
+            if key_size < 8 || reader.stream_position()? - 4 + key_size > end {
+                return Err(Error::InvalidData("keys entry size is out of range"));
+            }
+            let namespace = FourCC::from(reader.read_u32::<BigEndian>()?);
+            let mut buf = vec![0u8; (key_size - 8) as usize];
+            reader.read_exact(&mut buf)?;
+            entries.push(KeysEntry {
+                namespace,
+                key: String::from_utf8_lossy(&buf).into_owned(),
+            });
+        }
+
+        skip_bytes_to(reader, end)?;
+
+        Ok(KeysBox {
+            version,
+            flags,
+            entries,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for KeysBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        write_box_header_ext(writer, self.version, self.flags)?;
+
+        writer.write_u32::<BigEndian>(self.entries.len() as u32)?;
+        for entry in self.entries.iter() {
+            writer.write_u32::<BigEndian>(entry.get_size() as u32)?;
+            writer.write_u32::<BigEndian>((&entry.namespace).into())?;
+            writer.write_all(entry.key.as_bytes())?;
+        }
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_keys() {
+        let src_box = KeysBox {
+            version: 0,
+            flags: 0,
+            entries: vec![
+                KeysEntry {
+                    namespace: FourCC::from(*b"mdta"),
+                    key: String::from("com.apple.quicktime.make"),
+                },
+                KeysEntry {
+                    namespace: FourCC::from(*b"mdta"),
+                    key: String::from("com.apple.quicktime.location.ISO6709"),
+                },
+            ],
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::KeysBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = KeysBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+}
@@ -0,0 +1,124 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use serde::Serialize;
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::*;
+
+/// Mastering display colour volume (SMPTE ST 2086). Chromaticities are in
+/// 0.00002 units and luminances in 0.0001 cd/m².
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct MdcvBox {
+    /// (x, y) of the green, blue and red primaries, in that order.
+    pub display_primaries: [(u16, u16); 3],
+    pub white_point: (u16, u16),
+    pub max_display_mastering_luminance: u32,
+    pub min_display_mastering_luminance: u32,
+}
+
+impl MdcvBox {
+    pub fn get_type(&self) -> BoxType {
+        BoxType::MdcvBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        HEADER_SIZE + 24
+    }
+}
+
+impl Mp4Box for MdcvBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "white_point={:?} max_display_mastering_luminance={} min_display_mastering_luminance={}",
+            self.white_point,
+            self.max_display_mastering_luminance,
+            self.min_display_mastering_luminance
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for MdcvBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let mut display_primaries = [(0, 0); 3];
+        for primary in display_primaries.iter_mut() {
+            let x = reader.read_u16::<BigEndian>()?;
+            let y = reader.read_u16::<BigEndian>()?;
+            *primary = (x, y);
+        }
+        let white_point = (
+            reader.read_u16::<BigEndian>()?,
+            reader.read_u16::<BigEndian>()?,
+        );
+        let max_display_mastering_luminance = reader.read_u32::<BigEndian>()?;
+        let min_display_mastering_luminance = reader.read_u32::<BigEndian>()?;
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(MdcvBox {
+            display_primaries,
+            white_point,
+            max_display_mastering_luminance,
+            min_display_mastering_luminance,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for MdcvBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        for &(x, y) in self.display_primaries.iter() {
+            writer.write_u16::<BigEndian>(x)?;
+            writer.write_u16::<BigEndian>(y)?;
+        }
+        writer.write_u16::<BigEndian>(self.white_point.0)?;
+        writer.write_u16::<BigEndian>(self.white_point.1)?;
+        writer.write_u32::<BigEndian>(self.max_display_mastering_luminance)?;
+        writer.write_u32::<BigEndian>(self.min_display_mastering_luminance)?;
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_mdcv() {
+        let src_box = MdcvBox {
+            display_primaries: [(8500, 39850), (6550, 2300), (35400, 14600)],
+            white_point: (15635, 16450),
+            max_display_mastering_luminance: 10_000_000,
+            min_display_mastering_luminance: 50,
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::MdcvBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = MdcvBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+}
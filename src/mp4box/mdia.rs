@@ -2,11 +2,15 @@ use serde::Serialize;
 use std::io::{Read, Seek, Write};
 
 use crate::mp4box::*;
-use crate::mp4box::{hdlr::HdlrBox, mdhd::MdhdBox, minf::MinfBox};
+use crate::mp4box::{elng::ElngBox, hdlr::HdlrBox, mdhd::MdhdBox, minf::MinfBox};
 
 #[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
 pub struct MdiaBox {
     pub mdhd: MdhdBox,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub elng: Option<ElngBox>,
+
     pub hdlr: HdlrBox,
     pub minf: MinfBox,
 }
@@ -17,7 +21,12 @@ impl MdiaBox {
     }
 
     pub fn get_size(&self) -> u64 {
-        HEADER_SIZE + self.mdhd.box_size() + self.hdlr.box_size() + self.minf.box_size()
+        let mut size =
+            HEADER_SIZE + self.mdhd.box_size() + self.hdlr.box_size() + self.minf.box_size();
+        if let Some(ref elng) = self.elng {
+            size += elng.box_size();
+        }
+        size
     }
 }
 
@@ -45,6 +54,7 @@ impl<R: Read + Seek> ReadBox<&mut R> for MdiaBox {
         let start = box_start(reader)?;
 
         let mut mdhd = None;
+        let mut elng = None;
         let mut hdlr = None;
         let mut minf = None;
 
@@ -64,6 +74,9 @@ impl<R: Read + Seek> ReadBox<&mut R> for MdiaBox {
                 BoxType::MdhdBox => {
                     mdhd = Some(MdhdBox::read_box(reader, s)?);
                 }
+                BoxType::ElngBox => {
+                    elng = Some(ElngBox::read_box(reader, s)?);
+                }
                 BoxType::HdlrBox => {
                     hdlr = Some(HdlrBox::read_box(reader, s)?);
                 }
@@ -93,6 +106,7 @@ impl<R: Read + Seek> ReadBox<&mut R> for MdiaBox {
 
         Ok(MdiaBox {
             mdhd: mdhd.unwrap(),
+            elng,
             hdlr: hdlr.unwrap(),
             minf: minf.unwrap(),
         })
@@ -105,6 +119,9 @@ impl<W: Write> WriteBox<&mut W> for MdiaBox {
         BoxHeader::new(self.box_type(), size).write(writer)?;
 
         self.mdhd.write_box(writer)?;
+        if let Some(ref elng) = self.elng {
+            elng.write_box(writer)?;
+        }
         self.hdlr.write_box(writer)?;
         self.minf.write_box(writer)?;
 
@@ -1,20 +1,34 @@
+use std::collections::BTreeMap;
 use std::io::{Read, Seek};
 
 use serde::Serialize;
 
 use crate::mp4box::hdlr::HdlrBox;
-use crate::mp4box::ilst::IlstBox;
+use crate::mp4box::ilst::{IlstBox, IlstItemBox};
+use crate::mp4box::keys::KeysBox;
 use crate::mp4box::*;
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 #[serde(tag = "hdlr")]
 #[serde(rename_all = "lowercase")]
 pub enum MetaBox {
+    /// iTunes-style metadata. `name` is the `hdlr` name, which some players
+    /// expect to be set; it is empty unless specified.
     Mdir {
+        #[serde(skip_serializing_if = "String::is_empty")]
+        name: String,
+
         #[serde(skip_serializing_if = "Option::is_none")]
         ilst: Option<IlstBox>,
     },
 
+    /// QuickTime metadata: `ilst` items are named by the 1-based index of
+    /// their key in `keys` rather than by a fourcc.
+    Mdta {
+        keys: KeysBox,
+        items: BTreeMap<u32, DataBox>,
+    },
+
     #[serde(skip)]
     Unknown {
         #[serde(skip)]
@@ -26,6 +40,7 @@ pub enum MetaBox {
 }
 
 const MDIR: FourCC = FourCC { value: *b"mdir" };
+const MDTA: FourCC = FourCC { value: *b"mdta" };
 
 impl MetaBox {
     pub fn get_type(&self) -> BoxType {
@@ -35,12 +50,21 @@ impl MetaBox {
     pub fn get_size(&self) -> u64 {
         let mut size = HEADER_SIZE + HEADER_EXT_SIZE;
         match self {
-            Self::Mdir { ilst } => {
-                size += HdlrBox::default().box_size();
+            Self::Mdir { name, ilst } => {
+                size += HdlrBox {
+                    name: name.clone(),
+                    ..Default::default()
+                }
+                .box_size();
                 if let Some(ilst) = ilst {
                     size += ilst.box_size();
                 }
             }
+            Self::Mdta { keys, items } => {
+                size += HdlrBox::default().box_size();
+                size += keys.box_size();
+                size += mdta_ilst_size(items);
+            }
             Self::Unknown { hdlr, data } => {
                 size += hdlr.box_size()
                     + data
@@ -69,6 +93,7 @@ impl Mp4Box for MetaBox {
     fn summary(&self) -> Result<String> {
         let s = match self {
             Self::Mdir { .. } => "hdlr=ilst".to_string(),
+            Self::Mdta { keys, .. } => format!("hdlr=mdta key_count={}", keys.entries.len()),
             Self::Unknown { hdlr, data } => {
                 format!("hdlr={} data_len={}", hdlr.handler_type, data.len())
             }
@@ -160,7 +185,49 @@ impl<R: Read + Seek> ReadBox<&mut R> for MetaBox {
                     current = reader.stream_position()?;
                 }
 
-                Ok(MetaBox::Mdir { ilst })
+                Ok(MetaBox::Mdir {
+                    name: hdlr.name,
+                    ilst,
+                })
+            }
+            MDTA => {
+                let mut keys = None;
+                let mut items = BTreeMap::new();
+
+                while current < end {
+                    // Get box header.
+                    let header = BoxHeader::read(reader)?;
+                    let BoxHeader { name, size: s } = header;
+
+                    match name {
+                        BoxType::KeysBox => {
+                            keys = Some(KeysBox::read_box(reader, s)?);
+                        }
+                        BoxType::IlstBox => {
+                            let ilst_end = current + s;
+                            let mut item_current = reader.stream_position()?;
+                            while item_current < ilst_end {
+                                let header = BoxHeader::read(reader)?;
+                                let BoxHeader { name, size: s } = header;
+                                let item = IlstItemBox::read_box(reader, s)?;
+                                items.insert(u32::from(name), item.data);
+                                item_current = reader.stream_position()?;
+                            }
+                            skip_bytes_to(reader, ilst_end)?;
+                        }
+                        _ => {
+                            // XXX warn!()
+                            skip_box(reader, s)?;
+                        }
+                    }
+
+                    current = reader.stream_position()?;
+                }
+
+                Ok(MetaBox::Mdta {
+                    keys: keys.unwrap_or_default(),
+                    items,
+                })
             }
             _ => {
                 let mut data = Vec::new();
@@ -199,8 +266,13 @@ impl<W: Write> WriteBox<&mut W> for MetaBox {
         write_box_header_ext(writer, 0, 0)?;
 
         let hdlr = match self {
-            Self::Mdir { .. } => HdlrBox {
+            Self::Mdir { name, .. } => HdlrBox {
                 handler_type: MDIR,
+                name: name.clone(),
+                ..Default::default()
+            },
+            Self::Mdta { .. } => HdlrBox {
+                handler_type: MDTA,
                 ..Default::default()
             },
             Self::Unknown { hdlr, .. } => hdlr.clone(),
@@ -208,11 +280,20 @@ impl<W: Write> WriteBox<&mut W> for MetaBox {
         hdlr.write_box(writer)?;
 
         match self {
-            Self::Mdir { ilst } => {
+            Self::Mdir { ilst, .. } => {
                 if let Some(ilst) = ilst {
                     ilst.write_box(writer)?;
                 }
             }
+            Self::Mdta { keys, items } => {
+                keys.write_box(writer)?;
+                BoxHeader::new(BoxType::IlstBox, mdta_ilst_size(items)).write(writer)?;
+                for (index, data) in items {
+                    BoxHeader::new(BoxType::from(*index), HEADER_SIZE + data.box_size())
+                        .write(writer)?;
+                    data.write_box(writer)?;
+                }
+            }
             Self::Unknown { data, .. } => {
                 for (box_type, data) in data {
                     BoxHeader::new(*box_type, data.len() as u64 + HEADER_SIZE).write(writer)?;
@@ -224,6 +305,14 @@ impl<W: Write> WriteBox<&mut W> for MetaBox {
     }
 }
 
+fn mdta_ilst_size(items: &BTreeMap<u32, DataBox>) -> u64 {
+    HEADER_SIZE
+        + items
+            .values()
+            .map(|data| HEADER_SIZE + data.box_size())
+            .sum::<u64>()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -232,7 +321,10 @@ mod tests {
 
     #[test]
     fn test_meta_mdir_empty() {
-        let src_box = MetaBox::Mdir { ilst: None };
+        let src_box = MetaBox::Mdir {
+            name: String::new(),
+            ilst: None,
+        };
 
         let mut buf = Vec::new();
         src_box.write_box(&mut buf).unwrap();
@@ -250,6 +342,7 @@ mod tests {
     #[test]
     fn test_meta_mdir() {
         let src_box = MetaBox::Mdir {
+            name: String::from("Metadata"),
             ilst: Some(IlstBox::default()),
         };
 
@@ -266,6 +359,40 @@ mod tests {
         assert_eq!(dst_box, src_box);
     }
 
+    #[test]
+    fn test_meta_mdta() {
+        let src_box = MetaBox::Mdta {
+            keys: KeysBox {
+                version: 0,
+                flags: 0,
+                entries: vec![KeysEntry {
+                    namespace: MDTA,
+                    key: String::from("com.apple.quicktime.make"),
+                }],
+            },
+            items: [(
+                1,
+                DataBox {
+                    data: b"Apple".to_vec(),
+                    data_type: DataType::Text,
+                },
+            )]
+            .into(),
+        };
+
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::MetaBox);
+        assert_eq!(header.size, src_box.box_size());
+
+        let dst_box = MetaBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(dst_box, src_box);
+    }
+
     #[test]
     fn test_meta_hdrl_non_first() {
         let data = b"\x00\x00\x00\x7fmeta\x00\x00\x00\x00\x00\x00\x00Qilst\x00\x00\x00I\xa9too\x00\x00\x00Adata\x00\x00\x00\x01\x00\x00\x00\x00TMPGEnc Video Mastering Works 7 Version 7.0.15.17\x00\x00\x00\"hdlr\x00\x00\x00\x00\x00\x00\x00\x00mdirappl\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00";
@@ -280,6 +407,7 @@ mod tests {
         assert_eq!(
             meta_box,
             MetaBox::Mdir {
+                name: String::new(),
                 ilst: Some(IlstBox::default())
             }
         );
@@ -2,7 +2,10 @@ use serde::Serialize;
 use std::io::{Read, Seek, Write};
 
 use crate::mp4box::*;
-use crate::mp4box::{dinf::DinfBox, smhd::SmhdBox, stbl::StblBox, vmhd::VmhdBox};
+use crate::mp4box::{
+    dinf::DinfBox, gmhd::GmhdBox, hmhd::HmhdBox, nmhd::NmhdBox, smhd::SmhdBox, stbl::StblBox,
+    vmhd::VmhdBox,
+};
 
 #[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
 pub struct MinfBox {
@@ -12,6 +15,15 @@ pub struct MinfBox {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub smhd: Option<SmhdBox>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hmhd: Option<HmhdBox>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nmhd: Option<NmhdBox>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gmhd: Option<GmhdBox>,
+
     pub dinf: DinfBox,
     pub stbl: StblBox,
 }
@@ -29,6 +41,15 @@ impl MinfBox {
         if let Some(ref smhd) = self.smhd {
             size += smhd.box_size();
         }
+        if let Some(ref hmhd) = self.hmhd {
+            size += hmhd.box_size();
+        }
+        if let Some(ref nmhd) = self.nmhd {
+            size += nmhd.box_size();
+        }
+        if let Some(ref gmhd) = self.gmhd {
+            size += gmhd.box_size();
+        }
         size += self.dinf.box_size();
         size += self.stbl.box_size();
         size
@@ -60,6 +81,9 @@ impl<R: Read + Seek> ReadBox<&mut R> for MinfBox {
 
         let mut vmhd = None;
         let mut smhd = None;
+        let mut hmhd = None;
+        let mut nmhd = None;
+        let mut gmhd = None;
         let mut dinf = None;
         let mut stbl = None;
 
@@ -82,6 +106,15 @@ impl<R: Read + Seek> ReadBox<&mut R> for MinfBox {
                 BoxType::SmhdBox => {
                     smhd = Some(SmhdBox::read_box(reader, s)?);
                 }
+                BoxType::HmhdBox => {
+                    hmhd = Some(HmhdBox::read_box(reader, s)?);
+                }
+                BoxType::NmhdBox => {
+                    nmhd = Some(NmhdBox::read_box(reader, s)?);
+                }
+                BoxType::GmhdBox => {
+                    gmhd = Some(GmhdBox::read_box(reader, s)?);
+                }
                 BoxType::DinfBox => {
                     dinf = Some(DinfBox::read_box(reader, s)?);
                 }
@@ -109,6 +142,9 @@ impl<R: Read + Seek> ReadBox<&mut R> for MinfBox {
         Ok(MinfBox {
             vmhd,
             smhd,
+            hmhd,
+            nmhd,
+            gmhd,
             dinf: dinf.unwrap(),
             stbl: stbl.unwrap(),
         })
@@ -126,6 +162,15 @@ impl<W: Write> WriteBox<&mut W> for MinfBox {
         if let Some(ref smhd) = self.smhd {
             smhd.write_box(writer)?;
         }
+        if let Some(ref hmhd) = self.hmhd {
+            hmhd.write_box(writer)?;
+        }
+        if let Some(ref nmhd) = self.nmhd {
+            nmhd.write_box(writer)?;
+        }
+        if let Some(ref gmhd) = self.gmhd {
+            gmhd.write_box(writer)?;
+        }
         self.dinf.write_box(writer)?;
         self.stbl.write_box(writer)?;
 
@@ -13,45 +13,99 @@
 //! ftyp
 //! moov
 //!     mvhd
+//!     iods
+//!     meta
+//!         keys
+//!         ilst
+//!             data
 //!     udta
 //!         meta
 //!             ilst
 //!                 data
 //!     trak
 //!         tkhd
+//!         tref
 //!         mdia
 //!             mdhd
+//!             elng
 //!             hdlr
 //!             minf
 //!                 stbl
 //!                     stsd
-//!                         avc1
-//!                         hev1
-//!                         mp4a
+//!                         avc1/avc3/encv
+//!                             dvcC/dvvC
+//!                             pasp
+//!                             colr
+//!                             mdcv
+//!                             clli
+//!                             btrt
+//!                             sinf
+//!                         hev1/encv
+//!                             dvcC/dvvC
+//!                             pasp
+//!                             colr
+//!                             mdcv
+//!                             clli
+//!                             btrt
+//!                             sinf
+//!                         dvh1/dvhe
+//!                             colr
+//!                             mdcv
+//!                             clli
+//!                             btrt
+//!                         ac-3
+//!                             dac3
+//!                         ec-3
+//!                             dec3
+//!                         av01
+//!                             av1C
+//!                             pasp
+//!                             colr
+//!                             btrt
+//!                         mp4a/enca
+//!                             sinf
+//!                         Opus
+//!                             dOps
 //!                         tx3g
+//!                         text
+//!                         rtp
 //!                     stts
 //!                     stsc
 //!                     stsz
 //!                     stss
+//!                     sdtp
 //!                     stco
 //!                     co64
 //!                     ctts
+//!                     cslg
 //!                 dinf
 //!                     dref
 //!                 smhd
 //!                 vmhd
+//!                 hmhd
+//!                 nmhd
+//!                 gmhd
+//!                     gmin
+//!                     text
 //!         edts
 //!             elst
+//!         udta
+//!             hnti
+//!                 sdp
 //!     mvex
 //!         mehd
 //!         trex
+//! styp
 //! emsg
+//! sidx
+//! ssix
 //! moof
 //!     mfhd
 //!     traf
 //!         tfhd
 //!         tfdt
 //!         trun
+//!         senc
 //! mdat
 //! free
 //!
@@ -62,18 +116,35 @@ use std::io::{Read, Seek, SeekFrom, Write};
 
 use crate::*;
 
+pub(crate) mod ac3;
+pub(crate) mod av01;
 pub(crate) mod avc1;
+pub(crate) mod btrt;
+pub(crate) mod clli;
 pub(crate) mod co64;
+pub(crate) mod colr;
+pub(crate) mod cslg;
 pub(crate) mod ctts;
 pub(crate) mod data;
 pub(crate) mod dinf;
+pub(crate) mod dvcc;
+pub(crate) mod dvh1;
+pub(crate) mod eac3;
 pub(crate) mod edts;
+pub(crate) mod elng;
 pub(crate) mod elst;
 pub(crate) mod emsg;
+pub(crate) mod frma;
 pub(crate) mod ftyp;
+pub(crate) mod gmhd;
 pub(crate) mod hdlr;
 pub(crate) mod hev1;
+pub(crate) mod hmhd;
+pub(crate) mod hnti;
 pub(crate) mod ilst;
+pub(crate) mod iods;
+pub(crate) mod keys;
+pub(crate) mod mdcv;
 pub(crate) mod mdhd;
 pub(crate) mod mdia;
 pub(crate) mod mehd;
@@ -85,7 +156,17 @@ pub(crate) mod moov;
 pub(crate) mod mp4a;
 pub(crate) mod mvex;
 pub(crate) mod mvhd;
+pub(crate) mod nmhd;
+pub(crate) mod opus;
+pub(crate) mod pasp;
+pub(crate) mod rtp;
+pub(crate) mod schm;
+pub(crate) mod sdtp;
+pub(crate) mod senc;
+pub(crate) mod sidx;
+pub(crate) mod sinf;
 pub(crate) mod smhd;
+pub(crate) mod ssix;
 pub(crate) mod stbl;
 pub(crate) mod stco;
 pub(crate) mod stsc;
@@ -93,11 +174,15 @@ pub(crate) mod stsd;
 pub(crate) mod stss;
 pub(crate) mod stsz;
 pub(crate) mod stts;
+pub(crate) mod styp;
+pub(crate) mod tenc;
+pub(crate) mod text;
 pub(crate) mod tfdt;
 pub(crate) mod tfhd;
 pub(crate) mod tkhd;
 pub(crate) mod traf;
 pub(crate) mod trak;
+pub(crate) mod tref;
 pub(crate) mod trex;
 pub(crate) mod trun;
 pub(crate) mod tx3g;
@@ -106,18 +191,35 @@ pub(crate) mod vmhd;
 pub(crate) mod vp09;
 pub(crate) mod vpcc;
 
-pub use avc1::Avc1Box;
+pub use ac3::{Ac3Box, Dac3Box};
+pub use av01::{Av01Box, Av1CBox};
+pub use avc1::{Avc1Box, AvcCBox};
+pub use btrt::BtrtBox;
+pub use clli::ClliBox;
 pub use co64::Co64Box;
-pub use ctts::CttsBox;
+pub use colr::ColrBox;
+pub use cslg::CslgBox;
+pub use ctts::{CttsBox, CttsEntry};
 pub use data::DataBox;
 pub use dinf::DinfBox;
+pub use dvcc::DvccBox;
+pub use dvh1::Dvh1Box;
+pub use eac3::{Dec3Box, Eac3Box, Ec3Substream};
 pub use edts::EdtsBox;
-pub use elst::ElstBox;
+pub use elng::ElngBox;
+pub use elst::{ElstBox, ElstEntry};
 pub use emsg::EmsgBox;
+pub use frma::FrmaBox;
 pub use ftyp::FtypBox;
+pub use gmhd::{GmhdBox, GminBox};
 pub use hdlr::HdlrBox;
-pub use hev1::Hev1Box;
+pub use hev1::{Hev1Box, HvcCArray, HvcCArrayNalu, HvcCBox};
+pub use hmhd::HmhdBox;
+pub use hnti::HntiBox;
 pub use ilst::IlstBox;
+pub use iods::{IodsBox, IODS_NO_CAPABILITY};
+pub use keys::{KeysBox, KeysEntry};
+pub use mdcv::MdcvBox;
 pub use mdhd::MdhdBox;
 pub use mdia::MdiaBox;
 pub use mehd::MehdBox;
@@ -129,19 +231,33 @@ pub use moov::MoovBox;
 pub use mp4a::Mp4aBox;
 pub use mvex::MvexBox;
 pub use mvhd::MvhdBox;
+pub use nmhd::NmhdBox;
+pub use opus::{DopsBox, OpusBox};
+pub use pasp::PaspBox;
+pub use rtp::RtpBox;
+pub use schm::SchmBox;
+pub use sdtp::{SdtpBox, SdtpEntry};
+pub use senc::{SencBox, SencEntry, SencSubsample};
+pub use sidx::{SidxBox, SidxReference};
+pub use sinf::SinfBox;
 pub use smhd::SmhdBox;
+pub use ssix::{SsixBox, SsixRange, SsixSubsegment};
 pub use stbl::StblBox;
 pub use stco::StcoBox;
-pub use stsc::StscBox;
-pub use stsd::StsdBox;
+pub use stsc::{StscBox, StscEntry};
+pub use stsd::{RawSampleEntry, StsdBox};
 pub use stss::StssBox;
 pub use stsz::StszBox;
-pub use stts::SttsBox;
+pub use stts::{SttsBox, SttsEntry};
+pub use styp::StypBox;
+pub use tenc::TencBox;
+pub use text::TextBox;
 pub use tfdt::TfdtBox;
 pub use tfhd::TfhdBox;
 pub use tkhd::TkhdBox;
 pub use traf::TrafBox;
 pub use trak::TrakBox;
+pub use tref::TrefBox;
 pub use trex::TrexBox;
 pub use trun::TrunBox;
 pub use tx3g::Tx3gBox;
@@ -223,6 +339,7 @@ boxtype! {
     UrlBox  => 0x75726C20,
     SmhdBox => 0x736d6864,
     Avc1Box => 0x61766331,
+    Avc3Box => 0x61766333,
     AvcCBox => 0x61766343,
     Hev1Box => 0x68657631,
     HvcCBox => 0x68766343,
@@ -238,7 +355,51 @@ boxtype! {
     CovrBox => 0x636f7672,
     DescBox => 0x64657363,
     WideBox => 0x77696465,
-    WaveBox => 0x77617665
+    WaveBox => 0x77617665,
+    TrefBox => 0x74726566,
+    HmhdBox => 0x686d6864,
+    RtpBox  => 0x72747020,
+    TimsBox => 0x74696d73,
+    HntiBox => 0x686e7469,
+    SdpBox  => 0x73647020,
+    DvcCBox => 0x64766343,
+    DvvCBox => 0x64767643,
+    Dvh1Box => 0x64766831,
+    DvheBox => 0x64766865,
+    PaspBox => 0x70617370,
+    IodsBox => 0x696f6473,
+    SidxBox => 0x73696478,
+    SsixBox => 0x73736978,
+    StypBox => 0x73747970,
+    SdtpBox => 0x73647470,
+    EncvBox => 0x656e6376,
+    EncaBox => 0x656e6361,
+    SinfBox => 0x73696e66,
+    FrmaBox => 0x66726d61,
+    SchmBox => 0x7363686d,
+    SchiBox => 0x73636869,
+    ColrBox => 0x636f6c72,
+    MdcvBox => 0x6d646376,
+    ClliBox => 0x636c6c69,
+    ElngBox => 0x656c6e67,
+    GmhdBox => 0x676d6864,
+    GminBox => 0x676d696e,
+    TextBox => 0x74657874,
+    BtrtBox => 0x62747274,
+    CslgBox => 0x63736c67,
+    KeysBox => 0x6b657973,
+    TencBox => 0x74656e63,
+    SencBox => 0x73656e63,
+    OpusBox => 0x4f707573,
+    DopsBox => 0x644f7073,
+    NmhdBox => 0x6e6d6864,
+    Av01Box => 0x61763031,
+    Dav1Box => 0x64617631,
+    Av1CBox => 0x61763143,
+    Ac3Box => 0x61632d33,
+    Dac3Box => 0x64616333,
+    Eac3Box => 0x65632d33,
+    Dec3Box => 0x64656333
 }
 
 pub trait Mp4Box: Sized {
@@ -359,6 +520,64 @@ pub fn write_zeros<W: Write>(writer: &mut W, size: u64) -> Result<()> {
     Ok(())
 }
 
+/// Reads the `compressorname` of a visual sample entry: a Pascal string
+/// padded to 32 bytes.
+pub(crate) fn read_compressor_name<R: Read>(reader: &mut R) -> Result<String> {
+    let mut buf = [0u8; 32];
+    reader.read_exact(&mut buf)?;
+    let len = (buf[0] as usize).min(31);
+    Ok(String::from_utf8_lossy(&buf[1..1 + len]).into_owned())
+}
+
+/// Writes `name` as a visual sample entry `compressorname`, truncated to 31
+/// bytes.
+pub(crate) fn write_compressor_name<W: Write>(writer: &mut W, name: &str) -> Result<()> {
+    let name = &name.as_bytes()[..name.len().min(31)];
+    writer.write_u8(name.len() as u8)?;
+    writer.write_all(name)?;
+    write_zeros(writer, 31 - name.len() as u64)
+}
+
+/// Reads big-endian bit fields, as in codec configuration records and
+/// parameter sets. Reads past the end return `None`.
+pub(crate) struct BitReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        BitReader { bytes, position: 0 }
+    }
+
+    pub(crate) fn read(&mut self, bits: u32) -> Option<u32> {
+        let mut value = 0u32;
+        for _ in 0..bits {
+            let byte = self.bytes.get(self.position / 8)?;
+            let bit = (byte >> (7 - self.position % 8)) & 1;
+            value = (value << 1) | bit as u32;
+            self.position += 1;
+        }
+        Some(value)
+    }
+
+    pub(crate) fn read_flag(&mut self) -> Option<bool> {
+        self.read(1).map(|bit| bit == 1)
+    }
+
+    /// An Exp-Golomb code: AV1 `uvlc()`, or `ue(v)` in H.264/H.265.
+    pub(crate) fn read_uvlc(&mut self) -> Option<u32> {
+        let mut leading_zeros = 0;
+        while !self.read_flag()? {
+            leading_zeros += 1;
+            if leading_zeros >= 32 {
+                return None;
+            }
+        }
+        Some(self.read(leading_zeros)? + ((1u64 << leading_zeros) - 1) as u32)
+    }
+}
+
 mod value_u32 {
     use crate::types::FixedPointU16;
     use serde::{self, Serializer};
@@ -9,6 +9,9 @@ use crate::mp4box::{mvex::MvexBox, mvhd::MvhdBox, trak::TrakBox, udta::UdtaBox};
 pub struct MoovBox {
     pub mvhd: MvhdBox,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iods: Option<IodsBox>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub meta: Option<MetaBox>,
 
@@ -29,9 +32,15 @@ impl MoovBox {
 
     pub fn get_size(&self) -> u64 {
         let mut size = HEADER_SIZE + self.mvhd.box_size();
+        if let Some(iods) = &self.iods {
+            size += iods.box_size();
+        }
         for trak in self.traks.iter() {
             size += trak.box_size();
         }
+        if let Some(mvex) = &self.mvex {
+            size += mvex.box_size();
+        }
         if let Some(meta) = &self.meta {
             size += meta.box_size();
         }
@@ -66,6 +75,7 @@ impl<R: Read + Seek> ReadBox<&mut R> for MoovBox {
         let start = box_start(reader)?;
 
         let mut mvhd = None;
+        let mut iods = None;
         let mut meta = None;
         let mut udta = None;
         let mut mvex = None;
@@ -87,6 +97,9 @@ impl<R: Read + Seek> ReadBox<&mut R> for MoovBox {
                 BoxType::MvhdBox => {
                     mvhd = Some(MvhdBox::read_box(reader, s)?);
                 }
+                BoxType::IodsBox => {
+                    iods = Some(IodsBox::read_box(reader, s)?);
+                }
                 BoxType::MetaBox => {
                     meta = Some(MetaBox::read_box(reader, s)?);
                 }
@@ -117,6 +130,7 @@ impl<R: Read + Seek> ReadBox<&mut R> for MoovBox {
 
         Ok(MoovBox {
             mvhd: mvhd.unwrap(),
+            iods,
             meta,
             udta,
             mvex,
@@ -131,9 +145,15 @@ impl<W: Write> WriteBox<&mut W> for MoovBox {
         BoxHeader::new(self.box_type(), size).write(writer)?;
 
         self.mvhd.write_box(writer)?;
+        if let Some(iods) = &self.iods {
+            iods.write_box(writer)?;
+        }
         for trak in self.traks.iter() {
             trak.write_box(writer)?;
         }
+        if let Some(mvex) = &self.mvex {
+            mvex.write_box(writer)?;
+        }
         if let Some(meta) = &self.meta {
             meta.write_box(writer)?;
         }
@@ -154,7 +174,8 @@ mod tests {
     fn test_moov() {
         let src_box = MoovBox {
             mvhd: MvhdBox::default(),
-            mvex: None, // XXX mvex is not written currently
+            iods: Some(IodsBox::default()),
+            mvex: Some(MvexBox::default()),
             traks: vec![],
             meta: Some(MetaBox::default()),
             udta: Some(UdtaBox::default()),
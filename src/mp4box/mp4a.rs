@@ -13,6 +13,10 @@ pub struct Mp4aBox {
     #[serde(with = "value_u32")]
     pub samplerate: FixedPointU16,
     pub esds: Option<EsdsBox>,
+
+    /// Present when the entry is protected and stored as `enca`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sinf: Option<SinfBox>,
 }
 
 impl Default for Mp4aBox {
@@ -23,6 +27,7 @@ impl Default for Mp4aBox {
             samplesize: 16,
             samplerate: FixedPointU16::new(48000),
             esds: Some(EsdsBox::default()),
+            sinf: None,
         }
     }
 }
@@ -35,11 +40,16 @@ impl Mp4aBox {
             samplesize: 16,
             samplerate: FixedPointU16::new(config.freq_index.freq() as u16),
             esds: Some(EsdsBox::new(config)),
+            sinf: None,
         }
     }
 
     pub fn get_type(&self) -> BoxType {
-        BoxType::Mp4aBox
+        if self.sinf.is_some() {
+            BoxType::EncaBox
+        } else {
+            BoxType::Mp4aBox
+        }
     }
 
     pub fn get_size(&self) -> u64 {
@@ -47,6 +57,9 @@ impl Mp4aBox {
         if let Some(ref esds) = self.esds {
             size += esds.box_size();
         }
+        if let Some(ref sinf) = self.sinf {
+            size += sinf.box_size();
+        }
         size
     }
 }
@@ -96,8 +109,9 @@ impl<R: Read + Seek> ReadBox<&mut R> for Mp4aBox {
             reader.read_u64::<BigEndian>()?;
         }
 
-        // Find esds in mp4a or wave
+        // Find esds in mp4a or wave, and sinf for enca
         let mut esds = None;
+        let mut sinf = None;
         let end = start + size;
         loop {
             let current = reader.stream_position()?;
@@ -111,9 +125,14 @@ impl<R: Read + Seek> ReadBox<&mut R> for Mp4aBox {
                     "mp4a box contains a box with a larger size than it",
                 ));
             }
+            if s < HEADER_SIZE {
+                break;
+            }
             if name == BoxType::EsdsBox {
                 esds = Some(EsdsBox::read_box(reader, s)?);
-                break;
+                skip_bytes_to(reader, current + s)?;
+            } else if name == BoxType::SinfBox {
+                sinf = Some(SinfBox::read_box(reader, s)?);
             } else if name == BoxType::WaveBox {
                 // Typically contains frma, mp4a, esds, and a terminator atom
             } else {
@@ -131,6 +150,7 @@ impl<R: Read + Seek> ReadBox<&mut R> for Mp4aBox {
             samplesize,
             samplerate,
             esds,
+            sinf,
         })
     }
 }
@@ -153,6 +173,9 @@ impl<W: Write> WriteBox<&mut W> for Mp4aBox {
         if let Some(ref esds) = self.esds {
             esds.write_box(writer)?;
         }
+        if let Some(ref sinf) = self.sinf {
+            sinf.write_box(writer)?;
+        }
 
         Ok(size)
     }
@@ -258,7 +281,7 @@ trait WriteDesc<T>: Sized {
     fn write_desc(&self, _: T) -> Result<u32>;
 }
 
-fn read_desc<R: Read>(reader: &mut R) -> Result<(u8, u32)> {
+pub(crate) fn read_desc<R: Read>(reader: &mut R) -> Result<(u8, u32)> {
     let tag = reader.read_u8()?;
 
     let mut size: u32 = 0;
@@ -273,7 +296,7 @@ fn read_desc<R: Read>(reader: &mut R) -> Result<(u8, u32)> {
     Ok((tag, size))
 }
 
-fn size_of_length(size: u32) -> u32 {
+pub(crate) fn size_of_length(size: u32) -> u32 {
     match size {
         0x0..=0x7F => 1,
         0x80..=0x3FFF => 2,
@@ -282,10 +305,10 @@ fn size_of_length(size: u32) -> u32 {
     }
 }
 
-fn write_desc<W: Write>(writer: &mut W, tag: u8, size: u32) -> Result<u64> {
+pub(crate) fn write_desc<W: Write>(writer: &mut W, tag: u8, size: u32) -> Result<u64> {
     writer.write_u8(tag)?;
 
-    if size as u64 > std::u32::MAX as u64 {
+    if size as u64 > u32::MAX as u64 {
         return Err(Error::InvalidData("invalid descriptor length range"));
     }
 
@@ -487,6 +510,10 @@ pub struct DecoderSpecificDescriptor {
     pub profile: u8,
     pub freq_index: u8,
     pub chan_conf: u8,
+
+    /// Explicit sampling frequency, present when `freq_index` is 15.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sample_rate: Option<u32>,
 }
 
 impl DecoderSpecificDescriptor {
@@ -495,8 +522,35 @@ impl DecoderSpecificDescriptor {
             profile: config.profile as u8,
             freq_index: config.freq_index as u8,
             chan_conf: config.chan_conf as u8,
+            sample_rate: None,
         }
     }
+
+    /// The `AudioSpecificConfig` this descriptor carries, as decoders take it.
+    pub fn audio_specific_config(&self) -> Vec<u8> {
+        let mut bits = 0u64;
+        let mut len = 0u32;
+        let mut push = |value: u64, n: u32| {
+            bits = (bits << n) | (value & ((1 << n) - 1));
+            len += n;
+        };
+
+        if self.profile > 30 {
+            push(31, 5);
+            push(self.profile as u64 - 32, 6);
+        } else {
+            push(self.profile as u64, 5);
+        }
+        push(self.freq_index as u64, 4);
+        if self.freq_index == 15 {
+            push(self.sample_rate.unwrap_or(0) as u64, 24);
+        }
+        push(self.chan_conf as u64, 4);
+
+        let size = len.div_ceil(8);
+        bits <<= size * 8 - len;
+        bits.to_be_bytes()[8 - size as usize..].to_vec()
+    }
 }
 
 impl Descriptor for DecoderSpecificDescriptor {
@@ -523,12 +577,18 @@ fn get_chan_conf<R: Read + Seek>(
     byte_b: u8,
     freq_index: u8,
     extended_profile: bool,
-) -> Result<u8> {
+) -> Result<(u8, Option<u32>)> {
     let chan_conf;
+    let mut sample_rate = None;
     if freq_index == 15 {
-        // Skip the 24 bit sample rate
-        let sample_rate = reader.read_u24::<BigEndian>()?;
-        chan_conf = ((sample_rate >> 4) & 0x0F) as u8;
+        // The 24 bit sample rate starts right after the index.
+        let bytes = reader.read_u24::<BigEndian>()?;
+        sample_rate = Some(if extended_profile {
+            ((byte_b as u32 & 0x01) << 23) | (bytes >> 1)
+        } else {
+            ((byte_b as u32 & 0x7F) << 17) | (bytes >> 7)
+        });
+        chan_conf = ((bytes >> 4) & 0x0F) as u8;
     } else if extended_profile {
         let byte_c = reader.read_u8()?;
         chan_conf = (byte_b & 1) | (byte_c & 0xE0);
@@ -536,7 +596,7 @@ fn get_chan_conf<R: Read + Seek>(
         chan_conf = (byte_b >> 3) & 0x0F;
     }
 
-    Ok(chan_conf)
+    Ok((chan_conf, sample_rate))
 }
 
 impl<R: Read + Seek> ReadDesc<&mut R> for DecoderSpecificDescriptor {
@@ -546,18 +606,20 @@ impl<R: Read + Seek> ReadDesc<&mut R> for DecoderSpecificDescriptor {
         let profile = get_audio_object_type(byte_a, byte_b);
         let freq_index;
         let chan_conf;
+        let sample_rate;
         if profile > 31 {
             freq_index = (byte_b >> 1) & 0x0F;
-            chan_conf = get_chan_conf(reader, byte_b, freq_index, true)?;
+            (chan_conf, sample_rate) = get_chan_conf(reader, byte_b, freq_index, true)?;
         } else {
             freq_index = ((byte_a & 0x07) << 1) + (byte_b >> 7);
-            chan_conf = get_chan_conf(reader, byte_b, freq_index, false)?;
+            (chan_conf, sample_rate) = get_chan_conf(reader, byte_b, freq_index, false)?;
         }
 
         Ok(DecoderSpecificDescriptor {
             profile,
             freq_index,
             chan_conf,
+            sample_rate,
         })
     }
 }
@@ -640,11 +702,13 @@ mod tests {
                             profile: 2,
                             freq_index: 3,
                             chan_conf: 1,
+                            sample_rate: None,
                         },
                     },
                     sl_config: SLConfigDescriptor::default(),
                 },
             }),
+            sinf: None,
         };
         let mut buf = Vec::new();
         src_box.write_box(&mut buf).unwrap();
@@ -667,6 +731,7 @@ mod tests {
             samplesize: 16,
             samplerate: FixedPointU16::new(48000),
             esds: None,
+            sinf: None,
         };
         let mut buf = Vec::new();
         src_box.write_box(&mut buf).unwrap();
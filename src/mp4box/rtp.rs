@@ -0,0 +1,182 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use serde::Serialize;
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::*;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RtpBox {
+    pub data_reference_index: u16,
+    pub hint_track_version: u16,
+    pub highest_compatible_version: u16,
+    pub max_packet_size: u32,
+    pub timescale: u32,
+}
+
+impl Default for RtpBox {
+    fn default() -> Self {
+        RtpBox {
+            data_reference_index: 0,
+            hint_track_version: 1,
+            highest_compatible_version: 1,
+            max_packet_size: 0,
+            timescale: 0,
+        }
+    }
+}
+
+impl RtpBox {
+    pub fn new(config: &RtpHintConfig) -> Self {
+        RtpBox {
+            data_reference_index: 1,
+            max_packet_size: config.max_packet_size,
+            timescale: config.rtp_timescale,
+            ..Default::default()
+        }
+    }
+
+    pub fn get_type(&self) -> BoxType {
+        BoxType::RtpBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        // sample entry + rtp fields + tims box
+        HEADER_SIZE + 8 + 8 + HEADER_SIZE + 4
+    }
+}
+
+impl Mp4Box for RtpBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "data_reference_index={} max_packet_size={} timescale={}",
+            self.data_reference_index, self.max_packet_size, self.timescale
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for RtpBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        reader.read_u32::<BigEndian>()?; // reserved
+        reader.read_u16::<BigEndian>()?; // reserved
+        let data_reference_index = reader.read_u16::<BigEndian>()?;
+
+        let hint_track_version = reader.read_u16::<BigEndian>()?;
+        let highest_compatible_version = reader.read_u16::<BigEndian>()?;
+        let max_packet_size = reader.read_u32::<BigEndian>()?;
+
+        let mut timescale = 0;
+
+        let mut current = reader.stream_position()?;
+        let end = start + size;
+        while current < end {
+            // Get box header.
+            let header = BoxHeader::read(reader)?;
+            let BoxHeader { name, size: s } = header;
+            if s > size {
+                return Err(Error::InvalidData(
+                    "rtp box contains a box with a larger size than it",
+                ));
+            }
+
+            if name == BoxType::TimsBox {
+                if s != HEADER_SIZE + 4 {
+                    return Err(Error::InvalidData("tims box size must be 12"));
+                }
+                timescale = reader.read_u32::<BigEndian>()?;
+            }
+
+            skip_bytes_to(reader, current + s)?;
+            current = reader.stream_position()?;
+        }
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(RtpBox {
+            data_reference_index,
+            hint_track_version,
+            highest_compatible_version,
+            max_packet_size,
+            timescale,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for RtpBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        writer.write_u32::<BigEndian>(0)?; // reserved
+        writer.write_u16::<BigEndian>(0)?; // reserved
+        writer.write_u16::<BigEndian>(self.data_reference_index)?;
+
+        writer.write_u16::<BigEndian>(self.hint_track_version)?;
+        writer.write_u16::<BigEndian>(self.highest_compatible_version)?;
+        writer.write_u32::<BigEndian>(self.max_packet_size)?;
+
+        BoxHeader::new(BoxType::TimsBox, HEADER_SIZE + 4).write(writer)?;
+        writer.write_u32::<BigEndian>(self.timescale)?;
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_rtp() {
+        let src_box = RtpBox {
+            data_reference_index: 1,
+            hint_track_version: 1,
+            highest_compatible_version: 1,
+            max_packet_size: 1450,
+            timescale: 90000,
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::RtpBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = RtpBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+
+    #[test]
+    fn test_rtp_tims_size() {
+        let mut buf = Vec::new();
+        RtpBox::default().write_box(&mut buf).unwrap();
+        // A tims box too small to hold its timescale.
+        let tims = buf.len() - 12;
+        buf[tims..tims + 4].copy_from_slice(&8u32.to_be_bytes());
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert!(matches!(
+            RtpBox::read_box(&mut reader, header.size),
+            Err(Error::InvalidData("tims box size must be 12"))
+        ));
+    }
+}
@@ -0,0 +1,156 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use serde::Serialize;
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::*;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SchmBox {
+    pub version: u8,
+    pub flags: u32,
+    pub scheme_type: FourCC,
+    pub scheme_version: u32,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scheme_uri: Option<String>,
+}
+
+impl SchmBox {
+    pub fn get_type(&self) -> BoxType {
+        BoxType::SchmBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        let mut size = HEADER_SIZE + HEADER_EXT_SIZE + 8;
+        if let Some(ref scheme_uri) = self.scheme_uri {
+            size += scheme_uri.len() as u64 + 1;
+        }
+        size
+    }
+}
+
+impl Mp4Box for SchmBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "scheme_type={} scheme_version={:#x}",
+            self.scheme_type, self.scheme_version
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for SchmBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let (version, flags) = read_box_header_ext(reader)?;
+
+        let scheme_type = reader.read_u32::<BigEndian>()?;
+        let scheme_version = reader.read_u32::<BigEndian>()?;
+
+        let scheme_uri = if flags & 0x01 != 0 {
+            let buf_size = size
+                .checked_sub(HEADER_SIZE + HEADER_EXT_SIZE + 8)
+                .ok_or(Error::InvalidData("schm size too small"))?;
+            let mut buf = vec![0u8; buf_size as usize];
+            reader.read_exact(&mut buf)?;
+            if let Some(end) = buf.iter().position(|&b| b == b'\0') {
+                buf.truncate(end);
+            }
+            Some(String::from_utf8(buf).unwrap_or_default())
+        } else {
+            None
+        };
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(SchmBox {
+            version,
+            flags,
+            scheme_type: From::from(scheme_type),
+            scheme_version,
+            scheme_uri,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for SchmBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        write_box_header_ext(writer, self.version, self.flags)?;
+
+        writer.write_u32::<BigEndian>((&self.scheme_type).into())?;
+        writer.write_u32::<BigEndian>(self.scheme_version)?;
+        if let Some(ref scheme_uri) = self.scheme_uri {
+            writer.write_all(scheme_uri.as_bytes())?;
+            writer.write_u8(0)?;
+        }
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_schm() {
+        let src_box = SchmBox {
+            version: 0,
+            flags: 0,
+            scheme_type: str::parse("cenc").unwrap(),
+            scheme_version: 0x0001_0000,
+            scheme_uri: None,
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::SchmBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = SchmBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+
+    #[test]
+    fn test_schm_uri() {
+        let src_box = SchmBox {
+            version: 0,
+            flags: 1,
+            scheme_type: str::parse("cbcs").unwrap(),
+            scheme_version: 0x0001_0000,
+            scheme_uri: Some(String::from("urn:example:scheme")),
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::SchmBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = SchmBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+}
@@ -0,0 +1,143 @@
+use byteorder::{ReadBytesExt, WriteBytesExt};
+use serde::Serialize;
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::*;
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct SdtpBox {
+    pub version: u8,
+    pub flags: u32,
+
+    #[serde(skip_serializing)]
+    pub entries: Vec<SdtpEntry>,
+}
+
+/// Per-sample dependency flags. Each field is a 2-bit value where 0 means
+/// unknown, 1 means yes and 2 means no (for `is_leading`, 3 is also valid).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub struct SdtpEntry {
+    pub is_leading: u8,
+    pub sample_depends_on: u8,
+    pub sample_is_depended_on: u8,
+    pub sample_has_redundancy: u8,
+}
+
+impl SdtpBox {
+    pub fn get_type(&self) -> BoxType {
+        BoxType::SdtpBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        HEADER_SIZE + HEADER_EXT_SIZE + self.entries.len() as u64
+    }
+}
+
+impl Mp4Box for SdtpBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!("entries={}", self.entries.len());
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for SdtpBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let (version, flags) = read_box_header_ext(reader)?;
+
+        // The sample count comes from stsz; sdtp holds one byte per sample
+        // for the rest of the box.
+        let entry_count = size.saturating_sub(HEADER_SIZE + HEADER_EXT_SIZE);
+        let mut entries = Vec::with_capacity(entry_count as usize);
+        for _i in 0..entry_count {
+            let b = reader.read_u8()?;
+            entries.push(SdtpEntry {
+                is_leading: (b >> 6) & 0x03,
+                sample_depends_on: (b >> 4) & 0x03,
+                sample_is_depended_on: (b >> 2) & 0x03,
+                sample_has_redundancy: b & 0x03,
+            });
+        }
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(SdtpBox {
+            version,
+            flags,
+            entries,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for SdtpBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        write_box_header_ext(writer, self.version, self.flags)?;
+
+        for entry in self.entries.iter() {
+            writer.write_u8(
+                ((entry.is_leading & 0x03) << 6)
+                    | ((entry.sample_depends_on & 0x03) << 4)
+                    | ((entry.sample_is_depended_on & 0x03) << 2)
+                    | (entry.sample_has_redundancy & 0x03),
+            )?;
+        }
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_sdtp() {
+        let src_box = SdtpBox {
+            version: 0,
+            flags: 0,
+            entries: vec![
+                SdtpEntry {
+                    is_leading: 0,
+                    sample_depends_on: 2,
+                    sample_is_depended_on: 1,
+                    sample_has_redundancy: 0,
+                },
+                SdtpEntry {
+                    is_leading: 0,
+                    sample_depends_on: 1,
+                    sample_is_depended_on: 2,
+                    sample_has_redundancy: 0,
+                },
+            ],
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::SdtpBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = SdtpBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+}
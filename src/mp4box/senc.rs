@@ -0,0 +1,198 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use serde::Serialize;
+use std::io::{Cursor, Read, Seek, Write};
+
+use crate::mp4box::*;
+
+/// Common Encryption sample auxiliary information of a track fragment.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct SencBox {
+    pub version: u8,
+    pub flags: u32,
+    pub sample_count: u32,
+
+    /// The per-sample entries, left unparsed as the size of their IVs is
+    /// set by the track's `tenc`. See [`SencBox::entries`].
+    #[serde(skip_serializing)]
+    pub sample_info: Vec<u8>,
+}
+
+/// The IV and subsample layout of one encrypted sample.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct SencEntry {
+    pub iv: Vec<u8>,
+    pub subsamples: Vec<SencSubsample>,
+}
+
+/// A run of clear bytes followed by a run of protected bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub struct SencSubsample {
+    pub bytes_of_clear_data: u16,
+    pub bytes_of_protected_data: u32,
+}
+
+impl SencBox {
+    pub const FLAG_USE_SUBSAMPLE_ENCRYPTION: u32 = 0x02;
+
+    pub fn get_type(&self) -> BoxType {
+        BoxType::SencBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        HEADER_SIZE + HEADER_EXT_SIZE + 4 + self.sample_info.len() as u64
+    }
+
+    /// Builds a box holding `entries`, whose IVs must all be the same size.
+    pub fn new(entries: &[SencEntry]) -> Self {
+        let mut flags = 0;
+        if entries.iter().any(|entry| !entry.subsamples.is_empty()) {
+            flags |= Self::FLAG_USE_SUBSAMPLE_ENCRYPTION;
+        }
+        let mut sample_info = Vec::new();
+        for entry in entries {
+            sample_info.extend_from_slice(&entry.iv);
+            if flags & Self::FLAG_USE_SUBSAMPLE_ENCRYPTION != 0 {
+                sample_info.extend_from_slice(&(entry.subsamples.len() as u16).to_be_bytes());
+                for subsample in entry.subsamples.iter() {
+                    sample_info.extend_from_slice(&subsample.bytes_of_clear_data.to_be_bytes());
+                    sample_info.extend_from_slice(&subsample.bytes_of_protected_data.to_be_bytes());
+                }
+            }
+        }
+        SencBox {
+            version: 0,
+            flags,
+            sample_count: entries.len() as u32,
+            sample_info,
+        }
+    }
+
+    /// Parses the entries, given the track's per-sample IV size.
+    pub fn entries(&self, per_sample_iv_size: u8) -> Result<Vec<SencEntry>> {
+        let mut reader = Cursor::new(&self.sample_info);
+        let mut entries = Vec::with_capacity(self.sample_count as usize);
+        for _ in 0..self.sample_count {
+            let mut iv = vec![0u8; per_sample_iv_size as usize];
+            reader.read_exact(&mut iv)?;
+            let mut subsamples = Vec::new();
+            if self.flags & Self::FLAG_USE_SUBSAMPLE_ENCRYPTION != 0 {
+                let subsample_count = reader.read_u16::<BigEndian>()?;
+                for _ in 0..subsample_count {
+                    subsamples.push(SencSubsample {
+                        bytes_of_clear_data: reader.read_u16::<BigEndian>()?,
+                        bytes_of_protected_data: reader.read_u32::<BigEndian>()?,
+                    });
+                }
+            }
+            entries.push(SencEntry { iv, subsamples });
+        }
+        if reader.position() != self.sample_info.len() as u64 {
+            return Err(Error::InvalidData(
+                "senc entries don't match the per-sample IV size",
+            ));
+        }
+        Ok(entries)
+    }
+}
+
+impl Mp4Box for SencBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!("sample_count={}", self.sample_count);
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for SencBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let (version, flags) = read_box_header_ext(reader)?;
+        let sample_count = reader.read_u32::<BigEndian>()?;
+
+        let info_size = size
+            .checked_sub(HEADER_SIZE + HEADER_EXT_SIZE + 4)
+            .ok_or(Error::InvalidData("senc size too small"))?;
+        let mut sample_info = vec![0u8; info_size as usize];
+        reader.read_exact(&mut sample_info)?;
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(SencBox {
+            version,
+            flags,
+            sample_count,
+            sample_info,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for SencBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        write_box_header_ext(writer, self.version, self.flags)?;
+        writer.write_u32::<BigEndian>(self.sample_count)?;
+        writer.write_all(&self.sample_info)?;
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+
+    #[test]
+    fn test_senc() {
+        let entries = vec![
+            SencEntry {
+                iv: vec![1; 8],
+                subsamples: vec![SencSubsample {
+                    bytes_of_clear_data: 5,
+                    bytes_of_protected_data: 32,
+                }],
+            },
+            SencEntry {
+                iv: vec![2; 8],
+                subsamples: vec![
+                    SencSubsample {
+                        bytes_of_clear_data: 10,
+                        bytes_of_protected_data: 0,
+                    },
+                    SencSubsample {
+                        bytes_of_clear_data: 2,
+                        bytes_of_protected_data: 64,
+                    },
+                ],
+            },
+        ];
+        let src_box = SencBox::new(&entries);
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::SencBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = SencBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+        assert_eq!(dst_box.entries(8).unwrap(), entries);
+        assert!(dst_box.entries(16).is_err());
+    }
+}
@@ -0,0 +1,251 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use serde::Serialize;
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::*;
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct SidxBox {
+    pub version: u8,
+    pub flags: u32,
+    pub reference_id: u32,
+    pub timescale: u32,
+    pub earliest_presentation_time: u64,
+    pub first_offset: u64,
+
+    #[serde(skip_serializing)]
+    pub references: Vec<SidxReference>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct SidxReference {
+    /// Set if the reference points to another `sidx` rather than to media.
+    pub reference_type: bool,
+    pub referenced_size: u32,
+    pub subsegment_duration: u32,
+    pub starts_with_sap: bool,
+    pub sap_type: u8,
+    pub sap_delta_time: u32,
+}
+
+impl SidxBox {
+    pub fn get_type(&self) -> BoxType {
+        BoxType::SidxBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        let mut size = HEADER_SIZE + HEADER_EXT_SIZE + 8;
+        if self.version == 1 {
+            size += 16;
+        } else {
+            size += 8;
+        }
+        size += 4 + self.references.len() as u64 * 12;
+        size
+    }
+}
+
+impl Mp4Box for SidxBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "reference_id={} timescale={} earliest_presentation_time={} first_offset={} reference_count={}",
+            self.reference_id,
+            self.timescale,
+            self.earliest_presentation_time,
+            self.first_offset,
+            self.references.len()
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for SidxBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let (version, flags) = read_box_header_ext(reader)?;
+
+        let reference_id = reader.read_u32::<BigEndian>()?;
+        let timescale = reader.read_u32::<BigEndian>()?;
+        let (earliest_presentation_time, first_offset) = if version == 1 {
+            (
+                reader.read_u64::<BigEndian>()?,
+                reader.read_u64::<BigEndian>()?,
+            )
+        } else if version == 0 {
+            (
+                reader.read_u32::<BigEndian>()? as u64,
+                reader.read_u32::<BigEndian>()? as u64,
+            )
+        } else {
+            return Err(Error::InvalidData("version must be 0 or 1"));
+        };
+        reader.read_u16::<BigEndian>()?; // reserved
+        let reference_count = reader.read_u16::<BigEndian>()?;
+
+        let header_size = HEADER_SIZE + HEADER_EXT_SIZE;
+        let other_size = if version == 1 { 28 } else { 20 };
+        let entry_size = 12;
+        if u64::from(reference_count) > size.saturating_sub(header_size + other_size) / entry_size {
+            return Err(Error::InvalidData(
+                "sidx reference_count indicates more values than could fit in the box",
+            ));
+        }
+
+        let mut references = Vec::with_capacity(reference_count as usize);
+        for _ in 0..reference_count {
+            let type_and_size = reader.read_u32::<BigEndian>()?;
+            let subsegment_duration = reader.read_u32::<BigEndian>()?;
+            let sap = reader.read_u32::<BigEndian>()?;
+            references.push(SidxReference {
+                reference_type: type_and_size >> 31 == 1,
+                referenced_size: type_and_size & 0x7FFF_FFFF,
+                subsegment_duration,
+                starts_with_sap: sap >> 31 == 1,
+                sap_type: ((sap >> 28) & 0x07) as u8,
+                sap_delta_time: sap & 0x0FFF_FFFF,
+            });
+        }
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(SidxBox {
+            version,
+            flags,
+            reference_id,
+            timescale,
+            earliest_presentation_time,
+            first_offset,
+            references,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for SidxBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        write_box_header_ext(writer, self.version, self.flags)?;
+
+        writer.write_u32::<BigEndian>(self.reference_id)?;
+        writer.write_u32::<BigEndian>(self.timescale)?;
+        if self.version == 1 {
+            writer.write_u64::<BigEndian>(self.earliest_presentation_time)?;
+            writer.write_u64::<BigEndian>(self.first_offset)?;
+        } else if self.version == 0 {
+            writer.write_u32::<BigEndian>(self.earliest_presentation_time as u32)?;
+            writer.write_u32::<BigEndian>(self.first_offset as u32)?;
+        } else {
+            return Err(Error::InvalidData("version must be 0 or 1"));
+        }
+        writer.write_u16::<BigEndian>(0)?; // reserved
+        writer.write_u16::<BigEndian>(self.references.len() as u16)?;
+
+        for reference in self.references.iter() {
+            writer.write_u32::<BigEndian>(
+                ((reference.reference_type as u32) << 31)
+                    | (reference.referenced_size & 0x7FFF_FFFF),
+            )?;
+            writer.write_u32::<BigEndian>(reference.subsegment_duration)?;
+            writer.write_u32::<BigEndian>(
+                ((reference.starts_with_sap as u32) << 31)
+                    | ((reference.sap_type as u32 & 0x07) << 28)
+                    | (reference.sap_delta_time & 0x0FFF_FFFF),
+            )?;
+        }
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_sidx32() {
+        let src_box = SidxBox {
+            version: 0,
+            flags: 0,
+            reference_id: 1,
+            timescale: 90000,
+            earliest_presentation_time: 1234,
+            first_offset: 0,
+            references: vec![
+                SidxReference {
+                    reference_type: false,
+                    referenced_size: 5000,
+                    subsegment_duration: 180000,
+                    starts_with_sap: true,
+                    sap_type: 1,
+                    sap_delta_time: 0,
+                },
+                SidxReference {
+                    reference_type: false,
+                    referenced_size: 4000,
+                    subsegment_duration: 180000,
+                    starts_with_sap: false,
+                    sap_type: 0,
+                    sap_delta_time: 3003,
+                },
+            ],
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::SidxBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = SidxBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+
+    #[test]
+    fn test_sidx64() {
+        let src_box = SidxBox {
+            version: 1,
+            flags: 0,
+            reference_id: 1,
+            timescale: 1000,
+            earliest_presentation_time: 0x1_0000_0000,
+            first_offset: 16,
+            references: vec![SidxReference {
+                reference_type: true,
+                referenced_size: 1024,
+                subsegment_duration: 2000,
+                starts_with_sap: true,
+                sap_type: 2,
+                sap_delta_time: 0,
+            }],
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::SidxBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = SidxBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+}
@@ -0,0 +1,208 @@
+use serde::Serialize;
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+
+use crate::mp4box::*;
+use crate::mp4box::{frma::FrmaBox, schm::SchmBox, tenc::TencBox};
+
+/// Protection scheme information of an `encv`/`enca` sample entry.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SinfBox {
+    pub frma: FrmaBox,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schm: Option<SchmBox>,
+
+    /// The `schi` box (header included), kept verbatim.
+    #[serde(skip_serializing)]
+    pub schi: Option<Vec<u8>>,
+}
+
+impl SinfBox {
+    pub fn get_type(&self) -> BoxType {
+        BoxType::SinfBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        let mut size = HEADER_SIZE + self.frma.box_size();
+        if let Some(ref schm) = self.schm {
+            size += schm.box_size();
+        }
+        if let Some(ref schi) = self.schi {
+            size += schi.len() as u64;
+        }
+        size
+    }
+
+    /// The `tenc` box in `schi`, holding the Common Encryption defaults.
+    pub fn tenc(&self) -> Result<Option<TencBox>> {
+        let schi = match self.schi {
+            Some(ref schi) => schi,
+            None => return Ok(None),
+        };
+        let mut reader = Cursor::new(schi);
+        let end = BoxHeader::read(&mut reader)?.size.min(schi.len() as u64);
+        let mut current = reader.position();
+        while current + HEADER_SIZE <= end {
+            let header = BoxHeader::read(&mut reader)?;
+            if header.size < HEADER_SIZE || current + header.size > end {
+                return Err(Error::InvalidData("schi contains a box larger than it"));
+            }
+            if header.name == BoxType::TencBox {
+                return Ok(Some(TencBox::read_box(&mut reader, header.size)?));
+            }
+            current += header.size;
+            reader.set_position(current);
+        }
+        Ok(None)
+    }
+}
+
+impl Mp4Box for SinfBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = match self.schm {
+            Some(ref schm) => format!(
+                "original_format={} scheme_type={}",
+                self.frma.original_format, schm.scheme_type
+            ),
+            None => format!("original_format={}", self.frma.original_format),
+        };
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for SinfBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let mut frma = None;
+        let mut schm = None;
+        let mut schi = None;
+
+        let mut current = reader.stream_position()?;
+        let end = start + size;
+        while current < end {
+            let header = BoxHeader::read(reader)?;
+            let BoxHeader { name, size: s } = header;
+            if s > size {
+                return Err(Error::InvalidData(
+                    "sinf box contains a box with a larger size than it",
+                ));
+            }
+            match name {
+                BoxType::FrmaBox => {
+                    frma = Some(FrmaBox::read_box(reader, s)?);
+                }
+                BoxType::SchmBox => {
+                    schm = Some(SchmBox::read_box(reader, s)?);
+                }
+                BoxType::SchiBox => {
+                    let mut bytes = vec![0u8; s as usize];
+                    reader.seek(SeekFrom::Start(current))?;
+                    reader.read_exact(&mut bytes)?;
+                    schi = Some(bytes);
+                }
+                _ => {}
+            }
+            skip_bytes_to(reader, current + s)?;
+            current = reader.stream_position()?;
+        }
+
+        let frma = frma.ok_or(Error::BoxNotFound(BoxType::FrmaBox))?;
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(SinfBox { frma, schm, schi })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for SinfBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        self.frma.write_box(writer)?;
+        if let Some(ref schm) = self.schm {
+            schm.write_box(writer)?;
+        }
+        if let Some(ref schi) = self.schi {
+            writer.write_all(schi)?;
+        }
+
+        Ok(size)
+    }
+}
+
+/// Looks through the child boxes of a protected sample entry, from `start`
+/// up to `end`, for the original format recorded in `sinf/frma`. Leaves the
+/// reader at an unspecified position.
+pub(crate) fn read_original_format<R: Read + Seek>(
+    reader: &mut R,
+    start: u64,
+    end: u64,
+) -> Result<Option<FourCC>> {
+    reader.seek(SeekFrom::Start(start))?;
+    let mut current = start;
+    while current + HEADER_SIZE <= end {
+        let header = BoxHeader::read(reader)?;
+        let BoxHeader { name, size: s } = header;
+        if s < HEADER_SIZE || current + s > end {
+            break;
+        }
+        if name == BoxType::SinfBox {
+            let sinf = SinfBox::read_box(reader, s)?;
+            return Ok(Some(sinf.frma.original_format));
+        }
+        skip_bytes_to(reader, current + s)?;
+        current += s;
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_sinf() {
+        let src_box = SinfBox {
+            frma: FrmaBox {
+                original_format: str::parse("mp4a").unwrap(),
+            },
+            schm: Some(SchmBox {
+                version: 0,
+                flags: 0,
+                scheme_type: str::parse("cenc").unwrap(),
+                scheme_version: 0x0001_0000,
+                scheme_uri: None,
+            }),
+            schi: Some(vec![
+                0x00, 0x00, 0x00, 0x0C, b's', b'c', b'h', b'i', 0xDE, 0xAD, 0xBE, 0xEF,
+            ]),
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::SinfBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = SinfBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+}
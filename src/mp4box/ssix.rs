@@ -0,0 +1,168 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use serde::Serialize;
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::*;
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct SsixBox {
+    pub version: u8,
+    pub flags: u32,
+
+    #[serde(skip_serializing)]
+    pub subsegments: Vec<SsixSubsegment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct SsixSubsegment {
+    pub ranges: Vec<SsixRange>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub struct SsixRange {
+    pub level: u8,
+    /// 24-bit byte count of the range.
+    pub range_size: u32,
+}
+
+impl SsixBox {
+    pub fn get_type(&self) -> BoxType {
+        BoxType::SsixBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        let mut size = HEADER_SIZE + HEADER_EXT_SIZE + 4;
+        for subsegment in self.subsegments.iter() {
+            size += 4 + subsegment.ranges.len() as u64 * 4;
+        }
+        size
+    }
+}
+
+impl Mp4Box for SsixBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!("subsegment_count={}", self.subsegments.len());
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for SsixBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let (version, flags) = read_box_header_ext(reader)?;
+
+        let subsegment_count = reader.read_u32::<BigEndian>()?;
+        let header_size = HEADER_SIZE + HEADER_EXT_SIZE;
+        if u64::from(subsegment_count) > size.saturating_sub(header_size + 4) / 4 {
+            return Err(Error::InvalidData(
+                "ssix subsegment_count indicates more values than could fit in the box",
+            ));
+        }
+
+        let mut subsegments = Vec::with_capacity(subsegment_count as usize);
+        for _ in 0..subsegment_count {
+            let range_count = reader.read_u32::<BigEndian>()?;
+            let remaining = (start + size).saturating_sub(reader.stream_position()?);
+            if u64::from(range_count) > remaining / 4 {
+                return Err(Error::InvalidData(
+                    "ssix range_count indicates more values than could fit in the box",
+                ));
+            }
+
+            let mut ranges = Vec::with_capacity(range_count as usize);
+            for _ in 0..range_count {
+                let level = reader.read_u8()?;
+                let range_size = reader.read_u24::<BigEndian>()?;
+                ranges.push(SsixRange { level, range_size });
+            }
+            subsegments.push(SsixSubsegment { ranges });
+        }
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(SsixBox {
+            version,
+            flags,
+            subsegments,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for SsixBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        write_box_header_ext(writer, self.version, self.flags)?;
+
+        writer.write_u32::<BigEndian>(self.subsegments.len() as u32)?;
+        for subsegment in self.subsegments.iter() {
+            writer.write_u32::<BigEndian>(subsegment.ranges.len() as u32)?;
+            for range in subsegment.ranges.iter() {
+                writer.write_u8(range.level)?;
+                writer.write_u24::<BigEndian>(range.range_size)?;
+            }
+        }
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_ssix() {
+        let src_box = SsixBox {
+            version: 0,
+            flags: 0,
+            subsegments: vec![
+                SsixSubsegment {
+                    ranges: vec![
+                        SsixRange {
+                            level: 1,
+                            range_size: 1200,
+                        },
+                        SsixRange {
+                            level: 2,
+                            range_size: 3800,
+                        },
+                    ],
+                },
+                SsixSubsegment {
+                    ranges: vec![SsixRange {
+                        level: 1,
+                        range_size: 4000,
+                    }],
+                },
+            ],
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::SsixBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = SsixBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+}
@@ -3,8 +3,8 @@ use std::io::{Read, Seek, Write};
 
 use crate::mp4box::*;
 use crate::mp4box::{
-    co64::Co64Box, ctts::CttsBox, stco::StcoBox, stsc::StscBox, stsd::StsdBox, stss::StssBox,
-    stsz::StszBox, stts::SttsBox,
+    co64::Co64Box, cslg::CslgBox, ctts::CttsBox, sdtp::SdtpBox, stco::StcoBox, stsc::StscBox,
+    stsd::StsdBox, stss::StssBox, stsz::StszBox, stts::SttsBox,
 };
 
 #[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
@@ -15,8 +15,14 @@ pub struct StblBox {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub ctts: Option<CttsBox>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cslg: Option<CslgBox>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub stss: Option<StssBox>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sdtp: Option<SdtpBox>,
     pub stsc: StscBox,
     pub stsz: StszBox,
 
@@ -32,6 +38,26 @@ impl StblBox {
         BoxType::StblBox
     }
 
+    /// Sets the chunk offsets, in an `stco` if they all fit in 32 bits and in
+    /// a `co64` otherwise.
+    pub(crate) fn set_chunk_offsets(&mut self, offsets: Vec<u64>) {
+        if offsets.iter().all(|&offset| offset <= u32::MAX as u64) {
+            self.stco = Some(StcoBox {
+                version: 0,
+                flags: 0,
+                entries: offsets.into_iter().map(|offset| offset as u32).collect(),
+            });
+            self.co64 = None;
+        } else {
+            self.stco = None;
+            self.co64 = Some(Co64Box {
+                version: 0,
+                flags: 0,
+                entries: offsets,
+            });
+        }
+    }
+
     pub fn get_size(&self) -> u64 {
         let mut size = HEADER_SIZE;
         size += self.stsd.box_size();
@@ -39,9 +65,15 @@ impl StblBox {
         if let Some(ref ctts) = self.ctts {
             size += ctts.box_size();
         }
+        if let Some(ref cslg) = self.cslg {
+            size += cslg.box_size();
+        }
         if let Some(ref stss) = self.stss {
             size += stss.box_size();
         }
+        if let Some(ref sdtp) = self.sdtp {
+            size += sdtp.box_size();
+        }
         size += self.stsc.box_size();
         size += self.stsz.box_size();
         if let Some(ref stco) = self.stco {
@@ -80,7 +112,9 @@ impl<R: Read + Seek> ReadBox<&mut R> for StblBox {
         let mut stsd = None;
         let mut stts = None;
         let mut ctts = None;
+        let mut cslg = None;
         let mut stss = None;
+        let mut sdtp = None;
         let mut stsc = None;
         let mut stsz = None;
         let mut stco = None;
@@ -108,9 +142,15 @@ impl<R: Read + Seek> ReadBox<&mut R> for StblBox {
                 BoxType::CttsBox => {
                     ctts = Some(CttsBox::read_box(reader, s)?);
                 }
+                BoxType::CslgBox => {
+                    cslg = Some(CslgBox::read_box(reader, s)?);
+                }
                 BoxType::StssBox => {
                     stss = Some(StssBox::read_box(reader, s)?);
                 }
+                BoxType::SdtpBox => {
+                    sdtp = Some(SdtpBox::read_box(reader, s)?);
+                }
                 BoxType::StscBox => {
                     stsc = Some(StscBox::read_box(reader, s)?);
                 }
@@ -153,7 +193,9 @@ impl<R: Read + Seek> ReadBox<&mut R> for StblBox {
             stsd: stsd.unwrap(),
             stts: stts.unwrap(),
             ctts,
+            cslg,
             stss,
+            sdtp,
             stsc: stsc.unwrap(),
             stsz: stsz.unwrap(),
             stco,
@@ -172,9 +214,15 @@ impl<W: Write> WriteBox<&mut W> for StblBox {
         if let Some(ref ctts) = self.ctts {
             ctts.write_box(writer)?;
         }
+        if let Some(ref cslg) = self.cslg {
+            cslg.write_box(writer)?;
+        }
         if let Some(ref stss) = self.stss {
             stss.write_box(writer)?;
         }
+        if let Some(ref sdtp) = self.sdtp {
+            sdtp.write_box(writer)?;
+        }
         self.stsc.write_box(writer)?;
         self.stsz.write_box(writer)?;
         if let Some(ref stco) = self.stco {
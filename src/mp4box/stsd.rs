@@ -1,30 +1,131 @@
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use serde::Serialize;
-use std::io::{Read, Seek, Write};
+use std::io::{Read, Seek, SeekFrom, Write};
 
 use crate::mp4box::vp09::Vp09Box;
 use crate::mp4box::*;
-use crate::mp4box::{avc1::Avc1Box, hev1::Hev1Box, mp4a::Mp4aBox, tx3g::Tx3gBox};
+use crate::mp4box::{
+    ac3::Ac3Box, av01::Av01Box, avc1::Avc1Box, dvh1::Dvh1Box, eac3::Eac3Box, hev1::Hev1Box,
+    mp4a::Mp4aBox, opus::OpusBox, rtp::RtpBox, sinf::read_original_format, text::TextBox,
+    tx3g::Tx3gBox,
+};
 
 #[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
 pub struct StsdBox {
     pub version: u8,
     pub flags: u32,
 
+    /// The number of sample entries in the box as read. Only the first is
+    /// parsed, and exactly one is written.
+    pub entry_count: u32,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub avc1: Option<Avc1Box>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub hev1: Option<Hev1Box>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dvh1: Option<Dvh1Box>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub vp09: Option<Vp09Box>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub av01: Option<Av01Box>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub mp4a: Option<Mp4aBox>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub opus: Option<OpusBox>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ac3: Option<Ac3Box>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub eac3: Option<Eac3Box>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tx3g: Option<Tx3gBox>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<TextBox>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rtp: Option<RtpBox>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raw: Option<RawSampleEntry>,
+}
+
+/// A sample entry of a type this crate doesn't model, kept verbatim
+/// (header included) so it can be written back unchanged.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RawSampleEntry {
+    pub entry_type: FourCC,
+
+    #[serde(skip_serializing)]
+    pub bytes: Vec<u8>,
+}
+
+impl RawSampleEntry {
+    pub fn new(bytes: Vec<u8>) -> Result<Self> {
+        if bytes.len() < HEADER_SIZE as usize {
+            return Err(Error::InvalidData(
+                "sample entry is shorter than a box header",
+            ));
+        }
+        let size = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        if size as usize != bytes.len() {
+            return Err(Error::InvalidData(
+                "sample entry size does not match its length",
+            ));
+        }
+        let entry_type = FourCC::from([bytes[4], bytes[5], bytes[6], bytes[7]]);
+        Ok(RawSampleEntry { entry_type, bytes })
+    }
+
+    pub fn get_type(&self) -> BoxType {
+        BoxType::from(u32::from(self.entry_type))
+    }
+
+    pub fn get_size(&self) -> u64 {
+        self.bytes.len() as u64
+    }
+
+    /// Every sample entry starts with 6 reserved bytes followed by the
+    /// data_reference_index.
+    pub fn data_reference_index(&self) -> Option<u16> {
+        let index = self.bytes.get(14..16)?;
+        Some(u16::from_be_bytes([index[0], index[1]]))
+    }
+}
+
+impl Mp4Box for RawSampleEntry {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!("entry_type={} size={}", self.entry_type, self.bytes.len());
+        Ok(s)
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for RawSampleEntry {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        writer.write_all(&self.bytes)?;
+        Ok(self.box_size())
+    }
 }
 
 impl StsdBox {
@@ -38,15 +139,61 @@ impl StsdBox {
             size += avc1.box_size();
         } else if let Some(ref hev1) = self.hev1 {
             size += hev1.box_size();
+        } else if let Some(ref dvh1) = self.dvh1 {
+            size += dvh1.box_size();
         } else if let Some(ref vp09) = self.vp09 {
             size += vp09.box_size();
+        } else if let Some(ref av01) = self.av01 {
+            size += av01.box_size();
         } else if let Some(ref mp4a) = self.mp4a {
             size += mp4a.box_size();
+        } else if let Some(ref opus) = self.opus {
+            size += opus.box_size();
+        } else if let Some(ref ac3) = self.ac3 {
+            size += ac3.box_size();
+        } else if let Some(ref eac3) = self.eac3 {
+            size += eac3.box_size();
         } else if let Some(ref tx3g) = self.tx3g {
             size += tx3g.box_size();
+        } else if let Some(ref text) = self.text {
+            size += text.box_size();
+        } else if let Some(ref rtp) = self.rtp {
+            size += rtp.box_size();
+        } else if let Some(ref raw) = self.raw {
+            size += raw.box_size();
         }
         size
     }
+
+    pub fn data_reference_index(&self) -> Option<u16> {
+        if let Some(ref avc1) = self.avc1 {
+            Some(avc1.data_reference_index)
+        } else if let Some(ref hev1) = self.hev1 {
+            Some(hev1.data_reference_index)
+        } else if let Some(ref dvh1) = self.dvh1 {
+            Some(dvh1.data_reference_index)
+        } else if let Some(ref vp09) = self.vp09 {
+            Some(vp09.data_reference_index)
+        } else if let Some(ref av01) = self.av01 {
+            Some(av01.data_reference_index)
+        } else if let Some(ref mp4a) = self.mp4a {
+            Some(mp4a.data_reference_index)
+        } else if let Some(ref opus) = self.opus {
+            Some(opus.data_reference_index)
+        } else if let Some(ref ac3) = self.ac3 {
+            Some(ac3.data_reference_index)
+        } else if let Some(ref eac3) = self.eac3 {
+            Some(eac3.data_reference_index)
+        } else if let Some(ref tx3g) = self.tx3g {
+            Some(tx3g.data_reference_index)
+        } else if let Some(ref text) = self.text {
+            Some(text.data_reference_index)
+        } else if let Some(ref rtp) = self.rtp {
+            Some(rtp.data_reference_index)
+        } else {
+            self.raw.as_ref().and_then(|raw| raw.data_reference_index())
+        }
+    }
 }
 
 impl Mp4Box for StsdBox {
@@ -63,7 +210,7 @@ impl Mp4Box for StsdBox {
     }
 
     fn summary(&self) -> Result<String> {
-        let s = String::new();
+        let s = format!("entry_count={}", self.entry_count);
         Ok(s)
     }
 }
@@ -74,15 +221,24 @@ impl<R: Read + Seek> ReadBox<&mut R> for StsdBox {
 
         let (version, flags) = read_box_header_ext(reader)?;
 
-        reader.read_u32::<BigEndian>()?; // XXX entry_count
+        let entry_count = reader.read_u32::<BigEndian>()?;
 
         let mut avc1 = None;
         let mut hev1 = None;
+        let mut dvh1 = None;
         let mut vp09 = None;
+        let mut av01 = None;
         let mut mp4a = None;
+        let mut opus = None;
+        let mut ac3 = None;
+        let mut eac3 = None;
         let mut tx3g = None;
+        let mut text = None;
+        let mut rtp = None;
+        let mut raw = None;
 
         // Get box header.
+        let entry_start = reader.stream_position()?;
         let header = BoxHeader::read(reader)?;
         let BoxHeader { name, size: s } = header;
         if s > size {
@@ -90,36 +246,142 @@ impl<R: Read + Seek> ReadBox<&mut R> for StsdBox {
                 "stsd box contains a box with a larger size than it",
             ));
         }
+        let end = start + size;
+        if entry_start + s > end {
+            return Err(Error::InvalidData(
+                "stsd sample entry extends past the end of the stsd box",
+            ));
+        }
 
         match name {
-            BoxType::Avc1Box => {
-                avc1 = Some(Avc1Box::read_box(reader, s)?);
+            BoxType::Avc1Box | BoxType::Avc3Box => {
+                let mut entry = Avc1Box::read_box(reader, s)?;
+                entry.in_band_parameter_sets = name == BoxType::Avc3Box;
+                avc1 = Some(entry);
             }
             BoxType::Hev1Box => {
                 hev1 = Some(Hev1Box::read_box(reader, s)?);
             }
+            BoxType::Dvh1Box | BoxType::DvheBox => {
+                let mut entry = Dvh1Box::read_box(reader, s)?;
+                entry.in_band_parameter_sets = name == BoxType::DvheBox;
+                dvh1 = Some(entry);
+            }
             BoxType::Vp09Box => {
                 vp09 = Some(Vp09Box::read_box(reader, s)?);
             }
+            BoxType::Av01Box | BoxType::Dav1Box => {
+                let mut entry = Av01Box::read_box(reader, s)?;
+                entry.dolby_vision_only = name == BoxType::Dav1Box;
+                av01 = Some(entry);
+            }
             BoxType::Mp4aBox => {
                 mp4a = Some(Mp4aBox::read_box(reader, s)?);
             }
+            BoxType::OpusBox => {
+                opus = Some(OpusBox::read_box(reader, s)?);
+            }
+            BoxType::Ac3Box => {
+                ac3 = Some(Ac3Box::read_box(reader, s)?);
+            }
+            BoxType::Eac3Box => {
+                eac3 = Some(Eac3Box::read_box(reader, s)?);
+            }
             BoxType::Tx3gBox => {
                 tx3g = Some(Tx3gBox::read_box(reader, s)?);
             }
-            _ => {}
+            BoxType::TextBox => {
+                text = Some(TextBox::read_box(reader, s)?);
+            }
+            BoxType::RtpBox => {
+                rtp = Some(RtpBox::read_box(reader, s)?);
+            }
+            BoxType::EncvBox | BoxType::EncaBox => {
+                let children_start = if name == BoxType::EncvBox {
+                    entry_start + HEADER_SIZE + 78
+                } else {
+                    skip_bytes(reader, 8)?;
+                    match reader.read_u16::<BigEndian>()? {
+                        1 => entry_start + HEADER_SIZE + 28 + 16,
+                        2 => entry_start + HEADER_SIZE + 28 + 36,
+                        _ => entry_start + HEADER_SIZE + 28,
+                    }
+                };
+                let original_format =
+                    read_original_format(reader, children_start, entry_start + s)?
+                        .map(|format| BoxType::from(u32::from(format)));
+                reader.seek(SeekFrom::Start(entry_start + HEADER_SIZE))?;
+
+                match original_format {
+                    Some(BoxType::Avc1Box | BoxType::Avc3Box) if name == BoxType::EncvBox => {
+                        avc1 = Some(Avc1Box::read_box(reader, s)?);
+                    }
+                    Some(BoxType::Hev1Box) if name == BoxType::EncvBox => {
+                        hev1 = Some(Hev1Box::read_box(reader, s)?);
+                    }
+                    Some(BoxType::Mp4aBox) if name == BoxType::EncaBox => {
+                        mp4a = Some(Mp4aBox::read_box(reader, s)?);
+                    }
+                    _ => {
+                        let mut bytes = vec![0u8; s as usize];
+                        reader.seek(SeekFrom::Start(entry_start))?;
+                        reader.read_exact(&mut bytes)?;
+                        raw = Some(RawSampleEntry::new(bytes)?);
+                    }
+                }
+            }
+            _ => {
+                let mut bytes = vec![0u8; s as usize];
+                reader.seek(SeekFrom::Start(entry_start))?;
+                reader.read_exact(&mut bytes)?;
+                raw = Some(RawSampleEntry::new(bytes)?);
+            }
+        }
+
+        // Only the first entry is parsed, but the rest are counted so that a
+        // corrupt entry_count doesn't go unnoticed. Counting stops at
+        // trailing padding or any header too small to be an entry.
+        let mut parsed_count = 1;
+        let mut current = entry_start + s;
+        while current + HEADER_SIZE <= end {
+            skip_bytes_to(reader, current)?;
+            let entry_size = reader.read_u32::<BigEndian>()? as u64;
+            if entry_size < HEADER_SIZE {
+                break;
+            }
+            if current + entry_size > end {
+                return Err(Error::InvalidData(
+                    "stsd sample entry extends past the end of the stsd box",
+                ));
+            }
+            parsed_count += 1;
+            current += entry_size;
+        }
+        if parsed_count != entry_count {
+            return Err(Error::InvalidData(
+                "stsd entry_count does not match the number of sample entries",
+            ));
         }
 
-        skip_bytes_to(reader, start + size)?;
+        skip_bytes_to(reader, end)?;
 
         Ok(StsdBox {
             version,
             flags,
+            entry_count,
             avc1,
             hev1,
+            dvh1,
             vp09,
+            av01,
             mp4a,
+            opus,
+            ac3,
+            eac3,
             tx3g,
+            text,
+            rtp,
+            raw,
         })
     }
 }
@@ -137,12 +399,28 @@ impl<W: Write> WriteBox<&mut W> for StsdBox {
             avc1.write_box(writer)?;
         } else if let Some(ref hev1) = self.hev1 {
             hev1.write_box(writer)?;
+        } else if let Some(ref dvh1) = self.dvh1 {
+            dvh1.write_box(writer)?;
         } else if let Some(ref vp09) = self.vp09 {
             vp09.write_box(writer)?;
+        } else if let Some(ref av01) = self.av01 {
+            av01.write_box(writer)?;
         } else if let Some(ref mp4a) = self.mp4a {
             mp4a.write_box(writer)?;
+        } else if let Some(ref opus) = self.opus {
+            opus.write_box(writer)?;
+        } else if let Some(ref ac3) = self.ac3 {
+            ac3.write_box(writer)?;
+        } else if let Some(ref eac3) = self.eac3 {
+            eac3.write_box(writer)?;
         } else if let Some(ref tx3g) = self.tx3g {
             tx3g.write_box(writer)?;
+        } else if let Some(ref text) = self.text {
+            text.write_box(writer)?;
+        } else if let Some(ref rtp) = self.rtp {
+            rtp.write_box(writer)?;
+        } else if let Some(ref raw) = self.raw {
+            raw.write_box(writer)?;
         }
 
         Ok(size)
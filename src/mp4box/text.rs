@@ -0,0 +1,194 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use serde::Serialize;
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::*;
+
+/// QuickTime text sample entry. Samples use the same layout as `tx3g`: a
+/// 16-bit text length, the text, then optional modifier atoms.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TextBox {
+    pub data_reference_index: u16,
+    pub display_flags: u32,
+    pub text_justification: i32,
+    pub bg_color: [u16; 3],
+    /// Top, left, bottom, right.
+    pub default_text_box: [i16; 4],
+    pub font_number: i16,
+    pub font_face: u16,
+    pub fore_color: [u16; 3],
+    pub font_name: String,
+}
+
+impl Default for TextBox {
+    fn default() -> Self {
+        TextBox {
+            data_reference_index: 1,
+            display_flags: 0,
+            text_justification: 0,
+            bg_color: [0; 3],
+            default_text_box: [0; 4],
+            font_number: 0,
+            font_face: 0,
+            fore_color: [0xFFFF; 3],
+            font_name: String::new(),
+        }
+    }
+}
+
+impl TextBox {
+    pub fn get_type(&self) -> BoxType {
+        BoxType::TextBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        HEADER_SIZE + 8 + 44 + self.font_name.len() as u64
+    }
+}
+
+impl Mp4Box for TextBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "data_reference_index={} text_justification={} font_name={}",
+            self.data_reference_index, self.text_justification, self.font_name
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for TextBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        reader.read_u32::<BigEndian>()?; // reserved
+        reader.read_u16::<BigEndian>()?; // reserved
+        let data_reference_index = reader.read_u16::<BigEndian>()?;
+
+        let display_flags = reader.read_u32::<BigEndian>()?;
+        let text_justification = reader.read_i32::<BigEndian>()?;
+        let bg_color = [
+            reader.read_u16::<BigEndian>()?,
+            reader.read_u16::<BigEndian>()?,
+            reader.read_u16::<BigEndian>()?,
+        ];
+        let default_text_box = [
+            reader.read_i16::<BigEndian>()?,
+            reader.read_i16::<BigEndian>()?,
+            reader.read_i16::<BigEndian>()?,
+            reader.read_i16::<BigEndian>()?,
+        ];
+        reader.read_u64::<BigEndian>()?; // reserved
+        let font_number = reader.read_i16::<BigEndian>()?;
+        let font_face = reader.read_u16::<BigEndian>()?;
+        reader.read_u8()?; // reserved
+        reader.read_u16::<BigEndian>()?; // reserved
+        let fore_color = [
+            reader.read_u16::<BigEndian>()?,
+            reader.read_u16::<BigEndian>()?,
+            reader.read_u16::<BigEndian>()?,
+        ];
+
+        // Pascal string; some writers leave it out entirely.
+        let mut font_name = String::new();
+        if reader.stream_position()? < start + size {
+            let len = reader.read_u8()? as u64;
+            if reader.stream_position()? + len > start + size {
+                return Err(Error::InvalidData("text font name exceeds box size"));
+            }
+            let mut buf = vec![0u8; len as usize];
+            reader.read_exact(&mut buf)?;
+            font_name = String::from_utf8_lossy(&buf).into_owned();
+        }
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(TextBox {
+            data_reference_index,
+            display_flags,
+            text_justification,
+            bg_color,
+            default_text_box,
+            font_number,
+            font_face,
+            fore_color,
+            font_name,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for TextBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        writer.write_u32::<BigEndian>(0)?; // reserved
+        writer.write_u16::<BigEndian>(0)?; // reserved
+        writer.write_u16::<BigEndian>(self.data_reference_index)?;
+
+        writer.write_u32::<BigEndian>(self.display_flags)?;
+        writer.write_i32::<BigEndian>(self.text_justification)?;
+        for color in self.bg_color.iter() {
+            writer.write_u16::<BigEndian>(*color)?;
+        }
+        for edge in self.default_text_box.iter() {
+            writer.write_i16::<BigEndian>(*edge)?;
+        }
+        writer.write_u64::<BigEndian>(0)?; // reserved
+        writer.write_i16::<BigEndian>(self.font_number)?;
+        writer.write_u16::<BigEndian>(self.font_face)?;
+        writer.write_u8(0)?; // reserved
+        writer.write_u16::<BigEndian>(0)?; // reserved
+        for color in self.fore_color.iter() {
+            writer.write_u16::<BigEndian>(*color)?;
+        }
+        writer.write_u8(self.font_name.len() as u8)?;
+        writer.write_all(self.font_name.as_bytes())?;
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_text() {
+        let src_box = TextBox {
+            data_reference_index: 1,
+            display_flags: 0x2000,
+            text_justification: 1,
+            bg_color: [0, 0, 0],
+            default_text_box: [0, 0, 60, 640],
+            font_number: 3,
+            font_face: 1,
+            fore_color: [0xFFFF, 0xFFFF, 0xFFFF],
+            font_name: String::from("Helvetica"),
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::TextBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = TextBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+}
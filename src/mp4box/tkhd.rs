@@ -114,6 +114,49 @@ impl TkhdBox {
     pub fn set_height(&mut self, height: u16) {
         self.height = FixedPointU16::new(height);
     }
+
+    /// The clockwise rotation in degrees (0, 90, 180 or 270) of the matrix,
+    /// recognized from its `a`, `b`, `c` and `d` values. Any matrix that
+    /// isn't a pure rotation, e.g. one that scales or mirrors, returns 0.
+    pub fn rotation(&self) -> u16 {
+        const ONE: i32 = 0x00010000;
+        match (self.matrix.a, self.matrix.b, self.matrix.c, self.matrix.d) {
+            (0, ONE, c, 0) if c == -ONE => 90,
+            (a, 0, 0, d) if a == -ONE && d == -ONE => 180,
+            (0, b, ONE, 0) if b == -ONE => 270,
+            _ => 0,
+        }
+    }
+
+    /// Sets the matrix to a clockwise rotation of 0, 90, 180 or 270 degrees,
+    /// translated to keep the picture in positive coordinates. The
+    /// translation uses the current width and height, so set those first.
+    pub fn set_rotation(&mut self, degrees: u16) -> Result<()> {
+        const ONE: i32 = 0x00010000;
+        let width = self.width.raw_value() as i32;
+        let height = self.height.raw_value() as i32;
+        let (a, b, c, d, x, y) = match degrees {
+            0 => (ONE, 0, 0, ONE, 0, 0),
+            90 => (0, ONE, -ONE, 0, height, 0),
+            180 => (-ONE, 0, 0, -ONE, width, height),
+            270 => (0, -ONE, ONE, 0, 0, width),
+            _ => {
+                return Err(Error::InvalidData(
+                    "rotation must be 0, 90, 180 or 270 degrees",
+                ))
+            }
+        };
+        self.matrix = Matrix {
+            a,
+            b,
+            c,
+            d,
+            x,
+            y,
+            ..Matrix::default()
+        };
+        Ok(())
+    }
 }
 
 impl Mp4Box for TkhdBox {
@@ -320,4 +363,31 @@ mod tests {
         let dst_box = TkhdBox::read_box(&mut reader, header.size).unwrap();
         assert_eq!(src_box, dst_box);
     }
+
+    #[test]
+    fn test_tkhd_rotation() {
+        let mut tkhd = TkhdBox::default();
+        tkhd.set_width(1920);
+        tkhd.set_height(1080);
+        assert_eq!(tkhd.rotation(), 0);
+
+        for degrees in [90, 180, 270, 0] {
+            tkhd.set_rotation(degrees).unwrap();
+            assert_eq!(tkhd.rotation(), degrees);
+        }
+        tkhd.set_rotation(90).unwrap();
+        assert_eq!((tkhd.matrix.x, tkhd.matrix.y), (1080 << 16, 0));
+        assert_eq!(tkhd.matrix.w, 0x40000000);
+
+        assert!(tkhd.set_rotation(45).is_err());
+        assert_eq!(tkhd.rotation(), 90);
+
+        // Scaling isn't a rotation.
+        tkhd.matrix = Matrix {
+            a: 0x00020000,
+            d: 0x00020000,
+            ..Matrix::default()
+        };
+        assert_eq!(tkhd.rotation(), 0);
+    }
 }
@@ -2,13 +2,16 @@ use serde::Serialize;
 use std::io::{Read, Seek, Write};
 
 use crate::mp4box::*;
-use crate::mp4box::{tfdt::TfdtBox, tfhd::TfhdBox, trun::TrunBox};
+use crate::mp4box::{senc::SencBox, tfdt::TfdtBox, tfhd::TfhdBox, trun::TrunBox};
 
 #[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
 pub struct TrafBox {
     pub tfhd: TfhdBox,
     pub tfdt: Option<TfdtBox>,
     pub trun: Option<TrunBox>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub senc: Option<SencBox>,
 }
 
 impl TrafBox {
@@ -25,6 +28,9 @@ impl TrafBox {
         if let Some(ref trun) = self.trun {
             size += trun.box_size();
         }
+        if let Some(ref senc) = self.senc {
+            size += senc.box_size();
+        }
         size
     }
 }
@@ -55,6 +61,7 @@ impl<R: Read + Seek> ReadBox<&mut R> for TrafBox {
         let mut tfhd = None;
         let mut tfdt = None;
         let mut trun = None;
+        let mut senc = None;
 
         let mut current = reader.stream_position()?;
         let end = start + size;
@@ -78,6 +85,9 @@ impl<R: Read + Seek> ReadBox<&mut R> for TrafBox {
                 BoxType::TrunBox => {
                     trun = Some(TrunBox::read_box(reader, s)?);
                 }
+                BoxType::SencBox => {
+                    senc = Some(SencBox::read_box(reader, s)?);
+                }
                 _ => {
                     // XXX warn!()
                     skip_box(reader, s)?;
@@ -97,6 +107,7 @@ impl<R: Read + Seek> ReadBox<&mut R> for TrafBox {
             tfhd: tfhd.unwrap(),
             tfdt,
             trun,
+            senc,
         })
     }
 }
@@ -113,6 +124,9 @@ impl<W: Write> WriteBox<&mut W> for TrafBox {
         if let Some(ref trun) = self.trun {
             trun.write_box(writer)?;
         }
+        if let Some(ref senc) = self.senc {
+            senc.write_box(writer)?;
+        }
 
         Ok(size)
     }
@@ -3,12 +3,15 @@ use std::io::{Read, Seek, Write};
 
 use crate::meta::MetaBox;
 use crate::mp4box::*;
-use crate::mp4box::{edts::EdtsBox, mdia::MdiaBox, tkhd::TkhdBox};
+use crate::mp4box::{edts::EdtsBox, mdia::MdiaBox, tkhd::TkhdBox, tref::TrefBox, udta::UdtaBox};
 
 #[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
 pub struct TrakBox {
     pub tkhd: TkhdBox,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tref: Option<TrefBox>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub edts: Option<EdtsBox>,
 
@@ -16,6 +19,9 @@ pub struct TrakBox {
     pub meta: Option<MetaBox>,
 
     pub mdia: MdiaBox,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub udta: Option<UdtaBox>,
 }
 
 impl TrakBox {
@@ -26,10 +32,16 @@ impl TrakBox {
     pub fn get_size(&self) -> u64 {
         let mut size = HEADER_SIZE;
         size += self.tkhd.box_size();
+        if let Some(ref tref) = self.tref {
+            size += tref.box_size();
+        }
         if let Some(ref edts) = self.edts {
             size += edts.box_size();
         }
         size += self.mdia.box_size();
+        if let Some(ref udta) = self.udta {
+            size += udta.box_size();
+        }
         size
     }
 }
@@ -58,9 +70,11 @@ impl<R: Read + Seek> ReadBox<&mut R> for TrakBox {
         let start = box_start(reader)?;
 
         let mut tkhd = None;
+        let mut tref = None;
         let mut edts = None;
         let mut meta = None;
         let mut mdia = None;
+        let mut udta = None;
 
         let mut current = reader.stream_position()?;
         let end = start + size;
@@ -78,6 +92,9 @@ impl<R: Read + Seek> ReadBox<&mut R> for TrakBox {
                 BoxType::TkhdBox => {
                     tkhd = Some(TkhdBox::read_box(reader, s)?);
                 }
+                BoxType::TrefBox => {
+                    tref = Some(TrefBox::read_box(reader, s)?);
+                }
                 BoxType::EdtsBox => {
                     edts = Some(EdtsBox::read_box(reader, s)?);
                 }
@@ -87,6 +104,9 @@ impl<R: Read + Seek> ReadBox<&mut R> for TrakBox {
                 BoxType::MdiaBox => {
                     mdia = Some(MdiaBox::read_box(reader, s)?);
                 }
+                BoxType::UdtaBox => {
+                    udta = Some(UdtaBox::read_box(reader, s)?);
+                }
                 _ => {
                     // XXX warn!()
                     skip_box(reader, s)?;
@@ -107,9 +127,11 @@ impl<R: Read + Seek> ReadBox<&mut R> for TrakBox {
 
         Ok(TrakBox {
             tkhd: tkhd.unwrap(),
+            tref,
             edts,
             meta,
             mdia: mdia.unwrap(),
+            udta,
         })
     }
 }
@@ -120,10 +142,16 @@ impl<W: Write> WriteBox<&mut W> for TrakBox {
         BoxHeader::new(self.box_type(), size).write(writer)?;
 
         self.tkhd.write_box(writer)?;
+        if let Some(ref tref) = self.tref {
+            tref.write_box(writer)?;
+        }
         if let Some(ref edts) = self.edts {
             edts.write_box(writer)?;
         }
         self.mdia.write_box(writer)?;
+        if let Some(ref udta) = self.udta {
+            udta.write_box(writer)?;
+        }
 
         Ok(size)
     }
@@ -0,0 +1,152 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use serde::Serialize;
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::*;
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct TrefBox {
+    pub references: Vec<TrackReference>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct TrackReference {
+    pub reference_type: FourCC,
+    pub track_ids: Vec<u32>,
+}
+
+impl TrackReference {
+    fn size(&self) -> u64 {
+        HEADER_SIZE + 4 * self.track_ids.len() as u64
+    }
+}
+
+impl TrefBox {
+    pub fn get_type(&self) -> BoxType {
+        BoxType::TrefBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        HEADER_SIZE + self.references.iter().map(|r| r.size()).sum::<u64>()
+    }
+
+    pub fn track_ids(&self, reference_type: &FourCC) -> Option<&[u32]> {
+        self.references
+            .iter()
+            .find(|r| &r.reference_type == reference_type)
+            .map(|r| r.track_ids.as_slice())
+    }
+}
+
+impl Mp4Box for TrefBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = self
+            .references
+            .iter()
+            .map(|r| format!("{}={:?}", r.reference_type, r.track_ids))
+            .collect::<Vec<String>>()
+            .join(" ");
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for TrefBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let mut references = Vec::new();
+
+        let mut current = reader.stream_position()?;
+        let end = start + size;
+        while current < end {
+            // Get box header.
+            let header = BoxHeader::read(reader)?;
+            let BoxHeader { name, size: s } = header;
+            if s > size || s < HEADER_SIZE {
+                return Err(Error::InvalidData(
+                    "tref box contains a box with an invalid size",
+                ));
+            }
+
+            let count = (s - HEADER_SIZE) / 4;
+            let mut track_ids = Vec::with_capacity(count as usize);
+            for _ in 0..count {
+                track_ids.push(reader.read_u32::<BigEndian>()?);
+            }
+            references.push(TrackReference {
+                reference_type: name.into(),
+                track_ids,
+            });
+
+            skip_bytes_to(reader, current + s)?;
+            current = reader.stream_position()?;
+        }
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(TrefBox { references })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for TrefBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        for reference in self.references.iter() {
+            let name = BoxType::from(u32::from(reference.reference_type));
+            BoxHeader::new(name, reference.size()).write(writer)?;
+            for track_id in reference.track_ids.iter() {
+                writer.write_u32::<BigEndian>(*track_id)?;
+            }
+        }
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_tref() {
+        let src_box = TrefBox {
+            references: vec![
+                TrackReference {
+                    reference_type: str::parse("hint").unwrap(),
+                    track_ids: vec![1],
+                },
+                TrackReference {
+                    reference_type: str::parse("cdsc").unwrap(),
+                    track_ids: vec![2, 3],
+                },
+            ],
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::TrefBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = TrefBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+}
@@ -2,6 +2,7 @@ use std::io::{Read, Seek};
 
 use serde::Serialize;
 
+use crate::mp4box::hnti::HntiBox;
 use crate::mp4box::meta::MetaBox;
 use crate::mp4box::*;
 
@@ -9,6 +10,9 @@ use crate::mp4box::*;
 pub struct UdtaBox {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub meta: Option<MetaBox>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hnti: Option<HntiBox>,
 }
 
 impl UdtaBox {
@@ -21,6 +25,9 @@ impl UdtaBox {
         if let Some(meta) = &self.meta {
             size += meta.box_size();
         }
+        if let Some(hnti) = &self.hnti {
+            size += hnti.box_size();
+        }
         size
     }
 }
@@ -48,6 +55,7 @@ impl<R: Read + Seek> ReadBox<&mut R> for UdtaBox {
         let start = box_start(reader)?;
 
         let mut meta = None;
+        let mut hnti = None;
 
         let mut current = reader.stream_position()?;
         let end = start + size;
@@ -65,6 +73,9 @@ impl<R: Read + Seek> ReadBox<&mut R> for UdtaBox {
                 BoxType::MetaBox => {
                     meta = Some(MetaBox::read_box(reader, s)?);
                 }
+                BoxType::HntiBox => {
+                    hnti = Some(HntiBox::read_box(reader, s)?);
+                }
                 _ => {
                     // XXX warn!()
                     skip_box(reader, s)?;
@@ -76,7 +87,7 @@ impl<R: Read + Seek> ReadBox<&mut R> for UdtaBox {
 
         skip_bytes_to(reader, start + size)?;
 
-        Ok(UdtaBox { meta })
+        Ok(UdtaBox { meta, hnti })
     }
 }
 
@@ -88,6 +99,9 @@ impl<W: Write> WriteBox<&mut W> for UdtaBox {
         if let Some(meta) = &self.meta {
             meta.write_box(writer)?;
         }
+        if let Some(hnti) = &self.hnti {
+            hnti.write_box(writer)?;
+        }
         Ok(size)
     }
 }
@@ -100,7 +114,10 @@ mod tests {
 
     #[test]
     fn test_udta_empty() {
-        let src_box = UdtaBox { meta: None };
+        let src_box = UdtaBox {
+            meta: None,
+            hnti: None,
+        };
 
         let mut buf = Vec::new();
         src_box.write_box(&mut buf).unwrap();
@@ -119,6 +136,7 @@ mod tests {
     fn test_udta() {
         let src_box = UdtaBox {
             meta: Some(MetaBox::default()),
+            hnti: None,
         };
 
         let mut buf = Vec::new();
@@ -4,7 +4,7 @@ use std::io::{Read, Seek, Write};
 
 use crate::mp4box::*;
 
-#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct VmhdBox {
     pub version: u8,
     pub flags: u32,
@@ -19,7 +19,22 @@ pub struct RgbColor {
     pub blue: u16,
 }
 
+impl Default for VmhdBox {
+    fn default() -> Self {
+        VmhdBox {
+            version: 0,
+            // ISO/IEC 14496-12 requires vmhd flags to be 1.
+            flags: VmhdBox::FLAG_NO_LEAN_AHEAD,
+            graphics_mode: 0,
+            op_color: RgbColor::default(),
+        }
+    }
+}
+
 impl VmhdBox {
+    /// The "no-lean-ahead" flag, which the spec requires to be set.
+    pub const FLAG_NO_LEAN_AHEAD: u32 = 0x000001;
+
     pub fn get_type(&self) -> BoxType {
         BoxType::VmhdBox
     }
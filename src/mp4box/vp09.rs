@@ -20,6 +20,12 @@ pub struct Vp09Box {
     pub depth: u16,
     pub end_code: u16,
     pub vpcc: VpccBox,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pasp: Option<PaspBox>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub colr: Option<ColrBox>,
 }
 
 impl Vp09Box {
@@ -61,6 +67,8 @@ impl Vp09Box {
                 matrix_coefficients: 0,
                 codec_initialization_data_size: 0,
             },
+            pasp: None,
+            colr: None,
         }
     }
 }
@@ -71,7 +79,14 @@ impl Mp4Box for Vp09Box {
     }
 
     fn box_size(&self) -> u64 {
-        0x6A
+        let mut size = 0x6A;
+        if let Some(ref pasp) = self.pasp {
+            size += pasp.box_size();
+        }
+        if let Some(ref colr) = self.colr {
+            size += colr.box_size();
+        }
+        size
     }
 
     fn to_json(&self) -> Result<String> {
@@ -129,6 +144,32 @@ impl<R: Read + Seek> ReadBox<&mut R> for Vp09Box {
             VpccBox::read_box(reader, header.size)?
         };
 
+        let mut pasp = None;
+        let mut colr = None;
+
+        let mut current = reader.stream_position()?;
+        let end = start + size;
+        while current < end {
+            let header = BoxHeader::read(reader)?;
+            let BoxHeader { name, size: s } = header;
+            if s > size {
+                return Err(Error::InvalidData(
+                    "vp09 box contains a box with a larger size than it",
+                ));
+            }
+            match name {
+                BoxType::PaspBox => {
+                    pasp = Some(PaspBox::read_box(reader, s)?);
+                }
+                BoxType::ColrBox => {
+                    colr = Some(ColrBox::read_box(reader, s)?);
+                }
+                _ => {}
+            }
+            skip_bytes_to(reader, current + s)?;
+            current = reader.stream_position()?;
+        }
+
         skip_bytes_to(reader, start + size)?;
 
         Ok(Self {
@@ -147,6 +188,8 @@ impl<R: Read + Seek> ReadBox<&mut R> for Vp09Box {
             depth,
             end_code,
             vpcc,
+            pasp,
+            colr,
         })
     }
 }
@@ -173,6 +216,12 @@ impl<W: Write> WriteBox<&mut W> for Vp09Box {
         writer.write_u16::<BigEndian>(self.depth)?;
         writer.write_u16::<BigEndian>(self.end_code)?;
         VpccBox::write_box(&self.vpcc, writer)?;
+        if let Some(ref pasp) = self.pasp {
+            pasp.write_box(writer)?;
+        }
+        if let Some(ref colr) = self.colr {
+            colr.write_box(writer)?;
+        }
 
         Ok(size)
     }
@@ -202,4 +251,34 @@ mod tests {
         let dst_box = Vp09Box::read_box(&mut reader, header.size).unwrap();
         assert_eq!(src_box, dst_box);
     }
+
+    #[test]
+    fn test_vp09_pasp_colr() {
+        let mut src_box = Vp09Box::new(&Vp9Config {
+            width: 1920,
+            height: 1080,
+        });
+        src_box.pasp = Some(PaspBox {
+            h_spacing: 4,
+            v_spacing: 3,
+        });
+        src_box.colr = Some(ColrBox {
+            colour_primaries: 9,
+            transfer_characteristics: 16,
+            matrix_coefficients: 9,
+            full_range_flag: false,
+            ..Default::default()
+        });
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::Vp09Box);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = Vp09Box::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
 }
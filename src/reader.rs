@@ -1,8 +1,12 @@
 use std::collections::HashMap;
-use std::io::{Read, Seek};
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{Read, Seek, SeekFrom, Write};
 use std::time::Duration;
 
+use crate::elementary::Framing;
 use crate::meta::MetaBox;
+use crate::track::ChunkCache;
 use crate::*;
 
 #[derive(Debug)]
@@ -12,9 +16,72 @@ pub struct Mp4Reader<R> {
     pub moov: MoovBox,
     pub moofs: Vec<MoofBox>,
     pub emsgs: Vec<EmsgBox>,
+    pub sidxs: Vec<SidxBox>,
+    pub ssixs: Vec<SsixBox>,
 
     tracks: HashMap<u32, Mp4Track>,
     size: u64,
+    chunk_cache: ChunkCache,
+    duplicates: Vec<Warning>,
+    sidx_offsets: Vec<u64>,
+    ssix_offsets: Vec<u64>,
+    moof_offsets: Vec<u64>,
+    box_offsets: Vec<(BoxType, u64, u64)>,
+    // Payload byte ranges of the top-level mdat boxes.
+    mdat_ranges: Vec<(u64, u64)>,
+    external_data: ExternalData,
+    #[cfg(feature = "cenc")]
+    decryption_keys: HashMap<[u8; 16], [u8; 16]>,
+}
+
+/// A source of sample data kept outside the file, see
+/// [`Mp4Reader::set_data_resolver`].
+pub trait ReadSeek: Read + Seek + Send {}
+
+impl<T: Read + Seek + Send> ReadSeek for T {}
+
+type DataResolver = Box<dyn Fn(&str) -> Box<dyn ReadSeek> + Send>;
+
+// External data files, opened through the resolver on first use.
+#[derive(Default)]
+struct ExternalData {
+    resolver: Option<DataResolver>,
+    sources: HashMap<String, ExternalSource>,
+}
+
+struct ExternalSource {
+    reader: Box<dyn ReadSeek>,
+    size: u64,
+    chunk_cache: ChunkCache,
+}
+
+impl ExternalData {
+    fn source(&mut self, location: &str) -> Result<Option<&mut ExternalSource>> {
+        if !self.sources.contains_key(location) {
+            let resolver = match self.resolver {
+                Some(ref resolver) => resolver,
+                None => return Ok(None),
+            };
+            let mut reader = resolver(location);
+            let size = reader.seek(SeekFrom::End(0))?;
+            let source = ExternalSource {
+                reader,
+                size,
+                chunk_cache: ChunkCache::default(),
+            };
+            self.sources.insert(location.to_string(), source);
+        }
+        Ok(self.sources.get_mut(location))
+    }
+}
+
+impl fmt::Debug for ExternalData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExternalData")
+            .field("resolver", &self.resolver.is_some())
+            .field("sources", &self.sources.keys().collect::<Vec<_>>())
+            .finish()
+    }
 }
 
 impl<R: Read + Seek> Mp4Reader<R> {
@@ -25,13 +92,29 @@ impl<R: Read + Seek> Mp4Reader<R> {
         let mut moov = None;
         let mut moofs = Vec::new();
         let mut moof_offsets = Vec::new();
+        let mut sidxs = Vec::new();
+        let mut sidx_offsets = Vec::new();
+        let mut ssixs = Vec::new();
+        let mut ssix_offsets = Vec::new();
         let mut emsgs = Vec::new();
+        let mut duplicates = Vec::new();
+        let mut mdat_ranges = Vec::new();
+        let mut box_offsets = Vec::new();
 
         let mut current = start;
         while current < size {
             // Get box header.
             let header = BoxHeader::read(&mut reader)?;
             let BoxHeader { name, size: s } = header;
+            // `s` leaves out the extra 8 bytes of a 64-bit size header.
+            let box_size = s + (reader.stream_position()? - current - HEADER_SIZE);
+            if name == BoxType::MdatBox && (s == 0 || current + box_size > size) {
+                // The mdat runs to the end of the file, or was cut off there.
+                box_offsets.push((name, current, size - current));
+                mdat_ranges.push((reader.stream_position()?, size));
+                current = size;
+                break;
+            }
             if s > size {
                 return Err(Error::InvalidData(
                     "file contains a box with a larger size than it",
@@ -42,20 +125,32 @@ impl<R: Read + Seek> Mp4Reader<R> {
             if s == 0 {
                 break;
             }
+            box_offsets.push((name, current, box_size));
 
             // Match and parse the atom boxes.
             match name {
                 BoxType::FtypBox => {
-                    ftyp = Some(FtypBox::read_box(&mut reader, s)?);
+                    if ftyp.is_some() {
+                        duplicates.push(Warning::DuplicateBox(name, current));
+                        skip_box(&mut reader, s)?;
+                    } else {
+                        ftyp = Some(FtypBox::read_box(&mut reader, s)?);
+                    }
                 }
                 BoxType::FreeBox => {
                     skip_box(&mut reader, s)?;
                 }
                 BoxType::MdatBox => {
+                    mdat_ranges.push((reader.stream_position()?, current + box_size));
                     skip_box(&mut reader, s)?;
                 }
                 BoxType::MoovBox => {
-                    moov = Some(MoovBox::read_box(&mut reader, s)?);
+                    if moov.is_some() {
+                        duplicates.push(Warning::DuplicateBox(name, current));
+                        skip_box(&mut reader, s)?;
+                    } else {
+                        moov = Some(MoovBox::read_box(&mut reader, s)?);
+                    }
                 }
                 BoxType::MoofBox => {
                     let moof_offset = reader.stream_position()? - 8;
@@ -63,6 +158,14 @@ impl<R: Read + Seek> Mp4Reader<R> {
                     moofs.push(moof);
                     moof_offsets.push(moof_offset);
                 }
+                BoxType::SidxBox => {
+                    sidxs.push(SidxBox::read_box(&mut reader, s)?);
+                    sidx_offsets.push(current);
+                }
+                BoxType::SsixBox => {
+                    ssixs.push(SsixBox::read_box(&mut reader, s)?);
+                    ssix_offsets.push(current);
+                }
                 BoxType::EmsgBox => {
                     let emsg = EmsgBox::read_box(&mut reader, s)?;
                     emsgs.push(emsg);
@@ -97,19 +200,20 @@ impl<R: Read + Seek> Mp4Reader<R> {
 
         // Update tracks if any fragmented (moof) boxes are found.
         if !moofs.is_empty() {
-            let mut default_sample_duration = 0;
-            if let Some(ref moov) = moov {
-                if let Some(ref mvex) = &moov.mvex {
-                    default_sample_duration = mvex.trex.default_sample_duration
-                }
-            }
+            let mvex = moov.as_ref().and_then(|moov| moov.mvex.as_ref());
 
-            for (moof, moof_offset) in moofs.iter().zip(moof_offsets) {
-                for traf in moof.trafs.iter() {
+            for (moof, &moof_offset) in moofs.iter().zip(moof_offsets.iter()) {
+                let base_offsets = traf_base_offsets(moof, moof_offset, mvex);
+                for (traf, base_offset) in moof.trafs.iter().zip(base_offsets) {
                     let track_id = traf.tfhd.track_id;
                     if let Some(track) = tracks.get_mut(&track_id) {
-                        track.default_sample_duration = default_sample_duration;
+                        let trex = mvex.and_then(|mvex| mvex.trex(track_id));
+                        track.default_sample_duration =
+                            trex.map_or(0, |trex| trex.default_sample_duration);
+                        track.default_sample_flags =
+                            trex.map_or(0, |trex| trex.default_sample_flags);
                         track.moof_offsets.push(moof_offset);
+                        track.traf_base_offsets.push(base_offset);
                         track.trafs.push(traf.clone())
                     } else {
                         return Err(Error::TrakNotFound(track_id));
@@ -124,8 +228,63 @@ impl<R: Read + Seek> Mp4Reader<R> {
             moov: moov.unwrap(),
             moofs,
             emsgs,
+            sidxs,
+            ssixs,
             size,
             tracks,
+            chunk_cache: ChunkCache::default(),
+            duplicates,
+            sidx_offsets,
+            ssix_offsets,
+            moof_offsets,
+            box_offsets,
+            mdat_ranges,
+            external_data: ExternalData::default(),
+            #[cfg(feature = "cenc")]
+            decryption_keys: HashMap::new(),
+        })
+    }
+
+    /// Opens a capture that stopped before its `moov` was written, such as
+    /// a recording that crashed, by scanning its `mdat` for samples.
+    ///
+    /// Video samples are assembled from 4-byte length-prefixed NAL units and
+    /// AAC samples from ADTS frames (the ADTS header is left out of each
+    /// sample). Each track's samples all get its `sample_duration`. Scanning
+    /// stops at the first bytes that match none of the tracks, so a truncated
+    /// final sample is dropped.
+    pub fn recover_from_partial(
+        mut reader: R,
+        size: u64,
+        tracks: &[RecoveryTrack],
+    ) -> Result<Self> {
+        let (ftyp, moov, mdat_range) = recover::recover_header(&mut reader, size, tracks)?;
+        let tracks = moov
+            .traks
+            .iter()
+            .map(|trak| (trak.tkhd.track_id, Mp4Track::from(trak)))
+            .collect();
+
+        Ok(Mp4Reader {
+            reader,
+            ftyp,
+            moov,
+            moofs: Vec::new(),
+            emsgs: Vec::new(),
+            sidxs: Vec::new(),
+            ssixs: Vec::new(),
+            tracks,
+            size,
+            chunk_cache: ChunkCache::default(),
+            duplicates: Vec::new(),
+            sidx_offsets: Vec::new(),
+            ssix_offsets: Vec::new(),
+            moof_offsets: Vec::new(),
+            box_offsets: Vec::new(),
+            mdat_ranges: vec![mdat_range],
+            external_data: ExternalData::default(),
+            #[cfg(feature = "cenc")]
+            decryption_keys: HashMap::new(),
         })
     }
 
@@ -138,12 +297,27 @@ impl<R: Read + Seek> Mp4Reader<R> {
 
         let mut moofs = Vec::new();
         let mut moof_offsets = Vec::new();
+        let mut sidxs = Vec::new();
+        let mut sidx_offsets = Vec::new();
+        let mut ssixs = Vec::new();
+        let mut ssix_offsets = Vec::new();
+        let mut mdat_ranges = Vec::new();
+        let mut box_offsets = Vec::new();
 
         let mut current = start;
         while current < size {
             // Get box header.
             let header = BoxHeader::read(&mut reader)?;
             let BoxHeader { name, size: s } = header;
+            // `s` leaves out the extra 8 bytes of a 64-bit size header.
+            let box_size = s + (reader.stream_position()? - current - HEADER_SIZE);
+            if name == BoxType::MdatBox && (s == 0 || current + box_size > size) {
+                // The mdat runs to the end of the file, or was cut off there.
+                box_offsets.push((name, current, size - current));
+                mdat_ranges.push((reader.stream_position()?, size));
+                current = size;
+                break;
+            }
             if s > size {
                 return Err(Error::InvalidData(
                     "file contains a box with a larger size than it",
@@ -154,10 +328,12 @@ impl<R: Read + Seek> Mp4Reader<R> {
             if s == 0 {
                 break;
             }
+            box_offsets.push((name, current, box_size));
 
             // Match and parse the atom boxes.
             match name {
                 BoxType::MdatBox => {
+                    mdat_ranges.push((reader.stream_position()?, current + box_size));
                     skip_box(&mut reader, s)?;
                 }
                 BoxType::MoofBox => {
@@ -166,6 +342,14 @@ impl<R: Read + Seek> Mp4Reader<R> {
                     moofs.push(moof);
                     moof_offsets.push(moof_offset);
                 }
+                BoxType::SidxBox => {
+                    sidxs.push(SidxBox::read_box(&mut reader, s)?);
+                    sidx_offsets.push(current);
+                }
+                BoxType::SsixBox => {
+                    ssixs.push(SsixBox::read_box(&mut reader, s)?);
+                    ssix_offsets.push(current);
+                }
                 _ => {
                     // XXX warn!()
                     skip_box(&mut reader, s)?;
@@ -186,17 +370,19 @@ impl<R: Read + Seek> Mp4Reader<R> {
             .map(|trak| (trak.tkhd.track_id, Mp4Track::from(trak)))
             .collect();
 
-        let mut default_sample_duration = 0;
-        if let Some(ref mvex) = &self.moov.mvex {
-            default_sample_duration = mvex.trex.default_sample_duration
-        }
+        let mvex = self.moov.mvex.as_ref();
 
-        for (moof, moof_offset) in moofs.iter().zip(moof_offsets) {
-            for traf in moof.trafs.iter() {
+        for (moof, &moof_offset) in moofs.iter().zip(moof_offsets.iter()) {
+            let base_offsets = traf_base_offsets(moof, moof_offset, mvex);
+            for (traf, base_offset) in moof.trafs.iter().zip(base_offsets) {
                 let track_id = traf.tfhd.track_id;
                 if let Some(track) = tracks.get_mut(&track_id) {
-                    track.default_sample_duration = default_sample_duration;
+                    let trex = mvex.and_then(|mvex| mvex.trex(track_id));
+                    track.default_sample_duration =
+                        trex.map_or(0, |trex| trex.default_sample_duration);
+                    track.default_sample_flags = trex.map_or(0, |trex| trex.default_sample_flags);
                     track.moof_offsets.push(moof_offset);
+                    track.traf_base_offsets.push(base_offset);
                     track.trafs.push(traf.clone())
                 } else {
                     return Err(Error::TrakNotFound(track_id));
@@ -210,8 +396,20 @@ impl<R: Read + Seek> Mp4Reader<R> {
             moov: self.moov.clone(),
             moofs,
             emsgs: Vec::new(),
+            sidxs,
+            ssixs,
             tracks,
             size,
+            chunk_cache: ChunkCache::default(),
+            duplicates: Vec::new(),
+            sidx_offsets,
+            ssix_offsets,
+            moof_offsets,
+            box_offsets,
+            mdat_ranges,
+            external_data: ExternalData::default(),
+            #[cfg(feature = "cenc")]
+            decryption_keys: HashMap::new(),
         })
     }
 
@@ -232,7 +430,17 @@ impl<R: Read + Seek> Mp4Reader<R> {
     }
 
     pub fn duration(&self) -> Duration {
-        Duration::from_millis(self.moov.mvhd.duration * 1000 / self.moov.mvhd.timescale as u64)
+        Duration::from_millis(rescale(
+            self.moov.mvhd.duration,
+            self.moov.mvhd.timescale as u64,
+            1000,
+        ))
+    }
+
+    /// The `mvhd` duration in [`timescale`](Self::timescale) ticks, without
+    /// the rounding of [`duration`](Self::duration).
+    pub fn movie_duration_ticks(&self) -> u64 {
+        self.moov.mvhd.duration
     }
 
     pub fn timescale(&self) -> u32 {
@@ -247,6 +455,57 @@ impl<R: Read + Seek> Mp4Reader<R> {
         &self.tracks
     }
 
+    /// Maps each track ID to its language: the `elng` tag if present,
+    /// otherwise the `mdhd` code.
+    pub fn track_languages(&self) -> HashMap<u32, &str> {
+        self.tracks
+            .iter()
+            .map(|(&track_id, track)| {
+                let language = track.extended_language().unwrap_or(track.language());
+                (track_id, language)
+            })
+            .collect()
+    }
+
+    /// Returns the lowest-numbered track of `track_type` whose `mdhd` code
+    /// or `elng` tag matches `language` (e.g. `fra`, `pt-BR`, or `pt` for
+    /// any Portuguese variant).
+    pub fn track_by_language(&self, track_type: TrackType, language: &str) -> Option<&Mp4Track> {
+        self.tracks
+            .values()
+            .filter(|track| matches!(track.track_type(), Ok(t) if t == track_type))
+            .filter(|track| track.matches_language(language))
+            .min_by_key(|track| track.track_id())
+    }
+
+    pub fn media_track_count(&self, media_type: MediaType) -> usize {
+        self.tracks
+            .values()
+            .filter(|track| matches!(track.media_type(), Ok(t) if t == media_type))
+            .count()
+    }
+
+    pub fn has_media(&self, media_type: MediaType) -> bool {
+        self.media_track_count(media_type) > 0
+    }
+
+    /// Number of samples across all tracks.
+    pub fn total_sample_count(&self) -> u64 {
+        self.tracks
+            .values()
+            .map(|track| track.sample_count() as u64)
+            .sum()
+    }
+
+    /// Number of samples across the tracks of `media_type`.
+    pub fn sample_count_by_media(&self, media_type: MediaType) -> u64 {
+        self.tracks
+            .values()
+            .filter(|track| matches!(track.media_type(), Ok(t) if t == media_type))
+            .map(|track| track.sample_count() as u64)
+            .sum()
+    }
+
     pub fn sample_count(&self, track_id: u32) -> Result<u32> {
         if let Some(track) = self.tracks.get(&track_id) {
             Ok(track.sample_count())
@@ -255,9 +514,284 @@ impl<R: Read + Seek> Mp4Reader<R> {
         }
     }
 
+    /// Number of sample entries in the track's `stsd`, as checked against
+    /// the entries themselves when the header was read.
+    pub fn sample_entry_size(&self, track_id: u32) -> Result<u32> {
+        if let Some(track) = self.tracks.get(&track_id) {
+            Ok(track.trak.mdia.minf.stbl.stsd.entry_count)
+        } else {
+            Err(Error::TrakNotFound(track_id))
+        }
+    }
+
+    /// Sets the callback that opens the external files that tracks' `dref`
+    /// entries point to, such as in reference movies. It is passed the
+    /// entry's location and called once per location.
+    pub fn set_data_resolver<F>(&mut self, resolver: F)
+    where
+        F: Fn(&str) -> Box<dyn ReadSeek> + Send + 'static,
+    {
+        self.external_data = ExternalData {
+            resolver: Some(Box::new(resolver)),
+            sources: HashMap::new(),
+        };
+    }
+
+    /// Sets the Common Encryption keys, by KID, that [`read_sample`] uses to
+    /// return decrypted samples of protected tracks. Only the `cenc` scheme
+    /// is supported, for samples in fragments with their IVs in `senc`.
+    /// Requires the `cenc` feature.
+    ///
+    /// [`read_sample`]: Mp4Reader::read_sample
+    #[cfg(feature = "cenc")]
+    pub fn set_decryption_keys(&mut self, keys: HashMap<[u8; 16], [u8; 16]>) {
+        self.decryption_keys = keys;
+    }
+
+    /// Reads a sample of a track. Returns `None` past the last sample, while
+    /// an empty sample, such as a blank subtitle cue, is returned as
+    /// `Some` with empty `bytes`.
     pub fn read_sample(&mut self, track_id: u32, sample_id: u32) -> Result<Option<Mp4Sample>> {
+        let track = match self.tracks.get(&track_id) {
+            Some(track) => track,
+            None => return Err(Error::TrakNotFound(track_id)),
+        };
+        let sample = match track.data_location() {
+            None => track.read_sample(
+                &mut self.reader,
+                &mut self.chunk_cache,
+                sample_id,
+                self.size,
+            )?,
+            Some(location) => match self.external_data.source(location)? {
+                Some(source) => track.read_sample(
+                    &mut source.reader,
+                    &mut source.chunk_cache,
+                    sample_id,
+                    source.size,
+                )?,
+                None => {
+                    return Err(Error::ExternalDataNotResolved(
+                        track_id,
+                        location.to_string(),
+                    ))
+                }
+            },
+        };
+
+        #[cfg(feature = "cenc")]
+        let sample = match sample {
+            Some(mut sample) if !self.decryption_keys.is_empty() && track.is_encrypted() => {
+                sample.bytes =
+                    track.decrypt_sample(sample_id, sample.bytes, &self.decryption_keys)?;
+                Some(sample)
+            }
+            sample => sample,
+        };
+        Ok(sample)
+    }
+
+    /// Reads a sample into `buf`, which is resized to the sample size, and
+    /// returns its timing; one buffer can be reused across a whole track.
+    /// Decrypting a protected sample still allocates.
+    pub fn read_sample_into(
+        &mut self,
+        track_id: u32,
+        sample_id: u32,
+        buf: &mut Vec<u8>,
+    ) -> Result<Option<SampleInfo>> {
+        let track = match self.tracks.get(&track_id) {
+            Some(track) => track,
+            None => return Err(Error::TrakNotFound(track_id)),
+        };
+        let info = match track.data_location() {
+            None => track.read_sample_into(
+                &mut self.reader,
+                &self.chunk_cache,
+                sample_id,
+                self.size,
+                buf,
+            )?,
+            Some(location) => match self.external_data.source(location)? {
+                Some(source) => track.read_sample_into(
+                    &mut source.reader,
+                    &source.chunk_cache,
+                    sample_id,
+                    source.size,
+                    buf,
+                )?,
+                None => {
+                    return Err(Error::ExternalDataNotResolved(
+                        track_id,
+                        location.to_string(),
+                    ))
+                }
+            },
+        };
+
+        #[cfg(feature = "cenc")]
+        if info.is_some() && !self.decryption_keys.is_empty() && track.is_encrypted() {
+            let bytes = Bytes::copy_from_slice(buf);
+            let bytes = track.decrypt_sample(sample_id, bytes, &self.decryption_keys)?;
+            buf.clear();
+            buf.extend_from_slice(&bytes);
+        }
+        Ok(info)
+    }
+
+    /// Reads a sample of a timed text track (`tx3g` or QuickTime `text`) and
+    /// returns its text, without the length prefix or trailing modifiers.
+    /// UTF-16 text is recognized by its byte order mark. An empty sample is
+    /// read as an empty cue.
+    pub fn read_text_sample(&mut self, track_id: u32, sample_id: u32) -> Result<Option<String>> {
+        let sample = match self.read_sample(track_id, sample_id)? {
+            Some(sample) => sample,
+            None => return Ok(None),
+        };
+        if sample.bytes.is_empty() {
+            return Ok(Some(String::new()));
+        }
+        if sample.bytes.len() < 2 {
+            return Err(Error::InvalidData("text sample is missing its length"));
+        }
+        let len = u16::from_be_bytes([sample.bytes[0], sample.bytes[1]]) as usize;
+        let text = sample
+            .bytes
+            .get(2..2 + len)
+            .ok_or(Error::InvalidData("text length exceeds sample size"))?;
+        if let Some(utf16) = text.strip_prefix(&[0xFE, 0xFF]) {
+            let units: Vec<u16> = utf16
+                .chunks_exact(2)
+                .map(|unit| u16::from_be_bytes([unit[0], unit[1]]))
+                .collect();
+            Ok(Some(String::from_utf16_lossy(&units)))
+        } else {
+            Ok(Some(String::from_utf8_lossy(text).into_owned()))
+        }
+    }
+
+    /// Writes every sample of `track_id` as a raw elementary stream: an
+    /// Annex B byte stream for H.264 and H.265, with the parameter sets
+    /// before each sync sample, or ADTS frames for AAC.
+    pub fn dump_elementary_stream<W: Write>(
+        &mut self,
+        track_id: u32,
+        writer: &mut W,
+    ) -> Result<()> {
+        let track = self
+            .tracks
+            .get(&track_id)
+            .ok_or(Error::TrakNotFound(track_id))?;
+        let framing = Framing::new(track)?;
+        let sample_count = track.sample_count();
+
+        for sample_id in 1..=sample_count {
+            if let Some(sample) = self.read_sample(track_id, sample_id)? {
+                framing.write_sample(writer, &sample)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads a sample of an H.264 or H.265 track with its bytes converted to
+    /// Annex B (see [`Mp4Track::sample_to_annexb`]).
+    pub fn read_sample_annexb(
+        &mut self,
+        track_id: u32,
+        sample_id: u32,
+    ) -> Result<Option<Mp4Sample>> {
+        match self.read_sample(track_id, sample_id)? {
+            Some(mut sample) => {
+                sample.bytes = self.tracks[&track_id].sample_to_annexb(&sample)?;
+                Ok(Some(sample))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Reads the sample of `track_id` whose decode interval covers `time`
+    /// (see [`Mp4Track::sample_id_for_time`]), or `None` past the end.
+    pub fn sample_at_time(&mut self, track_id: u32, time: Duration) -> Result<Option<Mp4Sample>> {
+        let track = self
+            .tracks
+            .get(&track_id)
+            .ok_or(Error::TrakNotFound(track_id))?;
+        match track.sample_id_for_time(time) {
+            Some(sample_id) => self.read_sample(track_id, sample_id),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns an iterator over the samples of `track_id` in decode order,
+    /// reading each with [`read_sample`](Self::read_sample). It ends after the
+    /// last sample, or after yielding the first error.
+    pub fn samples(&mut self, track_id: u32) -> Result<Samples<'_, R>> {
+        let sample_count = self.sample_count(track_id)?;
+        Ok(Samples {
+            reader: self,
+            track_id,
+            sample_id: 1,
+            sample_count,
+        })
+    }
+
+    /// Returns an iterator over the samples of `track_id` with the edit list
+    /// already applied, for consumers that don't handle edit lists.
+    ///
+    /// Each yielded sample's `start_time` is its presentation time (see
+    /// [`Mp4Track::presentation_offset`]) and its `rendering_offset` is zero.
+    /// Samples are still yielded in decode order; those presented before the
+    /// edit starts are kept for decoding with their time clamped to zero.
+    pub fn read_edit_corrected_samples(
+        &mut self,
+        track_id: u32,
+    ) -> Result<EditCorrectedSamples<'_, R>> {
+        if let Some(track) = self.tracks.get(&track_id) {
+            let offset = track.presentation_offset(self.moov.mvhd.timescale);
+            let sample_count = track.sample_count();
+            Ok(EditCorrectedSamples {
+                reader: self,
+                track_id,
+                sample_id: 1,
+                sample_count,
+                offset,
+            })
+        } else {
+            Err(Error::TrakNotFound(track_id))
+        }
+    }
+
+    /// Reads `size` raw bytes starting at `offset`, e.g. to extract a box the
+    /// reader doesn't model. The range must lie within the parsed file size.
+    pub fn raw_box_reader(&mut self, offset: u64, size: u64) -> Result<Vec<u8>> {
+        match offset.checked_add(size) {
+            Some(end) if end <= self.size => {}
+            _ => {
+                return Err(Error::InvalidData(
+                    "byte range extends past the end of the file",
+                ))
+            }
+        }
+
+        let mut buffer = vec![0x0u8; size as usize];
+        self.reader.seek(SeekFrom::Start(offset))?;
+        self.reader.read_exact(&mut buffer)?;
+        Ok(buffer)
+    }
+
+    pub fn track_dimensions_with_pasp(&self, track_id: u32) -> Result<(u32, u32)> {
+        if let Some(track) = self.tracks.get(&track_id) {
+            Ok(track.display_dimensions_corrected())
+        } else {
+            Err(Error::TrakNotFound(track_id))
+        }
+    }
+
+    /// Returns the timing, position and size of every sample of a track
+    /// without reading the `mdat`; see [`Mp4Track::sample_index`].
+    pub fn sample_index(&self, track_id: u32) -> Result<Vec<SampleEntry>> {
         if let Some(track) = self.tracks.get(&track_id) {
-            track.read_sample(&mut self.reader, sample_id)
+            track.sample_index()
         } else {
             Err(Error::TrakNotFound(track_id))
         }
@@ -272,11 +806,516 @@ impl<R: Read + Seek> Mp4Reader<R> {
     }
 }
 
+impl<R: Read + Write + Seek> Mp4Reader<R> {
+    /// Rewrites each fragment's `tfhd.base_data_offset` and `trun.data_offset`
+    /// to point into the `mdat` that follows its `moof`, for files made by
+    /// concatenating independently produced segments. Each `traf`'s samples
+    /// are taken to follow the previous one's in that `mdat`. The fields are
+    /// patched in place, so nothing else in the file moves.
+    pub fn fix_fragment_run_offsets(&mut self) -> Result<()> {
+        let mvex = self.moov.mvex.as_ref();
+
+        for (moof, &moof_offset) in self.moofs.iter_mut().zip(self.moof_offsets.iter()) {
+            let mut data_offset = match self
+                .mdat_ranges
+                .iter()
+                .find(|&&(start, _)| start > moof_offset)
+            {
+                Some(&(start, _)) => start,
+                None => return Err(Error::InvalidData("moof is not followed by an mdat")),
+            };
+
+            let field_offsets = traf_field_offsets(&mut self.reader, moof_offset)?;
+            for (i, (traf, (tfhd_offset, trun_offset))) in
+                moof.trafs.iter_mut().zip(field_offsets).enumerate()
+            {
+                // Later trafs follow on from the data of the previous one,
+                // which starts at data_offset once fixed.
+                let implicit_base = if i == 0 || is_moof_relative(traf) {
+                    moof_offset
+                } else {
+                    data_offset
+                };
+                let run_has_offset = traf
+                    .trun
+                    .as_ref()
+                    .is_some_and(|trun| trun.data_offset.is_some());
+                if traf.tfhd.base_data_offset.is_some() {
+                    let base = if run_has_offset {
+                        moof_offset
+                    } else {
+                        data_offset
+                    };
+                    traf.tfhd.base_data_offset = Some(base);
+                    // After version, flags and track_ID.
+                    self.reader.seek(SeekFrom::Start(tfhd_offset + 8))?;
+                    self.reader.write_all(&base.to_be_bytes())?;
+                }
+
+                let trun = match traf.trun {
+                    Some(ref mut trun) => trun,
+                    None => continue,
+                };
+                if let (true, Some(trun_offset)) = (run_has_offset, trun_offset) {
+                    let base = traf.tfhd.base_data_offset.unwrap_or(implicit_base);
+                    let offset = i32::try_from(data_offset - base)
+                        .map_err(|_| Error::InvalidData("trun data_offset is out of range"))?;
+                    trun.data_offset = Some(offset);
+                    // After version, flags and sample_count.
+                    self.reader.seek(SeekFrom::Start(trun_offset + 8))?;
+                    self.reader.write_all(&offset.to_be_bytes())?;
+                }
+
+                data_offset += trun_data_size(&traf.tfhd, trun, mvex);
+            }
+        }
+
+        for track in self.tracks.values_mut() {
+            track.trafs.clear();
+            track.moof_offsets.clear();
+            track.traf_base_offsets.clear();
+        }
+        for (moof, &moof_offset) in self.moofs.iter().zip(self.moof_offsets.iter()) {
+            let base_offsets = traf_base_offsets(moof, moof_offset, mvex);
+            for (traf, base_offset) in moof.trafs.iter().zip(base_offsets) {
+                if let Some(track) = self.tracks.get_mut(&traf.tfhd.track_id) {
+                    track.moof_offsets.push(moof_offset);
+                    track.traf_base_offsets.push(base_offset);
+                    track.trafs.push(traf.clone());
+                }
+            }
+        }
+        self.chunk_cache = ChunkCache::default();
+        Ok(())
+    }
+}
+
+fn is_moof_relative(traf: &TrafBox) -> bool {
+    traf.tfhd.flags & TfhdBox::FLAG_DEFAULT_BASE_IS_MOOF != 0
+}
+
+// The size of the data a trun addresses, with the track's trex, if any,
+// providing the default sample size.
+fn trun_data_size(tfhd: &TfhdBox, trun: &TrunBox, mvex: Option<&MvexBox>) -> u64 {
+    if trun.sample_sizes.is_empty() {
+        let size = tfhd.default_sample_size.unwrap_or_else(|| {
+            mvex.and_then(|mvex| mvex.trex(tfhd.track_id))
+                .map_or(0, |trex| trex.default_sample_size)
+        });
+        size as u64 * trun.sample_count as u64
+    } else {
+        trun.sample_sizes.iter().map(|&size| size as u64).sum()
+    }
+}
+
+// Returns what the data of each traf in a moof is relative to when its tfhd
+// has no base_data_offset: the start of the moof for the first traf or with
+// default-base-is-moof, otherwise the end of the previous traf's data.
+fn traf_base_offsets(moof: &MoofBox, moof_offset: u64, mvex: Option<&MvexBox>) -> Vec<u64> {
+    let mut base_offsets = Vec::with_capacity(moof.trafs.len());
+    let mut data_end = moof_offset;
+    for (i, traf) in moof.trafs.iter().enumerate() {
+        let base_offset = if i == 0 || is_moof_relative(traf) {
+            moof_offset
+        } else {
+            data_end
+        };
+        base_offsets.push(base_offset);
+
+        let start = traf.tfhd.base_data_offset.unwrap_or(base_offset);
+        data_end = match traf.trun {
+            Some(ref trun) => start
+                .saturating_add_signed(trun.data_offset.unwrap_or(0) as i64)
+                .saturating_add(trun_data_size(&traf.tfhd, trun, mvex)),
+            None => start,
+        };
+    }
+    base_offsets
+}
+
+// Returns the payload offsets of the tfhd and (last) trun of each traf in the
+// moof at `moof_offset`.
+fn traf_field_offsets<R: Read + Seek>(
+    reader: &mut R,
+    moof_offset: u64,
+) -> Result<Vec<(u64, Option<u64>)>> {
+    reader.seek(SeekFrom::Start(moof_offset))?;
+    let moof_end = moof_offset + BoxHeader::read(reader)?.size;
+
+    let mut offsets = Vec::new();
+    let mut current = reader.stream_position()?;
+    while current < moof_end {
+        let header = BoxHeader::read(reader)?;
+        if header.size == 0 {
+            return Err(Error::InvalidData(
+                "moof contains a box with a size of zero",
+            ));
+        }
+        if header.name == BoxType::TrafBox {
+            let traf_end = current + header.size;
+            let mut tfhd = None;
+            let mut trun = None;
+            let mut child = reader.stream_position()?;
+            while child < traf_end {
+                let header = BoxHeader::read(reader)?;
+                if header.size == 0 {
+                    return Err(Error::InvalidData(
+                        "traf contains a box with a size of zero",
+                    ));
+                }
+                match header.name {
+                    BoxType::TfhdBox => tfhd = Some(reader.stream_position()?),
+                    BoxType::TrunBox => trun = Some(reader.stream_position()?),
+                    _ => {}
+                }
+                child += header.size;
+                reader.seek(SeekFrom::Start(child))?;
+            }
+            offsets.push((tfhd.ok_or(Error::BoxNotFound(BoxType::TfhdBox))?, trun));
+        }
+        current += header.size;
+        reader.seek(SeekFrom::Start(current))?;
+    }
+    Ok(offsets)
+}
+
+/// Iterator returned by [`Mp4Reader::samples`].
+#[derive(Debug)]
+pub struct Samples<'a, R> {
+    reader: &'a mut Mp4Reader<R>,
+    track_id: u32,
+    sample_id: u32,
+    sample_count: u32,
+}
+
+impl<R: Read + Seek> Iterator for Samples<'_, R> {
+    type Item = Result<Mp4Sample>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.sample_id <= self.sample_count {
+            let sample_id = self.sample_id;
+            self.sample_id += 1;
+            match self.reader.read_sample(self.track_id, sample_id) {
+                Ok(Some(sample)) => return Some(Ok(sample)),
+                Ok(None) => continue,
+                Err(err) => {
+                    self.sample_id = self.sample_count + 1;
+                    return Some(Err(err));
+                }
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = (self.sample_count + 1).saturating_sub(self.sample_id);
+        (0, Some(remaining as usize))
+    }
+}
+
+/// Iterator returned by [`Mp4Reader::read_edit_corrected_samples`].
+#[derive(Debug)]
+pub struct EditCorrectedSamples<'a, R> {
+    reader: &'a mut Mp4Reader<R>,
+    track_id: u32,
+    sample_id: u32,
+    sample_count: u32,
+    offset: i64,
+}
+
+impl<R: Read + Seek> Iterator for EditCorrectedSamples<'_, R> {
+    type Item = Result<Mp4Sample>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.sample_id <= self.sample_count {
+            let sample_id = self.sample_id;
+            self.sample_id += 1;
+            match self.reader.read_sample(self.track_id, sample_id) {
+                Ok(Some(mut sample)) => {
+                    let presentation_time = sample.presentation_time() + self.offset;
+                    sample.start_time = presentation_time.max(0) as u64;
+                    sample.rendering_offset = 0;
+                    return Some(Ok(sample));
+                }
+                Ok(None) => continue,
+                Err(err) => return Some(Err(err)),
+            }
+        }
+        None
+    }
+}
+
 impl<R> Mp4Reader<R> {
+    /// Checks the parsed header for structural inconsistencies that don't
+    /// prevent reading but may mislead consumers.
+    ///
+    /// Duplicate top-level `ftyp` and `moov` boxes are reported here as well;
+    /// the reader always uses the first occurrence, as is a `moov` that
+    /// follows the `mdat` (see [`is_streamable`](Self::is_streamable)).
+    pub fn validate(&self) -> Vec<Warning> {
+        let mut warnings = self.duplicates.clone();
+
+        if let (Some(moov), Some(mdat)) = (
+            self.first_box_offset(BoxType::MoovBox),
+            self.first_box_offset(BoxType::MdatBox),
+        ) {
+            if moov > mdat {
+                warnings.push(Warning::MoovAfterMdat(moov, mdat));
+            }
+        }
+
+        for trak in self.moov.traks.iter() {
+            let entry_count = trak.mdia.minf.dinf.dref.entry_count;
+            if let Some(index) = trak.mdia.minf.stbl.stsd.data_reference_index() {
+                if index == 0 || index as u32 > entry_count {
+                    warnings.push(Warning::DataReferenceIndexOutOfRange(
+                        trak.tkhd.track_id,
+                        index,
+                        entry_count,
+                    ));
+                }
+            }
+        }
+
+        warnings
+    }
+
+    /// Checks that every sample's byte range lies within a single top-level
+    /// `mdat`, as a truncated or corrupt file's `moov` can point past the
+    /// data that is actually present. Tracks are checked in ID order and the
+    /// first offending sample is returned as [`Error::SampleOutsideMdat`].
+    pub fn validate_offsets_against_mdat(&self) -> Result<()> {
+        let mut track_ids: Vec<u32> = self.tracks.keys().copied().collect();
+        track_ids.sort_unstable();
+        for track_id in track_ids {
+            let track = &self.tracks[&track_id];
+            for sample_id in 1..=track.sample_count() {
+                let offset = track.sample_offset(sample_id)?;
+                let size = track.sample_size(sample_id)?;
+                let end = offset + size as u64;
+                let in_mdat = self
+                    .mdat_ranges
+                    .iter()
+                    .any(|&(start, mdat_end)| offset >= start && end <= mdat_end);
+                if !in_mdat {
+                    return Err(Error::SampleOutsideMdat(track_id, sample_id, offset, size));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The type, file offset and size of every top-level box, in file order,
+    /// e.g. to answer byte-range requests for the `moov` or a `moof`. Empty
+    /// for a reader from [`recover_from_partial`](Self::recover_from_partial).
+    pub fn box_offsets(&self) -> &[(BoxType, u64, u64)] {
+        &self.box_offsets
+    }
+
+    /// Whether the `moov` precedes the first `mdat` ("faststart"), so playback
+    /// can begin before the whole file is downloaded. A file without an
+    /// `mdat`, e.g. an init segment, is streamable; a reader from
+    /// [`recover_from_partial`](Self::recover_from_partial) is not.
+    pub fn is_streamable(&self) -> bool {
+        match (
+            self.first_box_offset(BoxType::MoovBox),
+            self.first_box_offset(BoxType::MdatBox),
+        ) {
+            (Some(moov), Some(mdat)) => moov < mdat,
+            (Some(_), None) => true,
+            (None, _) => false,
+        }
+    }
+
+    /// The MIME type with an RFC 6381 `codecs` parameter, e.g.
+    /// `video/mp4; codecs="avc1.640028,mp4a.40.2"`, for `<source type>` or a
+    /// `Content-Type` header. Codecs are listed in track ID order; subtitle
+    /// and hint tracks are left out. If a track has no
+    /// [`codec_string`](Mp4Track::codec_string), the `codecs` parameter is
+    /// left out rather than listing only some of them.
+    pub fn guess_mime_type(&self) -> Result<String> {
+        let mut track_ids: Vec<u32> = self.tracks.keys().copied().collect();
+        track_ids.sort_unstable();
+
+        let mut has_video = false;
+        let mut has_audio = false;
+        let mut codecs = Vec::new();
+        let mut has_unknown_codec = false;
+        for track_id in track_ids {
+            let track = &self.tracks[&track_id];
+            match track.track_type()? {
+                TrackType::Video => has_video = true,
+                TrackType::Audio => has_audio = true,
+                TrackType::Subtitle | TrackType::Hint => continue,
+            }
+            match track.codec_string() {
+                Ok(codec) => codecs.push(codec),
+                Err(_) => has_unknown_codec = true,
+            }
+        }
+
+        let media_type = if has_video {
+            "video/mp4"
+        } else if has_audio {
+            "audio/mp4"
+        } else {
+            "application/mp4"
+        };
+        if codecs.is_empty() || has_unknown_codec {
+            Ok(media_type.to_string())
+        } else {
+            Ok(format!("{}; codecs=\"{}\"", media_type, codecs.join(",")))
+        }
+    }
+
+    fn first_box_offset(&self, box_type: BoxType) -> Option<u64> {
+        self.box_offsets
+            .iter()
+            .find(|&&(name, _, _)| name == box_type)
+            .map(|&(_, offset, _)| offset)
+    }
+
+    /// Iterates over the groups of pictures of a track. Each starts at a sync
+    /// sample; samples before the first sync sample, if any, form a GOP of
+    /// their own starting at sample 1.
+    pub fn gops(&self, track_id: u32) -> Result<impl Iterator<Item = Gop> + '_> {
+        let track = self
+            .tracks
+            .get(&track_id)
+            .ok_or(Error::TrakNotFound(track_id))?;
+        let sample_count = track.sample_count();
+
+        let mut next_sample = 1;
+        Ok(std::iter::from_fn(move || {
+            if next_sample > sample_count {
+                return None;
+            }
+            let start_sample = next_sample;
+            next_sample += 1;
+            while next_sample <= sample_count && !track.is_sync_sample(next_sample) {
+                next_sample += 1;
+            }
+            Some(Gop {
+                start_sample,
+                sample_ids: (start_sample..next_sample).collect(),
+            })
+        }))
+    }
+
+    /// Collects the brands, movie duration and timescale, fragmentation
+    /// status and a summary of every track.
+    pub fn stream_info(&self) -> StreamInfo {
+        let mut tracks: Vec<TrackInfo> = self
+            .tracks
+            .values()
+            .map(|track| TrackInfo {
+                track_id: track.track_id(),
+                track_type: track.track_type().ok(),
+                media_type: track.media_type().ok(),
+                language: track
+                    .extended_language()
+                    .unwrap_or(track.language())
+                    .to_owned(),
+                duration: track.duration(),
+                timescale: track.timescale(),
+                sample_count: track.sample_count(),
+                bitrate: track.bitrate(),
+                width: track.width(),
+                height: track.height(),
+            })
+            .collect();
+        tracks.sort_unstable_by_key(|track| track.track_id);
+
+        StreamInfo {
+            major_brand: self.ftyp.major_brand,
+            minor_version: self.ftyp.minor_version,
+            compatible_brands: self.ftyp.compatible_brands.clone(),
+            duration: Duration::from_millis(rescale(
+                self.moov.mvhd.duration,
+                self.moov.mvhd.timescale as u64,
+                1000,
+            )),
+            timescale: self.moov.mvhd.timescale,
+            is_fragmented: !self.moofs.is_empty(),
+            tracks,
+        }
+    }
+
+    /// Returns the subsegments listed by every top-level `sidx`, resolved to
+    /// absolute file offsets. An `ssix` directly following a `sidx` (before
+    /// the next one) contributes the level ranges of its subsegments.
+    pub fn segment_index(&self) -> Vec<Subsegment> {
+        let mut subsegments = Vec::new();
+
+        for (i, (sidx, sidx_offset)) in self.sidxs.iter().zip(&self.sidx_offsets).enumerate() {
+            let next_sidx_offset = self.sidx_offsets.get(i + 1).copied().unwrap_or(u64::MAX);
+            let ssix = self
+                .ssixs
+                .iter()
+                .zip(&self.ssix_offsets)
+                .find(|(_, &offset)| offset > *sidx_offset && offset < next_sidx_offset)
+                .map(|(ssix, _)| ssix);
+
+            let mut offset = sidx_offset + sidx.box_size() + sidx.first_offset;
+            let mut start_time = sidx.earliest_presentation_time;
+            for (j, reference) in sidx.references.iter().enumerate() {
+                let mut ranges = Vec::new();
+                if let Some(subsegment) = ssix.and_then(|ssix| ssix.subsegments.get(j)) {
+                    let mut range_offset = offset;
+                    for range in subsegment.ranges.iter() {
+                        ranges.push(SubsegmentRange {
+                            level: range.level,
+                            offset: range_offset,
+                            size: range.range_size,
+                        });
+                        range_offset += range.range_size as u64;
+                    }
+                }
+
+                subsegments.push(Subsegment {
+                    reference_id: sidx.reference_id,
+                    is_index: reference.reference_type,
+                    offset,
+                    size: reference.referenced_size,
+                    start_time,
+                    duration: reference.subsegment_duration,
+                    timescale: sidx.timescale,
+                    starts_with_sap: reference.starts_with_sap,
+                    sap_type: reference.sap_type,
+                    sap_delta_time: reference.sap_delta_time,
+                    ranges,
+                });
+
+                offset += reference.referenced_size as u64;
+                start_time += reference.subsegment_duration as u64;
+            }
+        }
+
+        subsegments
+    }
+
+    /// Looks up a QuickTime metadata item (`mdta` handler, e.g.
+    /// `com.apple.quicktime.make`) by key, in `moov/meta` or `moov/udta/meta`.
+    pub fn keyed_metadata(&self, key: &str) -> Option<&DataBox> {
+        let udta_meta = self.moov.udta.as_ref().and_then(|udta| udta.meta.as_ref());
+        self.moov
+            .meta
+            .iter()
+            .chain(udta_meta)
+            .find_map(|meta| match meta {
+                MetaBox::Mdta { keys, items } => keys
+                    .entries
+                    .iter()
+                    .position(|entry| entry.key == key)
+                    .and_then(|i| items.get(&(i as u32 + 1))),
+                _ => None,
+            })
+    }
+
     pub fn metadata(&self) -> impl Metadata<'_> {
         self.moov.udta.as_ref().and_then(|udta| {
             udta.meta.as_ref().and_then(|meta| match meta {
-                MetaBox::Mdir { ilst } => ilst.as_ref(),
+                MetaBox::Mdir { ilst, .. } => ilst.as_ref(),
                 _ => None,
             })
         })
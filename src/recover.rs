@@ -0,0 +1,345 @@
+//! Rebuilds a `moov` for a capture that stopped before it was written, by
+//! scanning the `mdat` for length-prefixed NAL units and ADTS frames.
+
+use std::convert::TryFrom;
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::mp4box::*;
+use crate::*;
+
+/// Length of the NAL unit size prefix written by [`Mp4Writer`].
+const NAL_LENGTH_SIZE: u64 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Codec {
+    Avc,
+    Hevc,
+    Aac,
+}
+
+#[derive(Debug, Default)]
+struct RecoveredTrack {
+    // (offset, size, is_sync) of every sample, in file order.
+    samples: Vec<(u64, u32, bool)>,
+    // Access unit being assembled from NAL units: (offset, size, is_sync).
+    pending: Option<(u64, u32, bool)>,
+    pending_has_vcl: bool,
+}
+
+impl RecoveredTrack {
+    /// Closes the access unit being assembled. Parameter sets or SEI
+    /// without a coded picture don't make a sample and are dropped.
+    fn finish_pending(&mut self) {
+        if let Some(sample) = self.pending.take() {
+            if self.pending_has_vcl {
+                self.samples.push(sample);
+            }
+        }
+        self.pending_has_vcl = false;
+    }
+}
+
+/// Scans `reader` (of `size` bytes from its current position) and returns
+/// the `ftyp`, a `moov` rebuilt for `tracks` and the mdat payload range.
+pub(crate) fn recover_header<R: Read + Seek>(
+    reader: &mut R,
+    size: u64,
+    tracks: &[RecoveryTrack],
+) -> Result<(FtypBox, MoovBox, (u64, u64))> {
+    let start = reader.stream_position()?;
+    let end = start + size;
+
+    let mut ftyp = None;
+    let mut mdat_range = None;
+    let mut current = start;
+    while current + HEADER_SIZE <= end {
+        let header = BoxHeader::read(reader)?;
+        let BoxHeader { name, size: s } = header;
+        match name {
+            BoxType::FtypBox if ftyp.is_none() && s <= end - current => {
+                ftyp = Some(FtypBox::read_box(reader, s)?);
+            }
+            BoxType::MdatBox => {
+                let mut data_start = reader.stream_position()?;
+                // A capture that never finished leaves the placeholder size
+                // (or 0) in the mdat header; its data then runs to the end.
+                let data_end = if s <= HEADER_SIZE || current + s > end {
+                    end
+                } else {
+                    current + s
+                };
+                if data_start + HEADER_SIZE <= data_end {
+                    let next = BoxHeader::read(reader)?;
+                    if next.name == BoxType::WideBox && next.size == HEADER_SIZE {
+                        data_start += HEADER_SIZE;
+                    }
+                }
+                mdat_range = Some((data_start, data_end));
+                break;
+            }
+            _ => {
+                if s < HEADER_SIZE || current + s > end {
+                    break;
+                }
+            }
+        }
+        current += s;
+        reader.seek(SeekFrom::Start(current))?;
+    }
+
+    let ftyp = ftyp.ok_or(Error::BoxNotFound(BoxType::FtypBox))?;
+    let (data_start, data_end) = mdat_range.ok_or(Error::BoxNotFound(BoxType::MdatBox))?;
+
+    let codecs = tracks
+        .iter()
+        .map(|track| match track.config.media_conf {
+            MediaConfig::AvcConfig(_) => Ok(Codec::Avc),
+            MediaConfig::HevcConfig(_) => Ok(Codec::Hevc),
+            MediaConfig::AacConfig(_) => Ok(Codec::Aac),
+            _ => Err(Error::InvalidData(
+                "only AVC, HEVC and AAC tracks can be recovered",
+            )),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let mut recovered: Vec<RecoveredTrack> =
+        tracks.iter().map(|_| RecoveredTrack::default()).collect();
+
+    let mut pos = data_start;
+    let mut last_track = None;
+    let mut buf = [0u8; 8];
+    while pos < data_end {
+        let available = (data_end - pos).min(buf.len() as u64) as usize;
+        reader.seek(SeekFrom::Start(pos))?;
+        reader.read_exact(&mut buf[..available])?;
+        let bytes = &buf[..available];
+
+        let unit = codecs.iter().enumerate().find_map(|(i, codec)| {
+            let (size, kind) = match codec {
+                Codec::Aac => parse_adts(bytes)?,
+                Codec::Avc | Codec::Hevc => parse_nal(bytes, *codec)?,
+            };
+            if pos + size > data_end {
+                return None;
+            }
+            Some((i, size, kind))
+        });
+        let (i, size, kind) = match unit {
+            Some(unit) => unit,
+            None => break,
+        };
+
+        if last_track != Some(i) {
+            if let Some(last) = last_track {
+                recovered[last].finish_pending();
+            }
+        }
+        last_track = Some(i);
+
+        let track = &mut recovered[i];
+        match kind {
+            Unit::Frame { header_size } => {
+                track
+                    .samples
+                    .push((pos + header_size, (size - header_size) as u32, true));
+            }
+            Unit::Nal {
+                starts_access_unit,
+                is_vcl,
+                is_sync,
+            } => {
+                if starts_access_unit && track.pending_has_vcl {
+                    track.finish_pending();
+                }
+                let pending = track.pending.get_or_insert((pos, 0, false));
+                pending.1 += size as u32;
+                pending.2 |= is_sync;
+                track.pending_has_vcl |= is_vcl;
+            }
+        }
+        pos += size;
+    }
+    // Trailing bytes that don't parse are a unit cut short, so the access
+    // unit it belonged to is incomplete.
+    if pos < data_end {
+        if let Some(last) = last_track {
+            recovered[last].pending = None;
+        }
+    }
+    for track in recovered.iter_mut() {
+        track.finish_pending();
+    }
+
+    let config = Mp4Config {
+        major_brand: ftyp.major_brand,
+        minor_version: ftyp.minor_version,
+        compatible_brands: ftyp.compatible_brands.clone(),
+        ..Default::default()
+    };
+    let mut header = Mp4Header::new_empty(&config);
+    for (track, samples) in tracks.iter().zip(recovered) {
+        let track_id = header.add_track(&track.config)?;
+        let stbl = &mut header.trak_mut(track_id)?.mdia.minf.stbl;
+        fill_sample_table(stbl, &samples.samples, track.sample_duration);
+    }
+    header.update_durations();
+
+    Ok((ftyp, header.moov, (data_start, data_end)))
+}
+
+fn fill_sample_table(stbl: &mut StblBox, samples: &[(u64, u32, bool)], sample_duration: u32) {
+    if !samples.is_empty() {
+        stbl.stts.entries.push(SttsEntry {
+            sample_count: samples.len() as u32,
+            sample_delta: sample_duration,
+        });
+    }
+
+    let mut chunk_offsets = Vec::new();
+    let mut chunk_end = None;
+    let mut chunk_samples = 0;
+    let mut sync_samples = Vec::new();
+    for (i, &(offset, size, is_sync)) in samples.iter().enumerate() {
+        let sample_id = i as u32 + 1;
+        if chunk_end != Some(offset) {
+            if chunk_samples > 0 {
+                push_stsc(stbl, chunk_offsets.len() as u32, chunk_samples, sample_id);
+            }
+            chunk_offsets.push(offset);
+            chunk_samples = 0;
+        }
+        chunk_samples += 1;
+        chunk_end = Some(offset + size as u64);
+
+        stbl.stsz.sample_sizes.push(size);
+        if is_sync {
+            sync_samples.push(sample_id);
+        }
+    }
+    if chunk_samples > 0 {
+        push_stsc(
+            stbl,
+            chunk_offsets.len() as u32,
+            chunk_samples,
+            samples.len() as u32 + 1,
+        );
+    }
+    stbl.stsz.sample_count = samples.len() as u32;
+
+    if sync_samples.len() < samples.len() {
+        stbl.stss = Some(StssBox {
+            entries: sync_samples,
+            ..Default::default()
+        });
+    }
+
+    let co64 = Co64Box {
+        entries: chunk_offsets,
+        ..Default::default()
+    };
+    if let Ok(stco) = StcoBox::try_from(&co64) {
+        stbl.stco = Some(stco);
+    } else {
+        stbl.stco = None;
+        stbl.co64 = Some(co64);
+    }
+}
+
+/// Records that chunk `chunk_id`, ending before `next_sample_id`, holds
+/// `samples_per_chunk` samples.
+fn push_stsc(stbl: &mut StblBox, chunk_id: u32, samples_per_chunk: u32, next_sample_id: u32) {
+    if let Some(entry) = stbl.stsc.entries.last() {
+        if entry.samples_per_chunk == samples_per_chunk {
+            return;
+        }
+    }
+    stbl.stsc.entries.push(StscEntry {
+        first_chunk: chunk_id,
+        samples_per_chunk,
+        sample_description_index: 1,
+        first_sample: next_sample_id - samples_per_chunk,
+    });
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Unit {
+    Frame {
+        header_size: u64,
+    },
+    Nal {
+        starts_access_unit: bool,
+        is_vcl: bool,
+        is_sync: bool,
+    },
+}
+
+/// Parses an ADTS header, returning the frame size (header included).
+fn parse_adts(bytes: &[u8]) -> Option<(u64, Unit)> {
+    if bytes.len() < 7 || bytes[0] != 0xFF || bytes[1] & 0xF6 != 0xF0 {
+        return None;
+    }
+    let header_size = if bytes[1] & 0x01 == 0 { 9 } else { 7 };
+    let frame_size =
+        ((bytes[3] as u64 & 0x03) << 11) | ((bytes[4] as u64) << 3) | ((bytes[5] as u64) >> 5);
+    if frame_size <= header_size {
+        return None;
+    }
+    Some((frame_size, Unit::Frame { header_size }))
+}
+
+/// Parses a length-prefixed NAL unit, returning its size (prefix included).
+fn parse_nal(bytes: &[u8], codec: Codec) -> Option<(u64, Unit)> {
+    if bytes.len() < NAL_LENGTH_SIZE as usize + 3 {
+        return None;
+    }
+    let length = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as u64;
+    let header = &bytes[NAL_LENGTH_SIZE as usize..];
+    if length < 2 || header[0] & 0x80 != 0 {
+        return None;
+    }
+
+    let unit = if codec == Codec::Avc {
+        let nal_type = header[0] & 0x1F;
+        match nal_type {
+            // Coded slices; first_mb_in_slice == 0 starts a new picture.
+            1 | 5 => Unit::Nal {
+                starts_access_unit: header[1] & 0x80 != 0,
+                is_vcl: true,
+                is_sync: nal_type == 5,
+            },
+            6..=9 | 14..=18 => Unit::Nal {
+                starts_access_unit: true,
+                is_vcl: false,
+                is_sync: false,
+            },
+            2..=4 | 10..=13 | 19..=23 => Unit::Nal {
+                starts_access_unit: false,
+                is_vcl: false,
+                is_sync: false,
+            },
+            _ => return None,
+        }
+    } else {
+        let nal_type = (header[0] >> 1) & 0x3F;
+        match nal_type {
+            // Coded slice segments; first_slice_segment_in_pic_flag starts
+            // a new picture.
+            0..=31 => Unit::Nal {
+                starts_access_unit: length > 2 && header[2] & 0x80 != 0,
+                is_vcl: true,
+                is_sync: (16..=23).contains(&nal_type),
+            },
+            32..=35 | 39 | 41..=44 | 48..=55 => Unit::Nal {
+                starts_access_unit: true,
+                is_vcl: false,
+                is_sync: false,
+            },
+            36..=38 | 40 | 45..=47 | 56..=63 => Unit::Nal {
+                starts_access_unit: false,
+                is_vcl: false,
+                is_sync: false,
+            },
+            _ => return None,
+        }
+    };
+    Some((NAL_LENGTH_SIZE + length, unit))
+}
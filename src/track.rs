@@ -1,16 +1,23 @@
 use bytes::BytesMut;
 use std::cmp;
+#[cfg(feature = "cenc")]
+use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::io::{Read, Seek, SeekFrom, Write};
+use std::iter;
+use std::sync::OnceLock;
 use std::time::Duration;
 
+use crate::elementary::Framing;
 use crate::mp4box::traf::TrafBox;
 use crate::mp4box::trak::TrakBox;
 use crate::mp4box::trun::TrunBox;
 use crate::mp4box::{
-    avc1::Avc1Box, co64::Co64Box, ctts::CttsBox, ctts::CttsEntry, hev1::Hev1Box, mp4a::Mp4aBox,
-    smhd::SmhdBox, stco::StcoBox, stsc::StscEntry, stss::StssBox, stts::SttsEntry, tx3g::Tx3gBox,
-    vmhd::VmhdBox, vp09::Vp09Box,
+    ac3::Ac3Box, av01::Av01Box, avc1::Avc1Box, btrt::BtrtBox, co64::Co64Box, ctts::CttsBox,
+    ctts::CttsEntry, dvcc::DvccBox, hev1::Hev1Box, hev1::HvcCBox, hmhd::HmhdBox, hnti::HntiBox,
+    hnti::SdpBox, mp4a::Mp4aBox, rtp::RtpBox, smhd::SmhdBox, stbl::StblBox, stsc::StscEntry,
+    stss::StssBox, stts::SttsEntry, tref::TrackReference, tref::TrefBox, tx3g::Tx3gBox,
+    udta::UdtaBox, vmhd::VmhdBox, vp09::Vp09Box,
 };
 use crate::*;
 
@@ -19,7 +26,43 @@ pub struct TrackConfig {
     pub track_type: TrackType,
     pub timescale: u32,
     pub language: String,
+    pub volume: FixedPointU8,
     pub media_conf: MediaConfig,
+
+    /// Written as the `compressorname` of AVC, HEVC and AV1 sample entries (e.g.
+    /// "AVC Coding"), truncated to 31 bytes. Ignored for other media.
+    pub compressor_name: String,
+
+    /// The presentation size written to `tkhd`, for display dimensions that
+    /// differ from the coded size in the sample entry (e.g. to scale or
+    /// letterbox). `None` uses the coded size.
+    pub display_size: Option<(u16, u16)>,
+
+    /// The pixel aspect ratio as `(h_spacing, v_spacing)`, written as a `pasp`
+    /// box in AVC, HEVC, VP9 and AV1 sample entries. Ignored for other media.
+    pub pixel_aspect_ratio: Option<(u32, u32)>,
+
+    /// The clockwise rotation in degrees (0, 90, 180 or 270) written as the
+    /// `tkhd` matrix, e.g. for video recorded in portrait.
+    pub rotation: u16,
+}
+
+/// A 1000 Hz video track in an undetermined language, with an empty AVC
+/// configuration.
+impl Default for TrackConfig {
+    fn default() -> Self {
+        Self {
+            track_type: TrackType::Video,
+            timescale: 1000,
+            language: String::from("und"),
+            volume: FixedPointU8::new(1),
+            media_conf: MediaConfig::AvcConfig(AvcConfig::default()),
+            compressor_name: String::new(),
+            display_size: None,
+            pixel_aspect_ratio: None,
+            rotation: 0,
+        }
+    }
 }
 
 impl From<MediaConfig> for TrackConfig {
@@ -28,8 +71,18 @@ impl From<MediaConfig> for TrackConfig {
             MediaConfig::AvcConfig(avc_conf) => Self::from(avc_conf),
             MediaConfig::HevcConfig(hevc_conf) => Self::from(hevc_conf),
             MediaConfig::AacConfig(aac_conf) => Self::from(aac_conf),
+            MediaConfig::OpusConfig(opus_conf) => Self::from(opus_conf),
+            MediaConfig::Ac3Config(ac3_conf) => Self::from(ac3_conf),
             MediaConfig::TtxtConfig(ttxt_conf) => Self::from(ttxt_conf),
             MediaConfig::Vp9Config(vp9_config) => Self::from(vp9_config),
+            MediaConfig::Av1Config(av1_config) => Self::from(av1_config),
+            MediaConfig::RtpHintConfig(hint_config) => Self::from(hint_config),
+            MediaConfig::RawSampleEntryConfig(ref raw_conf) => Self {
+                track_type: raw_conf.track_type,
+                timescale: raw_conf.timescale,
+                media_conf,
+                ..Default::default()
+            },
         }
     }
 }
@@ -38,9 +91,9 @@ impl From<AvcConfig> for TrackConfig {
     fn from(avc_conf: AvcConfig) -> Self {
         Self {
             track_type: TrackType::Video,
-            timescale: 1000,               // XXX
-            language: String::from("und"), // XXX
+            timescale: 1000, // XXX
             media_conf: MediaConfig::AvcConfig(avc_conf),
+            ..Default::default()
         }
     }
 }
@@ -49,9 +102,9 @@ impl From<HevcConfig> for TrackConfig {
     fn from(hevc_conf: HevcConfig) -> Self {
         Self {
             track_type: TrackType::Video,
-            timescale: 1000,               // XXX
-            language: String::from("und"), // XXX
+            timescale: 1000, // XXX
             media_conf: MediaConfig::HevcConfig(hevc_conf),
+            ..Default::default()
         }
     }
 }
@@ -60,9 +113,31 @@ impl From<AacConfig> for TrackConfig {
     fn from(aac_conf: AacConfig) -> Self {
         Self {
             track_type: TrackType::Audio,
-            timescale: 1000,               // XXX
-            language: String::from("und"), // XXX
+            timescale: 1000, // XXX
             media_conf: MediaConfig::AacConfig(aac_conf),
+            ..Default::default()
+        }
+    }
+}
+
+impl From<OpusConfig> for TrackConfig {
+    fn from(opus_conf: OpusConfig) -> Self {
+        Self {
+            track_type: TrackType::Audio,
+            timescale: 48000,
+            media_conf: MediaConfig::OpusConfig(opus_conf),
+            ..Default::default()
+        }
+    }
+}
+
+impl From<Ac3Config> for TrackConfig {
+    fn from(ac3_conf: Ac3Config) -> Self {
+        Self {
+            track_type: TrackType::Audio,
+            timescale: Dac3Box::new(&ac3_conf).sample_rate().unwrap_or(48000),
+            media_conf: MediaConfig::Ac3Config(ac3_conf),
+            ..Default::default()
         }
     }
 }
@@ -71,9 +146,9 @@ impl From<TtxtConfig> for TrackConfig {
     fn from(txtt_conf: TtxtConfig) -> Self {
         Self {
             track_type: TrackType::Subtitle,
-            timescale: 1000,               // XXX
-            language: String::from("und"), // XXX
+            timescale: 1000, // XXX
             media_conf: MediaConfig::TtxtConfig(txtt_conf),
+            ..Default::default()
         }
     }
 }
@@ -82,21 +157,93 @@ impl From<Vp9Config> for TrackConfig {
     fn from(vp9_conf: Vp9Config) -> Self {
         Self {
             track_type: TrackType::Video,
-            timescale: 1000,               // XXX
-            language: String::from("und"), // XXX
+            timescale: 1000, // XXX
             media_conf: MediaConfig::Vp9Config(vp9_conf),
+            ..Default::default()
+        }
+    }
+}
+
+impl From<Av1Config> for TrackConfig {
+    fn from(av1_conf: Av1Config) -> Self {
+        Self {
+            track_type: TrackType::Video,
+            timescale: 1000, // XXX
+            media_conf: MediaConfig::Av1Config(av1_conf),
+            ..Default::default()
         }
     }
 }
 
+impl From<RtpHintConfig> for TrackConfig {
+    fn from(hint_conf: RtpHintConfig) -> Self {
+        Self {
+            track_type: TrackType::Hint,
+            timescale: hint_conf.rtp_timescale,
+            media_conf: MediaConfig::RtpHintConfig(hint_conf),
+            ..Default::default()
+        }
+    }
+}
+
+// HEVC parameter set NAL unit types, as found in hvcC arrays.
+const HEVC_NAL_UNIT_TYPE_VPS: u8 = 32;
+const HEVC_NAL_UNIT_TYPE_SPS: u8 = 33;
+const HEVC_NAL_UNIT_TYPE_PPS: u8 = 34;
+
+// The sample_is_non_sync_sample bit of fragment sample flags.
+const SAMPLE_IS_NON_SYNC_SAMPLE: u32 = 0x0001_0000;
+
 #[derive(Debug)]
 pub struct Mp4Track {
     pub trak: TrakBox,
     pub trafs: Vec<TrafBox>,
     pub moof_offsets: Vec<u64>,
+    // What each traf's data is relative to without a tfhd base_data_offset:
+    // its moof's start, or the end of the previous traf's data in the moof.
+    pub(crate) traf_base_offsets: Vec<u64>,
 
     // Fragmented Tracks Defaults.
     pub default_sample_duration: u32,
+    pub default_sample_flags: u32,
+
+    // Built on first use by `sample_time`.
+    stts_index: OnceLock<SttsIndex>,
+}
+
+/// The first sample ID and start time of each `stts` entry, so a sample's
+/// time is found by binary search instead of walking the entries.
+#[derive(Debug)]
+struct SttsIndex {
+    entries: Vec<(u32, u64)>,
+    // The entries stop before the one whose sample count overflowed.
+    overflowed: bool,
+}
+
+impl SttsIndex {
+    fn new(stts_entries: &[SttsEntry]) -> Self {
+        let mut entries = Vec::with_capacity(stts_entries.len());
+        let mut sample_id: u32 = 1;
+        let mut elapsed = 0;
+        for entry in stts_entries {
+            let next_sample_id = match sample_id.checked_add(entry.sample_count) {
+                Some(next_sample_id) => next_sample_id,
+                None => {
+                    return SttsIndex {
+                        entries,
+                        overflowed: true,
+                    }
+                }
+            };
+            entries.push((sample_id, elapsed));
+            sample_id = next_sample_id;
+            elapsed += entry.sample_count as u64 * entry.sample_delta as u64;
+        }
+        SttsIndex {
+            entries,
+            overflowed: false,
+        }
+    }
 }
 
 impl Mp4Track {
@@ -106,7 +253,10 @@ impl Mp4Track {
             trak,
             trafs: Vec::new(),
             moof_offsets: Vec::new(),
+            traf_base_offsets: Vec::new(),
             default_sample_duration: 0,
+            default_sample_flags: 0,
+            stts_index: OnceLock::new(),
         }
     }
 
@@ -121,30 +271,143 @@ impl Mp4Track {
     pub fn media_type(&self) -> Result<MediaType> {
         if self.trak.mdia.minf.stbl.stsd.avc1.is_some() {
             Ok(MediaType::H264)
-        } else if self.trak.mdia.minf.stbl.stsd.hev1.is_some() {
+        } else if self.trak.mdia.minf.stbl.stsd.hev1.is_some()
+            || self.trak.mdia.minf.stbl.stsd.dvh1.is_some()
+        {
             Ok(MediaType::H265)
         } else if self.trak.mdia.minf.stbl.stsd.vp09.is_some() {
             Ok(MediaType::VP9)
+        } else if self.trak.mdia.minf.stbl.stsd.av01.is_some() {
+            Ok(MediaType::AV1)
         } else if self.trak.mdia.minf.stbl.stsd.mp4a.is_some() {
             Ok(MediaType::AAC)
-        } else if self.trak.mdia.minf.stbl.stsd.tx3g.is_some() {
+        } else if self.trak.mdia.minf.stbl.stsd.opus.is_some() {
+            Ok(MediaType::Opus)
+        } else if self.trak.mdia.minf.stbl.stsd.ac3.is_some() {
+            Ok(MediaType::AC3)
+        } else if self.trak.mdia.minf.stbl.stsd.eac3.is_some() {
+            Ok(MediaType::EAC3)
+        } else if self.trak.mdia.minf.stbl.stsd.tx3g.is_some()
+            || self.trak.mdia.minf.stbl.stsd.text.is_some()
+        {
             Ok(MediaType::TTXT)
         } else {
             Err(Error::InvalidData("unsupported media type"))
         }
     }
 
+    /// Whether the sample entry is protected (`encv`/`enca`). For modeled
+    /// codecs, [`media_type`](Self::media_type) still reports the original
+    /// codec recorded in `sinf/frma`.
+    pub fn is_encrypted(&self) -> bool {
+        let stsd = &self.trak.mdia.minf.stbl.stsd;
+        stsd.avc1.as_ref().is_some_and(|avc1| avc1.sinf.is_some())
+            || stsd.hev1.as_ref().is_some_and(|hev1| hev1.sinf.is_some())
+            || stsd.mp4a.as_ref().is_some_and(|mp4a| mp4a.sinf.is_some())
+            || stsd
+                .raw
+                .as_ref()
+                .is_some_and(|raw| matches!(raw.get_type(), BoxType::EncvBox | BoxType::EncaBox))
+    }
+
+    #[cfg(feature = "cenc")]
+    fn sinf(&self) -> Option<&SinfBox> {
+        let stsd = &self.trak.mdia.minf.stbl.stsd;
+        if let Some(ref avc1) = stsd.avc1 {
+            avc1.sinf.as_ref()
+        } else if let Some(ref hev1) = stsd.hev1 {
+            hev1.sinf.as_ref()
+        } else if let Some(ref mp4a) = stsd.mp4a {
+            mp4a.sinf.as_ref()
+        } else {
+            None
+        }
+    }
+
+    /// Decrypts a fragmented sample of a `cenc` protected track, using the
+    /// key for the track's default KID and the IV and subsamples in `senc`.
+    /// Samples of tracks that aren't encrypted are returned as they are.
+    #[cfg(feature = "cenc")]
+    pub(crate) fn decrypt_sample(
+        &self,
+        sample_id: u32,
+        bytes: Bytes,
+        keys: &HashMap<[u8; 16], [u8; 16]>,
+    ) -> Result<Bytes> {
+        let sinf = match self.sinf() {
+            Some(sinf) => sinf,
+            None => return Ok(bytes),
+        };
+        if let Some(ref schm) = sinf.schm {
+            if schm.scheme_type != FourCC::from(*b"cenc") {
+                return Err(Error::UnsupportedProtectionScheme(schm.scheme_type));
+            }
+        }
+        let tenc = sinf
+            .tenc()?
+            .ok_or(Error::BoxInStblNotFound(self.track_id(), BoxType::TencBox))?;
+        if tenc.default_is_protected == 0 {
+            return Ok(bytes);
+        }
+        let key = keys
+            .get(&tenc.default_kid)
+            .ok_or(Error::DecryptionKeyNotFound(
+                self.track_id(),
+                tenc.default_kid,
+            ))?;
+
+        let (traf_idx, sample_idx) =
+            self.find_traf_idx_and_sample_idx(sample_id)
+                .ok_or(Error::InvalidData(
+                    "only samples in fragments can be decrypted",
+                ))?;
+        let senc = self.trafs[traf_idx]
+            .senc
+            .as_ref()
+            .ok_or(Error::BoxInTrafNotFound(self.track_id(), BoxType::SencBox))?;
+        let entries = senc.entries(tenc.default_per_sample_iv_size)?;
+        let entry = entries.get(sample_idx).ok_or(Error::EntryInTrunNotFound(
+            self.track_id(),
+            BoxType::SencBox,
+            sample_id,
+        ))?;
+        let iv = match tenc.default_constant_iv {
+            Some(ref iv) if entry.iv.is_empty() => iv,
+            _ => &entry.iv,
+        };
+
+        let mut data = bytes.to_vec();
+        decrypt_cenc_sample(key, iv, &entry.subsamples, &mut data)?;
+        Ok(Bytes::from(data))
+    }
+
     pub fn box_type(&self) -> Result<FourCC> {
-        if self.trak.mdia.minf.stbl.stsd.avc1.is_some() {
-            Ok(FourCC::from(BoxType::Avc1Box))
-        } else if self.trak.mdia.minf.stbl.stsd.hev1.is_some() {
-            Ok(FourCC::from(BoxType::Hev1Box))
+        if let Some(ref avc1) = self.trak.mdia.minf.stbl.stsd.avc1 {
+            Ok(FourCC::from(avc1.get_type()))
+        } else if let Some(ref hev1) = self.trak.mdia.minf.stbl.stsd.hev1 {
+            Ok(FourCC::from(hev1.get_type()))
+        } else if let Some(ref dvh1) = self.trak.mdia.minf.stbl.stsd.dvh1 {
+            Ok(FourCC::from(dvh1.get_type()))
         } else if self.trak.mdia.minf.stbl.stsd.vp09.is_some() {
             Ok(FourCC::from(BoxType::Vp09Box))
-        } else if self.trak.mdia.minf.stbl.stsd.mp4a.is_some() {
-            Ok(FourCC::from(BoxType::Mp4aBox))
+        } else if let Some(ref av01) = self.trak.mdia.minf.stbl.stsd.av01 {
+            Ok(FourCC::from(av01.get_type()))
+        } else if let Some(ref mp4a) = self.trak.mdia.minf.stbl.stsd.mp4a {
+            Ok(FourCC::from(mp4a.get_type()))
+        } else if self.trak.mdia.minf.stbl.stsd.opus.is_some() {
+            Ok(FourCC::from(BoxType::OpusBox))
+        } else if self.trak.mdia.minf.stbl.stsd.ac3.is_some() {
+            Ok(FourCC::from(BoxType::Ac3Box))
+        } else if self.trak.mdia.minf.stbl.stsd.eac3.is_some() {
+            Ok(FourCC::from(BoxType::Eac3Box))
         } else if self.trak.mdia.minf.stbl.stsd.tx3g.is_some() {
             Ok(FourCC::from(BoxType::Tx3gBox))
+        } else if self.trak.mdia.minf.stbl.stsd.text.is_some() {
+            Ok(FourCC::from(BoxType::TextBox))
+        } else if self.trak.mdia.minf.stbl.stsd.rtp.is_some() {
+            Ok(FourCC::from(BoxType::RtpBox))
+        } else if let Some(ref raw) = self.trak.mdia.minf.stbl.stsd.raw {
+            Ok(raw.entry_type)
         } else {
             Err(Error::InvalidData("unsupported sample entry box"))
         }
@@ -153,6 +416,8 @@ impl Mp4Track {
     pub fn width(&self) -> u16 {
         if let Some(ref avc1) = self.trak.mdia.minf.stbl.stsd.avc1 {
             avc1.width
+        } else if let Some(ref av01) = self.trak.mdia.minf.stbl.stsd.av01 {
+            av01.width
         } else {
             self.trak.tkhd.width.value()
         }
@@ -161,11 +426,129 @@ impl Mp4Track {
     pub fn height(&self) -> u16 {
         if let Some(ref avc1) = self.trak.mdia.minf.stbl.stsd.avc1 {
             avc1.height
+        } else if let Some(ref av01) = self.trak.mdia.minf.stbl.stsd.av01 {
+            av01.height
         } else {
             self.trak.tkhd.height.value()
         }
     }
 
+    /// Returns the pixel aspect ratio (`pasp`) of the visual sample entry as
+    /// `(h_spacing, v_spacing)`, if it carries one. Pixels are square without.
+    pub fn pixel_aspect_ratio(&self) -> Option<(u32, u32)> {
+        let stsd = &self.trak.mdia.minf.stbl.stsd;
+        let pasp = if let Some(ref avc1) = stsd.avc1 {
+            avc1.pasp.as_ref()
+        } else if let Some(ref hev1) = stsd.hev1 {
+            hev1.pasp.as_ref()
+        } else if let Some(ref dvh1) = stsd.dvh1 {
+            dvh1.pasp.as_ref()
+        } else if let Some(ref vp09) = stsd.vp09 {
+            vp09.pasp.as_ref()
+        } else if let Some(ref av01) = stsd.av01 {
+            av01.pasp.as_ref()
+        } else {
+            None
+        };
+        pasp.map(|pasp| (pasp.h_spacing, pasp.v_spacing))
+    }
+
+    /// Returns the width and height a renderer should display, i.e. the coded
+    /// width scaled by the sample entry's pixel aspect ratio (`pasp`).
+    pub fn display_dimensions_corrected(&self) -> (u32, u32) {
+        let stsd = &self.trak.mdia.minf.stbl.stsd;
+        let (width, height) = if let Some(ref avc1) = stsd.avc1 {
+            (avc1.width, avc1.height)
+        } else if let Some(ref hev1) = stsd.hev1 {
+            (hev1.width, hev1.height)
+        } else if let Some(ref dvh1) = stsd.dvh1 {
+            (dvh1.width, dvh1.height)
+        } else if let Some(ref vp09) = stsd.vp09 {
+            (vp09.width, vp09.height)
+        } else if let Some(ref av01) = stsd.av01 {
+            (av01.width, av01.height)
+        } else {
+            (self.width(), self.height())
+        };
+
+        match self.pixel_aspect_ratio() {
+            Some((h_spacing, v_spacing)) if h_spacing > 0 && v_spacing > 0 => {
+                let width = width as u64 * h_spacing as u64 / v_spacing as u64;
+                (width as u32, height as u32)
+            }
+            _ => (width as u32, height as u32),
+        }
+    }
+
+    /// The clockwise rotation in degrees (0, 90, 180 or 270) a renderer
+    /// should apply, from the `tkhd` matrix; see [`TkhdBox::rotation`].
+    pub fn rotation(&self) -> u16 {
+        self.trak.tkhd.rotation()
+    }
+
+    /// Like [`display_dimensions_corrected`](Self::display_dimensions_corrected),
+    /// but with width and height swapped when the track is rotated by 90 or
+    /// 270 degrees, i.e. the size of the picture as shown.
+    pub fn display_dimensions_rotated(&self) -> (u32, u32) {
+        let (width, height) = self.display_dimensions_corrected();
+        match self.rotation() {
+            90 | 270 => (height, width),
+            _ => (width, height),
+        }
+    }
+
+    /// The `dref` entry (1-based) that holds a sample's data, from the
+    /// `data_reference_index` of its sample entry. The track's samples all
+    /// share its one sample entry, so this is the same for every sample.
+    pub fn data_reference_index(&self, sample_id: u32) -> Result<u16> {
+        if sample_id == 0 || sample_id > self.sample_count() {
+            return Err(if self.trafs.is_empty() {
+                Error::EntryInStblNotFound(self.track_id(), BoxType::StszBox, sample_id)
+            } else {
+                Error::EntryInTrunNotFound(self.track_id(), BoxType::TrunBox, sample_id)
+            });
+        }
+        self.trak
+            .mdia
+            .minf
+            .stbl
+            .stsd
+            .data_reference_index()
+            .ok_or(Error::BoxInStblNotFound(self.track_id(), BoxType::StsdBox))
+    }
+
+    /// The location of the file holding this track's sample data, if its
+    /// `dref` entry points outside this file.
+    pub fn data_location(&self) -> Option<&str> {
+        let url = self.trak.mdia.minf.dinf.dref.url.as_ref()?;
+        if url.flags & 1 != 0 || url.location.is_empty() {
+            None
+        } else {
+            Some(&url.location)
+        }
+    }
+
+    /// The `compressorname` of a visual sample entry, if it isn't empty.
+    pub fn compressor_name(&self) -> Option<&str> {
+        let stsd = &self.trak.mdia.minf.stbl.stsd;
+        let name = if let Some(ref avc1) = stsd.avc1 {
+            &avc1.compressor_name
+        } else if let Some(ref hev1) = stsd.hev1 {
+            &hev1.compressor_name
+        } else if let Some(ref dvh1) = stsd.dvh1 {
+            &dvh1.compressor_name
+        } else if let Some(ref av01) = stsd.av01 {
+            &av01.compressor_name
+        } else {
+            return None;
+        };
+        if name.is_empty() {
+            None
+        } else {
+            Some(name)
+        }
+    }
+
     pub fn frame_rate(&self) -> f64 {
         let dur = self.duration();
         if dur.is_zero() {
@@ -187,7 +570,29 @@ impl Mp4Track {
         }
     }
 
+    /// Returns the audio sampling rate in Hz, resolved from the `esds`
+    /// frequency index (or its explicit rate), falling back to the `mp4a`
+    /// sample entry. Returns `None` for non-`mp4a` tracks.
+    pub fn sample_rate(&self) -> Option<u32> {
+        let mp4a = self.trak.mdia.minf.stbl.stsd.mp4a.as_ref()?;
+        if let Some(ref esds) = mp4a.esds {
+            let dec_specific = &esds.es_desc.dec_config.dec_specific;
+            if let Some(sample_rate) = dec_specific.sample_rate {
+                return Some(sample_rate);
+            }
+            if let Ok(freq_index) = SampleFreqIndex::try_from(dec_specific.freq_index) {
+                return Some(freq_index.freq());
+            }
+        }
+        Some(mp4a.samplerate.value() as u32)
+    }
+
+    /// The channel layout of an AAC track, from `esds`, or of an AC-3 track,
+    /// from the channel count given by `acmod` and `lfeon` in `dac3`.
     pub fn channel_config(&self) -> Result<ChannelConfig> {
+        if let Some(ref ac3) = self.trak.mdia.minf.stbl.stsd.ac3 {
+            return ChannelConfig::try_from(ac3.dac3.channel_count());
+        }
         if let Some(ref mp4a) = self.trak.mdia.minf.stbl.stsd.mp4a {
             if let Some(ref esds) = mp4a.esds {
                 ChannelConfig::try_from(esds.es_desc.dec_config.dec_specific.chan_conf)
@@ -203,32 +608,80 @@ impl Mp4Track {
         &self.trak.mdia.mdhd.language
     }
 
+    /// Returns the BCP 47 tag from `elng`, if the track has one.
+    pub fn extended_language(&self) -> Option<&str> {
+        self.trak
+            .mdia
+            .elng
+            .as_ref()
+            .map(|elng| elng.extended_language.as_str())
+    }
+
+    /// Whether `language` names this track's language: either its `mdhd`
+    /// code or its `elng` tag, where `en` also matches `en-US`. Case is
+    /// ignored.
+    pub(crate) fn matches_language(&self, language: &str) -> bool {
+        if self.language().eq_ignore_ascii_case(language) {
+            return true;
+        }
+        match self.extended_language() {
+            Some(tag) => {
+                tag.eq_ignore_ascii_case(language)
+                    || (tag.len() > language.len()
+                        && tag.as_bytes()[language.len()] == b'-'
+                        && tag[..language.len()].eq_ignore_ascii_case(language))
+            }
+            None => false,
+        }
+    }
+
     pub fn timescale(&self) -> u32 {
         self.trak.mdia.mdhd.timescale
     }
 
     pub fn duration(&self) -> Duration {
-        Duration::from_micros(
-            self.trak.mdia.mdhd.duration * 1_000_000 / self.trak.mdia.mdhd.timescale as u64,
-        )
+        Duration::from_micros(rescale(
+            self.trak.mdia.mdhd.duration,
+            self.trak.mdia.mdhd.timescale as u64,
+            1_000_000,
+        ))
+    }
+
+    /// The `mdhd` duration in [`timescale`](Self::timescale) ticks, without
+    /// the rounding of [`duration`](Self::duration).
+    pub fn media_duration_ticks(&self) -> u64 {
+        self.trak.mdia.mdhd.duration
     }
 
+    /// Average bitrate in bits per second. Uses the value signalled in
+    /// `esds` or `btrt` when it is nonzero, and otherwise computes it from
+    /// the total sample size and the media duration.
     pub fn bitrate(&self) -> u32 {
-        if let Some(ref mp4a) = self.trak.mdia.minf.stbl.stsd.mp4a {
-            if let Some(ref esds) = mp4a.esds {
-                esds.es_desc.dec_config.avg_bitrate
-            } else {
-                0
-            }
-            // mp4a.esds.es_desc.dec_config.avg_bitrate
+        let stsd = &self.trak.mdia.minf.stbl.stsd;
+        let signalled = if let Some(ref mp4a) = stsd.mp4a {
+            mp4a.esds
+                .as_ref()
+                .map(|esds| esds.es_desc.dec_config.avg_bitrate)
         } else {
-            let dur = self.duration();
-            if dur.is_zero() {
-                0
-            } else {
-                let bitrate = self.total_sample_size() as f64 * 8.0 / dur.as_secs_f64();
-                bitrate as u32
-            }
+            self.btrt().map(|btrt| btrt.avg_bitrate)
+        };
+        match signalled {
+            Some(bitrate) if bitrate > 0 => bitrate,
+            _ => self.computed_bitrate(),
+        }
+    }
+
+    /// Peak bitrate in bits per second as signalled in `esds` or `btrt`.
+    pub fn max_bitrate(&self) -> Option<u32> {
+        let max_bitrate = if let Some(ref mp4a) = self.trak.mdia.minf.stbl.stsd.mp4a {
+            mp4a.esds.as_ref()?.es_desc.dec_config.max_bitrate
+        } else {
+            self.btrt()?.max_bitrate
+        };
+        if max_bitrate > 0 {
+            Some(max_bitrate)
+        } else {
+            None
         }
     }
 
@@ -248,6 +701,217 @@ impl Mp4Track {
         }
     }
 
+    /// Returns the duration of every sample, in the track's timescale, by
+    /// expanding the `stts` runs (or the `trun` durations of fragments).
+    pub fn sample_durations(&self) -> Vec<u32> {
+        if !self.trafs.is_empty() {
+            return (1..=self.sample_count())
+                .map_while(|sample_id| self.sample_time(sample_id).ok())
+                .map(|(_, duration)| duration)
+                .collect();
+        }
+
+        let mut durations = Vec::with_capacity(self.sample_count() as usize);
+        for entry in self.trak.mdia.minf.stbl.stts.entries.iter() {
+            durations.extend(std::iter::repeat_n(
+                entry.sample_delta,
+                entry.sample_count as usize,
+            ));
+        }
+        durations
+    }
+
+    /// Returns the sample IDs sorted by presentation time (decode time plus
+    /// the `ctts`/`trun` composition offset), for muxing into containers
+    /// that store samples in presentation order. Samples with equal
+    /// presentation times keep their decode order.
+    pub fn samples_in_presentation_order(&self) -> Result<Vec<u32>> {
+        let mut samples = Vec::with_capacity(self.sample_count() as usize);
+        for sample_id in 1..=self.sample_count() {
+            let (start_time, _) = self.sample_time(sample_id)?;
+            let rendering_offset = self.composition_offset(sample_id);
+            samples.push((start_time as i64 + rendering_offset as i64, sample_id));
+        }
+        samples.sort_by_key(|&(presentation_time, _)| presentation_time);
+        Ok(samples
+            .into_iter()
+            .map(|(_, sample_id)| sample_id)
+            .collect())
+    }
+
+    /// Returns the IDs, in decode order, of the samples needed to present
+    /// `[start, end)`: every sample whose presentation time falls in the
+    /// window, preceded by the samples back to the last sync sample before
+    /// them so that the first one can be decoded.
+    ///
+    /// Samples after the window in presentation order that are decoded
+    /// before the window's last sample are included as well, as the range is
+    /// contiguous in decode order.
+    pub fn sample_range_for_time(&self, start: Duration, end: Duration) -> Result<Vec<u32>> {
+        let timescale = self.timescale() as u128;
+        let to_ticks = |time: Duration| {
+            let nanos = time.as_nanos() * timescale;
+            nanos.div_ceil(1_000_000_000).min(i64::MAX as u128) as i64
+        };
+        let (start, end) = (to_ticks(start), to_ticks(end));
+
+        let mut first = None;
+        let mut last = None;
+        for sample_id in 1..=self.sample_count() {
+            let (start_time, _) = self.sample_time(sample_id)?;
+            let presentation_time = start_time as i64 + self.composition_offset(sample_id) as i64;
+            if presentation_time >= start && presentation_time < end {
+                first.get_or_insert(sample_id);
+                last = Some(sample_id);
+            }
+        }
+
+        let (first, last) = match (first, last) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return Ok(Vec::new()),
+        };
+        let sync = (1..=first)
+            .rev()
+            .find(|&sample_id| self.is_sync_sample(sample_id))
+            .unwrap_or(1);
+        Ok((sync..=last).collect())
+    }
+
+    /// Returns the ID of the sample whose decode interval covers `time`, in
+    /// media time from the start of the track, or `None` past the last
+    /// sample. Time zero is sample 1 of a non-empty track.
+    pub fn sample_id_for_time(&self, time: Duration) -> Option<u32> {
+        let ticks = self.duration_to_ticks(time);
+        if !self.trafs.is_empty() {
+            // Decode times only grow, so the covering sample is the last one
+            // starting at or before `ticks`.
+            let sample_count = self.sample_count();
+            let (mut low, mut high) = (1, sample_count + 1);
+            while low < high {
+                let mid = low + (high - low) / 2;
+                match self.sample_time(mid) {
+                    Ok((start_time, _)) if start_time <= ticks => low = mid + 1,
+                    _ => high = mid,
+                }
+            }
+            let sample_id = low.checked_sub(1).filter(|&id| id > 0)?;
+            let (start_time, duration) = self.sample_time(sample_id).ok()?;
+            return (ticks < start_time + duration as u64).then_some(sample_id);
+        }
+
+        let mut sample_id = 1u32;
+        let mut elapsed = 0u64;
+        for entry in self.trak.mdia.minf.stbl.stts.entries.iter() {
+            let span = entry.sample_count as u64 * entry.sample_delta as u64;
+            if ticks < elapsed + span {
+                let index = (ticks - elapsed) / entry.sample_delta as u64;
+                return Some(sample_id + index as u32);
+            }
+            elapsed += span;
+            sample_id = sample_id.checked_add(entry.sample_count)?;
+        }
+        None
+    }
+
+    /// Like [`sample_id_for_time`](Self::sample_id_for_time), but matches
+    /// `time` against presentation intervals, i.e. decode times shifted by
+    /// the `ctts`/`trun` composition offsets. Returns `None` if no sample is
+    /// presented at `time`.
+    pub fn sample_id_for_presentation_time(&self, time: Duration) -> Option<u32> {
+        let ticks = self.duration_to_ticks(time) as i64;
+        (1..=self.sample_count()).find(|&sample_id| {
+            let (start_time, duration) = match self.sample_time(sample_id) {
+                Ok(time) => time,
+                Err(_) => return false,
+            };
+            let presentation_time = start_time as i64 + self.composition_offset(sample_id) as i64;
+            presentation_time <= ticks && ticks < presentation_time + duration as i64
+        })
+    }
+
+    /// Like [`sample_id_for_time`](Self::sample_id_for_time), but snaps back
+    /// to the closest sync sample at or before the covering sample so that
+    /// decoding can start there. Falls back to sample 1 without one.
+    pub fn sync_sample_id_for_time(&self, time: Duration) -> Option<u32> {
+        let sample_id = self.sample_id_for_time(time)?;
+        Some(
+            (1..=sample_id)
+                .rev()
+                .find(|&sample_id| self.is_sync_sample(sample_id))
+                .unwrap_or(1),
+        )
+    }
+
+    fn duration_to_ticks(&self, time: Duration) -> u64 {
+        let ticks = time.as_nanos() * self.timescale() as u128 / 1_000_000_000;
+        ticks.min(u64::MAX as u128) as u64
+    }
+
+    /// Returns the IDs of the sync samples, from `stss`, or every sample
+    /// when it is absent. For fragmented tracks, the `trun`/`tfhd` sample
+    /// flags are used instead.
+    pub fn sync_samples(&self) -> Vec<u32> {
+        match self.trak.mdia.minf.stbl.stss {
+            Some(ref stss) if self.trafs.is_empty() => stss.entries.clone(),
+            _ => (1..=self.sample_count())
+                .filter(|&sample_id| self.is_sync_sample(sample_id))
+                .collect(),
+        }
+    }
+
+    /// Average number of samples per group of pictures, i.e. the sample
+    /// count over the sync sample count. Returns 0 without sync samples.
+    pub fn average_gop_size(&self) -> f64 {
+        let sync_count = self.sync_samples().len();
+        if sync_count == 0 {
+            return 0.0;
+        }
+        self.sample_count() as f64 / sync_count as f64
+    }
+
+    /// Returns the average and maximum distance, in samples, between
+    /// consecutive sync samples, or `(0.0, 0)` with fewer than two of them.
+    pub fn keyframe_interval(&self) -> (f64, u32) {
+        let sync_samples = self.sync_samples();
+        if sync_samples.len() < 2 {
+            return (0.0, 0);
+        }
+        let max = sync_samples
+            .windows(2)
+            .map(|pair| pair[1] - pair[0])
+            .max()
+            .unwrap_or(0);
+        let span = sync_samples[sync_samples.len() - 1] - sync_samples[0];
+        (span as f64 / (sync_samples.len() - 1) as f64, max)
+    }
+
+    /// Like [`keyframe_interval`](Self::keyframe_interval), but measures the
+    /// average and maximum distance in decode time.
+    pub fn keyframe_interval_duration(&self) -> Result<(Duration, Duration)> {
+        let timescale = self.timescale() as u64;
+        if timescale == 0 {
+            return Err(Error::InvalidData("track timescale is zero"));
+        }
+        let sync_samples = self.sync_samples();
+        if sync_samples.len() < 2 {
+            return Ok((Duration::ZERO, Duration::ZERO));
+        }
+        let mut times = Vec::with_capacity(sync_samples.len());
+        for &sample_id in sync_samples.iter() {
+            times.push(self.sample_time(sample_id)?.0);
+        }
+        let max = times
+            .windows(2)
+            .map(|pair| pair[1].saturating_sub(pair[0]))
+            .max()
+            .unwrap_or(0);
+        let span = times[times.len() - 1].saturating_sub(times[0]);
+        let average = span / (times.len() - 1) as u64;
+
+        let to_duration = |ticks: u64| Duration::from_micros(rescale(ticks, timescale, 1_000_000));
+        Ok((to_duration(average), to_duration(max)))
+    }
+
     pub fn video_profile(&self) -> Result<AvcProfile> {
         if let Some(ref avc1) = self.trak.mdia.minf.stbl.stsd.avc1 {
             AvcProfile::try_from((
@@ -259,9 +923,238 @@ impl Mp4Track {
         }
     }
 
+    /// Returns the offset, in media timescale units, to add to a sample's
+    /// composition time to get its presentation time under the edit list.
+    ///
+    /// Leading empty edits (`media_time` of -1) delay presentation by their
+    /// duration, and the first media edit's `media_time` is subtracted.
+    /// Further edits are not taken into account.
+    pub fn presentation_offset(&self, movie_timescale: u32) -> i64 {
+        let elst = match self.trak.edts.as_ref().and_then(|edts| edts.elst.as_ref()) {
+            Some(elst) => elst,
+            None => return 0,
+        };
+        let empty_media_time = if elst.version == 1 {
+            u64::MAX
+        } else {
+            u32::MAX as u64
+        };
+
+        let mut offset = 0i64;
+        for entry in elst.entries.iter() {
+            if entry.media_time == empty_media_time {
+                if movie_timescale != 0 {
+                    offset += (entry.segment_duration as u128 * self.timescale() as u128
+                        / movie_timescale as u128) as i64;
+                }
+            } else {
+                offset -= entry.media_time as i64;
+                break;
+            }
+        }
+        offset
+    }
+
+    /// Returns the earliest presentation time of any sample, in media
+    /// timescale units: its decode time plus composition offset, shifted by
+    /// [`presentation_offset`](Self::presentation_offset). As with
+    /// [`Mp4Reader::read_edit_corrected_samples`], samples presented before
+    /// the edit starts have their time clamped to zero.
+    pub fn first_sample_time(&self, movie_timescale: u32) -> Result<u64> {
+        let (first, _) = self.presentation_time_range()?;
+        Ok((first + self.presentation_offset(movie_timescale)).max(0) as u64)
+    }
+
+    /// Like [`first_sample_time`](Self::first_sample_time), but returns the
+    /// latest presentation time of any sample, i.e. when the last sample in
+    /// presentation order starts.
+    pub fn last_sample_time(&self, movie_timescale: u32) -> Result<u64> {
+        let (_, last) = self.presentation_time_range()?;
+        Ok((last + self.presentation_offset(movie_timescale)).max(0) as u64)
+    }
+
+    /// Returns the earliest and latest composition times of the samples.
+    /// Without composition offsets these are the first and last samples'
+    /// decode times, so only `ctts` or `trun` offsets require a full scan.
+    fn presentation_time_range(&self) -> Result<(i64, i64)> {
+        let sample_count = self.sample_count();
+        if sample_count == 0 {
+            return Err(Error::EntryInStblNotFound(
+                self.track_id(),
+                BoxType::StszBox,
+                1,
+            ));
+        }
+        let presentation_time = |sample_id| -> Result<i64> {
+            let (start_time, _) = self.sample_time(sample_id)?;
+            Ok(start_time as i64 + self.composition_offset(sample_id) as i64)
+        };
+
+        if self.trafs.is_empty() && self.trak.mdia.minf.stbl.ctts.is_none() {
+            return Ok((presentation_time(1)?, presentation_time(sample_count)?));
+        }
+        let mut first = i64::MAX;
+        let mut last = i64::MIN;
+        for sample_id in 1..=sample_count {
+            let time = presentation_time(sample_id)?;
+            first = first.min(time);
+            last = last.max(time);
+        }
+        Ok((first, last))
+    }
+
+    /// Reports whether `sample_id` can be dropped without breaking decode of
+    /// the retained samples.
+    ///
+    /// `sdtp` is authoritative when it says whether the sample is referenced.
+    /// Otherwise sync samples are kept, a sample presented before an earlier
+    /// decoded sample of its GOP is treated as a non-reference B-frame (via
+    /// `ctts`), and without reordering only the last sample before the next
+    /// sync sample is droppable.
+    pub fn dependency_info(&self, sample_id: u32) -> Result<SampleDependency> {
+        let sample_count = self.sample_count();
+        if sample_id == 0 || sample_id > sample_count {
+            return Err(Error::EntryInStblNotFound(
+                self.track_id(),
+                BoxType::StszBox,
+                sample_id,
+            ));
+        }
+
+        let is_sync = self.is_sync_sample(sample_id);
+        let sdtp_entry = self
+            .trak
+            .mdia
+            .minf
+            .stbl
+            .sdtp
+            .as_ref()
+            .and_then(|sdtp| sdtp.entries.get(sample_id as usize - 1));
+        let flag = |value: u8| match value {
+            1 => Some(true),
+            2 => Some(false),
+            _ => None,
+        };
+        let depends_on_others = sdtp_entry.and_then(|e| flag(e.sample_depends_on));
+        let is_depended_on = sdtp_entry.and_then(|e| flag(e.sample_is_depended_on));
+
+        let droppable = if let Some(is_depended_on) = is_depended_on {
+            !is_depended_on
+        } else if is_sync {
+            false
+        } else if self.trak.mdia.minf.stbl.ctts.is_some() && self.is_reordered(sample_id)? {
+            true
+        } else {
+            sample_id == sample_count || self.is_sync_sample(sample_id + 1)
+        };
+
+        Ok(SampleDependency {
+            is_sync,
+            depends_on_others,
+            is_depended_on,
+            droppable,
+        })
+    }
+
+    /// Whether `sample_id` is presented before a sample decoded earlier in
+    /// the same GOP.
+    fn is_reordered(&self, sample_id: u32) -> Result<bool> {
+        let composition_time = |id: u32| -> Result<i64> {
+            let (start_time, _) = self.sample_time(id)?;
+            Ok(start_time as i64 + self.composition_offset(id) as i64)
+        };
+
+        let time = composition_time(sample_id)?;
+        let mut id = sample_id;
+        while id > 1 {
+            id -= 1;
+            if composition_time(id)? > time {
+                return Ok(true);
+            }
+            if self.is_sync_sample(id) {
+                break;
+            }
+        }
+        Ok(false)
+    }
+
+    /// Returns the Dolby Vision configuration (`dvcC`/`dvvC`) of the sample
+    /// entry, if the track carries one.
+    pub fn dolby_vision_info(&self) -> Option<&DvccBox> {
+        let stsd = &self.trak.mdia.minf.stbl.stsd;
+        if let Some(ref dvh1) = stsd.dvh1 {
+            Some(&dvh1.dvcc)
+        } else if let Some(ref hev1) = stsd.hev1 {
+            hev1.dvcc.as_ref()
+        } else if let Some(ref avc1) = stsd.avc1 {
+            avc1.dvcc.as_ref()
+        } else if let Some(ref av01) = stsd.av01 {
+            av01.dvcc.as_ref()
+        } else {
+            None
+        }
+    }
+
+    /// Returns the colour description (`colr`) of the sample entry, if it
+    /// carries one. Only `nclx` and `nclc` descriptions have code points for
+    /// the primaries, transfer characteristics and matrix coefficients; an
+    /// ICC profile is kept in [`ColrBox::icc_profile`].
+    pub fn colour_info(&self) -> Option<&ColrBox> {
+        let stsd = &self.trak.mdia.minf.stbl.stsd;
+        if let Some(ref avc1) = stsd.avc1 {
+            avc1.colr.as_ref()
+        } else if let Some(ref hev1) = stsd.hev1 {
+            hev1.colr.as_ref()
+        } else if let Some(ref dvh1) = stsd.dvh1 {
+            dvh1.colr.as_ref()
+        } else if let Some(ref vp09) = stsd.vp09 {
+            vp09.colr.as_ref()
+        } else if let Some(ref av01) = stsd.av01 {
+            av01.colr.as_ref()
+        } else {
+            None
+        }
+    }
+
+    /// Returns the colour description (`colr`) and HDR static metadata
+    /// (`mdcv`, `clli`) of the sample entry, if it carries any of them.
+    pub fn hdr_metadata(&self) -> Option<HdrConfig> {
+        let stsd = &self.trak.mdia.minf.stbl.stsd;
+        let (colr, mdcv, clli) = if let Some(ref hev1) = stsd.hev1 {
+            (&hev1.colr, &hev1.mdcv, &hev1.clli)
+        } else if let Some(ref dvh1) = stsd.dvh1 {
+            (&dvh1.colr, &dvh1.mdcv, &dvh1.clli)
+        } else if let Some(ref avc1) = stsd.avc1 {
+            (&avc1.colr, &avc1.mdcv, &avc1.clli)
+        } else {
+            return None;
+        };
+        if colr.is_none() && mdcv.is_none() && clli.is_none() {
+            return None;
+        }
+        Some(HdrConfig {
+            colr: colr.clone(),
+            mdcv: mdcv.clone(),
+            clli: clli.clone(),
+        })
+    }
+
+    /// Returns the complete bytes (header included) of a sample entry this
+    /// crate doesn't model, so it can be passed through when remuxing.
+    pub fn sample_entry_raw_bytes(&self) -> Option<&[u8]> {
+        self.trak
+            .mdia
+            .minf
+            .stbl
+            .stsd
+            .raw
+            .as_ref()
+            .map(|raw| raw.bytes.as_slice())
+    }
+
     pub fn sequence_parameter_set(&self) -> Result<&[u8]> {
         if let Some(ref avc1) = self.trak.mdia.minf.stbl.stsd.avc1 {
-            match avc1.avcc.sequence_parameter_sets.get(0) {
+            match avc1.avcc.sequence_parameter_sets.first() {
                 Some(nal) => Ok(nal.bytes.as_ref()),
                 None => Err(Error::EntryInStblNotFound(
                     self.track_id(),
@@ -276,7 +1169,7 @@ impl Mp4Track {
 
     pub fn picture_parameter_set(&self) -> Result<&[u8]> {
         if let Some(ref avc1) = self.trak.mdia.minf.stbl.stsd.avc1 {
-            match avc1.avcc.picture_parameter_sets.get(0) {
+            match avc1.avcc.picture_parameter_sets.first() {
                 Some(nal) => Ok(nal.bytes.as_ref()),
                 None => Err(Error::EntryInStblNotFound(
                     self.track_id(),
@@ -289,6 +1182,215 @@ impl Mp4Track {
         }
     }
 
+    /// All SPS NAL units of the `avcC`, in order.
+    pub fn sequence_parameter_sets(&self) -> Result<Vec<&[u8]>> {
+        if let Some(ref avc1) = self.trak.mdia.minf.stbl.stsd.avc1 {
+            Ok(avc1
+                .avcc
+                .sequence_parameter_sets
+                .iter()
+                .map(|nal| nal.bytes.as_ref())
+                .collect())
+        } else {
+            Err(Error::BoxInStblNotFound(self.track_id(), BoxType::Avc1Box))
+        }
+    }
+
+    /// All PPS NAL units of the `avcC`, in order.
+    pub fn picture_parameter_sets(&self) -> Result<Vec<&[u8]>> {
+        if let Some(ref avc1) = self.trak.mdia.minf.stbl.stsd.avc1 {
+            Ok(avc1
+                .avcc
+                .picture_parameter_sets
+                .iter()
+                .map(|nal| nal.bytes.as_ref())
+                .collect())
+        } else {
+            Err(Error::BoxInStblNotFound(self.track_id(), BoxType::Avc1Box))
+        }
+    }
+
+    /// The VPS NAL units (type 32) of the `hvcC`, or none if it has no VPS
+    /// array.
+    pub fn hevc_vps(&self) -> Result<Vec<&[u8]>> {
+        self.hevc_parameter_sets(HEVC_NAL_UNIT_TYPE_VPS)
+    }
+
+    /// The SPS NAL units (type 33) of the `hvcC`, or none if it has no SPS
+    /// array.
+    pub fn hevc_sps(&self) -> Result<Vec<&[u8]>> {
+        self.hevc_parameter_sets(HEVC_NAL_UNIT_TYPE_SPS)
+    }
+
+    /// The PPS NAL units (type 34) of the `hvcC`, or none if it has no PPS
+    /// array.
+    pub fn hevc_pps(&self) -> Result<Vec<&[u8]>> {
+        self.hevc_parameter_sets(HEVC_NAL_UNIT_TYPE_PPS)
+    }
+
+    /// The HEVC `general_profile_idc` of the `hvcC`, e.g. 1 for Main or 2 for
+    /// Main 10.
+    pub fn hevc_profile(&self) -> Result<u8> {
+        Ok(self.hvcc()?.general_profile_idc)
+    }
+
+    fn hvcc(&self) -> Result<&HvcCBox> {
+        let stsd = &self.trak.mdia.minf.stbl.stsd;
+        if let Some(ref hev1) = stsd.hev1 {
+            Ok(&hev1.hvcc)
+        } else if let Some(ref dvh1) = stsd.dvh1 {
+            Ok(&dvh1.hvcc)
+        } else {
+            Err(Error::BoxInStblNotFound(self.track_id(), BoxType::Hev1Box))
+        }
+    }
+
+    fn hevc_parameter_sets(&self, nal_unit_type: u8) -> Result<Vec<&[u8]>> {
+        Ok(self
+            .hvcc()?
+            .arrays
+            .iter()
+            .filter(|array| array.nal_unit_type == nal_unit_type)
+            .flat_map(|array| array.nalus.iter())
+            .map(|nalu| nalu.data.as_ref())
+            .collect())
+    }
+
+    /// Converts an H.264 or H.265 sample from length-prefixed NAL units, as
+    /// sized by the `avcC`/`hvcC` `length_size_minus_one`, to an Annex B byte
+    /// stream with `00 00 00 01` start codes. Sync samples are preceded by
+    /// the parameter sets from the sample entry.
+    pub fn sample_to_annexb(&self, sample: &Mp4Sample) -> Result<Bytes> {
+        let framing = Framing::new(self)?;
+        if !matches!(framing, Framing::AnnexB { .. }) {
+            return Err(Error::InvalidData(
+                "Annex B is only defined for H.264 and H.265",
+            ));
+        }
+        let mut bytes = Vec::new();
+        framing.write_sample(&mut bytes, sample)?;
+        Ok(Bytes::from(bytes))
+    }
+
+    /// The RFC 6381 codec string for manifests and MIME types, e.g.
+    /// `avc1.640028`, `hev1.2.4.L123.B0`, `av01.0.08M.08` or `mp4a.40.2`.
+    /// Supported for AVC, HEVC, VP9, AV1, MPEG-4 audio, Opus, AC-3 and
+    /// E-AC-3. Protected entries use their original format.
+    pub fn codec_string(&self) -> Result<String> {
+        let stsd = &self.trak.mdia.minf.stbl.stsd;
+        if let Some(ref avc1) = stsd.avc1 {
+            let sample_entry = if avc1.in_band_parameter_sets {
+                "avc3"
+            } else {
+                "avc1"
+            };
+            Ok(format!(
+                "{}.{:02x}{:02x}{:02x}",
+                sample_entry,
+                avc1.avcc.avc_profile_indication,
+                avc1.avcc.profile_compatibility,
+                avc1.avcc.avc_level_indication
+            ))
+        } else if let Some(ref hev1) = stsd.hev1 {
+            let hvcc = &hev1.hvcc;
+            let profile_space = match hvcc.general_profile_space {
+                1 => "A",
+                2 => "B",
+                3 => "C",
+                _ => "",
+            };
+            let tier = if hvcc.general_tier_flag { 'H' } else { 'L' };
+            let mut codec = format!(
+                "hev1.{}{}.{:X}.{}{}",
+                profile_space,
+                hvcc.general_profile_idc,
+                hvcc.general_profile_compatibility_flags.reverse_bits(),
+                tier,
+                hvcc.general_level_idc
+            );
+            // The six constraint bytes, with trailing zero bytes omitted.
+            let constraints = hvcc.general_constraint_indicator_flag.to_be_bytes();
+            let constraints = &constraints[2..];
+            let len = constraints
+                .iter()
+                .rposition(|&byte| byte != 0)
+                .map_or(0, |i| i + 1);
+            for byte in &constraints[..len] {
+                codec.push_str(&format!(".{:02X}", byte));
+            }
+            Ok(codec)
+        } else if let Some(ref mp4a) = stsd.mp4a {
+            let esds = mp4a
+                .esds
+                .as_ref()
+                .ok_or(Error::BoxInStblNotFound(self.track_id(), BoxType::EsdsBox))?;
+            let dec_config = &esds.es_desc.dec_config;
+            if dec_config.object_type_indication == 0x40 {
+                Ok(format!("mp4a.40.{}", dec_config.dec_specific.profile))
+            } else {
+                Ok(format!("mp4a.{:02x}", dec_config.object_type_indication))
+            }
+        } else if let Some(ref vp09) = stsd.vp09 {
+            let vpcc = &vp09.vpcc;
+            Ok(format!(
+                "vp09.{:02}.{:02}.{:02}",
+                vpcc.profile, vpcc.level, vpcc.bit_depth
+            ))
+        } else if let Some(ref av01) = stsd.av01 {
+            let av1c = &av01.av1c;
+            let bit_depth = match (av1c.high_bitdepth, av1c.twelve_bit) {
+                (false, _) => 8,
+                (true, false) => 10,
+                (true, true) => 12,
+            };
+            Ok(format!(
+                "av01.{}.{:02}{}.{:02}",
+                av1c.seq_profile,
+                av1c.seq_level_idx_0,
+                if av1c.seq_tier_0 { 'H' } else { 'M' },
+                bit_depth
+            ))
+        } else if stsd.opus.is_some() {
+            Ok("Opus".to_string())
+        } else if stsd.ac3.is_some() {
+            Ok("ac-3".to_string())
+        } else if stsd.eac3.is_some() {
+            Ok("ec-3".to_string())
+        } else {
+            Err(Error::InvalidData(
+                "codec strings are only supported for AVC, HEVC, VP9, AV1, MPEG-4 audio, Opus, AC-3 and E-AC-3",
+            ))
+        }
+    }
+
+    /// The decoder configuration that platform decoders take as codec
+    /// private data: the `avcC`, `hvcC`, `vpcC` or `av1C` box body, or the AAC
+    /// `AudioSpecificConfig` from `esds`.
+    pub fn codec_private_data(&self) -> Result<Vec<u8>> {
+        let stsd = &self.trak.mdia.minf.stbl.stsd;
+        let mut buffer = Vec::new();
+        if let Some(ref avc1) = stsd.avc1 {
+            avc1.avcc.write_box(&mut buffer)?;
+        } else if let Some(ref hev1) = stsd.hev1 {
+            hev1.hvcc.write_box(&mut buffer)?;
+        } else if let Some(ref dvh1) = stsd.dvh1 {
+            dvh1.hvcc.write_box(&mut buffer)?;
+        } else if let Some(ref vp09) = stsd.vp09 {
+            vp09.vpcc.write_box(&mut buffer)?;
+        } else if let Some(ref av01) = stsd.av01 {
+            av01.av1c.write_box(&mut buffer)?;
+        } else if let Some(ref mp4a) = stsd.mp4a {
+            return match mp4a.esds {
+                Some(ref esds) => Ok(esds.es_desc.dec_config.dec_specific.audio_specific_config()),
+                None => Err(Error::BoxInStblNotFound(self.track_id(), BoxType::EsdsBox)),
+            };
+        } else {
+            return Err(Error::InvalidData("track has no codec private data"));
+        }
+        buffer.drain(..HEADER_SIZE as usize);
+        Ok(buffer)
+    }
+
     pub fn audio_profile(&self) -> Result<AudioObjectType> {
         if let Some(ref mp4a) = self.trak.mdia.minf.stbl.stsd.mp4a {
             if let Some(ref esds) = mp4a.esds {
@@ -301,6 +1403,52 @@ impl Mp4Track {
         }
     }
 
+    /// Returns the decoder setup from the `dOps` box of an Opus track.
+    pub fn opus_config(&self) -> Result<OpusConfig> {
+        let opus = self
+            .trak
+            .mdia
+            .minf
+            .stbl
+            .stsd
+            .opus
+            .as_ref()
+            .ok_or(Error::BoxInStblNotFound(self.track_id(), BoxType::OpusBox))?;
+        let dops = &opus.dops;
+        Ok(OpusConfig {
+            output_channel_count: dops.output_channel_count,
+            pre_skip: dops.pre_skip,
+            input_sample_rate: dops.input_sample_rate,
+            output_gain: dops.output_gain,
+            channel_mapping_family: dops.channel_mapping_family,
+            stream_count: dops.stream_count,
+            coupled_count: dops.coupled_count,
+            channel_mapping: dops.channel_mapping.clone(),
+        })
+    }
+
+    /// Returns the stream parameters from the `dac3` box of an AC-3 track.
+    pub fn ac3_config(&self) -> Result<Ac3Config> {
+        let ac3 = self
+            .trak
+            .mdia
+            .minf
+            .stbl
+            .stsd
+            .ac3
+            .as_ref()
+            .ok_or(Error::BoxInStblNotFound(self.track_id(), BoxType::Ac3Box))?;
+        let dac3 = &ac3.dac3;
+        Ok(Ac3Config {
+            fscod: dac3.fscod,
+            bsid: dac3.bsid,
+            bsmod: dac3.bsmod,
+            acmod: dac3.acmod,
+            lfeon: dac3.lfeon,
+            bit_rate_code: dac3.bit_rate_code,
+        })
+    }
+
     fn stsc_index(&self, sample_id: u32) -> Result<usize> {
         if self.trak.mdia.minf.stbl.stsc.entries.is_empty() {
             return Err(Error::InvalidData("no stsc entries"));
@@ -317,6 +1465,17 @@ impl Mp4Track {
         Ok(self.trak.mdia.minf.stbl.stsc.entries.len() - 1)
     }
 
+    fn chunk_count(&self) -> Result<u32> {
+        let stbl = &self.trak.mdia.minf.stbl;
+        if let Some(ref stco) = stbl.stco {
+            Ok(stco.entries.len() as u32)
+        } else if let Some(ref co64) = stbl.co64 {
+            Ok(co64.entries.len() as u32)
+        } else {
+            Err(Error::Box2NotFound(BoxType::StcoBox, BoxType::Co64Box))
+        }
+    }
+
     fn chunk_offset(&self, chunk_id: u32) -> Result<u64> {
         if self.trak.mdia.minf.stbl.stco.is_none() && self.trak.mdia.minf.stbl.co64.is_none() {
             return Err(Error::InvalidData("must have either stco or co64 boxes"));
@@ -386,7 +1545,7 @@ impl Mp4Track {
         None
     }
 
-    fn sample_size(&self, sample_id: u32) -> Result<u32> {
+    pub(crate) fn sample_size(&self, sample_id: u32) -> Result<u32> {
         if !self.trafs.is_empty() {
             if let Some((traf_idx, sample_idx)) = self.find_traf_idx_and_sample_idx(sample_id) {
                 if let Some(size) = self.trafs[traf_idx]
@@ -424,6 +1583,48 @@ impl Mp4Track {
         }
     }
 
+    fn btrt(&self) -> Option<&BtrtBox> {
+        let stsd = &self.trak.mdia.minf.stbl.stsd;
+        if let Some(ref avc1) = stsd.avc1 {
+            avc1.btrt.as_ref()
+        } else if let Some(ref hev1) = stsd.hev1 {
+            hev1.btrt.as_ref()
+        } else if let Some(ref dvh1) = stsd.dvh1 {
+            dvh1.btrt.as_ref()
+        } else {
+            None
+        }
+    }
+
+    // Prefers the summed stts deltas over mdhd, which some muxers leave at
+    // zero or round to the movie timescale.
+    fn computed_bitrate(&self) -> u32 {
+        let timescale = self.trak.mdia.mdhd.timescale;
+        if timescale == 0 {
+            return 0;
+        }
+        let stts_duration: u64 = self
+            .trak
+            .mdia
+            .minf
+            .stbl
+            .stts
+            .entries
+            .iter()
+            .map(|entry| entry.sample_count as u64 * entry.sample_delta as u64)
+            .sum();
+        let duration = if stts_duration > 0 {
+            stts_duration
+        } else {
+            self.trak.mdia.mdhd.duration
+        };
+        if duration == 0 {
+            return 0;
+        }
+        let bitrate = self.total_sample_size() as f64 * 8.0 * timescale as f64 / duration as f64;
+        bitrate as u32
+    }
+
     fn total_sample_size(&self) -> u64 {
         let stsz = &self.trak.mdia.minf.stbl.stsz;
         if stsz.sample_size > 0 {
@@ -437,23 +1638,17 @@ impl Mp4Track {
         }
     }
 
+    /// Returns the file offset of a sample's data. For fragmented tracks this
+    /// is resolved from the `tfhd` base data offset, plus the `trun` data
+    /// offset and the sizes of the preceding samples in the run. Without a
+    /// base data offset, the first `traf` of a `moof`, or one flagged
+    /// default-base-is-moof, is relative to the start of the `moof`, and any
+    /// other to the end of the previous `traf`'s data. To look up every
+    /// sample at once, prefer [`sample_offsets`](Self::sample_offsets).
     pub fn sample_offset(&self, sample_id: u32) -> Result<u64> {
         if !self.trafs.is_empty() {
             if let Some((traf_idx, sample_idx)) = self.find_traf_idx_and_sample_idx(sample_id) {
-                let mut sample_offset = self.trafs[traf_idx]
-                    .tfhd
-                    .base_data_offset
-                    .unwrap_or(self.moof_offsets[traf_idx]);
-
-                if let Some(data_offset) = self.trafs[traf_idx]
-                    .trun
-                    .as_ref()
-                    .and_then(|trun| trun.data_offset)
-                {
-                    sample_offset = sample_offset.checked_add_signed(data_offset as i64).ok_or(
-                        Error::InvalidData("attempt to calculate trun sample offset with overflow"),
-                    )?;
-                }
+                let mut sample_offset = self.traf_data_offset(traf_idx)?;
 
                 let first_sample_in_trun = sample_id - sample_idx as u32;
                 for i in first_sample_in_trun..sample_id {
@@ -469,86 +1664,320 @@ impl Mp4Track {
                 Err(Error::BoxInTrafNotFound(self.track_id(), BoxType::TrafBox))
             }
         } else {
-            let stsc_index = self.stsc_index(sample_id)?;
+            let (chunk_offset, first_sample_in_chunk, _) = self.chunk_of_sample(sample_id)?;
 
-            let stsc = &self.trak.mdia.minf.stbl.stsc;
-            let stsc_entry = stsc.entries.get(stsc_index).unwrap();
+            let mut sample_offset = chunk_offset;
+            for i in first_sample_in_chunk..sample_id {
+                sample_offset = sample_offset
+                    .checked_add(self.sample_size(i)? as u64)
+                    .ok_or(Error::InvalidData(
+                        "attempt to calculate chunk sample offset with overflow",
+                    ))?;
+            }
 
-            let first_chunk = stsc_entry.first_chunk;
-            let first_sample = stsc_entry.first_sample;
-            let samples_per_chunk = stsc_entry.samples_per_chunk;
+            Ok(sample_offset)
+        }
+    }
 
-            let chunk_id = sample_id
-                .checked_sub(first_sample)
-                .map(|n| n / samples_per_chunk)
-                .and_then(|n| n.checked_add(first_chunk))
+    // The file offset of the first sample of a traf's run.
+    fn traf_data_offset(&self, traf_idx: usize) -> Result<u64> {
+        let traf = &self.trafs[traf_idx];
+        let data_offset = traf.trun.as_ref().and_then(|trun| trun.data_offset);
+        match (traf.tfhd.base_data_offset, data_offset) {
+            (base_data_offset, Some(data_offset)) => base_data_offset
+                .or_else(|| self.traf_base_offsets.get(traf_idx).copied())
+                .unwrap_or(self.moof_offsets[traf_idx])
+                .checked_add_signed(data_offset as i64)
                 .ok_or(Error::InvalidData(
-                    "attempt to calculate stsc chunk_id with overflow",
-                ))?;
+                    "attempt to calculate trun sample offset with overflow",
+                )),
+            (Some(base_data_offset), None) => Ok(base_data_offset),
+            (None, None) => Err(Error::InvalidData(
+                "trun has no data_offset and tfhd has no base_data_offset",
+            )),
+        }
+    }
+
+    /// Returns the file offset of every sample, indexed by `sample_id - 1`,
+    /// in a single pass over the chunk tables, or the `trun`s of a fragmented
+    /// track, rather than a [`sample_offset`](Self::sample_offset) lookup per
+    /// sample.
+    pub fn sample_offsets(&self) -> Result<Vec<u64>> {
+        let sample_count = self.sample_count();
+        if !self.trafs.is_empty() {
+            let mut offsets = Vec::with_capacity(sample_count as usize);
+            for (traf_idx, traf) in self.trafs.iter().enumerate() {
+                let trun = match traf.trun {
+                    Some(ref trun) => trun,
+                    None => continue,
+                };
+                let mut offset = self.traf_data_offset(traf_idx)?;
+                for sample_idx in 0..trun.sample_count as usize {
+                    offsets.push(offset);
+                    if sample_idx + 1 == trun.sample_count as usize {
+                        break;
+                    }
+                    let size =
+                        trun.sample_sizes
+                            .get(sample_idx)
+                            .ok_or(Error::EntryInTrunNotFound(
+                                self.track_id(),
+                                BoxType::TrunBox,
+                                offsets.len() as u32,
+                            ))?;
+                    offset = offset.checked_add(*size as u64).ok_or(Error::InvalidData(
+                        "attempt to calculate trun entry sample offset with overflow",
+                    ))?;
+                }
+            }
+            return Ok(offsets);
+        }
+
+        let stbl = &self.trak.mdia.minf.stbl;
+        let chunk_count = self.chunk_count()?;
+
+        let mut offsets = Vec::with_capacity(sample_count as usize);
+        for (i, entry) in stbl.stsc.entries.iter().enumerate() {
+            let last_chunk = match stbl.stsc.entries.get(i + 1) {
+                Some(next) => next.first_chunk.saturating_sub(1),
+                None => chunk_count,
+            };
+            for chunk_id in entry.first_chunk.max(1)..=last_chunk {
+                let mut offset = self.chunk_offset(chunk_id)?;
+                for _ in 0..entry.samples_per_chunk {
+                    if offsets.len() as u32 == sample_count {
+                        return Ok(offsets);
+                    }
+                    offsets.push(offset);
+                    offset += self.sample_size(offsets.len() as u32)? as u64;
+                }
+            }
+        }
+        Ok(offsets)
+    }
 
-            let chunk_offset = self.chunk_offset(chunk_id)?;
+    /// Returns the timing, position and size of every sample, e.g. to build a
+    /// seek index, from the sample tables alone without reading any sample
+    /// data. For a track with external data, offsets are into that file.
+    pub fn sample_index(&self) -> Result<Vec<SampleEntry>> {
+        let offsets = self.sample_offsets()?;
+        let mut entries = Vec::with_capacity(offsets.len());
+        for (id, offset) in (1..).zip(offsets) {
+            let (dts, _) = self.sample_time(id)?;
+            let pts = cmp::max(dts as i64 + self.composition_offset(id) as i64, 0) as u64;
+            entries.push(SampleEntry {
+                id,
+                dts,
+                pts,
+                offset,
+                size: self.sample_size(id)?,
+                is_sync: self.is_sync_sample(id),
+            });
+        }
+        Ok(entries)
+    }
 
-            let first_sample_in_chunk = sample_id - (sample_id - first_sample) % samples_per_chunk;
+    /// Returns the chunk id and chunk file offset of every sample, indexed by
+    /// `sample_id - 1`, as resolved from `stsc` and `stco`/`co64`. Only
+    /// available for non-fragmented tracks, as fragments have no chunks.
+    pub fn sample_to_chunk_map(&self) -> Result<Vec<(u32, u64)>> {
+        if !self.trafs.is_empty() {
+            return Err(Error::InvalidData("fragmented tracks have no chunks"));
+        }
 
-            let mut sample_offset = 0;
-            for i in first_sample_in_chunk..sample_id {
-                sample_offset += self.sample_size(i)?;
+        let sample_count = self.sample_count();
+        let stbl = &self.trak.mdia.minf.stbl;
+        let chunk_count = self.chunk_count()?;
+
+        let mut chunks = Vec::with_capacity(sample_count as usize);
+        for (i, entry) in stbl.stsc.entries.iter().enumerate() {
+            let last_chunk = match stbl.stsc.entries.get(i + 1) {
+                Some(next) => next.first_chunk.saturating_sub(1),
+                None => chunk_count,
+            };
+            for chunk_id in entry.first_chunk.max(1)..=last_chunk {
+                let offset = self.chunk_offset(chunk_id)?;
+                for _ in 0..entry.samples_per_chunk {
+                    if chunks.len() as u32 == sample_count {
+                        return Ok(chunks);
+                    }
+                    chunks.push((chunk_id, offset));
+                }
             }
+        }
+        Ok(chunks)
+    }
 
-            Ok(chunk_offset + sample_offset as u64)
+    /// Returns the file offset of the chunk containing `sample_id`, along with
+    /// the first sample id in that chunk and the chunk's samples_per_chunk.
+    fn chunk_of_sample(&self, sample_id: u32) -> Result<(u64, u32, u32)> {
+        let stsc_index = self.stsc_index(sample_id)?;
+
+        let stsc = &self.trak.mdia.minf.stbl.stsc;
+        let stsc_entry = stsc.entries.get(stsc_index).unwrap();
+
+        let first_chunk = stsc_entry.first_chunk;
+        let first_sample = stsc_entry.first_sample;
+        let samples_per_chunk = stsc_entry.samples_per_chunk;
+
+        let chunk_id = sample_id
+            .checked_sub(first_sample)
+            .map(|n| n / samples_per_chunk)
+            .and_then(|n| n.checked_add(first_chunk))
+            .ok_or(Error::InvalidData(
+                "attempt to calculate stsc chunk_id with overflow",
+            ))?;
+
+        // A truncated file's stsc can describe more chunks than stco/co64 has.
+        // Past the last sample, the missing entry is left to chunk_offset to
+        // report, which read_sample takes as the end of the track.
+        let chunk_count = self.chunk_count()?;
+        if chunk_id == 0 || (chunk_id > chunk_count && sample_id <= self.sample_count()) {
+            return Err(Error::ChunkOutOfRange(
+                self.track_id(),
+                sample_id,
+                chunk_id,
+                chunk_count,
+            ));
         }
+
+        let chunk_offset = self.chunk_offset(chunk_id)?;
+
+        let first_sample_in_chunk = sample_id - (sample_id - first_sample) % samples_per_chunk;
+
+        Ok((chunk_offset, first_sample_in_chunk, samples_per_chunk))
+    }
+
+    /// Returns the file offset and byte size of the chunk containing
+    /// `sample_id`. Only available for non-fragmented tracks.
+    fn chunk_range(&self, sample_id: u32) -> Result<(u64, u64)> {
+        let (chunk_offset, first_sample_in_chunk, samples_per_chunk) =
+            self.chunk_of_sample(sample_id)?;
+
+        let last_sample = first_sample_in_chunk
+            .saturating_add(samples_per_chunk)
+            .min(self.sample_count().saturating_add(1));
+
+        let mut chunk_size = 0u64;
+        for i in first_sample_in_chunk..last_sample {
+            chunk_size += self.sample_size(i)? as u64;
+        }
+
+        Ok((chunk_offset, chunk_size))
+    }
+
+    /// The decode time of the first sample of a traf: its `tfdt`, or else
+    /// where the previous traf ended.
+    fn traf_start_time(&self, traf_idx: usize) -> Result<u64> {
+        let (mut start_time, from) = match self.trafs[..=traf_idx]
+            .iter()
+            .rposition(|traf| traf.tfdt.is_some())
+        {
+            Some(idx) => (
+                self.trafs[idx]
+                    .tfdt
+                    .as_ref()
+                    .unwrap()
+                    .base_media_decode_time,
+                idx,
+            ),
+            None => (0, 0),
+        };
+        for traf in &self.trafs[from..traf_idx] {
+            start_time =
+                start_time
+                    .checked_add(self.traf_duration(traf)?)
+                    .ok_or(Error::InvalidData(
+                        "attempt to sum sample durations with overflow",
+                    ))?;
+        }
+        Ok(start_time)
     }
 
-    fn sample_time(&self, sample_id: u32) -> Result<(u64, u32)> {
+    fn traf_duration(&self, traf: &TrafBox) -> Result<u64> {
+        let trun = match traf.trun {
+            Some(ref trun) => trun,
+            None => return Ok(0),
+        };
+        if TrunBox::FLAG_SAMPLE_DURATION & trun.flags != 0 {
+            trun.sample_durations
+                .iter()
+                .try_fold(0u64, |sum, &duration| sum.checked_add(duration as u64))
+                .ok_or(Error::InvalidData(
+                    "attempt to sum sample durations with overflow",
+                ))
+        } else {
+            Ok(trun.sample_count as u64 * self.traf_default_sample_duration(traf) as u64)
+        }
+    }
+
+    fn traf_default_sample_duration(&self, traf: &TrafBox) -> u32 {
+        traf.tfhd
+            .default_sample_duration
+            .unwrap_or(self.default_sample_duration)
+    }
+
+    pub(crate) fn sample_time(&self, sample_id: u32) -> Result<(u64, u32)> {
         if !self.trafs.is_empty() {
-            let mut base_start_time = 0;
-            let mut default_sample_duration = self.default_sample_duration;
             if let Some((traf_idx, sample_idx)) = self.find_traf_idx_and_sample_idx(sample_id) {
                 let traf = &self.trafs[traf_idx];
-                if let Some(tfdt) = &traf.tfdt {
-                    base_start_time = tfdt.base_media_decode_time;
-                }
-                if let Some(duration) = traf.tfhd.default_sample_duration {
-                    default_sample_duration = duration;
-                }
+                let base_start_time = self.traf_start_time(traf_idx)?;
+                let overflow = Error::InvalidData("attempt to sum sample durations with overflow");
                 if let Some(trun) = &traf.trun {
                     if TrunBox::FLAG_SAMPLE_DURATION & trun.flags != 0 {
-                        let mut start_offset = 0u64;
-                        for duration in &trun.sample_durations[..sample_idx] {
-                            start_offset = start_offset.checked_add(*duration as u64).ok_or(
-                                Error::InvalidData("attempt to sum sample durations with overflow"),
-                            )?;
-                        }
-                        let duration = trun.sample_durations[sample_idx];
-                        return Ok((base_start_time + start_offset, duration));
+                        let duration = *trun.sample_durations.get(sample_idx).ok_or(
+                            Error::EntryInTrunNotFound(
+                                self.track_id(),
+                                BoxType::TrunBox,
+                                sample_id,
+                            ),
+                        )?;
+                        let start_time = trun.sample_durations[..sample_idx]
+                            .iter()
+                            .try_fold(base_start_time, |sum, &duration| {
+                                sum.checked_add(duration as u64)
+                            })
+                            .ok_or(overflow)?;
+                        return Ok((start_time, duration));
                     }
                 }
+                let default_sample_duration = self.traf_default_sample_duration(traf);
+                let start_time = (sample_idx as u64)
+                    .checked_mul(default_sample_duration as u64)
+                    .and_then(|offset| base_start_time.checked_add(offset))
+                    .ok_or(overflow)?;
+                return Ok((start_time, default_sample_duration));
             }
-            let start_offset = ((sample_id - 1) * default_sample_duration) as u64;
-            Ok((base_start_time + start_offset, default_sample_duration))
+            let start_offset =
+                (sample_id as u64).saturating_sub(1) * self.default_sample_duration as u64;
+            Ok((start_offset, self.default_sample_duration))
         } else {
             let stts = &self.trak.mdia.minf.stbl.stts;
-
-            let mut sample_count: u32 = 1;
-            let mut elapsed = 0;
-
-            for entry in stts.entries.iter() {
-                let new_sample_count =
-                    sample_count
-                        .checked_add(entry.sample_count)
-                        .ok_or(Error::InvalidData(
-                            "attempt to sum stts entries sample_count with overflow",
-                        ))?;
-                if sample_id < new_sample_count {
+            let index = self
+                .stts_index
+                .get_or_init(|| SttsIndex::new(&stts.entries));
+
+            // The last entry starting at or before the sample; empty entries
+            // share their first sample ID with the next one.
+            let idx = index
+                .entries
+                .partition_point(|&(first_sample_id, _)| first_sample_id <= sample_id);
+            if let Some(idx) = idx.checked_sub(1) {
+                let (first_sample_id, elapsed) = index.entries[idx];
+                let entry = &stts.entries[idx];
+                if idx + 1 < index.entries.len() || sample_id - first_sample_id < entry.sample_count
+                {
                     let start_time =
-                        (sample_id - sample_count) as u64 * entry.sample_delta as u64 + elapsed;
+                        (sample_id - first_sample_id) as u64 * entry.sample_delta as u64 + elapsed;
                     return Ok((start_time, entry.sample_delta));
                 }
-
-                sample_count = new_sample_count;
-                elapsed += entry.sample_count as u64 * entry.sample_delta as u64;
             }
 
+            if index.overflowed {
+                return Err(Error::InvalidData(
+                    "attempt to sum stts entries sample_count with overflow",
+                ));
+            }
             Err(Error::EntryInStblNotFound(
                 self.track_id(),
                 BoxType::SttsBox,
@@ -557,7 +1986,9 @@ impl Mp4Track {
         }
     }
 
-    fn sample_rendering_offset(&self, sample_id: u32) -> i32 {
+    /// The composition (rendering) offset of a sample from `ctts`, or from
+    /// `trun` in fragments; 0 without one.
+    pub fn composition_offset(&self, sample_id: u32) -> i32 {
         if !self.trafs.is_empty() {
             if let Some((traf_idx, sample_idx)) = self.find_traf_idx_and_sample_idx(sample_id) {
                 if let Some(cts) = self.trafs[traf_idx]
@@ -577,10 +2008,36 @@ impl Mp4Track {
         0
     }
 
-    fn is_sync_sample(&self, sample_id: u32) -> bool {
+    /// The presentation time of a sample in track timescale units: its
+    /// decode time plus [`composition_offset`](Self::composition_offset),
+    /// clamped at 0. Edit lists are not applied.
+    pub fn presentation_time(&self, sample_id: u32) -> Result<u64> {
+        let (start_time, _) = self.sample_time(sample_id)?;
+        let time = start_time as i64 + self.composition_offset(sample_id) as i64;
+        Ok(cmp::max(time, 0) as u64)
+    }
+
+    /// Whether a sample is a sync sample (a random access point). Without an
+    /// `stss` box every sample is one. In fragments, it's read from the
+    /// sample's flags in `trun`, or else the `tfhd` and `trex` defaults.
+    pub fn is_sync_sample(&self, sample_id: u32) -> bool {
         if !self.trafs.is_empty() {
-            let sample_sizes_count = self.sample_count() / self.trafs.len() as u32;
-            return sample_id == 1 || sample_id % sample_sizes_count == 0;
+            let (traf_idx, sample_idx) = match self.find_traf_idx_and_sample_idx(sample_id) {
+                Some(idx) => idx,
+                None => return false,
+            };
+            let traf = &self.trafs[traf_idx];
+            let sample_flags = traf
+                .trun
+                .as_ref()
+                .and_then(|trun| match trun.first_sample_flags {
+                    Some(flags) if sample_idx == 0 => Some(flags),
+                    _ => trun.sample_flags.get(sample_idx).copied(),
+                });
+            let flags = sample_flags
+                .or(traf.tfhd.default_sample_flags)
+                .unwrap_or(self.default_sample_flags);
+            return flags & SAMPLE_IS_NON_SYNC_SAMPLE == 0;
         }
 
         if let Some(ref stss) = self.trak.mdia.minf.stbl.stss {
@@ -590,11 +2047,74 @@ impl Mp4Track {
         }
     }
 
+    /// Reads a sample, failing with [`Error::Truncated`] rather than reading
+    /// if its byte range runs past `file_size`.
     pub(crate) fn read_sample<R: Read + Seek>(
         &self,
         reader: &mut R,
+        cache: &mut ChunkCache,
         sample_id: u32,
+        file_size: u64,
     ) -> Result<Option<Mp4Sample>> {
+        let (sample_offset, sample_size) = match self.sample_range(sample_id, file_size)? {
+            Some(range) => range,
+            None => return Ok(None),
+        };
+
+        let bytes = match cache.get(sample_offset, sample_size) {
+            Some(bytes) => bytes,
+            None if sample_size == 0 => Bytes::new(),
+            None => self.read_sample_bytes(
+                reader,
+                cache,
+                sample_id,
+                sample_offset,
+                sample_size,
+                file_size,
+            )?,
+        };
+
+        let info = self.sample_info(sample_id)?;
+        Ok(Some(Mp4Sample {
+            start_time: info.start_time,
+            duration: info.duration,
+            rendering_offset: info.rendering_offset,
+            is_sync: info.is_sync,
+            bytes,
+        }))
+    }
+
+    /// Like [`read_sample`](Self::read_sample), but reads the bytes into
+    /// `buf`, resized to the sample size, instead of allocating.
+    pub(crate) fn read_sample_into<R: Read + Seek>(
+        &self,
+        reader: &mut R,
+        cache: &ChunkCache,
+        sample_id: u32,
+        file_size: u64,
+        buf: &mut Vec<u8>,
+    ) -> Result<Option<SampleInfo>> {
+        let (sample_offset, sample_size) = match self.sample_range(sample_id, file_size)? {
+            Some(range) => range,
+            None => return Ok(None),
+        };
+
+        buf.clear();
+        match cache.get(sample_offset, sample_size) {
+            Some(bytes) => buf.extend_from_slice(&bytes),
+            None if sample_size == 0 => {}
+            None => {
+                buf.resize(sample_size as usize, 0);
+                reader.seek(SeekFrom::Start(sample_offset))?;
+                reader.read_exact(buf)?;
+            }
+        }
+
+        self.sample_info(sample_id).map(Some)
+    }
+
+    /// The offset and size of a sample, or `None` past the end of the track.
+    fn sample_range(&self, sample_id: u32, file_size: u64) -> Result<Option<(u64, u32)>> {
         let sample_offset = match self.sample_offset(sample_id) {
             Ok(offset) => offset,
             Err(Error::EntryInStblNotFound(_, _, _)) => return Ok(None),
@@ -605,26 +2125,86 @@ impl Mp4Track {
             Err(Error::EntryInStblNotFound(_, _, _)) => return Ok(None),
             Err(err) => return Err(err),
         };
+        // An empty sample has no data to be truncated, wherever it points.
+        if sample_size > 0 && sample_offset.saturating_add(sample_size as u64) > file_size {
+            return Err(Error::Truncated(
+                self.track_id(),
+                sample_id,
+                sample_offset,
+                sample_size,
+                file_size,
+            ));
+        }
+        Ok(Some((sample_offset, sample_size)))
+    }
+
+    fn sample_info(&self, sample_id: u32) -> Result<SampleInfo> {
+        let (start_time, duration) = self.sample_time(sample_id)?;
+        Ok(SampleInfo {
+            start_time,
+            duration,
+            rendering_offset: self.composition_offset(sample_id),
+            is_sync: self.is_sync_sample(sample_id),
+        })
+    }
+
+    fn read_sample_bytes<R: Read + Seek>(
+        &self,
+        reader: &mut R,
+        cache: &mut ChunkCache,
+        sample_id: u32,
+        sample_offset: u64,
+        sample_size: u32,
+        file_size: u64,
+    ) -> Result<Bytes> {
+        if self.trafs.is_empty() {
+            if let Ok((chunk_offset, chunk_size)) = self.chunk_range(sample_id) {
+                // Later samples of a chunk may run past the end of a
+                // truncated file; read this one alone then.
+                if chunk_size <= MAX_CACHED_CHUNK_SIZE && chunk_offset + chunk_size <= file_size {
+                    let mut buffer = vec![0x0u8; chunk_size as usize];
+                    reader.seek(SeekFrom::Start(chunk_offset))?;
+                    reader.read_exact(&mut buffer)?;
+                    cache.offset = chunk_offset;
+                    cache.bytes = Bytes::from(buffer);
+
+                    if let Some(bytes) = cache.get(sample_offset, sample_size) {
+                        return Ok(bytes);
+                    }
+                }
+            }
+        }
 
         let mut buffer = vec![0x0u8; sample_size as usize];
         reader.seek(SeekFrom::Start(sample_offset))?;
         reader.read_exact(&mut buffer)?;
+        Ok(Bytes::from(buffer))
+    }
+}
 
-        let (start_time, duration) = self.sample_time(sample_id).unwrap(); // XXX
-        let rendering_offset = self.sample_rendering_offset(sample_id);
-        let is_sync = self.is_sync_sample(sample_id);
+/// Chunks larger than this are not cached; their samples are read individually.
+const MAX_CACHED_CHUNK_SIZE: u64 = 8 * 1024 * 1024;
 
-        Ok(Some(Mp4Sample {
-            start_time,
-            duration,
-            rendering_offset,
-            is_sync,
-            bytes: Bytes::from(buffer),
-        }))
+/// Holds the most recently read chunk so that consecutive samples from the
+/// same chunk are sliced from memory instead of re-read.
+#[derive(Debug, Default)]
+pub(crate) struct ChunkCache {
+    offset: u64,
+    bytes: Bytes,
+}
+
+impl ChunkCache {
+    fn get(&self, offset: u64, size: u32) -> Option<Bytes> {
+        let start = offset.checked_sub(self.offset)? as usize;
+        let end = start.checked_add(size as usize)?;
+        if end <= self.bytes.len() {
+            Some(self.bytes.slice(start..end))
+        } else {
+            None
+        }
     }
 }
 
-// TODO creation_time, modification_time
 #[derive(Debug, Default)]
 pub(crate) struct Mp4TrackWriter {
     trak: TrakBox,
@@ -638,35 +2218,57 @@ pub(crate) struct Mp4TrackWriter {
 
     samples_per_chunk: u32,
     duration_per_chunk: u32,
+
+    // Full chunks are left for Mp4Writer to write out in interleaved order.
+    interleaved: bool,
+
+    negative_composition_offsets: bool,
+    write_cslg: bool,
+
+    // Audio samples are all sync samples with no composition offsets, so
+    // neither ctts nor stss is written whatever the samples say.
+    is_audio: bool,
 }
 
 impl Mp4TrackWriter {
     pub(crate) fn new(track_id: u32, config: &TrackConfig) -> Result<Self> {
         let mut trak = TrakBox::default();
         trak.tkhd.track_id = track_id;
+        trak.tkhd.volume = config.volume;
         trak.mdia.mdhd.timescale = config.timescale;
-        trak.mdia.mdhd.language = config.language.to_owned();
+        if is_iso639_2_code(&config.language) {
+            trak.mdia.mdhd.language = config.language.to_owned();
+        } else {
+            // mdhd only holds a three-letter code; anything else goes in elng.
+            trak.mdia.elng = Some(ElngBox {
+                extended_language: config.language.to_owned(),
+                ..Default::default()
+            });
+        }
         trak.mdia.hdlr.handler_type = config.track_type.into();
         trak.mdia.minf.stbl.co64 = Some(Co64Box::default());
+        // Exactly one media header, matching the handler type.
+        match config.track_type {
+            TrackType::Video => trak.mdia.minf.vmhd = Some(VmhdBox::default()),
+            TrackType::Audio => trak.mdia.minf.smhd = Some(SmhdBox::default()),
+            TrackType::Hint => trak.mdia.minf.hmhd = Some(HmhdBox::default()),
+            TrackType::Subtitle => trak.mdia.minf.nmhd = Some(NmhdBox::default()),
+        }
         match config.media_conf {
             MediaConfig::AvcConfig(ref avc_config) => {
                 trak.tkhd.set_width(avc_config.width);
                 trak.tkhd.set_height(avc_config.height);
 
-                let vmhd = VmhdBox::default();
-                trak.mdia.minf.vmhd = Some(vmhd);
-
-                let avc1 = Avc1Box::new(avc_config);
+                let mut avc1 = Avc1Box::new(avc_config);
+                avc1.compressor_name = config.compressor_name.clone();
                 trak.mdia.minf.stbl.stsd.avc1 = Some(avc1);
             }
             MediaConfig::HevcConfig(ref hevc_config) => {
                 trak.tkhd.set_width(hevc_config.width);
                 trak.tkhd.set_height(hevc_config.height);
 
-                let vmhd = VmhdBox::default();
-                trak.mdia.minf.vmhd = Some(vmhd);
-
-                let hev1 = Hev1Box::new(hevc_config);
+                let mut hev1 = Hev1Box::new(hevc_config);
+                hev1.compressor_name = config.compressor_name.clone();
                 trak.mdia.minf.stbl.stsd.hev1 = Some(hev1);
             }
             MediaConfig::Vp9Config(ref config) => {
@@ -675,23 +2277,90 @@ impl Mp4TrackWriter {
 
                 trak.mdia.minf.stbl.stsd.vp09 = Some(Vp09Box::new(config));
             }
-            MediaConfig::AacConfig(ref aac_config) => {
-                let smhd = SmhdBox::default();
-                trak.mdia.minf.smhd = Some(smhd);
+            MediaConfig::Av1Config(ref av1_config) => {
+                trak.tkhd.set_width(av1_config.width);
+                trak.tkhd.set_height(av1_config.height);
 
+                let mut av01 = Av01Box::new(av1_config);
+                av01.compressor_name = config.compressor_name.clone();
+                trak.mdia.minf.stbl.stsd.av01 = Some(av01);
+            }
+            MediaConfig::AacConfig(ref aac_config) => {
                 let mp4a = Mp4aBox::new(aac_config);
                 trak.mdia.minf.stbl.stsd.mp4a = Some(mp4a);
             }
+            MediaConfig::OpusConfig(ref opus_config) => {
+                let opus = OpusBox::new(opus_config);
+                trak.mdia.minf.stbl.stsd.opus = Some(opus);
+            }
+            MediaConfig::Ac3Config(ref ac3_config) => {
+                let ac3 = Ac3Box::new(ac3_config);
+                trak.mdia.minf.stbl.stsd.ac3 = Some(ac3);
+            }
             MediaConfig::TtxtConfig(ref _ttxt_config) => {
                 let tx3g = Tx3gBox::default();
                 trak.mdia.minf.stbl.stsd.tx3g = Some(tx3g);
             }
+            MediaConfig::RtpHintConfig(ref hint_config) => {
+                trak.tref = Some(TrefBox {
+                    references: vec![TrackReference {
+                        reference_type: TrackType::Hint.into(),
+                        track_ids: vec![hint_config.reference_track_id],
+                    }],
+                });
+
+                let rtp = RtpBox::new(hint_config);
+                trak.mdia.minf.stbl.stsd.rtp = Some(rtp);
+
+                if !hint_config.sdp.is_empty() {
+                    trak.udta = Some(UdtaBox {
+                        hnti: Some(HntiBox {
+                            sdp: Some(SdpBox {
+                                sdp_text: hint_config.sdp.clone(),
+                            }),
+                        }),
+                        ..Default::default()
+                    });
+                }
+            }
+            MediaConfig::RawSampleEntryConfig(ref raw_config) => {
+                if raw_config.width > 0 || raw_config.height > 0 {
+                    trak.tkhd.set_width(raw_config.width);
+                    trak.tkhd.set_height(raw_config.height);
+                }
+
+                let raw = RawSampleEntry::new(raw_config.sample_entry.clone())?;
+                trak.mdia.minf.stbl.stsd.raw = Some(raw);
+            }
+        }
+        trak.mdia.minf.stbl.stsd.entry_count = 1;
+        if let Some((h_spacing, v_spacing)) = config.pixel_aspect_ratio {
+            let pasp = Some(PaspBox {
+                h_spacing,
+                v_spacing,
+            });
+            let stsd = &mut trak.mdia.minf.stbl.stsd;
+            if let Some(ref mut avc1) = stsd.avc1 {
+                avc1.pasp = pasp;
+            } else if let Some(ref mut hev1) = stsd.hev1 {
+                hev1.pasp = pasp;
+            } else if let Some(ref mut vp09) = stsd.vp09 {
+                vp09.pasp = pasp;
+            } else if let Some(ref mut av01) = stsd.av01 {
+                av01.pasp = pasp;
+            }
         }
+        if let Some((width, height)) = config.display_size {
+            trak.tkhd.set_width(width);
+            trak.tkhd.set_height(height);
+        }
+        trak.tkhd.set_rotation(config.rotation)?;
         Ok(Mp4TrackWriter {
             trak,
             chunk_buffer: BytesMut::new(),
             sample_id: 1,
             duration_per_chunk: config.timescale, // 1 second
+            is_audio: config.track_type == TrackType::Audio,
             ..Self::default()
         })
     }
@@ -805,6 +2474,46 @@ impl Mp4TrackWriter {
         }
     }
 
+    /// The track's boxes as configured, with no samples written.
+    pub(crate) fn into_trak(self) -> TrakBox {
+        self.trak
+    }
+
+    /// Targets chunks of `duration` (in the track's timescale) and leaves
+    /// writing them out to the caller, which interleaves across tracks.
+    pub(crate) fn set_interleave_duration(&mut self, duration: u32) {
+        self.samples_per_chunk = 0;
+        self.duration_per_chunk = duration;
+        self.interleaved = true;
+    }
+
+    /// Centers the composition offsets around zero on `write_end`, writing
+    /// `ctts` version 1 and, if `write_cslg` is set, a `cslg` box.
+    pub(crate) fn set_negative_composition_offsets(&mut self, write_cslg: bool) {
+        self.negative_composition_offsets = true;
+        self.write_cslg = write_cslg;
+    }
+
+    /// The decode time of the first buffered sample and the track's
+    /// timescale, or `None` if nothing is buffered.
+    pub(crate) fn chunk_start_time(&self) -> Option<(u64, u32)> {
+        if self.chunk_buffer.is_empty() {
+            return None;
+        }
+        let start_time = self.trak.mdia.mdhd.duration - self.chunk_duration as u64;
+        Some((start_time, self.trak.mdia.mdhd.timescale))
+    }
+
+    /// The decode time the written and buffered samples run up to, and the
+    /// track's timescale.
+    pub(crate) fn end_time(&self) -> (u64, u32) {
+        (self.trak.mdia.mdhd.duration, self.trak.mdia.mdhd.timescale)
+    }
+
+    pub(crate) fn is_chunk_ready(&self) -> bool {
+        !self.chunk_buffer.is_empty() && self.is_chunk_full()
+    }
+
     fn update_durations(&mut self, dur: u32, movie_timescale: u32) {
         self.trak.mdia.mdhd.duration += dur as u64;
         if self.trak.mdia.mdhd.duration > (u32::MAX as u64) {
@@ -828,9 +2537,11 @@ impl Mp4TrackWriter {
         self.chunk_duration += sample.duration;
         self.update_sample_sizes(sample.bytes.len() as u32);
         self.update_sample_times(sample.duration);
-        self.update_rendering_offsets(sample.rendering_offset);
-        self.update_sync_samples(sample.is_sync);
-        if self.is_chunk_full() {
+        if !self.is_audio {
+            self.update_rendering_offsets(sample.rendering_offset);
+            self.update_sync_samples(sample.is_sync);
+        }
+        if !self.interleaved && self.is_chunk_full() {
             self.write_chunk(writer)?;
         }
         self.update_durations(sample.duration, movie_timescale);
@@ -866,7 +2577,7 @@ impl Mp4TrackWriter {
         co64.entries.push(offset);
     }
 
-    fn write_chunk<W: Write + Seek>(&mut self, writer: &mut W) -> Result<()> {
+    pub(crate) fn write_chunk<W: Write + Seek>(&mut self, writer: &mut W) -> Result<()> {
         if self.chunk_buffer.is_empty() {
             return Ok(());
         }
@@ -896,23 +2607,188 @@ impl Mp4TrackWriter {
         }
     }
 
+    /// The average bitrate of the written samples over the track duration,
+    /// and the peak bitrate over any one second window of decode time, in
+    /// bits per second.
+    fn written_bitrates(&self) -> (u32, u32) {
+        let stbl = &self.trak.mdia.minf.stbl;
+        let timescale = self.trak.mdia.mdhd.timescale as u64;
+        let sample_count = stbl.stsz.sample_count as usize;
+        let sizes = (0..sample_count).map(|i| {
+            if stbl.stsz.sample_size > 0 {
+                stbl.stsz.sample_size as u64
+            } else {
+                stbl.stsz.sample_sizes[i] as u64
+            }
+        });
+        let times = stbl.stts.entries.iter().flat_map(|entry| {
+            iter::repeat_n(entry.sample_delta as u64, entry.sample_count as usize)
+        });
+        let mut samples = Vec::with_capacity(sample_count);
+        let mut duration = 0u64;
+        for (size, delta) in sizes.zip(times) {
+            samples.push((duration, size));
+            duration += delta;
+        }
+        if timescale == 0 || duration == 0 {
+            return (0, 0);
+        }
+
+        let total_size: u64 = samples.iter().map(|&(_, size)| size).sum();
+        let avg_bitrate = total_size as f64 * 8.0 * timescale as f64 / duration as f64;
+
+        let mut max_window_size = 0;
+        let mut window_size = 0;
+        let mut window_end = 0;
+        for (i, &(start_time, _)) in samples.iter().enumerate() {
+            while window_end < samples.len() && samples[window_end].0 < start_time + timescale {
+                window_size += samples[window_end].1;
+                window_end += 1;
+            }
+            max_window_size = cmp::max(max_window_size, window_size);
+            window_size -= samples[i].1;
+        }
+        // A track shorter than a second peaks at least at its average.
+        let max_bitrate = (max_window_size as f64 * 8.0).max(avg_bitrate);
+
+        (
+            avg_bitrate.min(u32::MAX as f64) as u32,
+            max_bitrate.min(u32::MAX as f64) as u32,
+        )
+    }
+
+    /// Writes out the buffered samples and returns the finished `trak`. The
+    /// writer's own state is left as is, so more samples can follow, e.g.
+    /// after an [`Mp4Writer::checkpoint`].
     pub(crate) fn write_end<W: Write + Seek>(&mut self, writer: &mut W) -> Result<TrakBox> {
         self.write_chunk(writer)?;
 
+        let mut trak = self.trak.clone();
         let max_sample_size = self.max_sample_size();
-        if let Some(ref mut mp4a) = self.trak.mdia.minf.stbl.stsd.mp4a {
+        let (avg_bitrate, max_bitrate) = self.written_bitrates();
+        let btrt = BtrtBox {
+            buffer_size_db: max_sample_size,
+            max_bitrate,
+            avg_bitrate,
+        };
+        let stsd = &mut trak.mdia.minf.stbl.stsd;
+        if let Some(ref mut mp4a) = stsd.mp4a {
             if let Some(ref mut esds) = mp4a.esds {
                 esds.es_desc.dec_config.buffer_size_db = max_sample_size;
+                esds.es_desc.dec_config.max_bitrate = max_bitrate;
+                esds.es_desc.dec_config.avg_bitrate = avg_bitrate;
             }
-            // TODO
-            // mp4a.esds.es_desc.dec_config.max_bitrate
-            // mp4a.esds.es_desc.dec_config.avg_bitrate
+        } else if let Some(ref mut avc1) = stsd.avc1 {
+            avc1.btrt = Some(btrt);
+        } else if let Some(ref mut hev1) = stsd.hev1 {
+            hev1.btrt = Some(btrt);
+        } else if let Some(ref mut av01) = stsd.av01 {
+            av01.btrt = Some(btrt);
         }
-        if let Ok(stco) = StcoBox::try_from(self.trak.mdia.minf.stbl.co64.as_ref().unwrap()) {
-            self.trak.mdia.minf.stbl.stco = Some(stco);
-            self.trak.mdia.minf.stbl.co64 = None;
+        // Chunk offsets are collected as 64-bit; only files past 4 GiB need
+        // them written that way.
+        let stbl = &mut trak.mdia.minf.stbl;
+        if let Some(co64) = stbl.co64.take() {
+            stbl.set_chunk_offsets(co64.entries);
         }
+        if self.negative_composition_offsets {
+            self.center_composition_offsets(stbl);
+        }
+
+        Ok(trak)
+    }
+
+    fn center_composition_offsets(&self, stbl: &mut StblBox) {
+        let ctts = match stbl.ctts {
+            Some(ref mut ctts) if !ctts.entries.is_empty() => ctts,
+            _ => return,
+        };
 
-        Ok(self.trak.clone())
+        let offsets = ctts.entries.iter().map(|entry| entry.sample_offset as i64);
+        let least = offsets.clone().min().unwrap();
+        let greatest = offsets.max().unwrap();
+        let shift = (least + greatest).div_euclid(2);
+        for entry in ctts.entries.iter_mut() {
+            entry.sample_offset = (entry.sample_offset as i64 - shift) as i32;
+        }
+        ctts.version = 1;
+
+        if !self.write_cslg {
+            return;
+        }
+
+        let deltas = stbl
+            .stts
+            .entries
+            .iter()
+            .flat_map(|entry| iter::repeat_n(entry.sample_delta, entry.sample_count as usize));
+        let offsets = ctts
+            .entries
+            .iter()
+            .flat_map(|entry| iter::repeat_n(entry.sample_offset, entry.sample_count as usize));
+        let mut decode_time = 0i64;
+        let mut composition_start_time = i64::MAX;
+        let mut composition_end_time = i64::MIN;
+        for (delta, offset) in deltas.zip(offsets) {
+            let composition_time = decode_time + offset as i64;
+            composition_start_time = composition_start_time.min(composition_time);
+            composition_end_time = composition_end_time.max(composition_time + delta as i64);
+            decode_time += delta as i64;
+        }
+
+        let mut cslg = CslgBox {
+            composition_to_dts_shift: (shift - least).max(0),
+            least_decode_to_display_delta: least - shift,
+            greatest_decode_to_display_delta: greatest - shift,
+            composition_start_time,
+            composition_end_time,
+            ..Default::default()
+        };
+        let fits_i32 = |value: i64| i32::try_from(value).is_ok();
+        if !(fits_i32(cslg.composition_start_time) && fits_i32(cslg.composition_end_time)) {
+            cslg.version = 1;
+        }
+        stbl.cslg = Some(cslg);
+    }
+}
+
+fn is_iso639_2_code(language: &str) -> bool {
+    language.len() == 3 && language.bytes().all(|b| b.is_ascii_lowercase())
+}
+
+/// Decrypts a `cenc` sample in place with AES-128 in CTR mode. The protected
+/// bytes of all subsamples form one key stream, started from `iv` (an 8-byte
+/// IV is padded with zeros); without subsamples the whole sample is protected.
+#[cfg(feature = "cenc")]
+fn decrypt_cenc_sample(
+    key: &[u8; 16],
+    iv: &[u8],
+    subsamples: &[SencSubsample],
+    data: &mut [u8],
+) -> Result<()> {
+    use aes::cipher::{KeyIvInit, StreamCipher};
+
+    if iv.len() != 8 && iv.len() != 16 {
+        return Err(Error::InvalidData("cenc IV must be 8 or 16 bytes"));
+    }
+    let mut counter = [0u8; 16];
+    counter[..iv.len()].copy_from_slice(iv);
+    // The block counter is the low 64 bits.
+    let mut cipher = ctr::Ctr64BE::<aes::Aes128>::new(key.into(), &counter.into());
+
+    if subsamples.is_empty() {
+        cipher.apply_keystream(data);
+        return Ok(());
+    }
+    let mut offset = 0;
+    for subsample in subsamples {
+        let start = offset + subsample.bytes_of_clear_data as usize;
+        let end = start + subsample.bytes_of_protected_data as usize;
+        if end > data.len() {
+            return Err(Error::InvalidData("subsamples extend past the sample"));
+        }
+        cipher.apply_keystream(&mut data[start..end]);
+        offset = end;
     }
+    Ok(())
 }
@@ -2,6 +2,7 @@ use serde::Serialize;
 use std::borrow::Cow;
 use std::convert::TryFrom;
 use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use crate::mp4box::*;
 use crate::*;
@@ -155,6 +156,7 @@ impl fmt::Display for FourCC {
 const DISPLAY_TYPE_VIDEO: &str = "Video";
 const DISPLAY_TYPE_AUDIO: &str = "Audio";
 const DISPLAY_TYPE_SUBTITLE: &str = "Subtitle";
+const DISPLAY_TYPE_HINT: &str = "Hint";
 
 const HANDLER_TYPE_VIDEO: &str = "vide";
 const HANDLER_TYPE_VIDEO_FOURCC: [u8; 4] = [b'v', b'i', b'd', b'e'];
@@ -165,11 +167,19 @@ const HANDLER_TYPE_AUDIO_FOURCC: [u8; 4] = [b's', b'o', b'u', b'n'];
 const HANDLER_TYPE_SUBTITLE: &str = "sbtl";
 const HANDLER_TYPE_SUBTITLE_FOURCC: [u8; 4] = [b's', b'b', b't', b'l'];
 
+// QuickTime text tracks, read as subtitles.
+const HANDLER_TYPE_TEXT: &str = "text";
+const HANDLER_TYPE_TEXT_FOURCC: [u8; 4] = [b't', b'e', b'x', b't'];
+
+const HANDLER_TYPE_HINT: &str = "hint";
+const HANDLER_TYPE_HINT_FOURCC: [u8; 4] = [b'h', b'i', b'n', b't'];
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TrackType {
     Video,
     Audio,
     Subtitle,
+    Hint,
 }
 
 impl fmt::Display for TrackType {
@@ -178,6 +188,7 @@ impl fmt::Display for TrackType {
             TrackType::Video => DISPLAY_TYPE_VIDEO,
             TrackType::Audio => DISPLAY_TYPE_AUDIO,
             TrackType::Subtitle => DISPLAY_TYPE_SUBTITLE,
+            TrackType::Hint => DISPLAY_TYPE_HINT,
         };
         write!(f, "{s}")
     }
@@ -189,7 +200,8 @@ impl TryFrom<&str> for TrackType {
         match handler {
             HANDLER_TYPE_VIDEO => Ok(TrackType::Video),
             HANDLER_TYPE_AUDIO => Ok(TrackType::Audio),
-            HANDLER_TYPE_SUBTITLE => Ok(TrackType::Subtitle),
+            HANDLER_TYPE_SUBTITLE | HANDLER_TYPE_TEXT => Ok(TrackType::Subtitle),
+            HANDLER_TYPE_HINT => Ok(TrackType::Hint),
             _ => Err(Error::InvalidData("unsupported handler type")),
         }
     }
@@ -201,7 +213,8 @@ impl TryFrom<&FourCC> for TrackType {
         match fourcc.value {
             HANDLER_TYPE_VIDEO_FOURCC => Ok(TrackType::Video),
             HANDLER_TYPE_AUDIO_FOURCC => Ok(TrackType::Audio),
-            HANDLER_TYPE_SUBTITLE_FOURCC => Ok(TrackType::Subtitle),
+            HANDLER_TYPE_SUBTITLE_FOURCC | HANDLER_TYPE_TEXT_FOURCC => Ok(TrackType::Subtitle),
+            HANDLER_TYPE_HINT_FOURCC => Ok(TrackType::Hint),
             _ => Err(Error::InvalidData("unsupported handler type")),
         }
     }
@@ -213,6 +226,7 @@ impl From<TrackType> for FourCC {
             TrackType::Video => HANDLER_TYPE_VIDEO_FOURCC.into(),
             TrackType::Audio => HANDLER_TYPE_AUDIO_FOURCC.into(),
             TrackType::Subtitle => HANDLER_TYPE_SUBTITLE_FOURCC.into(),
+            TrackType::Hint => HANDLER_TYPE_HINT_FOURCC.into(),
         }
     }
 }
@@ -220,7 +234,11 @@ impl From<TrackType> for FourCC {
 const MEDIA_TYPE_H264: &str = "h264";
 const MEDIA_TYPE_H265: &str = "h265";
 const MEDIA_TYPE_VP9: &str = "vp9";
+const MEDIA_TYPE_AV1: &str = "av1";
 const MEDIA_TYPE_AAC: &str = "aac";
+const MEDIA_TYPE_OPUS: &str = "opus";
+const MEDIA_TYPE_AC3: &str = "ac3";
+const MEDIA_TYPE_EAC3: &str = "eac3";
 const MEDIA_TYPE_TTXT: &str = "ttxt";
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -228,7 +246,11 @@ pub enum MediaType {
     H264,
     H265,
     VP9,
+    AV1,
     AAC,
+    Opus,
+    AC3,
+    EAC3,
     TTXT,
 }
 
@@ -246,7 +268,11 @@ impl TryFrom<&str> for MediaType {
             MEDIA_TYPE_H264 => Ok(MediaType::H264),
             MEDIA_TYPE_H265 => Ok(MediaType::H265),
             MEDIA_TYPE_VP9 => Ok(MediaType::VP9),
+            MEDIA_TYPE_AV1 => Ok(MediaType::AV1),
             MEDIA_TYPE_AAC => Ok(MediaType::AAC),
+            MEDIA_TYPE_OPUS => Ok(MediaType::Opus),
+            MEDIA_TYPE_AC3 => Ok(MediaType::AC3),
+            MEDIA_TYPE_EAC3 => Ok(MediaType::EAC3),
             MEDIA_TYPE_TTXT => Ok(MediaType::TTXT),
             _ => Err(Error::InvalidData("unsupported media type")),
         }
@@ -259,7 +285,11 @@ impl From<MediaType> for &str {
             MediaType::H264 => MEDIA_TYPE_H264,
             MediaType::H265 => MEDIA_TYPE_H265,
             MediaType::VP9 => MEDIA_TYPE_VP9,
+            MediaType::AV1 => MEDIA_TYPE_AV1,
             MediaType::AAC => MEDIA_TYPE_AAC,
+            MediaType::Opus => MEDIA_TYPE_OPUS,
+            MediaType::AC3 => MEDIA_TYPE_AC3,
+            MediaType::EAC3 => MEDIA_TYPE_EAC3,
             MediaType::TTXT => MEDIA_TYPE_TTXT,
         }
     }
@@ -271,7 +301,11 @@ impl From<&MediaType> for &str {
             MediaType::H264 => MEDIA_TYPE_H264,
             MediaType::H265 => MEDIA_TYPE_H265,
             MediaType::VP9 => MEDIA_TYPE_VP9,
+            MediaType::AV1 => MEDIA_TYPE_AV1,
             MediaType::AAC => MEDIA_TYPE_AAC,
+            MediaType::Opus => MEDIA_TYPE_OPUS,
+            MediaType::AC3 => MEDIA_TYPE_AC3,
+            MediaType::EAC3 => MEDIA_TYPE_EAC3,
             MediaType::TTXT => MEDIA_TYPE_TTXT,
         }
     }
@@ -576,6 +610,19 @@ pub struct AvcConfig {
 pub struct HevcConfig {
     pub width: u16,
     pub height: u16,
+    pub hdr: Option<HdrConfig>,
+    pub video_param_set: Vec<u8>,
+    pub seq_param_set: Vec<u8>,
+    pub pic_param_set: Vec<u8>,
+}
+
+/// Colour description and HDR static metadata carried by a visual sample
+/// entry, as returned by [`Mp4Track::hdr_metadata`](crate::Mp4Track::hdr_metadata).
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct HdrConfig {
+    pub colr: Option<ColrBox>,
+    pub mdcv: Option<MdcvBox>,
+    pub clli: Option<ClliBox>,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Default)]
@@ -584,6 +631,15 @@ pub struct Vp9Config {
     pub height: u16,
 }
 
+/// An AV1 track. `config_obus` holds the sequence header OBU, from which
+/// the `av1C` profile, level and color fields are derived.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Av1Config {
+    pub width: u16,
+    pub height: u16,
+    pub config_obus: Vec<u8>,
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct AacConfig {
     pub bitrate: u32,
@@ -603,16 +659,208 @@ impl Default for AacConfig {
     }
 }
 
+/// Opus decoder setup, mirroring the fields of the `dOps` box. The
+/// channel mapping table (`stream_count`, `coupled_count` and
+/// `channel_mapping`) is only used when `channel_mapping_family` isn't 0.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct OpusConfig {
+    pub output_channel_count: u8,
+    pub pre_skip: u16,
+    pub input_sample_rate: u32,
+    pub output_gain: i16,
+    pub channel_mapping_family: u8,
+    pub stream_count: u8,
+    pub coupled_count: u8,
+    pub channel_mapping: Vec<u8>,
+}
+
+impl Default for OpusConfig {
+    fn default() -> Self {
+        Self {
+            output_channel_count: 2,
+            pre_skip: 312,
+            input_sample_rate: 48000,
+            output_gain: 0,
+            channel_mapping_family: 0,
+            stream_count: 0,
+            coupled_count: 0,
+            channel_mapping: Vec::new(),
+        }
+    }
+}
+
+/// AC-3 stream parameters, mirroring the fields of the `dac3` box.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Ac3Config {
+    pub fscod: u8,
+    pub bsid: u8,
+    pub bsmod: u8,
+    pub acmod: u8,
+    pub lfeon: bool,
+    pub bit_rate_code: u8,
+}
+
+impl Default for Ac3Config {
+    fn default() -> Self {
+        // Stereo at 48 kHz and 192 kbit/s.
+        Self {
+            fscod: 0,
+            bsid: 8,
+            bsmod: 0,
+            acmod: 2,
+            lfeon: false,
+            bit_rate_code: 10,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Default)]
 pub struct TtxtConfig {}
 
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct RtpHintConfig {
+    pub reference_track_id: u32,
+    pub max_packet_size: u32,
+    pub rtp_timescale: u32,
+    pub sdp: String,
+}
+
+/// Configures a track whose sample entry is copied verbatim, e.g. from
+/// [`Mp4Track::sample_entry_raw_bytes`](crate::Mp4Track::sample_entry_raw_bytes).
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct RawSampleEntryConfig {
+    /// Track type and media timescale of the track the sample entry is
+    /// taken from, as they can't be derived from an unknown sample entry.
+    pub track_type: TrackType,
+    pub timescale: u32,
+    pub width: u16,
+    pub height: u16,
+    pub sample_entry: Vec<u8>,
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum MediaConfig {
     AvcConfig(AvcConfig),
     HevcConfig(HevcConfig),
     Vp9Config(Vp9Config),
+    Av1Config(Av1Config),
     AacConfig(AacConfig),
+    OpusConfig(OpusConfig),
+    Ac3Config(Ac3Config),
     TtxtConfig(TtxtConfig),
+    RtpHintConfig(RtpHintConfig),
+    RawSampleEntryConfig(RawSampleEntryConfig),
+}
+
+/// A track to rebuild with
+/// [`Mp4Reader::recover_from_partial`](crate::Mp4Reader::recover_from_partial).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecoveryTrack {
+    pub config: TrackConfig,
+    /// Duration given to every recovered sample, in `config.timescale` units.
+    pub sample_duration: u32,
+}
+
+/// How a sample relates to the rest of the decode order, as returned by
+/// [`Mp4Track::dependency_info`](crate::Mp4Track::dependency_info).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SampleDependency {
+    pub is_sync: bool,
+    /// From `sdtp`, if present: whether the sample references other samples.
+    pub depends_on_others: Option<bool>,
+    /// From `sdtp`, if present: whether other samples reference this one.
+    pub is_depended_on: Option<bool>,
+    /// Whether the sample can be dropped without breaking decode of the
+    /// samples that are kept.
+    pub droppable: bool,
+}
+
+/// A group of pictures, as yielded by [`Mp4Reader::gops`]: a sync sample and
+/// the non-sync samples following it in decode order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Gop {
+    pub start_sample: u32,
+    /// Sample IDs in decode order, starting with `start_sample`.
+    pub sample_ids: Vec<u32>,
+}
+
+/// File-level metadata gathered in one call by [`Mp4Reader::stream_info`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamInfo {
+    pub major_brand: FourCC,
+    pub minor_version: u32,
+    pub compatible_brands: Vec<FourCC>,
+    pub duration: Duration,
+    pub timescale: u32,
+    pub is_fragmented: bool,
+    /// One entry per track, ordered by track ID.
+    pub tracks: Vec<TrackInfo>,
+}
+
+/// A track summary within [`StreamInfo`]. `track_type` and `media_type` are
+/// `None` for handlers and sample entries this crate doesn't recognize, and
+/// `width`/`height` are 0 for non-video tracks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrackInfo {
+    pub track_id: u32,
+    pub track_type: Option<TrackType>,
+    pub media_type: Option<MediaType>,
+    pub language: String,
+    pub duration: Duration,
+    pub timescale: u32,
+    pub sample_count: u32,
+    pub bitrate: u32,
+    pub width: u16,
+    pub height: u16,
+}
+
+/// A subsegment listed in a `sidx`, with its absolute byte range and, when a
+/// matching `ssix` is present, its partial-segment level ranges.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Subsegment {
+    pub reference_id: u32,
+    /// Set if the range holds another `sidx` rather than media.
+    pub is_index: bool,
+    pub offset: u64,
+    pub size: u32,
+    pub start_time: u64,
+    pub duration: u32,
+    pub timescale: u32,
+    pub starts_with_sap: bool,
+    pub sap_type: u8,
+    pub sap_delta_time: u32,
+    pub ranges: Vec<SubsegmentRange>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubsegmentRange {
+    pub level: u8,
+    pub offset: u64,
+    pub size: u32,
+}
+
+/// The timing and sync flag of a sample, without its bytes; see
+/// [`Mp4Reader::read_sample_into`](crate::Mp4Reader::read_sample_into).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SampleInfo {
+    pub start_time: u64,
+    pub duration: u32,
+    pub rendering_offset: i32,
+    pub is_sync: bool,
+}
+
+/// Where a sample is and when it plays, without its bytes; see
+/// [`Mp4Track::sample_index`](crate::Mp4Track::sample_index).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SampleEntry {
+    pub id: u32,
+    /// Decode time in the track's timescale.
+    pub dts: u64,
+    /// Presentation time in the track's timescale, clamped at 0.
+    pub pts: u64,
+    pub offset: u64,
+    pub size: u32,
+    pub is_sync: bool,
 }
 
 #[derive(Debug)]
@@ -624,6 +872,14 @@ pub struct Mp4Sample {
     pub bytes: Bytes,
 }
 
+impl Mp4Sample {
+    /// Presentation timestamp in track timescale units, i.e. `start_time`
+    /// (the decode timestamp) shifted by `rendering_offset`.
+    pub fn presentation_time(&self) -> i64 {
+        self.start_time as i64 + self.rendering_offset as i64
+    }
+}
+
 impl PartialEq for Mp4Sample {
     fn eq(&self, other: &Self) -> bool {
         self.start_time == other.start_time
@@ -657,12 +913,34 @@ pub fn creation_time(creation_time: u64) -> u64 {
     }
 }
 
+/// Converts `ticks` from `from_timescale` to `to_timescale`, rounding down.
+/// A zero `from_timescale` yields 0 and the result saturates at `u64::MAX`.
+pub(crate) fn rescale(ticks: u64, from_timescale: u64, to_timescale: u64) -> u64 {
+    if from_timescale == 0 {
+        return 0;
+    }
+    let value = ticks as u128 * to_timescale as u128 / from_timescale as u128;
+    value.min(u64::MAX as u128) as u64
+}
+
+/// The current time in seconds since the MP4 epoch (1904-01-01).
+pub(crate) fn mp4_time_now() -> u64 {
+    let unix_time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or(0);
+    unix_time + 2082844800
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub enum DataType {
     Binary = 0x000000,
     Text = 0x000001,
+    /// JPEG image.
     Image = 0x00000D,
+    Png = 0x00000E,
     TempoCpil = 0x000015,
+    Bmp = 0x00001B,
 }
 
 #[allow(clippy::derivable_impls)]
@@ -679,13 +957,15 @@ impl TryFrom<u32> for DataType {
             0x000000 => Ok(DataType::Binary),
             0x000001 => Ok(DataType::Text),
             0x00000D => Ok(DataType::Image),
+            0x00000E => Ok(DataType::Png),
             0x000015 => Ok(DataType::TempoCpil),
+            0x00001B => Ok(DataType::Bmp),
             _ => Err(Error::InvalidData("invalid data type")),
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
 pub enum MetadataKey {
     Title,
     Year,
@@ -695,17 +975,19 @@ pub enum MetadataKey {
 
 pub trait Metadata<'a> {
     /// The video's title
-    fn title(&self) -> Option<Cow<str>>;
+    fn title(&self) -> Option<Cow<'_, str>>;
     /// The video's release year
     fn year(&self) -> Option<u32>;
     /// The video's poster (cover art)
     fn poster(&self) -> Option<&[u8]>;
+    /// The image format of the poster
+    fn poster_type(&self) -> Option<DataType>;
     /// The video's summary
-    fn summary(&self) -> Option<Cow<str>>;
+    fn summary(&self) -> Option<Cow<'_, str>>;
 }
 
 impl<'a, T: Metadata<'a>> Metadata<'a> for &'a T {
-    fn title(&self) -> Option<Cow<str>> {
+    fn title(&self) -> Option<Cow<'_, str>> {
         (**self).title()
     }
 
@@ -717,13 +999,17 @@ impl<'a, T: Metadata<'a>> Metadata<'a> for &'a T {
         (**self).poster()
     }
 
-    fn summary(&self) -> Option<Cow<str>> {
+    fn poster_type(&self) -> Option<DataType> {
+        (**self).poster_type()
+    }
+
+    fn summary(&self) -> Option<Cow<'_, str>> {
         (**self).summary()
     }
 }
 
 impl<'a, T: Metadata<'a>> Metadata<'a> for Option<T> {
-    fn title(&self) -> Option<Cow<str>> {
+    fn title(&self) -> Option<Cow<'_, str>> {
         self.as_ref().and_then(|t| t.title())
     }
 
@@ -735,7 +1021,11 @@ impl<'a, T: Metadata<'a>> Metadata<'a> for Option<T> {
         self.as_ref().and_then(|t| t.poster())
     }
 
-    fn summary(&self) -> Option<Cow<str>> {
+    fn poster_type(&self) -> Option<DataType> {
+        self.as_ref().and_then(|t| t.poster_type())
+    }
+
+    fn summary(&self) -> Option<Cow<'_, str>> {
         self.as_ref().and_then(|t| t.summary())
     }
 }
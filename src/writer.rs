@@ -1,6 +1,9 @@
 use byteorder::{BigEndian, WriteBytesExt};
-use std::io::{Seek, SeekFrom, Write};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::io::{Read, Seek, SeekFrom, Write};
 
+use crate::mp4box::ilst::IlstItemBox;
 use crate::mp4box::*;
 use crate::track::Mp4TrackWriter;
 use crate::*;
@@ -11,15 +14,73 @@ pub struct Mp4Config {
     pub minor_version: u32,
     pub compatible_brands: Vec<FourCC>,
     pub timescale: u32,
+    pub rate: FixedPointU16,
+    pub write_iods: bool,
+
+    /// Target chunk duration, in `timescale` units, for interleaving all
+    /// tracks: buffered chunks are written out earliest first. 0 keeps the
+    /// default per-track chunking.
+    pub interleave_duration: u32,
+
+    /// Leaves creation and modification times at 0 instead of the time of
+    /// writing, so that the same input always produces the same bytes.
+    pub deterministic: bool,
+
+    /// Size in bytes, header included, of a `free` box written between the
+    /// `ftyp` and the `mdat`, e.g. to later overwrite with a `moov` for
+    /// faststart. 0 writes no `free` box. Only used by [`Mp4Writer`].
+    pub free_size: u32,
+}
+
+/// An `isom` file with brands for AVC and MP4 v1 players, a timescale of
+/// 1000 and no optional boxes.
+impl Default for Mp4Config {
+    fn default() -> Self {
+        Self {
+            major_brand: FourCC::from(*b"isom"),
+            minor_version: 512,
+            compatible_brands: vec![
+                FourCC::from(*b"isom"),
+                FourCC::from(*b"iso2"),
+                FourCC::from(*b"avc1"),
+                FourCC::from(*b"mp41"),
+            ],
+            timescale: 1000,
+            rate: FixedPointU16::new(1),
+            write_iods: false,
+            interleave_duration: 0,
+            deterministic: false,
+            free_size: 0,
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct Mp4Writer<W> {
     writer: W,
     tracks: Vec<Mp4TrackWriter>,
+    // Position of the `wide` box in front of the mdat, where a 64-bit mdat
+    // header starts.
     mdat_pos: u64,
+    free_size: u32,
     timescale: u32,
+    rate: FixedPointU16,
+    write_iods: bool,
+    interleave_duration: u32,
+    deterministic: bool,
     duration: u64,
+    ilst: IlstBox,
+    metadata_handler_name: String,
+    keyed_metadata: Vec<(String, DataBox)>,
+    negative_composition_offsets: Option<bool>,
+    // Position of the `moov` written by the last checkpoint, if any.
+    checkpoint_pos: Option<u64>,
+    // Set by write_fragment_start, with each track's samples buffered for
+    // the next fragment and the decode time that fragment starts at.
+    fragmented: bool,
+    fragment_runs: Vec<FragmentRun>,
+    fragment_decode_times: Vec<u64>,
+    sequence_number: u32,
 }
 
 impl<W> Mp4Writer<W> {
@@ -36,15 +97,8 @@ impl<W> Mp4Writer<W> {
     ///
     /// # fn main() -> mp4::Result<()> {
     /// let config = Mp4Config {
-    ///     major_brand: str::parse("isom").unwrap(),
-    ///     minor_version: 512,
-    ///     compatible_brands: vec![
-    ///         str::parse("isom").unwrap(),
-    ///         str::parse("iso2").unwrap(),
-    ///         str::parse("avc1").unwrap(),
-    ///         str::parse("mp41").unwrap(),
-    ///     ],
     ///     timescale: 1000,
+    ///     ..Default::default()
     /// };
     ///
     /// let data = Cursor::new(Vec::<u8>::new());
@@ -60,7 +114,16 @@ impl<W> Mp4Writer<W> {
 }
 
 impl<W: Write + Seek> Mp4Writer<W> {
+    /// Writes the `ftyp`, the optional `free` box, then an 8 byte `wide` box
+    /// and the `mdat` header. As in QuickTime files, if the `mdat` outgrows
+    /// a 32-bit size, [`write_end`](Self::write_end) turns the `wide` box and
+    /// the `mdat` header into a single 16 byte header with a 64-bit size,
+    /// without moving any sample data.
     pub fn write_start(mut writer: W, config: &Mp4Config) -> Result<Self> {
+        if config.timescale == 0 {
+            return Err(Error::InvalidData("timescale must be non-zero"));
+        }
+
         let ftyp = FtypBox {
             major_brand: config.major_brand,
             minor_version: config.minor_version,
@@ -68,30 +131,109 @@ impl<W: Write + Seek> Mp4Writer<W> {
         };
         ftyp.write_box(&mut writer)?;
 
-        // TODO largesize
+        if config.free_size > 0 {
+            if (config.free_size as u64) < HEADER_SIZE {
+                return Err(Error::InvalidData("free box is smaller than its header"));
+            }
+            BoxHeader::new(BoxType::FreeBox, config.free_size as u64).write(&mut writer)?;
+            writer.write_all(&vec![0; config.free_size as usize - HEADER_SIZE as usize])?;
+        }
+
         let mdat_pos = writer.stream_position()?;
-        BoxHeader::new(BoxType::MdatBox, HEADER_SIZE).write(&mut writer)?;
         BoxHeader::new(BoxType::WideBox, HEADER_SIZE).write(&mut writer)?;
+        BoxHeader::new(BoxType::MdatBox, HEADER_SIZE).write(&mut writer)?;
 
         let tracks = Vec::new();
         let timescale = config.timescale;
+        let rate = config.rate;
+        let write_iods = config.write_iods;
+        let interleave_duration = config.interleave_duration;
+        let deterministic = config.deterministic;
         let duration = 0;
         Ok(Self {
             writer,
             tracks,
             mdat_pos,
+            free_size: config.free_size,
             timescale,
+            rate,
+            write_iods,
+            interleave_duration,
+            deterministic,
             duration,
+            ilst: IlstBox::default(),
+            metadata_handler_name: String::new(),
+            keyed_metadata: Vec::new(),
+            negative_composition_offsets: None,
+            checkpoint_pos: None,
+            fragmented: false,
+            fragment_runs: Vec::new(),
+            fragment_decode_times: Vec::new(),
+            sequence_number: 0,
         })
     }
 
     pub fn add_track(&mut self, config: &TrackConfig) -> Result<()> {
         let track_id = self.tracks.len() as u32 + 1;
-        let track = Mp4TrackWriter::new(track_id, config)?;
+        let mut track = Mp4TrackWriter::new(track_id, config)?;
+        if self.interleave_duration > 0 {
+            let duration = rescale(
+                self.interleave_duration as u64,
+                self.timescale as u64,
+                config.timescale as u64,
+            );
+            track.set_interleave_duration(duration.clamp(1, u32::MAX as u64) as u32);
+        }
         self.tracks.push(track);
         Ok(())
     }
 
+    /// Sets the cover art (`covr`) written in the `moov/udta/meta/ilst`
+    /// metadata. `data_type` must be one of the image types: [`DataType::Image`]
+    /// (JPEG), [`DataType::Png`] or [`DataType::Bmp`].
+    pub fn set_cover_art(&mut self, bytes: Vec<u8>, data_type: DataType) -> Result<()> {
+        if !matches!(data_type, DataType::Image | DataType::Png | DataType::Bmp) {
+            return Err(Error::InvalidData(
+                "cover art must be a JPEG, PNG or BMP image",
+            ));
+        }
+        self.ilst.items.insert(
+            MetadataKey::Poster,
+            IlstItemBox {
+                data: DataBox {
+                    data: bytes,
+                    data_type,
+                },
+            },
+        );
+        Ok(())
+    }
+
+    /// Sets the `hdlr` name of the `mdir` metadata handler written with the
+    /// `ilst` items. It is empty by default.
+    pub fn set_metadata_handler_name(&mut self, name: &str) {
+        self.metadata_handler_name = name.to_owned();
+    }
+
+    /// Sets a QuickTime metadata item, written in `moov/meta` under an `mdta`
+    /// handler with its `key` (e.g. `com.apple.quicktime.location.ISO6709`)
+    /// listed in `keys`. Setting a key again replaces its value.
+    pub fn set_keyed_metadata(&mut self, key: &str, data: DataBox) {
+        if let Some(item) = self.keyed_metadata.iter_mut().find(|(k, _)| k == key) {
+            item.1 = data;
+        } else {
+            self.keyed_metadata.push((key.to_owned(), data));
+        }
+    }
+
+    /// Writes each track's composition offsets centered around zero, as a
+    /// version 1 `ctts` with negative offsets, for players that prefer that to
+    /// an edit list. If `write_cslg` is set, a `cslg` box describing the
+    /// shifted offsets is written as well.
+    pub fn set_negative_composition_offsets(&mut self, write_cslg: bool) {
+        self.negative_composition_offsets = Some(write_cslg);
+    }
+
     fn update_durations(&mut self, track_dur: u64) {
         if track_dur > self.duration {
             self.duration = track_dur;
@@ -99,6 +241,11 @@ impl<W: Write + Seek> Mp4Writer<W> {
     }
 
     pub fn write_sample(&mut self, track_id: u32, sample: &Mp4Sample) -> Result<()> {
+        if self.fragmented {
+            return Err(Error::InvalidData(
+                "fragmented output is written with write_fragment_sample",
+            ));
+        }
         if track_id == 0 {
             return Err(Error::TrakNotFound(track_id));
         }
@@ -111,39 +258,1020 @@ impl<W: Write + Seek> Mp4Writer<W> {
 
         self.update_durations(track_dur);
 
+        if self.interleave_duration > 0 {
+            self.write_interleaved_chunks(false)?;
+        }
+
         Ok(())
     }
 
+    /// Writes buffered chunks in order of their start time. A chunk is only
+    /// written once no other track has buffered samples that start earlier,
+    /// and either it is full or another track has run more than the
+    /// interleave duration past its start, e.g. when the chunk belongs to a
+    /// sparse subtitle track, unless `flush_all` is set.
+    fn write_interleaved_chunks(&mut self, flush_all: bool) -> Result<()> {
+        loop {
+            let earliest = self
+                .tracks
+                .iter()
+                .enumerate()
+                .filter_map(|(i, track)| track.chunk_start_time().map(|time| (i, time)))
+                .min_by(|(_, (a, a_scale)), (_, (b, b_scale))| {
+                    (*a as u128 * *b_scale as u128).cmp(&(*b as u128 * *a_scale as u128))
+                });
+
+            match earliest {
+                Some((i, start))
+                    if flush_all
+                        || self.tracks[i].is_chunk_ready()
+                        || self.is_overtaken(i, start) =>
+                {
+                    self.tracks[i].write_chunk(&mut self.writer)?;
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    /// Whether a track other than `track_idx` has samples more than the
+    /// interleave duration past `start`, a decode time and timescale.
+    fn is_overtaken(&self, track_idx: usize, (start, scale): (u64, u32)) -> bool {
+        let limit = start as u128 * self.timescale as u128
+            + self.interleave_duration as u128 * scale as u128;
+        self.tracks
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| i != track_idx)
+            .any(|(_, track)| {
+                let (end, end_scale) = track.end_time();
+                end as u128 * scale as u128 * self.timescale as u128 > limit * end_scale as u128
+            })
+    }
+
+    /// Writes out the samples buffered for `track_id` as a chunk, even if the
+    /// chunk isn't full yet.
+    ///
+    /// Useful for capture applications that want buffered samples on disk at
+    /// a cue point rather than waiting for the chunk duration to elapse.
+    pub fn flush_chunk(&mut self, track_id: u32) -> Result<()> {
+        if track_id == 0 {
+            return Err(Error::TrakNotFound(track_id));
+        }
+
+        if let Some(ref mut track) = self.tracks.get_mut(track_id as usize - 1) {
+            track.write_chunk(&mut self.writer)
+        } else {
+            Err(Error::TrakNotFound(track_id))
+        }
+    }
+
     fn update_mdat_size(&mut self) -> Result<()> {
         let mdat_end = self.writer.stream_position()?;
-        let mdat_size = mdat_end - self.mdat_pos;
-        if mdat_size > std::u32::MAX as u64 {
+        let mdat_size = mdat_end - self.mdat_pos - HEADER_SIZE;
+        if mdat_size > u32::MAX as u64 {
+            // Extend the header over the wide box, with a 64-bit size.
             self.writer.seek(SeekFrom::Start(self.mdat_pos))?;
-            self.writer.write_u32::<BigEndian>(1)?;
-            self.writer.seek(SeekFrom::Start(self.mdat_pos + 8))?;
-            self.writer.write_u64::<BigEndian>(mdat_size)?;
+            BoxHeader::new(BoxType::MdatBox, mdat_end - self.mdat_pos).write(&mut self.writer)?;
         } else {
-            self.writer.seek(SeekFrom::Start(self.mdat_pos))?;
+            self.writer
+                .seek(SeekFrom::Start(self.mdat_pos + HEADER_SIZE))?;
             self.writer.write_u32::<BigEndian>(mdat_size as u32)?;
         }
         self.writer.seek(SeekFrom::Start(mdat_end))?;
         Ok(())
     }
 
+    /// Writes out the remaining samples followed by the `moov`, or with
+    /// fragmented output, the last fragment.
     pub fn write_end(&mut self) -> Result<()> {
+        if self.fragmented {
+            return self.write_fragment_end();
+        }
+        let moov = self.finish()?;
+        moov.write_box(&mut self.writer)?;
+        self.free_checkpoint()?;
+        Ok(())
+    }
+
+    /// Writes out the samples so far followed by a `moov` describing them,
+    /// so that if recording stops before [`write_end`](Self::write_end), the
+    /// file still plays up to this point.
+    ///
+    /// Later samples go into a new `mdat`, sized to run to the end of the
+    /// file until it is closed. Once the next checkpoint or `write_end` has
+    /// written its own `moov`, this one is turned into a `free` box.
+    pub fn checkpoint(&mut self) -> Result<()> {
+        if self.fragmented {
+            return Err(Error::InvalidData(
+                "checkpoints are not supported in fragmented output",
+            ));
+        }
+        let moov = self.finish()?;
+        let moov_pos = self.writer.stream_position()?;
+        moov.write_box(&mut self.writer)?;
+        self.free_checkpoint()?;
+        self.checkpoint_pos = Some(moov_pos);
+
+        self.mdat_pos = self.writer.stream_position()?;
+        BoxHeader::new(BoxType::WideBox, HEADER_SIZE).write(&mut self.writer)?;
+        BoxHeader::new(BoxType::MdatBox, 0).write(&mut self.writer)?;
+        Ok(())
+    }
+
+    /// Switches to fragmented output, e.g. for DASH or CMAF segments: the
+    /// `moov` of the tracks added so far is written as the init segment,
+    /// with a `trex` per track in its `mvex`, in place of the `mdat` begun
+    /// by [`write_start`](Self::write_start). Samples are then buffered with
+    /// [`write_fragment_sample`](Self::write_fragment_sample) and written
+    /// out by [`write_fragment_end`](Self::write_fragment_end). Fails if
+    /// samples were already written with [`write_sample`](Self::write_sample).
+    pub fn write_fragment_start(&mut self) -> Result<()> {
+        if self.fragmented {
+            return Err(Error::InvalidData("fragmented output was already started"));
+        }
+        let mut moov = self.finish()?;
+        if self.checkpoint_pos.is_some()
+            || self.writer.stream_position()? != self.mdat_pos + 2 * HEADER_SIZE
+        {
+            return Err(Error::InvalidData(
+                "samples were written before fragmented output was started",
+            ));
+        }
+
+        moov.mvhd.next_track_id = moov.traks.len() as u32 + 1;
+        moov.mvex = Some(MvexBox {
+            mehd: None,
+            trexs: moov
+                .traks
+                .iter()
+                .map(|trak| TrexBox {
+                    version: 0,
+                    flags: 0,
+                    track_id: trak.tkhd.track_id,
+                    default_sample_description_index: 1,
+                    default_sample_duration: 0,
+                    default_sample_size: 0,
+                    default_sample_flags: 0,
+                })
+                .collect(),
+        });
+        self.writer.seek(SeekFrom::Start(self.mdat_pos))?;
+        moov.write_box(&mut self.writer)?;
+
+        self.fragment_runs = self.tracks.iter().map(|_| FragmentRun::default()).collect();
+        self.fragment_decode_times = vec![0; self.tracks.len()];
+        self.fragmented = true;
+        Ok(())
+    }
+
+    /// Buffers a sample of `track_id` for the next fragment.
+    pub fn write_fragment_sample(&mut self, track_id: u32, sample: &Mp4Sample) -> Result<()> {
+        if !self.fragmented {
+            return Err(Error::InvalidData("fragmented output was not started"));
+        }
+        if track_id == 0 {
+            return Err(Error::TrakNotFound(track_id));
+        }
+
+        match self.fragment_runs.get_mut(track_id as usize - 1) {
+            Some(run) => {
+                run.push(sample);
+                Ok(())
+            }
+            None => Err(Error::TrakNotFound(track_id)),
+        }
+    }
+
+    /// Writes the samples buffered since the last fragment as a `moof`, with
+    /// a `traf` for each track that has any, followed by an `mdat` with their
+    /// data in the same order. Each `tfhd` is default-base-is-moof, so every
+    /// `trun` data_offset is relative to the `moof`, and each `tfdt` carries
+    /// on from the track's previous fragment. Does nothing if no samples are
+    /// buffered.
+    pub fn write_fragment_end(&mut self) -> Result<()> {
+        if !self.fragmented {
+            return Err(Error::InvalidData("fragmented output was not started"));
+        }
+
+        let mut trafs = Vec::new();
+        let mut run_offsets = Vec::new();
+        let mut data = Vec::new();
+        for (i, run) in self.fragment_runs.iter_mut().enumerate() {
+            if run.is_empty() {
+                continue;
+            }
+            let (trun, run_data, duration) = run.take();
+            trafs.push(TrafBox {
+                tfhd: TfhdBox {
+                    flags: TfhdBox::FLAG_DEFAULT_BASE_IS_MOOF,
+                    track_id: i as u32 + 1,
+                    ..Default::default()
+                },
+                tfdt: Some(TfdtBox {
+                    version: 1,
+                    flags: 0,
+                    base_media_decode_time: self.fragment_decode_times[i],
+                }),
+                trun: Some(trun),
+                senc: None,
+            });
+            self.fragment_decode_times[i] += duration;
+            run_offsets.push(data.len() as u64);
+            data.extend_from_slice(&run_data);
+        }
+        if trafs.is_empty() {
+            return Ok(());
+        }
+
+        self.sequence_number += 1;
+        let mut moof = MoofBox {
+            mfhd: MfhdBox {
+                sequence_number: self.sequence_number,
+                ..Default::default()
+            },
+            trafs,
+        };
+        let payload_offset = moof.box_size() + HEADER_SIZE;
+        for (traf, run_offset) in moof.trafs.iter_mut().zip(run_offsets) {
+            if let Some(ref mut trun) = traf.trun {
+                let data_offset = i32::try_from(payload_offset + run_offset)
+                    .map_err(|_| Error::InvalidData("trun data_offset is out of range"))?;
+                trun.data_offset = Some(data_offset);
+            }
+        }
+
+        moof.write_box(&mut self.writer)?;
+        BoxHeader::new(BoxType::MdatBox, HEADER_SIZE + data.len() as u64)
+            .write(&mut self.writer)?;
+        self.writer.write_all(&data)?;
+        Ok(())
+    }
+
+    /// Turns the `moov` of the last checkpoint into a `free` box.
+    fn free_checkpoint(&mut self) -> Result<()> {
+        if let Some(pos) = self.checkpoint_pos.take() {
+            let end = self.writer.stream_position()?;
+            self.writer.seek(SeekFrom::Start(pos + 4))?;
+            self.writer
+                .write_u32::<BigEndian>(BoxType::FreeBox.into())?;
+            self.writer.seek(SeekFrom::Start(end))?;
+        }
+        Ok(())
+    }
+
+    /// Writes out the remaining samples and returns the `moov`, with the
+    /// writer positioned at the end of the `mdat`.
+    fn finish(&mut self) -> Result<MoovBox> {
+        if self.interleave_duration > 0 {
+            self.write_interleaved_chunks(true)?;
+        }
+
         let mut moov = MoovBox::default();
 
         for track in self.tracks.iter_mut() {
+            if let Some(write_cslg) = self.negative_composition_offsets {
+                track.set_negative_composition_offsets(write_cslg);
+            }
             moov.traks.push(track.write_end(&mut self.writer)?);
         }
         self.update_mdat_size()?;
 
         moov.mvhd.timescale = self.timescale;
         moov.mvhd.duration = self.duration;
+        moov.mvhd.rate = self.rate;
+        if self.write_iods {
+            moov.iods = Some(IodsBox::default());
+        }
+        if !self.ilst.items.is_empty() {
+            moov.udta = Some(UdtaBox {
+                meta: Some(MetaBox::Mdir {
+                    name: self.metadata_handler_name.clone(),
+                    ilst: Some(self.ilst.clone()),
+                }),
+                ..Default::default()
+            });
+        }
+        if !self.keyed_metadata.is_empty() {
+            let mut keys = KeysBox::default();
+            let mut items = BTreeMap::new();
+            for (key, data) in self.keyed_metadata.iter() {
+                keys.entries.push(KeysEntry {
+                    namespace: FourCC::from(*b"mdta"),
+                    key: key.clone(),
+                });
+                items.insert(keys.entries.len() as u32, data.clone());
+            }
+            moov.meta = Some(MetaBox::Mdta { keys, items });
+        }
         if moov.mvhd.duration > (u32::MAX as u64) {
             moov.mvhd.version = 1
         }
+        if !self.deterministic {
+            set_creation_time(&mut moov, mp4_time_now());
+        }
+        Ok(moov)
+    }
+}
+
+impl<W: Read + Write + Seek> Mp4Writer<W> {
+    /// Like [`write_end`](Self::write_end), but places the `moov` before the
+    /// `mdat` so that playback can start before the whole file is downloaded.
+    ///
+    /// The `moov` is written into the `free` box reserved with
+    /// [`Mp4Config::free_size`] if it fits. Otherwise the `mdat` is moved
+    /// forward to make room, which reads it back through the writer, and
+    /// every chunk offset is shifted to match. Not available after a
+    /// [`checkpoint`](Self::checkpoint), which splits the samples over
+    /// several `mdat` boxes.
+    pub fn write_end_faststart(&mut self) -> Result<()> {
+        if self.fragmented {
+            return Err(Error::InvalidData(
+                "faststart is not supported in fragmented output",
+            ));
+        }
+        if self.checkpoint_pos.is_some() {
+            return Err(Error::InvalidData(
+                "faststart is not supported after a checkpoint",
+            ));
+        }
+        let mut moov = self.finish()?;
+        let mdat_end = self.writer.stream_position()?;
+
+        let available = self.free_size as u64;
+        let moov_pos = self.mdat_pos - available;
+        let chunk_offsets: Vec<Vec<u64>> = moov.traks.iter().map(chunk_offsets).collect();
+
+        // Shifting can turn an stco into a co64, growing the moov and thus
+        // the shift; it settles once every offset needs 64 bits or fits in 32.
+        let (delta, padding) = loop {
+            let moov_size = moov.box_size();
+            let (delta, padding) = if moov_size == available || moov_size + HEADER_SIZE <= available
+            {
+                (0, available - moov_size)
+            } else if moov_size < available {
+                // Too little room left for a free box header: pad past it.
+                (HEADER_SIZE, available - moov_size + HEADER_SIZE)
+            } else {
+                (moov_size - available, 0)
+            };
+            for (trak, offsets) in moov.traks.iter_mut().zip(chunk_offsets.iter()) {
+                set_chunk_offsets(trak, offsets, delta)?;
+            }
+            if moov.box_size() == moov_size {
+                break (delta, padding);
+            }
+        };
+
+        if delta > 0 {
+            self.move_mdat(mdat_end, delta)?;
+        }
+        self.writer.seek(SeekFrom::Start(moov_pos))?;
         moov.write_box(&mut self.writer)?;
+        if padding > 0 {
+            BoxHeader::new(BoxType::FreeBox, padding).write(&mut self.writer)?;
+        }
+        self.writer.seek(SeekFrom::Start(mdat_end + delta))?;
+        Ok(())
+    }
+
+    /// Moves the `mdat`, which ends at `mdat_end`, `delta` bytes forward,
+    /// copying from the end so that the data isn't overwritten before it is
+    /// read.
+    fn move_mdat(&mut self, mdat_end: u64, delta: u64) -> Result<()> {
+        let mut buffer = vec![0u8; MOVE_BUFFER_SIZE.min(mdat_end - self.mdat_pos) as usize];
+        let mut end = mdat_end;
+        while end > self.mdat_pos {
+            let len = (end - self.mdat_pos).min(buffer.len() as u64);
+            let start = end - len;
+            let chunk = &mut buffer[..len as usize];
+            self.writer.seek(SeekFrom::Start(start))?;
+            self.writer.read_exact(chunk)?;
+            self.writer.seek(SeekFrom::Start(start + delta))?;
+            self.writer.write_all(chunk)?;
+            end = start;
+        }
+        self.mdat_pos += delta;
         Ok(())
     }
 }
+
+/// The `mdat` is moved for faststart in pieces of at most this size.
+const MOVE_BUFFER_SIZE: u64 = 1024 * 1024;
+
+fn chunk_offsets(trak: &TrakBox) -> Vec<u64> {
+    let stbl = &trak.mdia.minf.stbl;
+    match (&stbl.stco, &stbl.co64) {
+        (Some(stco), _) => stco.entries.iter().map(|&offset| offset as u64).collect(),
+        (None, Some(co64)) => co64.entries.clone(),
+        (None, None) => Vec::new(),
+    }
+}
+
+/// Sets the chunk offsets of `trak` to `offsets` shifted by `delta`.
+fn set_chunk_offsets(trak: &mut TrakBox, offsets: &[u64], delta: u64) -> Result<()> {
+    let offsets = offsets
+        .iter()
+        .map(|offset| offset.checked_add(delta))
+        .collect::<Option<_>>()
+        .ok_or(Error::InvalidData(
+            "attempt to shift chunk offsets with overflow",
+        ))?;
+    trak.mdia.minf.stbl.set_chunk_offsets(offsets);
+    Ok(())
+}
+
+/// Stamps `time` as the creation and modification time of the movie and of
+/// each track and its media.
+fn set_creation_time(moov: &mut MoovBox, time: u64) {
+    moov.mvhd.creation_time = time;
+    moov.mvhd.modification_time = time;
+    for trak in moov.traks.iter_mut() {
+        trak.tkhd.creation_time = time;
+        trak.tkhd.modification_time = time;
+        trak.mdia.mdhd.creation_time = time;
+        trak.mdia.mdhd.modification_time = time;
+    }
+}
+
+/// An `ftyp` + `moov` assembled directly rather than through [`Mp4Writer`],
+/// e.g. for synthetic files whose sample tables are already known.
+///
+/// Tracks are created from [`TrackConfig`]s with empty sample tables (`stco`
+/// for chunk offsets) that the caller fills in through [`trak_mut`]. Durations
+/// are derived from each track's `stts` when the header is written.
+///
+/// [`trak_mut`]: Mp4Header::trak_mut
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mp4Header {
+    pub ftyp: FtypBox,
+    pub moov: MoovBox,
+}
+
+impl Mp4Header {
+    pub fn new_empty(config: &Mp4Config) -> Self {
+        let ftyp = FtypBox {
+            major_brand: config.major_brand,
+            minor_version: config.minor_version,
+            compatible_brands: config.compatible_brands.clone(),
+        };
+
+        let mut moov = MoovBox::default();
+        moov.mvhd.timescale = config.timescale;
+        moov.mvhd.rate = config.rate;
+        if config.write_iods {
+            moov.iods = Some(IodsBox::default());
+        }
+
+        Self { ftyp, moov }
+    }
+
+    /// Adds a track built from `config` and returns its track id.
+    pub fn add_track(&mut self, config: &TrackConfig) -> Result<u32> {
+        let track_id = self.moov.traks.len() as u32 + 1;
+        let mut trak = Mp4TrackWriter::new(track_id, config)?.into_trak();
+        trak.mdia.minf.stbl.co64 = None;
+        trak.mdia.minf.stbl.stco = Some(StcoBox::default());
+        self.moov.traks.push(trak);
+        Ok(track_id)
+    }
+
+    pub fn trak_mut(&mut self, track_id: u32) -> Result<&mut TrakBox> {
+        if track_id == 0 {
+            return Err(Error::TrakNotFound(track_id));
+        }
+        self.moov
+            .traks
+            .get_mut(track_id as usize - 1)
+            .ok_or(Error::TrakNotFound(track_id))
+    }
+
+    /// Fills in the track and movie durations from the `stts` tables.
+    pub fn update_durations(&mut self) {
+        let movie_timescale = self.moov.mvhd.timescale as u64;
+        let mut movie_duration = 0;
+        for trak in self.moov.traks.iter_mut() {
+            let duration: u64 = trak
+                .mdia
+                .minf
+                .stbl
+                .stts
+                .entries
+                .iter()
+                .map(|entry| entry.sample_count as u64 * entry.sample_delta as u64)
+                .sum();
+            trak.mdia.mdhd.duration = duration;
+            if duration > u32::MAX as u64 {
+                trak.mdia.mdhd.version = 1;
+            }
+
+            let media_timescale = trak.mdia.mdhd.timescale as u64;
+            if let Some(tkhd_duration) = (duration * movie_timescale).checked_div(media_timescale) {
+                trak.tkhd.duration = tkhd_duration;
+            }
+            if trak.tkhd.duration > u32::MAX as u64 {
+                trak.tkhd.version = 1;
+            }
+            movie_duration = movie_duration.max(trak.tkhd.duration);
+        }
+
+        self.moov.mvhd.duration = movie_duration;
+        if movie_duration > u32::MAX as u64 {
+            self.moov.mvhd.version = 1;
+        }
+        self.moov.mvhd.next_track_id = self.moov.traks.len() as u32 + 1;
+    }
+
+    /// Updates durations and writes the `ftyp` and `moov` boxes.
+    pub fn write<W: Write>(&mut self, writer: &mut W) -> Result<u64> {
+        self.update_durations();
+        self.ftyp.write_box(writer)?;
+        self.moov.write_box(writer)?;
+        Ok(self.ftyp.box_size() + self.moov.box_size())
+    }
+}
+
+const SAMPLE_FLAGS_SYNC: u32 = 0x0200_0000;
+const SAMPLE_FLAGS_NON_SYNC: u32 = 0x0101_0000;
+
+/// Samples buffered for the `trun` of one `traf`, with their data.
+#[derive(Debug, Default)]
+struct FragmentRun {
+    trun: TrunBox,
+    data: Vec<u8>,
+    duration: u64,
+}
+
+impl FragmentRun {
+    fn is_empty(&self) -> bool {
+        self.trun.sample_count == 0
+    }
+
+    fn push(&mut self, sample: &Mp4Sample) {
+        let trun = &mut self.trun;
+        trun.sample_count += 1;
+        trun.sample_durations.push(sample.duration);
+        trun.sample_sizes.push(sample.bytes.len() as u32);
+        trun.sample_flags.push(if sample.is_sync {
+            SAMPLE_FLAGS_SYNC
+        } else {
+            SAMPLE_FLAGS_NON_SYNC
+        });
+        trun.sample_cts.push(sample.rendering_offset as u32);
+        if sample.rendering_offset < 0 {
+            trun.version = 1;
+        }
+        self.data.extend_from_slice(&sample.bytes);
+        self.duration += sample.duration as u64;
+    }
+
+    /// Returns the `trun`, with a `data_offset` to fill in, the data and the
+    /// duration of the buffered samples, leaving the run empty.
+    fn take(&mut self) -> (TrunBox, Vec<u8>, u64) {
+        let mut trun = std::mem::take(&mut self.trun);
+        trun.flags = TrunBox::FLAG_DATA_OFFSET
+            | TrunBox::FLAG_SAMPLE_DURATION
+            | TrunBox::FLAG_SAMPLE_SIZE
+            | TrunBox::FLAG_SAMPLE_FLAGS;
+        if trun.sample_cts.iter().any(|cts| *cts != 0) {
+            trun.flags |= TrunBox::FLAG_SAMPLE_CTS;
+        }
+        trun.data_offset = Some(0);
+        let data = std::mem::take(&mut self.data);
+        (trun, data, std::mem::take(&mut self.duration))
+    }
+}
+
+/// Init segment options of [`Mp4FragmentWriter`], written to its `mvex`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FragmentConfig {
+    /// ID of the track, e.g. to avoid clashes when segments of several
+    /// single-track streams are combined. Must be non-zero.
+    pub track_id: u32,
+
+    /// Total duration of all fragments, in `Mp4Config::timescale` units,
+    /// written as `mehd` when known up front.
+    pub fragment_duration: Option<u64>,
+
+    /// `trex` defaults for samples whose `tfhd` and `trun` don't override
+    /// them. The writer's `trun`s always carry per-sample values.
+    pub default_sample_duration: u32,
+    pub default_sample_size: u32,
+    pub default_sample_flags: u32,
+
+    /// Begin each media segment with its own `styp` and `sidx`, so segments
+    /// can be served on their own. The chunks of a segment are held in
+    /// memory until [`end_segment`](Mp4FragmentWriter::end_segment) writes
+    /// it, as the `sidx` has to precede them.
+    pub index_segments: bool,
+}
+
+impl Default for FragmentConfig {
+    fn default() -> Self {
+        FragmentConfig {
+            track_id: 1,
+            fragment_duration: None,
+            default_sample_duration: 0,
+            default_sample_size: 0,
+            default_sample_flags: 0,
+            index_segments: false,
+        }
+    }
+}
+
+/// Writes a single-track fragmented (CMAF-style) MP4: an init segment
+/// followed by media segments, each made of one or more `moof`+`mdat` chunks.
+#[derive(Debug)]
+pub struct Mp4FragmentWriter<W> {
+    writer: W,
+    styp: StypBox,
+    track_id: u32,
+    timescale: u32,
+    sequence_number: u32,
+    base_media_decode_time: u64,
+    chunk: FragmentRun,
+    segment_start_time: u64,
+    segment_references: Vec<SidxReference>,
+    // Where the current segment's styp was written, if no chunk followed yet.
+    segment_offset: Option<u64>,
+    // Start offset and sidx reference of each chunk, for write_end.
+    fragments: Vec<(u64, SidxReference)>,
+    fragments_start_time: u64,
+    index_segments: bool,
+    // Chunks of the current segment not written yet, with index_segments.
+    segment_data: Vec<u8>,
+}
+
+impl<W> Mp4FragmentWriter<W> {
+    /// Consume self, returning the inner writer.
+    pub fn into_writer(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Read + Write + Seek> Mp4FragmentWriter<W> {
+    /// Opens a fragmented file written by [`write_init`] (and possibly some
+    /// segments) to append further segments at its end. The init segment is
+    /// left untouched; the `tfdt` and `mfhd` sequence number continue from
+    /// the last `moof` in the file.
+    ///
+    /// [`write_init`]: Mp4FragmentWriter::write_init
+    pub fn open_append(mut writer: W) -> Result<Self> {
+        let size = writer.seek(SeekFrom::End(0))?;
+        writer.seek(SeekFrom::Start(0))?;
+
+        let mp4 = Mp4Reader::read_header(&mut writer, size)?;
+        let mvex = mp4
+            .moov
+            .mvex
+            .as_ref()
+            .ok_or(Error::BoxNotFound(BoxType::MvexBox))?;
+        let track_id = match mvex.trexs[..] {
+            [ref trex] => trex.track_id,
+            _ => {
+                return Err(Error::InvalidData(
+                    "only single-track fragmented files can be appended to",
+                ))
+            }
+        };
+        let track = mp4
+            .tracks()
+            .get(&track_id)
+            .ok_or(Error::TrakNotFound(track_id))?;
+
+        let sample_count = track.sample_count();
+        let base_media_decode_time = if sample_count > 0 {
+            let (start_time, duration) = track.sample_time(sample_count)?;
+            start_time + duration as u64
+        } else {
+            0
+        };
+        let sequence_number = mp4
+            .moofs
+            .iter()
+            .map(|moof| moof.mfhd.sequence_number)
+            .max()
+            .unwrap_or(0);
+        let styp = StypBox {
+            major_brand: mp4.ftyp.major_brand,
+            minor_version: mp4.ftyp.minor_version,
+            compatible_brands: mp4.ftyp.compatible_brands.clone(),
+        };
+        let timescale = track.timescale();
+
+        writer.seek(SeekFrom::End(0))?;
+
+        Ok(Self {
+            writer,
+            styp,
+            track_id,
+            timescale,
+            sequence_number,
+            base_media_decode_time,
+            chunk: FragmentRun::default(),
+            segment_start_time: base_media_decode_time,
+            segment_references: Vec::new(),
+            segment_offset: None,
+            fragments: Vec::new(),
+            fragments_start_time: base_media_decode_time,
+            index_segments: false,
+            segment_data: Vec::new(),
+        })
+    }
+
+    /// Ends the open segment, if any, and indexes the file for the DASH
+    /// on-demand profile: a top-level `sidx` with one reference per chunk
+    /// is inserted in front of the chunks written by this writer, moving
+    /// them back. Returns the `sidx` written.
+    pub fn write_end(&mut self) -> Result<SidxBox> {
+        self.end_segment()?;
+
+        let start = match self.fragments.first() {
+            Some(&(start, _)) => start,
+            None => return Err(Error::InvalidData("no fragments were written")),
+        };
+        let end = self.writer.seek(SeekFrom::End(0))?;
+        let mut references = Vec::with_capacity(self.fragments.len());
+        for (i, (offset, reference)) in self.fragments.iter().enumerate() {
+            let next = self.fragments.get(i + 1).map_or(end, |(next, _)| *next);
+            references.push(SidxReference {
+                referenced_size: sidx_referenced_size(next - offset)?,
+                ..reference.clone()
+            });
+        }
+        let sidx = SidxBox {
+            version: 1,
+            flags: 0,
+            reference_id: self.track_id,
+            timescale: self.timescale,
+            earliest_presentation_time: self.fragments_start_time,
+            first_offset: 0,
+            references,
+        };
+
+        let mut index = Vec::with_capacity(sidx.box_size() as usize);
+        sidx.write_box(&mut index)?;
+        insert_bytes(&mut self.writer, start, end, &index)?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.fragments.clear();
+
+        Ok(sidx)
+    }
+}
+
+// A sidx reference stores its size in 31 bits.
+fn sidx_referenced_size(size: u64) -> Result<u32> {
+    match u32::try_from(size) {
+        Ok(size) if size <= 0x7FFF_FFFF => Ok(size),
+        _ => Err(Error::InvalidData(
+            "sidx referenced_size does not fit in 31 bits",
+        )),
+    }
+}
+
+// Inserts `bytes` at `offset`, moving the data up to `end` back to make room.
+fn insert_bytes<W: Read + Write + Seek>(
+    writer: &mut W,
+    offset: u64,
+    end: u64,
+    bytes: &[u8],
+) -> Result<()> {
+    let mut buffer = vec![0u8; (end - offset).min(1 << 20) as usize];
+    let mut remaining = end - offset;
+    while remaining > 0 {
+        let len = remaining.min(buffer.len() as u64);
+        let from = offset + remaining - len;
+        writer.seek(SeekFrom::Start(from))?;
+        writer.read_exact(&mut buffer[..len as usize])?;
+        writer.seek(SeekFrom::Start(from + bytes.len() as u64))?;
+        writer.write_all(&buffer[..len as usize])?;
+        remaining -= len;
+    }
+    writer.seek(SeekFrom::Start(offset))?;
+    writer.write_all(bytes)?;
+    Ok(())
+}
+
+impl<W: Write + Seek> Mp4FragmentWriter<W> {
+    /// Writes the init segment (`ftyp` + `moov` with `mvex`) for one track.
+    pub fn write_init(writer: W, config: &Mp4Config, track_config: &TrackConfig) -> Result<Self> {
+        Self::write_init_with(writer, config, track_config, &FragmentConfig::default())
+    }
+
+    /// Like [`write_init`](Self::write_init), with the track ID, `trex`
+    /// defaults and `mehd` duration taken from `fragment_config`.
+    pub fn write_init_with(
+        mut writer: W,
+        config: &Mp4Config,
+        track_config: &TrackConfig,
+        fragment_config: &FragmentConfig,
+    ) -> Result<Self> {
+        if fragment_config.track_id == 0 {
+            return Err(Error::InvalidData("track_id must be non-zero"));
+        }
+
+        let ftyp = FtypBox {
+            major_brand: config.major_brand,
+            minor_version: config.minor_version,
+            compatible_brands: config.compatible_brands.clone(),
+        };
+        ftyp.write_box(&mut writer)?;
+
+        let track_id = fragment_config.track_id;
+        let mut track = Mp4TrackWriter::new(track_id, track_config)?;
+
+        let mut moov = MoovBox::default();
+        moov.mvhd.timescale = config.timescale;
+        moov.mvhd.rate = config.rate;
+        moov.mvhd.next_track_id = track_id.saturating_add(1);
+        if config.write_iods {
+            moov.iods = Some(IodsBox::default());
+        }
+        moov.traks.push(track.write_end(&mut writer)?);
+        moov.mvex = Some(MvexBox {
+            mehd: fragment_config
+                .fragment_duration
+                .map(|fragment_duration| MehdBox {
+                    version: if fragment_duration > u32::MAX as u64 {
+                        1
+                    } else {
+                        0
+                    },
+                    flags: 0,
+                    fragment_duration,
+                }),
+            trexs: vec![TrexBox {
+                version: 0,
+                flags: 0,
+                track_id,
+                default_sample_description_index: 1,
+                default_sample_duration: fragment_config.default_sample_duration,
+                default_sample_size: fragment_config.default_sample_size,
+                default_sample_flags: fragment_config.default_sample_flags,
+            }],
+        });
+        if !config.deterministic {
+            set_creation_time(&mut moov, mp4_time_now());
+        }
+        moov.write_box(&mut writer)?;
+
+        Ok(Self {
+            writer,
+            styp: StypBox {
+                major_brand: config.major_brand,
+                minor_version: config.minor_version,
+                compatible_brands: config.compatible_brands.clone(),
+            },
+            track_id,
+            timescale: track_config.timescale,
+            sequence_number: 0,
+            base_media_decode_time: 0,
+            chunk: FragmentRun::default(),
+            segment_start_time: 0,
+            segment_references: Vec::new(),
+            segment_offset: None,
+            fragments: Vec::new(),
+            fragments_start_time: 0,
+            index_segments: fragment_config.index_segments,
+            segment_data: Vec::new(),
+        })
+    }
+
+    /// Starts a media segment by writing its `styp`. Any segment still open
+    /// is ended first. With [`FragmentConfig::index_segments`] the `styp` is
+    /// written by [`end_segment`](Self::end_segment) instead.
+    pub fn start_segment(&mut self) -> Result<()> {
+        if !self.segment_references.is_empty() || !self.chunk.is_empty() {
+            self.end_segment()?;
+        }
+        if self.index_segments {
+            self.segment_start_time = self.base_media_decode_time;
+            return Ok(());
+        }
+        self.segment_offset = Some(self.writer.stream_position()?);
+        self.styp.write_box(&mut self.writer)?;
+        self.segment_start_time = self.base_media_decode_time;
+        Ok(())
+    }
+
+    /// Buffers a sample into the current chunk.
+    pub fn write_sample(&mut self, sample: &Mp4Sample) -> Result<()> {
+        self.chunk.push(sample);
+        Ok(())
+    }
+
+    /// Writes the buffered samples as one `moof`+`mdat` chunk. The `tfdt`
+    /// continues from where the previous chunk ended.
+    pub fn flush_chunk(&mut self) -> Result<()> {
+        if self.chunk.is_empty() {
+            return Ok(());
+        }
+        let subsegment_duration = u32::try_from(self.chunk.duration)
+            .map_err(|_| Error::InvalidData("sidx subsegment_duration does not fit in 32 bits"))?;
+
+        let (trun, data, duration) = self.chunk.take();
+        let starts_with_sap = trun.sample_flags.first() == Some(&SAMPLE_FLAGS_SYNC);
+
+        self.sequence_number += 1;
+        let mut moof = MoofBox {
+            mfhd: MfhdBox {
+                sequence_number: self.sequence_number,
+                ..Default::default()
+            },
+            trafs: vec![TrafBox {
+                tfhd: TfhdBox {
+                    flags: TfhdBox::FLAG_DEFAULT_BASE_IS_MOOF,
+                    track_id: self.track_id,
+                    ..Default::default()
+                },
+                tfdt: Some(TfdtBox {
+                    version: 1,
+                    flags: 0,
+                    base_media_decode_time: self.base_media_decode_time,
+                }),
+                trun: Some(trun),
+                senc: None,
+            }],
+        };
+        let moof_size = moof.box_size();
+        if let Some(ref mut trun) = moof.trafs[0].trun {
+            trun.data_offset = Some((moof_size + HEADER_SIZE) as i32);
+        }
+
+        let mdat_size = HEADER_SIZE + data.len() as u64;
+        let referenced_size = sidx_referenced_size(moof_size + mdat_size)?;
+
+        // With index_segments, the offset is relative to the segment's
+        // chunks until end_segment writes them.
+        let offset = if self.index_segments {
+            let offset = self.segment_data.len() as u64;
+            moof.write_box(&mut self.segment_data)?;
+            BoxHeader::new(BoxType::MdatBox, mdat_size).write(&mut self.segment_data)?;
+            self.segment_data.extend_from_slice(&data);
+            offset
+        } else {
+            let offset = match self.segment_offset.take() {
+                Some(offset) => offset,
+                None => self.writer.stream_position()?,
+            };
+            moof.write_box(&mut self.writer)?;
+            BoxHeader::new(BoxType::MdatBox, mdat_size).write(&mut self.writer)?;
+            self.writer.write_all(&data)?;
+            offset
+        };
+
+        let reference = SidxReference {
+            reference_type: false,
+            referenced_size,
+            subsegment_duration,
+            starts_with_sap,
+            sap_type: if starts_with_sap { 1 } else { 0 },
+            sap_delta_time: 0,
+        };
+        self.fragments.push((offset, reference.clone()));
+        self.segment_references.push(reference);
+
+        self.base_media_decode_time += duration;
+
+        Ok(())
+    }
+
+    /// Flushes the last chunk and ends the segment, returning a `sidx` with
+    /// one reference per chunk. With [`FragmentConfig::index_segments`] the
+    /// segment is written here as `styp`, that `sidx` and its chunks.
+    /// Otherwise the chunks are already written and the `sidx` is left to
+    /// the caller, e.g. for a separate index segment, as it would have to
+    /// precede them.
+    pub fn end_segment(&mut self) -> Result<SidxBox> {
+        self.flush_chunk()?;
+
+        let sidx = SidxBox {
+            version: 1,
+            flags: 0,
+            reference_id: self.track_id,
+            timescale: self.timescale,
+            earliest_presentation_time: self.segment_start_time,
+            first_offset: 0,
+            references: std::mem::take(&mut self.segment_references),
+        };
+
+        if self.index_segments && !sidx.references.is_empty() {
+            let start = self.writer.stream_position()?;
+            self.styp.write_box(&mut self.writer)?;
+            sidx.write_box(&mut self.writer)?;
+            self.writer.write_all(&self.segment_data)?;
+            self.segment_data.clear();
+
+            // The first chunk's range starts with the styp, as without
+            // index_segments, so write_end's sidx covers the whole file.
+            let chunks = self.fragments.len() - sidx.references.len();
+            let data_offset = start + self.styp.box_size() + sidx.box_size();
+            for (i, (offset, _)) in self.fragments[chunks..].iter_mut().enumerate() {
+                *offset = if i == 0 { start } else { data_offset + *offset };
+            }
+            self.segment_start_time = self.base_media_decode_time;
+        }
+
+        Ok(sidx)
+    }
+}
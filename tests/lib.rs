@@ -1,9 +1,24 @@
 use mp4::{
-    AudioObjectType, AvcProfile, ChannelConfig, MediaType, Metadata, Mp4Reader, SampleFreqIndex,
-    TrackType,
+    AacConfig, Ac3Config, AudioObjectType, Av1Config, AvcCBox, AvcConfig, AvcProfile, BoxHeader,
+    BoxType, Bytes, ChannelConfig, ClliBox, ColrBox, CslgBox, DataBox, DataType, Dec3Box, DinfBox,
+    DvccBox, Eac3Box, Ec3Substream, EdtsBox, ElstBox, ElstEntry, Error, FixedPointU16,
+    FixedPointU8, FragmentConfig, FrmaBox, FtypBox, GmhdBox, GminBox, Gop, HdrConfig, HevcConfig,
+    HvcCArray, HvcCArrayNalu, IodsBox, MdcvBox, MediaConfig, MediaType, MetaBox, Metadata, MfhdBox,
+    MoofBox, Mp4Box, Mp4Config, Mp4FragmentWriter, Mp4Header, Mp4Reader, Mp4Sample, Mp4Writer,
+    NmhdBox, OpusConfig, PaspBox, RawSampleEntryConfig, ReadBox, RecoveryTrack, RtpHintConfig,
+    SampleFreqIndex, SampleInfo, SchmBox, SdtpBox, SdtpEntry, SidxBox, SidxReference, SinfBox,
+    SsixBox, SsixRange, SsixSubsegment, StscEntry, StsdBox, SttsEntry, SubsegmentRange, TextBox,
+    TfdtBox, TfhdBox, TrackConfig, TrackType, TrafBox, TrexBox, TrunBox, TtxtConfig, VmhdBox,
+    Vp9Config, Warning, WriteBox,
 };
+#[cfg(feature = "cenc")]
+use mp4::{SencBox, SencEntry, SencSubsample, TencBox};
+use std::cell::Cell;
+#[cfg(feature = "cenc")]
+use std::collections::HashMap;
 use std::fs::{self, File};
-use std::io::BufReader;
+use std::io::{BufReader, Cursor, Read, Seek, SeekFrom};
+use std::rc::Rc;
 use std::time::Duration;
 
 #[test]
@@ -118,6 +133,16 @@ fn test_read_mp4() {
     assert_eq!(track2.bitrate(), 67695);
 }
 
+#[test]
+fn test_media_track_count() {
+    let mp4 = get_reader("tests/samples/minimal.mp4");
+    assert_eq!(mp4.media_track_count(MediaType::H264), 1);
+    assert_eq!(mp4.media_track_count(MediaType::AAC), 1);
+    assert_eq!(mp4.media_track_count(MediaType::H265), 0);
+    assert!(mp4.has_media(MediaType::AAC));
+    assert!(!mp4.has_media(MediaType::VP9));
+}
+
 #[test]
 fn test_read_extended_audio_object_type() {
     // Extended audio object type and sample rate index of 15
@@ -209,3 +234,5560 @@ fn test_read_fragments() {
     let eos = mp4_fragment.read_sample(1, 2);
     assert!(eos.is_err());
 }
+
+#[test]
+fn test_validate_data_reference_index() {
+    let mut mp4 = get_reader("tests/samples/minimal.mp4");
+    assert!(mp4.validate().is_empty());
+
+    let avc1 = mp4.moov.traks[0].mdia.minf.stbl.stsd.avc1.as_mut().unwrap();
+    avc1.data_reference_index = 2;
+
+    assert_eq!(
+        mp4.validate(),
+        vec![Warning::DataReferenceIndexOutOfRange(1, 2, 1)]
+    );
+}
+
+#[test]
+fn test_write_hint_track() {
+    let config = Mp4Config {
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        ..Default::default()
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+
+    writer
+        .add_track(&TrackConfig::from(AvcConfig {
+            width: 320,
+            height: 240,
+            seq_param_set: vec![0x67, 0x64, 0x00, 0x0D],
+            pic_param_set: vec![0x68, 0xEB, 0xE3, 0xCB],
+        }))
+        .unwrap();
+    writer
+        .add_track(&TrackConfig::from(RtpHintConfig {
+            reference_track_id: 1,
+            max_packet_size: 1450,
+            rtp_timescale: 90000,
+            sdp: String::from("m=video 0 RTP/AVP 96\r\n"),
+        }))
+        .unwrap();
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+
+    let hint = mp4.tracks().get(&2).unwrap();
+    assert_eq!(hint.track_type().unwrap(), TrackType::Hint);
+    assert_eq!(hint.box_type().unwrap(), str::parse("rtp ").unwrap());
+    assert_eq!(hint.timescale(), 90000);
+
+    let tref = hint.trak.tref.as_ref().unwrap();
+    assert_eq!(tref.track_ids(&str::parse("hint").unwrap()), Some(&[1][..]));
+
+    let rtp = hint.trak.mdia.minf.stbl.stsd.rtp.as_ref().unwrap();
+    assert_eq!(rtp.max_packet_size, 1450);
+    assert_eq!(rtp.timescale, 90000);
+
+    let sdp = hint.trak.udta.as_ref().unwrap().hnti.as_ref().unwrap();
+    assert_eq!(
+        sdp.sdp.as_ref().unwrap().sdp_text,
+        "m=video 0 RTP/AVP 96\r\n"
+    );
+}
+
+#[test]
+fn test_write_rate_and_volume() {
+    let config = Mp4Config {
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        rate: FixedPointU16::new_raw(0x00020000),
+        write_iods: true,
+        ..Default::default()
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+
+    let mut track_conf = TrackConfig::from(AacConfig::default());
+    track_conf.volume = FixedPointU8::new_raw(0x0080);
+    writer.add_track(&track_conf).unwrap();
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+
+    assert_eq!(mp4.moov.mvhd.rate.value(), 2);
+    assert_eq!(mp4.moov.iods, Some(IodsBox::default()));
+    let track = mp4.tracks().get(&1).unwrap();
+    assert_eq!(track.trak.tkhd.volume.raw_value(), 0x0080);
+}
+
+struct CountingReader<R> {
+    inner: R,
+    reads: Rc<Cell<usize>>,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.reads.set(self.reads.get() + 1);
+        self.inner.read(buf)
+    }
+}
+
+impl<R: Seek> Seek for CountingReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+#[test]
+fn test_read_sample_chunk_cache() {
+    let data = fs::read("tests/samples/minimal.mp4").unwrap();
+    let size = data.len() as u64;
+    let reads = Rc::new(Cell::new(0));
+    let reader = CountingReader {
+        inner: Cursor::new(data.clone()),
+        reads: reads.clone(),
+    };
+    let mut mp4 = Mp4Reader::read_header(reader, size).unwrap();
+
+    // Track 2 holds 3 samples spread over 2 chunks.
+    reads.set(0);
+    for sample_id in 1..=3 {
+        let offset = mp4.sample_offset(2, sample_id).unwrap() as usize;
+        let sample = mp4.read_sample(2, sample_id).unwrap().unwrap();
+        assert_eq!(sample.bytes, data[offset..offset + sample.bytes.len()]);
+    }
+    assert_eq!(reads.get(), 2);
+}
+
+#[test]
+fn test_write_flush_chunk() {
+    let config = Mp4Config {
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        ..Default::default()
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    writer
+        .add_track(&TrackConfig::from(AacConfig::default()))
+        .unwrap();
+
+    let sample = Mp4Sample {
+        start_time: 0,
+        duration: 1024,
+        rendering_offset: 0,
+        is_sync: true,
+        bytes: Bytes::from_static(&[0x21, 0x10, 0x04]),
+    };
+    writer.write_sample(1, &sample).unwrap();
+    writer.flush_chunk(1).unwrap();
+    writer.write_sample(1, &sample).unwrap();
+    writer.write_end().unwrap();
+    assert!(matches!(writer.flush_chunk(2), Err(Error::TrakNotFound(2))));
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+
+    let track = mp4.tracks().get(&1).unwrap();
+    let stco = track.trak.mdia.minf.stbl.stco.as_ref().unwrap();
+    assert_eq!(stco.entries.len(), 2);
+
+    let sample_2 = mp4.read_sample(1, 2).unwrap().unwrap();
+    assert_eq!(sample_2.bytes, sample.bytes);
+}
+
+#[test]
+fn test_read_anamorphic_dimensions() {
+    let src = get_reader("tests/samples/minimal.mp4");
+    let mut moov = src.moov.clone();
+    moov.traks[0]
+        .mdia
+        .minf
+        .stbl
+        .stsd
+        .avc1
+        .as_mut()
+        .unwrap()
+        .pasp = Some(PaspBox {
+        h_spacing: 3,
+        v_spacing: 2,
+    });
+
+    let mut data = Vec::new();
+    src.ftyp.write_box(&mut data).unwrap();
+    moov.write_box(&mut data).unwrap();
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+
+    let track = mp4.tracks().get(&1).unwrap();
+    assert_eq!((track.width(), track.height()), (320, 240));
+    assert_eq!(track.display_dimensions_corrected(), (480, 240));
+    assert_eq!(mp4.track_dimensions_with_pasp(2).unwrap(), (0, 0));
+}
+
+#[test]
+fn test_validate_duplicate_moov() {
+    let mut data = fs::read("tests/samples/minimal.mp4").unwrap();
+    let first = get_reader("tests/samples/minimal.mp4");
+
+    let mut moov = first.moov.clone();
+    moov.mvhd.timescale = 90000;
+    let offset = data.len() as u64;
+    moov.write_box(&mut data).unwrap();
+
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+
+    assert_eq!(mp4.moov.mvhd.timescale, first.moov.mvhd.timescale);
+    assert_eq!(
+        mp4.validate(),
+        vec![Warning::DuplicateBox(BoxType::MoovBox, offset)]
+    );
+}
+
+#[test]
+fn test_remux_raw_sample_entry() {
+    let sample_entry = vec![
+        0x00, 0x00, 0x00, 0x14, b'x', b'y', b'z', b'1', // header
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, // reserved, data_reference_index
+        0xDE, 0xAD, 0xBE, 0xEF, // codec specific
+    ];
+    let sample = Mp4Sample {
+        start_time: 0,
+        duration: 1000,
+        rendering_offset: 0,
+        is_sync: true,
+        bytes: Bytes::from_static(&[0x01, 0x02, 0x03]),
+    };
+
+    let write = |sample_entry: Vec<u8>| {
+        let config = Mp4Config {
+            compatible_brands: vec![str::parse("isom").unwrap()],
+            ..Default::default()
+        };
+        let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+        writer
+            .add_track(&TrackConfig::from(MediaConfig::RawSampleEntryConfig(
+                RawSampleEntryConfig {
+                    track_type: TrackType::Video,
+                    timescale: 1000,
+                    width: 64,
+                    height: 48,
+                    sample_entry,
+                },
+            )))
+            .unwrap();
+        writer.write_sample(1, &sample).unwrap();
+        writer.write_end().unwrap();
+
+        let data = writer.into_writer().into_inner();
+        let size = data.len() as u64;
+        Mp4Reader::read_header(Cursor::new(data), size).unwrap()
+    };
+
+    let src = write(sample_entry.clone());
+    let src_track = src.tracks().get(&1).unwrap();
+    assert_eq!(src_track.box_type().unwrap(), str::parse("xyz1").unwrap());
+    assert!(src_track.codec_string().is_err());
+    assert_eq!(src.guess_mime_type().unwrap(), "video/mp4");
+    assert_eq!(src_track.sample_entry_raw_bytes(), Some(&sample_entry[..]));
+    let offset = |box_type| {
+        src.box_offsets()
+            .iter()
+            .find(|&&(name, _, _)| name == box_type)
+            .unwrap()
+            .1
+    };
+    assert_eq!(
+        src.validate(),
+        vec![Warning::MoovAfterMdat(
+            offset(BoxType::MoovBox),
+            offset(BoxType::MdatBox)
+        )]
+    );
+
+    let mut dst = write(src_track.sample_entry_raw_bytes().unwrap().to_vec());
+    let dst_track = dst.tracks().get(&1).unwrap();
+    assert_eq!(dst_track.sample_entry_raw_bytes(), Some(&sample_entry[..]));
+    assert_eq!((dst_track.width(), dst_track.height()), (64, 48));
+    assert_eq!(dst_track.track_type().unwrap(), TrackType::Video);
+    assert_eq!(dst_track.timescale(), 1000);
+    assert_eq!(dst.read_sample(1, 1).unwrap().unwrap().bytes, sample.bytes);
+}
+
+#[test]
+fn test_read_segment_index() {
+    let mut data = fs::read("tests/samples/minimal.mp4").unwrap();
+    let sidx_offset = data.len() as u64;
+    let sidx = SidxBox {
+        version: 0,
+        flags: 0,
+        reference_id: 1,
+        timescale: 1000,
+        earliest_presentation_time: 500,
+        first_offset: 0,
+        references: vec![
+            SidxReference {
+                referenced_size: 300,
+                subsegment_duration: 2000,
+                starts_with_sap: true,
+                sap_type: 1,
+                ..Default::default()
+            },
+            SidxReference {
+                referenced_size: 200,
+                subsegment_duration: 1000,
+                ..Default::default()
+            },
+        ],
+    };
+    sidx.write_box(&mut data).unwrap();
+    let ssix = SsixBox {
+        version: 0,
+        flags: 0,
+        subsegments: vec![
+            SsixSubsegment {
+                ranges: vec![
+                    SsixRange {
+                        level: 0,
+                        range_size: 100,
+                    },
+                    SsixRange {
+                        level: 1,
+                        range_size: 200,
+                    },
+                ],
+            },
+            SsixSubsegment { ranges: vec![] },
+        ],
+    };
+    ssix.write_box(&mut data).unwrap();
+
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    assert_eq!(mp4.sidxs, vec![sidx.clone()]);
+    assert_eq!(mp4.ssixs, vec![ssix]);
+
+    let anchor = sidx_offset + sidx.box_size();
+    let index = mp4.segment_index();
+    assert_eq!(index.len(), 2);
+    assert_eq!(index[0].offset, anchor);
+    assert_eq!(index[0].start_time, 500);
+    assert!(index[0].starts_with_sap);
+    assert_eq!(
+        index[0].ranges,
+        vec![
+            SubsegmentRange {
+                level: 0,
+                offset: anchor,
+                size: 100,
+            },
+            SubsegmentRange {
+                level: 1,
+                offset: anchor + 100,
+                size: 200,
+            },
+        ]
+    );
+    assert_eq!(index[1].offset, anchor + 300);
+    assert_eq!(index[1].start_time, 2500);
+    assert!(index[1].ranges.is_empty());
+}
+
+#[test]
+fn test_write_chunked_fragments() {
+    let config = Mp4Config {
+        major_brand: str::parse("cmfc").unwrap(),
+        minor_version: 0,
+        compatible_brands: vec![str::parse("iso6").unwrap(), str::parse("cmfc").unwrap()],
+        ..Default::default()
+    };
+    let track_config = TrackConfig::from(AacConfig::default());
+    let mut writer =
+        Mp4FragmentWriter::write_init(Cursor::new(Vec::<u8>::new()), &config, &track_config)
+            .unwrap();
+
+    writer.start_segment().unwrap();
+    for chunk in 0..3u8 {
+        for i in 0..2u8 {
+            writer
+                .write_sample(&Mp4Sample {
+                    start_time: 0,
+                    duration: 1024,
+                    rendering_offset: 0,
+                    is_sync: true,
+                    bytes: Bytes::from(vec![chunk, i, 0xAA]),
+                })
+                .unwrap();
+        }
+        writer.flush_chunk().unwrap();
+    }
+    let sidx = writer.end_segment().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let full = Mp4Reader::read_header(Cursor::new(data.clone()), data.len() as u64).unwrap();
+    let init_size = full.ftyp.box_size() + full.moov.box_size();
+    let (init, segment) = data.split_at(init_size as usize);
+
+    assert_eq!(sidx.references.len(), 3);
+    assert_eq!(sidx.earliest_presentation_time, 0);
+    let chunks_size: u64 = sidx
+        .references
+        .iter()
+        .map(|r| r.referenced_size as u64)
+        .sum();
+    assert_eq!(segment.len() as u64 - chunks_size, 8 + 8 + 2 * 4); // styp
+
+    let mp4 = Mp4Reader::read_header(Cursor::new(init.to_vec()), init.len() as u64).unwrap();
+    assert!(mp4.moov.mvex.is_some());
+    let mut fragment = mp4
+        .read_fragment_header(Cursor::new(segment.to_vec()), segment.len() as u64)
+        .unwrap();
+    assert_eq!(fragment.moofs.len(), 3);
+    assert_eq!(fragment.sample_count(1).unwrap(), 6);
+
+    for sample_id in 1..=6u32 {
+        let sample = fragment.read_sample(1, sample_id).unwrap().unwrap();
+        let (chunk, i) = ((sample_id - 1) / 2, (sample_id - 1) % 2);
+        assert_eq!(sample.start_time, (sample_id as u64 - 1) * 1024);
+        assert_eq!(sample.bytes, Bytes::from(vec![chunk as u8, i as u8, 0xAA]));
+    }
+}
+
+#[test]
+fn test_sample_dependencies() {
+    let config = Mp4Config {
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        ..Default::default()
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    writer
+        .add_track(&TrackConfig::from(AvcConfig {
+            width: 320,
+            height: 240,
+            seq_param_set: vec![0x67, 0x64, 0x00, 0x0D],
+            pic_param_set: vec![0x68, 0xEB, 0xE3, 0xCB],
+        }))
+        .unwrap();
+
+    // Decode order I P B B P B B | I, presented as I B B P B B P | I.
+    let offsets = [1, 3, 0, 0, 3, 0, 0, 1];
+    for (i, rendering_offset) in offsets.iter().enumerate() {
+        let sample = Mp4Sample {
+            start_time: i as u64,
+            duration: 1,
+            rendering_offset: *rendering_offset,
+            is_sync: i % 7 == 0,
+            bytes: Bytes::from_static(&[0x00]),
+        };
+        writer.write_sample(1, &sample).unwrap();
+    }
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+
+    let track = mp4.tracks().get(&1).unwrap();
+    let droppable: Vec<bool> = (1..=8)
+        .map(|id| track.dependency_info(id).unwrap().droppable)
+        .collect();
+    assert_eq!(
+        droppable,
+        vec![false, false, true, true, false, true, true, false]
+    );
+    let info = track.dependency_info(1).unwrap();
+    assert!(info.is_sync);
+    assert_eq!(info.is_depended_on, None);
+    assert!(matches!(
+        track.dependency_info(9),
+        Err(Error::EntryInStblNotFound(1, BoxType::StszBox, 9))
+    ));
+
+    // sdtp overrides the heuristics.
+    let mut moov = mp4.moov.clone();
+    let mut entries = vec![SdtpEntry::default(); 8];
+    entries[4].sample_is_depended_on = 2;
+    moov.traks[0].mdia.minf.stbl.sdtp = Some(SdtpBox {
+        version: 0,
+        flags: 0,
+        entries,
+    });
+
+    let mut data = Vec::new();
+    mp4.ftyp.write_box(&mut data).unwrap();
+    moov.write_box(&mut data).unwrap();
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+
+    let track = mp4.tracks().get(&1).unwrap();
+    let info = track.dependency_info(5).unwrap();
+    assert_eq!(info.is_depended_on, Some(false));
+    assert!(info.droppable);
+    assert!(!track.dependency_info(2).unwrap().droppable);
+}
+
+#[test]
+fn test_read_edit_corrected_samples() {
+    let config = Mp4Config {
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        ..Default::default()
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    let mut track_config = TrackConfig::from(AvcConfig {
+        width: 320,
+        height: 240,
+        seq_param_set: vec![0x67, 0x64, 0x00, 0x0D],
+        pic_param_set: vec![0x68, 0xEB, 0xE3, 0xCB],
+    });
+    track_config.timescale = 90000;
+    writer.add_track(&track_config).unwrap();
+    for i in 0..3 {
+        let sample = Mp4Sample {
+            start_time: i * 3000,
+            duration: 3000,
+            rendering_offset: 1500,
+            is_sync: i == 0,
+            bytes: Bytes::from_static(&[0x00, 0x01]),
+        };
+        writer.write_sample(1, &sample).unwrap();
+    }
+    writer.write_end().unwrap();
+    let mut data = writer.into_writer().into_inner();
+
+    // Replace the trailing moov with one carrying a 500ms initial empty edit
+    // followed by an edit starting at the first composition time.
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data.clone()), size).unwrap();
+    let mut moov = mp4.moov.clone();
+    data.truncate(data.len() - moov.box_size() as usize);
+    moov.traks[0].edts = Some(EdtsBox {
+        elst: Some(ElstBox {
+            version: 0,
+            flags: 0,
+            entries: vec![
+                ElstEntry {
+                    segment_duration: 500,
+                    media_time: u32::MAX as u64,
+                    media_rate: 1,
+                    media_rate_fraction: 0,
+                },
+                ElstEntry {
+                    segment_duration: 100,
+                    media_time: 1500,
+                    media_rate: 1,
+                    media_rate_fraction: 0,
+                },
+            ],
+        }),
+    });
+    moov.write_box(&mut data).unwrap();
+
+    let size = data.len() as u64;
+    let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    assert_eq!(
+        mp4.tracks().get(&1).unwrap().presentation_offset(1000),
+        43500
+    );
+
+    let samples: Vec<Mp4Sample> = mp4
+        .read_edit_corrected_samples(1)
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    let times: Vec<u64> = samples.iter().map(|s| s.start_time).collect();
+    assert_eq!(times, vec![45000, 48000, 51000]);
+    assert!(samples.iter().all(|s| s.rendering_offset == 0));
+    assert!(matches!(
+        mp4.read_edit_corrected_samples(2),
+        Err(Error::TrakNotFound(2))
+    ));
+}
+
+#[test]
+fn test_write_long_duration_headers() {
+    let config = Mp4Config {
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        ..Default::default()
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    writer
+        .add_track(&TrackConfig::from(AacConfig::default()))
+        .unwrap();
+
+    let mut track_config = TrackConfig::from(AacConfig::default());
+    track_config.timescale = 1000;
+    writer.add_track(&track_config).unwrap();
+
+    let sample = Mp4Sample {
+        start_time: 0,
+        duration: u32::MAX,
+        rendering_offset: 0,
+        is_sync: true,
+        bytes: Bytes::from_static(&[0x21, 0x10, 0x04]),
+    };
+    let short_sample = Mp4Sample {
+        start_time: 0,
+        duration: 1024,
+        rendering_offset: 0,
+        is_sync: true,
+        bytes: sample.bytes.clone(),
+    };
+    writer.write_sample(1, &short_sample).unwrap();
+    writer.write_sample(2, &sample).unwrap();
+    writer.write_sample(2, &sample).unwrap();
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+
+    let long_duration = u32::MAX as u64 * 2;
+    assert_eq!(mp4.moov.mvhd.version, 1);
+    assert_eq!(mp4.moov.mvhd.duration, long_duration);
+
+    let track = mp4.tracks().get(&2).unwrap();
+    assert_eq!(track.trak.tkhd.version, 1);
+    assert_eq!(track.trak.tkhd.duration, long_duration);
+    assert_eq!(track.trak.mdia.mdhd.version, 1);
+    assert_eq!(track.trak.mdia.mdhd.duration, long_duration);
+
+    let track = mp4.tracks().get(&1).unwrap();
+    assert_eq!(track.trak.tkhd.version, 0);
+    assert_eq!(track.trak.mdia.mdhd.version, 0);
+}
+
+#[test]
+fn test_raw_box_reader() {
+    let mut mp4 = get_reader("tests/samples/minimal.mp4");
+    let ftyp_size = mp4.ftyp.box_size();
+
+    let bytes = mp4.raw_box_reader(0, ftyp_size).unwrap();
+    let mut reader = Cursor::new(&bytes);
+    let header = BoxHeader::read(&mut reader).unwrap();
+    assert_eq!(header.name, BoxType::FtypBox);
+    let ftyp = FtypBox::read_box(&mut reader, header.size).unwrap();
+    assert_eq!(ftyp, mp4.ftyp);
+
+    let size = mp4.size();
+    assert!(mp4.raw_box_reader(size - 4, 4).is_ok());
+    assert!(matches!(
+        mp4.raw_box_reader(size - 4, 5),
+        Err(Error::InvalidData(_))
+    ));
+    assert!(mp4.raw_box_reader(u64::MAX, 1).is_err());
+}
+
+#[test]
+fn test_read_sample_rate() {
+    let mp4 = get_reader("tests/samples/minimal.mp4");
+    assert_eq!(mp4.tracks().get(&1).unwrap().sample_rate(), None);
+    assert_eq!(mp4.tracks().get(&2).unwrap().sample_rate(), Some(48000));
+}
+
+#[test]
+fn test_read_encrypted_sample_entries() {
+    let src = get_reader("tests/samples/minimal.mp4");
+    let sinf = |original_format: &str| SinfBox {
+        frma: FrmaBox {
+            original_format: str::parse(original_format).unwrap(),
+        },
+        schm: Some(SchmBox {
+            version: 0,
+            flags: 0,
+            scheme_type: str::parse("cenc").unwrap(),
+            scheme_version: 0x0001_0000,
+            scheme_uri: None,
+        }),
+        schi: None,
+    };
+
+    let mut moov = src.moov.clone();
+    let stsd = &mut moov.traks[0].mdia.minf.stbl.stsd;
+    stsd.avc1.as_mut().unwrap().sinf = Some(sinf("avc1"));
+    let stsd = &mut moov.traks[1].mdia.minf.stbl.stsd;
+    stsd.mp4a.as_mut().unwrap().sinf = Some(sinf("mp4a"));
+
+    let mut data = Vec::new();
+    src.ftyp.write_box(&mut data).unwrap();
+    moov.write_box(&mut data).unwrap();
+    assert!(data.windows(4).any(|w| w == b"encv"));
+    assert!(data.windows(4).any(|w| w == b"enca"));
+
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+
+    let video = mp4.tracks().get(&1).unwrap();
+    assert_eq!(video.media_type().unwrap(), MediaType::H264);
+    assert!(video.is_encrypted());
+    assert_eq!(video.box_type().unwrap().to_string(), "encv");
+    assert_eq!(video.width(), 320);
+    assert_eq!(
+        video.trak.mdia.minf.stbl.stsd.avc1.as_ref().unwrap().sinf,
+        Some(sinf("avc1"))
+    );
+
+    let audio = mp4.tracks().get(&2).unwrap();
+    assert_eq!(audio.media_type().unwrap(), MediaType::AAC);
+    assert!(audio.is_encrypted());
+    assert_eq!(audio.box_type().unwrap().to_string(), "enca");
+    assert_eq!(audio.sample_rate(), Some(48000));
+
+    assert!(!src.tracks().get(&1).unwrap().is_encrypted());
+}
+
+#[test]
+fn test_write_interleaved_chunks() {
+    let config = Mp4Config {
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        interleave_duration: 500,
+        ..Default::default()
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    let mut video_config = TrackConfig::from(AvcConfig {
+        width: 320,
+        height: 240,
+        seq_param_set: vec![0x67, 0x64, 0x00, 0x0D],
+        pic_param_set: vec![0x68, 0xEB, 0xE3, 0xCB],
+    });
+    video_config.timescale = 90000;
+    writer.add_track(&video_config).unwrap();
+    let mut audio_config = TrackConfig::from(AacConfig::default());
+    audio_config.timescale = 48000;
+    writer.add_track(&audio_config).unwrap();
+
+    // 3 seconds of 30fps video and 48kHz AAC, fed in decode time order.
+    let (mut video_time, mut audio_time) = (0u64, 0u64);
+    while video_time < 270000 || audio_time < 144000 {
+        if video_time * 48000 <= audio_time * 90000 {
+            let sample = Mp4Sample {
+                start_time: video_time,
+                duration: 3000,
+                rendering_offset: 0,
+                is_sync: video_time == 0,
+                bytes: Bytes::from_static(&[0x00, 0x01, 0x02]),
+            };
+            writer.write_sample(1, &sample).unwrap();
+            video_time += 3000;
+        } else {
+            let sample = Mp4Sample {
+                start_time: audio_time,
+                duration: 1024,
+                rendering_offset: 0,
+                is_sync: true,
+                bytes: Bytes::from_static(&[0x21, 0x10, 0x04]),
+            };
+            writer.write_sample(2, &sample).unwrap();
+            audio_time += 1024;
+        }
+    }
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+
+    // (chunk offset, track id, chunk start time in seconds)
+    let mut chunks = Vec::new();
+    for track_id in 1..=2 {
+        let track = mp4.tracks().get(&track_id).unwrap();
+        let timescale = track.timescale() as f64;
+        let chunk_offsets = track
+            .trak
+            .mdia
+            .minf
+            .stbl
+            .stco
+            .as_ref()
+            .unwrap()
+            .entries
+            .clone();
+        assert!(chunk_offsets.len() >= 6);
+        for sample_id in 1..=mp4.sample_count(track_id).unwrap() {
+            let offset = mp4.sample_offset(track_id, sample_id).unwrap();
+            if chunk_offsets.contains(&(offset as u32)) {
+                let sample = mp4.read_sample(track_id, sample_id).unwrap().unwrap();
+                chunks.push((offset, track_id, sample.start_time as f64 / timescale));
+            }
+        }
+    }
+    chunks.sort_by_key(|&(offset, _, _)| offset);
+
+    assert!(chunks.windows(2).all(|w| w[0].2 <= w[1].2));
+    let switches = chunks.windows(2).filter(|w| w[0].1 != w[1].1).count();
+    assert!(switches >= 10);
+}
+
+#[test]
+fn test_write_interleaved_chunks_sparse_track() {
+    let config = Mp4Config {
+        interleave_duration: 500,
+        ..Default::default()
+    };
+    let zero_timescale = Mp4Config {
+        timescale: 0,
+        ..config.clone()
+    };
+    assert!(matches!(
+        Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &zero_timescale),
+        Err(Error::InvalidData("timescale must be non-zero"))
+    ));
+
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    let mut video_config = TrackConfig::from(AvcConfig {
+        width: 320,
+        height: 240,
+        seq_param_set: vec![0x67, 0x64, 0x00, 0x0D],
+        pic_param_set: vec![0x68, 0xEB, 0xE3, 0xCB],
+    });
+    video_config.timescale = 90000;
+    writer.add_track(&video_config).unwrap();
+    writer.add_track(&TrackConfig::from(TtxtConfig {})).unwrap();
+
+    // A single short cue, then 3 seconds of 30fps video: the subtitle chunk
+    // never fills up, but must not hold back the video.
+    let cue = Mp4Sample {
+        start_time: 0,
+        duration: 100,
+        rendering_offset: 0,
+        is_sync: true,
+        bytes: Bytes::from_static(b"\x00\x02Hi"),
+    };
+    writer.write_sample(2, &cue).unwrap();
+    for i in 0..90u64 {
+        let sample = Mp4Sample {
+            start_time: i * 3000,
+            duration: 3000,
+            rendering_offset: 0,
+            is_sync: i == 0,
+            bytes: Bytes::from_static(&[0x00, 0x01, 0x02]),
+        };
+        writer.write_sample(1, &sample).unwrap();
+    }
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    let video_chunks = mp4.tracks()[&1].sample_to_chunk_map().unwrap();
+    let chunk_count = video_chunks.last().unwrap().0;
+    assert!(chunk_count >= 6, "{} video chunks", chunk_count);
+    let subtitle_chunks = mp4.tracks()[&2].sample_to_chunk_map().unwrap();
+    assert_eq!(subtitle_chunks.len(), 1);
+    let second_chunk = video_chunks.iter().find(|chunk| chunk.0 == 2).unwrap();
+    assert!(subtitle_chunks[0].1 < second_chunk.1);
+}
+#[test]
+fn test_read_fragment_data_offset() {
+    let mp4 = get_reader("tests/samples/minimal_init.mp4");
+
+    let fragment = |data_offset: Option<i32>| {
+        let mut moof = MoofBox::default();
+        let mut trun = TrunBox {
+            flags: TrunBox::FLAG_SAMPLE_DURATION | TrunBox::FLAG_SAMPLE_SIZE,
+            sample_count: 2,
+            sample_durations: vec![512, 512],
+            sample_sizes: vec![3, 4],
+            ..Default::default()
+        };
+        if data_offset.is_some() {
+            trun.flags |= TrunBox::FLAG_DATA_OFFSET;
+        }
+        moof.trafs.push(TrafBox {
+            tfhd: TfhdBox {
+                flags: TfhdBox::FLAG_DEFAULT_BASE_IS_MOOF,
+                track_id: 1,
+                ..Default::default()
+            },
+            tfdt: None,
+            trun: Some(trun),
+            senc: None,
+        });
+        // Point past the moof, the mdat header and 5 bytes of padding.
+        let padding = 5;
+        let data_offset = data_offset.map(|_| (moof.box_size() + 8 + padding) as i32);
+        moof.trafs[0].trun.as_mut().unwrap().data_offset = data_offset;
+
+        let mut data = Vec::new();
+        moof.write_box(&mut data).unwrap();
+        let mdat_payload = [0xFFu8, 0xFF, 0xFF, 0xFF, 0xFF, 1, 2, 3, 4, 5, 6, 7];
+        BoxHeader::new(BoxType::MdatBox, 8 + mdat_payload.len() as u64)
+            .write(&mut data)
+            .unwrap();
+        data.extend_from_slice(&mdat_payload);
+        data
+    };
+
+    let data = fragment(Some(0));
+    let size = data.len() as u64;
+    let mut mp4_fragment = mp4.read_fragment_header(Cursor::new(data), size).unwrap();
+    let sample_1 = mp4_fragment.read_sample(1, 1).unwrap().unwrap();
+    assert_eq!(sample_1.bytes.as_ref(), &[1, 2, 3]);
+    let sample_2 = mp4_fragment.read_sample(1, 2).unwrap().unwrap();
+    assert_eq!(sample_2.bytes.as_ref(), &[4, 5, 6, 7]);
+
+    let data = fragment(None);
+    let size = data.len() as u64;
+    let mut mp4_fragment = mp4.read_fragment_header(Cursor::new(data), size).unwrap();
+    assert!(matches!(
+        mp4_fragment.read_sample(1, 1),
+        Err(Error::InvalidData(_))
+    ));
+}
+
+#[test]
+fn test_write_synthetic_header() {
+    let config = Mp4Config {
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        ..Default::default()
+    };
+    let mut header = Mp4Header::new_empty(&config);
+    let track_id = header
+        .add_track(&TrackConfig::from(AvcConfig {
+            width: 320,
+            height: 240,
+            seq_param_set: vec![0x67, 0x64, 0x00, 0x0D],
+            pic_param_set: vec![0x68, 0xEB, 0xE3, 0xCB],
+        }))
+        .unwrap();
+    assert_eq!(track_id, 1);
+    assert!(matches!(header.trak_mut(2), Err(Error::TrakNotFound(2))));
+
+    let stbl = &mut header.trak_mut(track_id).unwrap().mdia.minf.stbl;
+    stbl.stts.entries.push(SttsEntry {
+        sample_count: 10,
+        sample_delta: 512,
+    });
+    stbl.stsz.sample_size = 100;
+    stbl.stsz.sample_count = 10;
+    stbl.stsc.entries.push(StscEntry {
+        first_chunk: 1,
+        samples_per_chunk: 10,
+        sample_description_index: 1,
+        first_sample: 1,
+    });
+    stbl.stco.as_mut().unwrap().entries.push(4096);
+
+    let mut data = Vec::new();
+    let size = header.write(&mut data).unwrap();
+    assert_eq!(size, data.len() as u64);
+
+    let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    assert_eq!(mp4.moov.mvhd, header.moov.mvhd);
+    assert_eq!(mp4.moov.mvhd.next_track_id, 2);
+    assert_eq!(mp4.duration(), Duration::from_millis(5120));
+
+    let track = mp4.tracks().get(&1).unwrap();
+    assert_eq!(track.media_type().unwrap(), MediaType::H264);
+    assert_eq!(track.sample_count(), 10);
+    assert_eq!(track.duration(), Duration::from_millis(5120));
+    assert_eq!(mp4.sample_offset(1, 3).unwrap(), 4096 + 200);
+}
+
+#[test]
+fn test_read_sample_durations() {
+    let mp4 = get_reader("tests/samples/minimal.mp4");
+    for track in mp4.tracks().values() {
+        let durations = track.sample_durations();
+        assert_eq!(durations.len(), track.sample_count() as usize);
+        assert_eq!(
+            durations.iter().map(|&d| d as u64).sum::<u64>(),
+            track.trak.mdia.mdhd.duration
+        );
+    }
+
+    let f = File::open("tests/samples/minimal_fragment.m4s").unwrap();
+    let f_size = f.metadata().unwrap().len();
+    let init = get_reader("tests/samples/minimal_init.mp4");
+    let fragment = init
+        .read_fragment_header(BufReader::new(f), f_size)
+        .unwrap();
+    assert_eq!(
+        fragment.tracks().get(&1).unwrap().sample_durations(),
+        vec![512]
+    );
+}
+
+#[test]
+fn test_recover_from_partial() {
+    let config = Mp4Config {
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        interleave_duration: 250,
+        ..Default::default()
+    };
+    let avc_config = AvcConfig {
+        width: 320,
+        height: 240,
+        seq_param_set: vec![0x67, 0x64, 0x00, 0x0D],
+        pic_param_set: vec![0x68, 0xEB, 0xE3, 0xCB],
+    };
+    let mut video_config = TrackConfig::from(avc_config);
+    video_config.timescale = 90000;
+    let mut audio_config = TrackConfig::from(AacConfig::default());
+    audio_config.timescale = 48000;
+
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    writer.add_track(&video_config).unwrap();
+    writer.add_track(&audio_config).unwrap();
+
+    let nal = |bytes: &[u8]| {
+        let mut nal = (bytes.len() as u32).to_be_bytes().to_vec();
+        nal.extend_from_slice(bytes);
+        nal
+    };
+    let adts = |payload: &[u8]| {
+        let len = payload.len() + 7;
+        let mut frame = vec![
+            0xFF,
+            0xF1,
+            0x4C,
+            0x80 | (len >> 11) as u8,
+            (len >> 3) as u8,
+            ((len & 7) << 5) as u8 | 0x1F,
+            0xFC,
+        ];
+        frame.extend_from_slice(payload);
+        frame
+    };
+
+    let mut video_samples = Vec::new();
+    let mut audio_samples = Vec::new();
+    let (mut video_time, mut audio_time) = (0u64, 0u64);
+    while video_time < 90000 || audio_time < 48000 {
+        if video_time * 48000 <= audio_time * 90000 {
+            let i = video_samples.len() as u8;
+            let is_sync = i.is_multiple_of(10);
+            let bytes = if is_sync {
+                let mut bytes = nal(&[0x67, 0x64, 0x00, 0x0D]);
+                bytes.extend(nal(&[0x68, 0xEB, 0xE3, 0xCB]));
+                bytes.extend(nal(&[0x65, 0x88, i, i]));
+                bytes
+            } else {
+                // Two slices of the same picture.
+                let mut bytes = nal(&[0x41, 0x9A, i]);
+                bytes.extend(nal(&[0x41, 0x1A, i]));
+                bytes
+            };
+            let sample = Mp4Sample {
+                start_time: video_time,
+                duration: 3000,
+                rendering_offset: 0,
+                is_sync,
+                bytes: Bytes::from(bytes),
+            };
+            writer.write_sample(1, &sample).unwrap();
+            video_samples.push(sample);
+            video_time += 3000;
+        } else {
+            let i = audio_samples.len() as u8;
+            let sample = Mp4Sample {
+                start_time: audio_time,
+                duration: 1024,
+                rendering_offset: 0,
+                is_sync: true,
+                bytes: Bytes::from(adts(&[0x21, 0x10, 0x04, i])),
+            };
+            writer.write_sample(2, &sample).unwrap();
+            audio_samples.push(sample);
+            audio_time += 1024;
+        }
+    }
+    writer.write_end().unwrap();
+    let mut data = writer.into_writer().into_inner();
+
+    // Simulate a crash: no moov, the mdat size still the placeholder and
+    // the last chunk cut short.
+    let mp4 = Mp4Reader::read_header(Cursor::new(data.clone()), data.len() as u64).unwrap();
+    let moov_size = mp4.moov.box_size() as usize;
+    let ftyp_size = mp4.ftyp.box_size() as usize;
+    data.truncate(data.len() - moov_size - 2);
+    data[ftyp_size..ftyp_size + 4].copy_from_slice(&8u32.to_be_bytes());
+
+    let tracks = vec![
+        RecoveryTrack {
+            config: video_config,
+            sample_duration: 3000,
+        },
+        RecoveryTrack {
+            config: audio_config,
+            sample_duration: 1024,
+        },
+    ];
+    let size = data.len() as u64;
+    let mut recovered = Mp4Reader::recover_from_partial(Cursor::new(data), size, &tracks).unwrap();
+
+    let video_count = recovered.sample_count(1).unwrap() as usize;
+    let audio_count = recovered.sample_count(2).unwrap() as usize;
+    assert_eq!(
+        video_count + audio_count,
+        video_samples.len() + audio_samples.len() - 1
+    );
+    assert!(video_count >= video_samples.len() - 1 && audio_count >= audio_samples.len() - 1);
+
+    for (i, expected) in video_samples.iter().take(video_count).enumerate() {
+        let sample = recovered.read_sample(1, i as u32 + 1).unwrap().unwrap();
+        assert_eq!(sample.bytes, expected.bytes);
+        assert_eq!(sample.is_sync, expected.is_sync);
+        assert_eq!(sample.start_time, expected.start_time);
+    }
+    for (i, expected) in audio_samples.iter().take(audio_count).enumerate() {
+        let sample = recovered.read_sample(2, i as u32 + 1).unwrap().unwrap();
+        assert_eq!(sample.bytes, expected.bytes.slice(7..));
+        assert_eq!(sample.start_time, expected.start_time);
+    }
+    assert_eq!(
+        recovered.tracks().get(&1).unwrap().media_type().unwrap(),
+        MediaType::H264
+    );
+}
+
+#[test]
+fn test_write_hdr_metadata() {
+    let hdr = HdrConfig {
+        colr: Some(ColrBox {
+            colour_type: str::parse("nclx").unwrap(),
+            colour_primaries: 9,
+            transfer_characteristics: 16,
+            matrix_coefficients: 9,
+            full_range_flag: false,
+            icc_profile: Vec::new(),
+        }),
+        mdcv: Some(MdcvBox {
+            display_primaries: [(8500, 39850), (6550, 2300), (35400, 14600)],
+            white_point: (15635, 16450),
+            max_display_mastering_luminance: 10_000_000,
+            min_display_mastering_luminance: 50,
+        }),
+        clli: Some(ClliBox {
+            max_content_light_level: 1000,
+            max_pic_average_light_level: 400,
+        }),
+    };
+    let config = Mp4Config {
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        ..Default::default()
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    writer
+        .add_track(&TrackConfig::from(HevcConfig {
+            width: 3840,
+            height: 2160,
+            hdr: Some(hdr.clone()),
+            ..Default::default()
+        }))
+        .unwrap();
+    writer
+        .write_sample(
+            1,
+            &Mp4Sample {
+                start_time: 0,
+                duration: 1000,
+                rendering_offset: 0,
+                is_sync: true,
+                bytes: Bytes::from_static(&[0, 0, 0, 2, 0x26, 0x01]),
+            },
+        )
+        .unwrap();
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let mp4 = Mp4Reader::read_header(Cursor::new(data.clone()), data.len() as u64).unwrap();
+    let track = mp4.tracks().get(&1).unwrap();
+    assert_eq!(track.hdr_metadata(), Some(hdr));
+
+    // Tracks without colour information report none.
+    let mp4 = get_reader("tests/samples/minimal.mp4");
+    assert_eq!(mp4.tracks().get(&1).unwrap().hdr_metadata(), None);
+}
+
+#[test]
+fn test_track_by_language() {
+    let config = Mp4Config {
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        ..Default::default()
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    for language in ["eng", "fra", "pt-BR"] {
+        let mut track_config = TrackConfig::from(AacConfig::default());
+        track_config.language = String::from(language);
+        writer.add_track(&track_config).unwrap();
+    }
+    let mut subtitle_config = TrackConfig::from(TtxtConfig {});
+    subtitle_config.language = String::from("fra");
+    writer.add_track(&subtitle_config).unwrap();
+    for track_id in 1..=4 {
+        writer
+            .write_sample(
+                track_id,
+                &Mp4Sample {
+                    start_time: 0,
+                    duration: 1000,
+                    rendering_offset: 0,
+                    is_sync: true,
+                    bytes: Bytes::from_static(&[0x21, 0x10]),
+                },
+            )
+            .unwrap();
+    }
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let mp4 = Mp4Reader::read_header(Cursor::new(data.clone()), data.len() as u64).unwrap();
+
+    let languages = mp4.track_languages();
+    assert_eq!(languages[&1], "eng");
+    assert_eq!(languages[&3], "pt-BR");
+    assert_eq!(mp4.tracks()[&3].language(), "und");
+
+    let track = mp4.track_by_language(TrackType::Audio, "fra").unwrap();
+    assert_eq!(track.track_id(), 2);
+    let track = mp4.track_by_language(TrackType::Subtitle, "FRA").unwrap();
+    assert_eq!(track.track_id(), 4);
+    let track = mp4.track_by_language(TrackType::Audio, "pt").unwrap();
+    assert_eq!(track.track_id(), 3);
+    assert_eq!(track.extended_language(), Some("pt-BR"));
+    assert!(mp4.track_by_language(TrackType::Audio, "p").is_none());
+    assert!(mp4.track_by_language(TrackType::Video, "eng").is_none());
+    assert!(mp4.track_by_language(TrackType::Audio, "deu").is_none());
+}
+
+#[test]
+fn test_samples_in_presentation_order() {
+    let config = Mp4Config {
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        ..Default::default()
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    writer
+        .add_track(&TrackConfig::from(AvcConfig {
+            width: 320,
+            height: 240,
+            seq_param_set: vec![0x67, 0x64, 0x00, 0x0D],
+            pic_param_set: vec![0x68, 0xEB, 0xE3, 0xCB],
+        }))
+        .unwrap();
+
+    // Decode order I P B B P B B, presented as I B B P B B P.
+    let offsets = [1, 3, 0, 0, 3, 0, 0];
+    for (i, rendering_offset) in offsets.iter().enumerate() {
+        let sample = Mp4Sample {
+            start_time: i as u64,
+            duration: 1,
+            rendering_offset: *rendering_offset,
+            is_sync: i == 0,
+            bytes: Bytes::from(vec![i as u8]),
+        };
+        writer.write_sample(1, &sample).unwrap();
+    }
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+
+    let order = mp4
+        .tracks()
+        .get(&1)
+        .unwrap()
+        .samples_in_presentation_order()
+        .unwrap();
+    assert_eq!(order, vec![1, 3, 4, 2, 6, 7, 5]);
+    assert_ne!(order, (1..=7).collect::<Vec<u32>>());
+
+    let mut last_pts = i64::MIN;
+    for sample_id in order {
+        let sample = mp4.read_sample(1, sample_id).unwrap().unwrap();
+        let pts = sample.presentation_time();
+        assert!(pts > last_pts);
+        last_pts = pts;
+    }
+
+    // Without ctts, presentation order is decode order.
+    let mp4 = get_reader("tests/samples/minimal.mp4");
+    let track = mp4.tracks().get(&1).unwrap();
+    assert_eq!(
+        track.samples_in_presentation_order().unwrap(),
+        (1..=track.sample_count()).collect::<Vec<u32>>()
+    );
+}
+
+#[test]
+fn test_read_quicktime_text_track() {
+    let config = Mp4Config {
+        major_brand: str::parse("qt  ").unwrap(),
+        minor_version: 0,
+        compatible_brands: vec![str::parse("qt  ").unwrap()],
+        timescale: 600,
+        ..Default::default()
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    writer.add_track(&TrackConfig::from(TtxtConfig {})).unwrap();
+    let mut utf16 = vec![0x00, 0x08, 0xFE, 0xFF];
+    utf16.extend("Olé".encode_utf16().flat_map(|unit| unit.to_be_bytes()));
+    let texts = [
+        b"\x00\x05Hello".to_vec(),
+        // Trailing modifier atoms are not part of the text.
+        b"\x00\x05World\x00\x00\x00\x0Cstyl\x00\x00\x00\x00".to_vec(),
+        utf16,
+    ];
+    for (i, text) in texts.iter().enumerate() {
+        let sample = Mp4Sample {
+            start_time: i as u64 * 1000,
+            duration: 1000,
+            rendering_offset: 0,
+            is_sync: true,
+            bytes: Bytes::from(text.clone()),
+        };
+        writer.write_sample(1, &sample).unwrap();
+    }
+    writer.write_end().unwrap();
+
+    // Rewrite the trailing moov the way QuickTime lays out a text track.
+    let data = writer.into_writer().into_inner();
+    let mp4 = Mp4Reader::read_header(Cursor::new(data.clone()), data.len() as u64).unwrap();
+    let mut moov = mp4.moov.clone();
+    let mdia = &mut moov.traks[0].mdia;
+    mdia.hdlr.handler_type = str::parse("text").unwrap();
+    mdia.minf.gmhd = Some(GmhdBox {
+        gmin: GminBox::default(),
+        text_matrix: Some([0x10000, 0, 0, 0, 0x10000, 0, 0, 0, 0x40000000]),
+    });
+    mdia.minf.stbl.stsd.tx3g = None;
+    mdia.minf.stbl.stsd.text = Some(TextBox {
+        font_name: String::from("Helvetica"),
+        ..TextBox::default()
+    });
+    let mut data = data[..data.len() - mp4.moov.box_size() as usize].to_vec();
+    moov.write_box(&mut data).unwrap();
+
+    let size = data.len() as u64;
+    let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    let track = mp4.tracks().get(&1).unwrap();
+    assert_eq!(track.track_type().unwrap(), TrackType::Subtitle);
+    assert_eq!(track.media_type().unwrap(), MediaType::TTXT);
+    assert_eq!(track.box_type().unwrap(), str::parse("text").unwrap());
+    assert_eq!(
+        track
+            .trak
+            .mdia
+            .minf
+            .stbl
+            .stsd
+            .text
+            .as_ref()
+            .unwrap()
+            .font_name,
+        "Helvetica"
+    );
+    assert!(track.trak.mdia.minf.gmhd.is_some());
+
+    assert_eq!(mp4.read_text_sample(1, 1).unwrap().unwrap(), "Hello");
+    assert_eq!(mp4.read_text_sample(1, 2).unwrap().unwrap(), "World");
+    assert_eq!(mp4.read_text_sample(1, 3).unwrap().unwrap(), "Olé");
+    assert!(mp4.read_text_sample(1, 4).unwrap().is_none());
+}
+
+#[test]
+fn test_write_cover_art() {
+    let config = Mp4Config {
+        major_brand: str::parse("M4A ").unwrap(),
+        compatible_brands: vec![str::parse("M4A ").unwrap(), str::parse("isom").unwrap()],
+        ..Default::default()
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    writer
+        .add_track(&TrackConfig::from(AacConfig::default()))
+        .unwrap();
+    writer
+        .write_sample(
+            1,
+            &Mp4Sample {
+                start_time: 0,
+                duration: 1024,
+                rendering_offset: 0,
+                is_sync: true,
+                bytes: Bytes::from_static(&[0x21, 0x10]),
+            },
+        )
+        .unwrap();
+
+    // Larger than 64 KiB.
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    png.extend((0..100_000u32).map(|i| i as u8));
+    assert!(matches!(
+        writer.set_cover_art(png.clone(), DataType::Text),
+        Err(Error::InvalidData(_))
+    ));
+    writer.set_cover_art(png.clone(), DataType::Png).unwrap();
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let mp4 = Mp4Reader::read_header(Cursor::new(data.clone()), data.len() as u64).unwrap();
+    let metadata = mp4.metadata();
+    assert_eq!(metadata.poster(), Some(png.as_slice()));
+    assert_eq!(metadata.poster_type(), Some(DataType::Png));
+    assert_eq!(mp4.tracks().get(&1).unwrap().sample_count(), 1);
+}
+
+#[test]
+fn test_validate_offsets_against_mdat() {
+    let config = Mp4Config {
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        ..Default::default()
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    writer
+        .add_track(&TrackConfig::from(AacConfig::default()))
+        .unwrap();
+    for i in 0..10 {
+        let sample = Mp4Sample {
+            start_time: i * 1024,
+            duration: 1024,
+            rendering_offset: 0,
+            is_sync: true,
+            bytes: Bytes::from(vec![i as u8; 100]),
+        };
+        writer.write_sample(1, &sample).unwrap();
+    }
+    writer.write_end().unwrap();
+    let data = writer.into_writer().into_inner();
+
+    let mp4 = Mp4Reader::read_header(Cursor::new(data.clone()), data.len() as u64).unwrap();
+    mp4.validate_offsets_against_mdat().unwrap();
+
+    // Move the moov in front of the mdat, as an upload of a fast-start file
+    // would have it, then cut the upload short.
+    let ftyp_size = mp4.ftyp.box_size() as usize;
+    let moov_size = mp4.moov.box_size();
+    let mut moov = mp4.moov.clone();
+    let stco = moov.traks[0].mdia.minf.stbl.stco.as_mut().unwrap();
+    for entry in stco.entries.iter_mut() {
+        *entry += moov_size as u32;
+    }
+    let mut faststart = data[..ftyp_size].to_vec();
+    moov.write_box(&mut faststart).unwrap();
+    faststart.extend_from_slice(&data[ftyp_size..data.len() - moov_size as usize]);
+
+    let mp4 =
+        Mp4Reader::read_header(Cursor::new(faststart.clone()), faststart.len() as u64).unwrap();
+    mp4.validate_offsets_against_mdat().unwrap();
+
+    faststart.truncate(faststart.len() - 150);
+    let size = faststart.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(faststart), size).unwrap();
+    match mp4.validate_offsets_against_mdat() {
+        Err(Error::SampleOutsideMdat(track_id, sample_id, offset, size)) => {
+            assert_eq!((track_id, sample_id, size), (1, 9, 100));
+            assert_eq!(offset, mp4.tracks()[&1].sample_offset(9).unwrap());
+        }
+        result => panic!("unexpected result {:?}", result),
+    }
+}
+
+#[test]
+fn test_bitrate_without_esds_average() {
+    let config = Mp4Config {
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        ..Default::default()
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    let mut track_config = TrackConfig::from(AacConfig::default());
+    track_config.timescale = 48000;
+    writer.add_track(&track_config).unwrap();
+    for i in 0..10 {
+        let sample = Mp4Sample {
+            start_time: i * 1024,
+            duration: 1024,
+            rendering_offset: 0,
+            is_sync: true,
+            bytes: Bytes::from(vec![0u8; 100]),
+        };
+        writer.write_sample(1, &sample).unwrap();
+    }
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let mp4 = Mp4Reader::read_header(Cursor::new(data.clone()), data.len() as u64).unwrap();
+
+    // The writer signals the bitrate; clear it to compute it instead.
+    let mut moov = mp4.moov.clone();
+    let dec_config = &mut moov.traks[0]
+        .mdia
+        .minf
+        .stbl
+        .stsd
+        .mp4a
+        .as_mut()
+        .unwrap()
+        .esds
+        .as_mut()
+        .unwrap()
+        .es_desc
+        .dec_config;
+    assert_eq!(dec_config.avg_bitrate, 37500);
+    dec_config.avg_bitrate = 0;
+    dec_config.max_bitrate = 0;
+    let mut data = data[..data.len() - mp4.moov.box_size() as usize].to_vec();
+    moov.write_box(&mut data).unwrap();
+
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    let track = mp4.tracks().get(&1).unwrap();
+
+    // 8000 bits over 10240 / 48000 seconds.
+    assert_eq!(track.bitrate(), 37500);
+    assert_eq!(track.max_bitrate(), None);
+
+    // A signalled average still takes precedence.
+    let mp4 = get_reader("tests/samples/minimal.mp4");
+    let track = mp4.tracks().get(&2).unwrap();
+    assert_eq!(track.bitrate(), 67695);
+}
+
+#[test]
+fn test_sample_presentation_time() {
+    let config = Mp4Config {
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        ..Default::default()
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    writer
+        .add_track(&TrackConfig::from(AvcConfig {
+            width: 320,
+            height: 240,
+            seq_param_set: vec![0x67, 0x64, 0x00, 0x0D],
+            pic_param_set: vec![0x68, 0xEB, 0xE3, 0xCB],
+        }))
+        .unwrap();
+
+    // Decode order I P B, presented as I B P.
+    let offsets = [100, 200, 0];
+    for (i, rendering_offset) in offsets.iter().enumerate() {
+        let sample = Mp4Sample {
+            start_time: i as u64 * 100,
+            duration: 100,
+            rendering_offset: *rendering_offset,
+            is_sync: i == 0,
+            bytes: Bytes::from(vec![i as u8]),
+        };
+        writer.write_sample(1, &sample).unwrap();
+    }
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+
+    let b_frame = mp4.read_sample(1, 3).unwrap().unwrap();
+    assert_eq!(b_frame.start_time, 200);
+    assert_eq!(b_frame.rendering_offset, 0);
+    assert_eq!(b_frame.presentation_time(), 200);
+
+    let p_frame = mp4.read_sample(1, 2).unwrap().unwrap();
+    assert_eq!(p_frame.start_time, 100);
+    assert_eq!(
+        p_frame.presentation_time(),
+        p_frame.start_time as i64 + p_frame.rendering_offset as i64
+    );
+    assert_eq!(p_frame.presentation_time(), 300);
+}
+
+#[test]
+fn test_write_negative_composition_offsets() {
+    let config = Mp4Config {
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        ..Default::default()
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    writer
+        .add_track(&TrackConfig::from(AvcConfig {
+            width: 320,
+            height: 240,
+            seq_param_set: vec![0x67, 0x64, 0x00, 0x0D],
+            pic_param_set: vec![0x68, 0xEB, 0xE3, 0xCB],
+        }))
+        .unwrap();
+    writer.set_negative_composition_offsets(true);
+
+    // Decode order I P B B, presented as I B B P.
+    let offsets = [100, 300, 0, 0];
+    for (i, rendering_offset) in offsets.iter().enumerate() {
+        let sample = Mp4Sample {
+            start_time: i as u64 * 100,
+            duration: 100,
+            rendering_offset: *rendering_offset,
+            is_sync: i == 0,
+            bytes: Bytes::from(vec![i as u8]),
+        };
+        writer.write_sample(1, &sample).unwrap();
+    }
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+
+    let stbl = &mp4.tracks().get(&1).unwrap().trak.mdia.minf.stbl;
+    let ctts = stbl.ctts.as_ref().unwrap();
+    assert_eq!(ctts.version, 1);
+    assert_eq!(
+        ctts.entries
+            .iter()
+            .map(|entry| entry.sample_offset)
+            .collect::<Vec<i32>>(),
+        vec![-50, 150, -150]
+    );
+    assert_eq!(
+        stbl.cslg,
+        Some(CslgBox {
+            version: 0,
+            flags: 0,
+            composition_to_dts_shift: 150,
+            least_decode_to_display_delta: -150,
+            greatest_decode_to_display_delta: 150,
+            composition_start_time: -50,
+            composition_end_time: 350,
+        })
+    );
+
+    let times: Vec<i64> = (1..=4)
+        .map(|sample_id| {
+            mp4.read_sample(1, sample_id)
+                .unwrap()
+                .unwrap()
+                .presentation_time()
+        })
+        .collect();
+    assert_eq!(times, vec![-50, 250, 50, 150]);
+}
+
+#[test]
+fn test_stream_info() {
+    let mp4 = get_reader("tests/samples/minimal.mp4");
+    let info = mp4.stream_info();
+
+    assert_eq!(info.major_brand, str::parse("isom").unwrap());
+    assert_eq!(info.minor_version, 512);
+    assert_eq!(info.compatible_brands, mp4.compatible_brands());
+    assert_eq!(info.duration, Duration::from_millis(62));
+    assert_eq!(info.timescale, 1000);
+    assert!(!info.is_fragmented);
+    assert_eq!(info.tracks.len(), 2);
+
+    let video = &info.tracks[0];
+    assert_eq!(video.track_id, 1);
+    assert_eq!(video.track_type, Some(TrackType::Video));
+    assert_eq!(video.media_type, Some(MediaType::H264));
+    assert_eq!(video.language, "und");
+    assert_eq!(video.sample_count, 1);
+    assert_eq!(video.bitrate, 150200);
+    assert_eq!((video.width, video.height), (320, 240));
+
+    let audio = &info.tracks[1];
+    assert_eq!(audio.track_id, 2);
+    assert_eq!(audio.track_type, Some(TrackType::Audio));
+    assert_eq!(audio.media_type, Some(MediaType::AAC));
+    assert_eq!(audio.timescale, 48000);
+    assert_eq!(audio.sample_count, 3);
+    assert_eq!(audio.bitrate, 67695);
+    assert_eq!((audio.width, audio.height), (0, 0));
+
+    for track in info.tracks.iter() {
+        let reader_track = mp4.tracks().get(&track.track_id).unwrap();
+        assert_eq!(track.duration, reader_track.duration());
+        assert_eq!(track.language, reader_track.language());
+    }
+}
+
+#[test]
+fn test_stream_info_zero_timescale() {
+    let mut buf = std::fs::read("tests/samples/minimal.mp4").unwrap();
+    for name in [b"mvhd", b"mdhd"] {
+        let mut pos = 0;
+        while let Some(found) = buf[pos..].windows(4).position(|w| w == name) {
+            // Version 0: fourcc, version/flags, creation and modification times.
+            let timescale = pos + found + 16;
+            buf[timescale..timescale + 4].copy_from_slice(&0u32.to_be_bytes());
+            pos = timescale;
+        }
+    }
+    let size = buf.len() as u64;
+    let mp4 = mp4::Mp4Reader::read_header(Cursor::new(buf), size).unwrap();
+
+    assert_eq!(mp4.duration(), Duration::ZERO);
+    let info = mp4.stream_info();
+    assert_eq!(info.duration, Duration::ZERO);
+    assert_eq!(info.timescale, 0);
+    for track in info.tracks.iter() {
+        assert_eq!(track.duration, Duration::ZERO);
+    }
+}
+
+#[test]
+fn test_sample_range_for_time() {
+    let config = Mp4Config {
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        ..Default::default()
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    let mut track_config = TrackConfig::from(AvcConfig {
+        width: 320,
+        height: 240,
+        seq_param_set: vec![0x67, 0x64, 0x00, 0x0D],
+        pic_param_set: vec![0x68, 0xEB, 0xE3, 0xCB],
+    });
+    track_config.timescale = 1000;
+    writer.add_track(&track_config).unwrap();
+
+    // 3 seconds at 10 fps with a sync sample every 8 samples (1, 9, 17, 25).
+    for i in 0..30 {
+        let sample = Mp4Sample {
+            start_time: i * 100,
+            duration: 100,
+            rendering_offset: 0,
+            is_sync: i % 8 == 0,
+            bytes: Bytes::from(vec![i as u8]),
+        };
+        writer.write_sample(1, &sample).unwrap();
+    }
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    let track = mp4.tracks().get(&1).unwrap();
+
+    // Samples 11..=20 are presented in [1s, 2s) and decode from sample 9.
+    let samples = track
+        .sample_range_for_time(Duration::from_secs(1), Duration::from_secs(2))
+        .unwrap();
+    assert_eq!(samples, (9..=20).collect::<Vec<u32>>());
+
+    // A window starting on a sync sample needs nothing before it.
+    let samples = track
+        .sample_range_for_time(Duration::from_millis(1600), Duration::from_millis(1850))
+        .unwrap();
+    assert_eq!(samples, vec![17, 18, 19]);
+
+    assert!(track
+        .sample_range_for_time(Duration::from_secs(3), Duration::from_secs(4))
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn test_append_fragments() {
+    let config = Mp4Config {
+        major_brand: str::parse("iso6").unwrap(),
+        minor_version: 0,
+        compatible_brands: vec![str::parse("iso6").unwrap(), str::parse("dash").unwrap()],
+        ..Default::default()
+    };
+    let track_config = TrackConfig::from(AacConfig::default());
+    let sample = |i: u8| Mp4Sample {
+        start_time: 0,
+        duration: 1024,
+        rendering_offset: 0,
+        is_sync: true,
+        bytes: Bytes::from(vec![i, 0xAA]),
+    };
+
+    let mut writer =
+        Mp4FragmentWriter::write_init(Cursor::new(Vec::<u8>::new()), &config, &track_config)
+            .unwrap();
+    writer.start_segment().unwrap();
+    for i in 0..3 {
+        writer.write_sample(&sample(i)).unwrap();
+    }
+    writer.end_segment().unwrap();
+    let data = writer.into_writer().into_inner();
+    let written_size = data.len();
+
+    let mut writer = Mp4FragmentWriter::open_append(Cursor::new(data)).unwrap();
+    writer.start_segment().unwrap();
+    for i in 3..5 {
+        writer.write_sample(&sample(i)).unwrap();
+    }
+    let sidx = writer.end_segment().unwrap();
+    assert_eq!(sidx.earliest_presentation_time, 3 * 1024);
+
+    let data = writer.into_writer().into_inner();
+    assert!(data.len() > written_size);
+    let size = data.len() as u64;
+    let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    assert_eq!(mp4.moofs.len(), 2);
+    assert_eq!(
+        mp4.moofs
+            .iter()
+            .map(|moof| moof.mfhd.sequence_number)
+            .collect::<Vec<u32>>(),
+        vec![1, 2]
+    );
+    assert_eq!(
+        mp4.moofs[1].trafs[0]
+            .tfdt
+            .as_ref()
+            .unwrap()
+            .base_media_decode_time,
+        3 * 1024
+    );
+
+    assert_eq!(mp4.sample_count(1).unwrap(), 5);
+    for sample_id in 1..=5u32 {
+        let sample = mp4.read_sample(1, sample_id).unwrap().unwrap();
+        assert_eq!(sample.start_time, (sample_id as u64 - 1) * 1024);
+        assert_eq!(sample.bytes, Bytes::from(vec![sample_id as u8 - 1, 0xAA]));
+    }
+}
+
+#[test]
+fn test_total_sample_count() {
+    let mp4 = get_reader("tests/samples/minimal.mp4");
+    assert_eq!(mp4.total_sample_count(), 4);
+    assert_eq!(mp4.sample_count_by_media(MediaType::H264), 1);
+    assert_eq!(mp4.sample_count_by_media(MediaType::AAC), 3);
+    assert_eq!(mp4.sample_count_by_media(MediaType::H265), 0);
+}
+
+#[test]
+fn test_write_compressor_name() {
+    let config = Mp4Config {
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        ..Default::default()
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    let mut track_config = TrackConfig::from(AvcConfig {
+        width: 320,
+        height: 240,
+        seq_param_set: vec![0x67, 0x64, 0x00, 0x0D],
+        pic_param_set: vec![0x68, 0xEB, 0xE3, 0xCB],
+    });
+    track_config.compressor_name = String::from("AVC Coding");
+    writer.add_track(&track_config).unwrap();
+    track_config.compressor_name = "x".repeat(40);
+    writer.add_track(&track_config).unwrap();
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+
+    let track = mp4.tracks().get(&1).unwrap();
+    assert_eq!(track.compressor_name(), Some("AVC Coding"));
+
+    // Longer names are cut to the 31 bytes the field can hold.
+    let track = mp4.tracks().get(&2).unwrap();
+    assert_eq!(track.compressor_name(), Some("x".repeat(31).as_str()));
+
+    let mp4 = get_reader("tests/samples/minimal.mp4");
+    let track = mp4.tracks().get(&1).unwrap();
+    assert_eq!(track.compressor_name(), None);
+}
+
+#[test]
+fn test_read_sample_past_end_of_file() {
+    let config = Mp4Config {
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        ..Default::default()
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    writer
+        .add_track(&TrackConfig::from(AacConfig::default()))
+        .unwrap();
+    for i in 1..=3u8 {
+        let sample = Mp4Sample {
+            start_time: (i as u64 - 1) * 1024,
+            duration: 1024,
+            rendering_offset: 0,
+            is_sync: true,
+            bytes: Bytes::from(vec![i; i as usize * 10]),
+        };
+        writer.write_sample(1, &sample).unwrap();
+    }
+    writer.write_end().unwrap();
+    let mut data = writer.into_writer().into_inner();
+
+    // Declare a huge size for sample 2 in stsz.
+    let stsz = data.windows(4).position(|w| w == b"stsz").unwrap();
+    let entry = stsz + 4 + 4 + 4 + 4 + 4;
+    data[entry..entry + 4].copy_from_slice(&0x7FFF_FFFFu32.to_be_bytes());
+
+    let size = data.len() as u64;
+    let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+
+    match mp4.read_sample(1, 2) {
+        Err(Error::Truncated(1, 2, _, 0x7FFF_FFFF, file_size)) => assert_eq!(file_size, size),
+        result => panic!("unexpected result {:?}", result),
+    }
+
+    // Samples before it in the same chunk are still readable.
+    let sample = mp4.read_sample(1, 1).unwrap().unwrap();
+    assert_eq!(sample.bytes, Bytes::from(vec![1u8; 10]));
+}
+
+#[test]
+fn test_sample_offsets() {
+    let mp4 = get_reader("tests/samples/minimal.mp4");
+    for track in mp4.tracks().values() {
+        let offsets = track.sample_offsets().unwrap();
+        assert_eq!(offsets.len() as u32, track.sample_count());
+        for (i, offset) in offsets.iter().enumerate() {
+            assert_eq!(*offset, track.sample_offset(i as u32 + 1).unwrap());
+        }
+    }
+
+    let config = Mp4Config {
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        ..Default::default()
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    let mut track_config = TrackConfig::from(AacConfig::default());
+    track_config.timescale = 48000;
+    writer.add_track(&track_config).unwrap();
+    for i in 1..=12u8 {
+        let sample = Mp4Sample {
+            start_time: (i as u64 - 1) * 1024,
+            duration: 1024,
+            rendering_offset: 0,
+            is_sync: true,
+            bytes: Bytes::from(vec![i; i as usize]),
+        };
+        writer.write_sample(1, &sample).unwrap();
+        if i % 5 == 0 {
+            writer.flush_chunk(1).unwrap();
+        }
+    }
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mut mp4 = Mp4Reader::read_header(Cursor::new(data.clone()), size).unwrap();
+    let track = mp4.tracks().get(&1).unwrap();
+    assert_eq!(track.trak.mdia.minf.stbl.stsc.entries.len(), 2);
+    let offsets = track.sample_offsets().unwrap();
+    assert_eq!(offsets.len(), 12);
+
+    // Chunks hold samples 1-5, 6-10 and 11-12.
+    for chunk in [0..5, 5..10, 10..12].iter() {
+        let chunk_offsets = &offsets[chunk.clone()];
+        assert!(chunk_offsets.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    for (i, offset) in offsets.iter().enumerate() {
+        let sample = mp4.read_sample(1, i as u32 + 1).unwrap().unwrap();
+        let offset = *offset as usize;
+        assert_eq!(
+            &data[offset..offset + sample.bytes.len()],
+            &sample.bytes[..]
+        );
+    }
+}
+
+#[test]
+fn test_write_keyed_metadata() {
+    let config = Mp4Config {
+        major_brand: str::parse("qt  ").unwrap(),
+        minor_version: 0,
+        compatible_brands: vec![str::parse("qt  ").unwrap()],
+        ..Default::default()
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    writer
+        .add_track(&TrackConfig::from(AacConfig::default()))
+        .unwrap();
+    let text = |value: &str| DataBox {
+        data: value.as_bytes().to_vec(),
+        data_type: DataType::Text,
+    };
+    writer.set_keyed_metadata("com.apple.quicktime.make", text("Acme"));
+    writer.set_keyed_metadata("com.example.custom", text("draft"));
+    writer.set_keyed_metadata("com.example.custom", text("final"));
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+
+    match mp4.moov.meta {
+        Some(MetaBox::Mdta {
+            ref keys,
+            ref items,
+        }) => {
+            assert_eq!(keys.entries.len(), 2);
+            assert_eq!(keys.entries[1].namespace, str::parse("mdta").unwrap());
+            assert_eq!(items.keys().copied().collect::<Vec<u32>>(), vec![1, 2]);
+        }
+        ref meta => panic!("unexpected meta {:?}", meta),
+    }
+    assert_eq!(
+        mp4.keyed_metadata("com.apple.quicktime.make"),
+        Some(&text("Acme"))
+    );
+    assert_eq!(
+        mp4.keyed_metadata("com.example.custom"),
+        Some(&text("final"))
+    );
+    assert_eq!(mp4.keyed_metadata("com.example.missing"), None);
+}
+
+#[test]
+fn test_gops() {
+    let config = Mp4Config {
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        ..Default::default()
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    writer
+        .add_track(&TrackConfig::from(AvcConfig {
+            width: 320,
+            height: 240,
+            seq_param_set: vec![0x67, 0x64, 0x00, 0x0D],
+            pic_param_set: vec![0x68, 0xEB, 0xE3, 0xCB],
+        }))
+        .unwrap();
+
+    // A keyframe every 8 samples: 1, 9, 17 and 25.
+    for i in 0..30 {
+        let sample = Mp4Sample {
+            start_time: i * 40,
+            duration: 40,
+            rendering_offset: 0,
+            is_sync: i % 8 == 0,
+            bytes: Bytes::from(vec![i as u8]),
+        };
+        writer.write_sample(1, &sample).unwrap();
+    }
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+
+    let gops: Vec<Gop> = mp4.gops(1).unwrap().collect();
+    assert_eq!(gops.len(), 4);
+    assert_eq!(
+        gops.iter()
+            .map(|gop| gop.start_sample)
+            .collect::<Vec<u32>>(),
+        vec![1, 9, 17, 25]
+    );
+    assert_eq!(gops[1].sample_ids, (9..=16).collect::<Vec<u32>>());
+    assert_eq!(gops[3].sample_ids, (25..=30).collect::<Vec<u32>>());
+
+    assert!(matches!(mp4.gops(2), Err(Error::TrakNotFound(2))));
+
+    // Without stss every sample is a sync sample.
+    let mp4 = get_reader("tests/samples/minimal.mp4");
+    assert_eq!(mp4.gops(2).unwrap().count(), 3);
+}
+
+#[test]
+fn test_deterministic_output() {
+    let write = |deterministic: bool| {
+        let config = Mp4Config {
+            compatible_brands: vec![str::parse("isom").unwrap()],
+            deterministic,
+            ..Default::default()
+        };
+        let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+        writer
+            .add_track(&TrackConfig::from(AacConfig::default()))
+            .unwrap();
+        for i in 0..5u8 {
+            let sample = Mp4Sample {
+                start_time: i as u64 * 1024,
+                duration: 1024,
+                rendering_offset: 0,
+                is_sync: true,
+                bytes: Bytes::from(vec![i; 16]),
+            };
+            writer.write_sample(1, &sample).unwrap();
+        }
+        writer
+            .set_cover_art(vec![0xFF, 0xD8, 0xFF, 0xE0], DataType::Image)
+            .unwrap();
+        writer.write_end().unwrap();
+        writer.into_writer().into_inner()
+    };
+
+    let first = write(true);
+    let second = write(true);
+    assert_eq!(first, second);
+
+    let mp4 = Mp4Reader::read_header(Cursor::new(first.clone()), first.len() as u64).unwrap();
+    assert_eq!(mp4.moov.mvhd.creation_time, 0);
+    assert_eq!(mp4.moov.mvhd.modification_time, 0);
+
+    let stamped = write(false);
+    let mp4 = Mp4Reader::read_header(Cursor::new(stamped.clone()), stamped.len() as u64).unwrap();
+    assert!(mp4.moov.mvhd.creation_time > 0);
+    let track = mp4.tracks().get(&1).unwrap();
+    assert_eq!(track.trak.tkhd.creation_time, mp4.moov.mvhd.creation_time);
+    assert_eq!(
+        track.trak.mdia.mdhd.creation_time,
+        mp4.moov.mvhd.creation_time
+    );
+}
+
+#[test]
+fn test_read_sample_from_external_data() {
+    let config = Mp4Config {
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        ..Default::default()
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    writer
+        .add_track(&TrackConfig::from(AacConfig::default()))
+        .unwrap();
+    for i in 0..3u8 {
+        let sample = Mp4Sample {
+            start_time: i as u64 * 1024,
+            duration: 1024,
+            rendering_offset: 0,
+            is_sync: true,
+            bytes: Bytes::from(vec![i + 1; 8]),
+        };
+        writer.write_sample(1, &sample).unwrap();
+    }
+    writer.write_end().unwrap();
+    let media = writer.into_writer().into_inner();
+
+    // A reference movie: the same moov, with its sample data left in the
+    // media file.
+    let mp4 = Mp4Reader::read_header(Cursor::new(media.clone()), media.len() as u64).unwrap();
+    let mut moov = mp4.moov.clone();
+    let url = moov.traks[0].mdia.minf.dinf.dref.url.as_mut().unwrap();
+    url.flags = 0;
+    url.location = "media.mp4".to_string();
+    let mut reference = Vec::new();
+    mp4.ftyp.write_box(&mut reference).unwrap();
+    moov.write_box(&mut reference).unwrap();
+
+    let size = reference.len() as u64;
+    let mut mp4 = Mp4Reader::read_header(Cursor::new(reference), size).unwrap();
+    assert_eq!(mp4.tracks()[&1].data_location(), Some("media.mp4"));
+    assert!(matches!(
+        mp4.read_sample(1, 1),
+        Err(Error::ExternalDataNotResolved(1, ref location)) if location == "media.mp4"
+    ));
+
+    mp4.set_data_resolver(move |location| {
+        assert_eq!(location, "media.mp4");
+        Box::new(Cursor::new(media.clone()))
+    });
+    for i in 1..=3u32 {
+        let sample = mp4.read_sample(1, i).unwrap().unwrap();
+        assert_eq!(sample.bytes, Bytes::from(vec![i as u8; 8]));
+    }
+}
+
+#[test]
+fn test_codec_private_data() {
+    let mp4 = get_reader("tests/samples/minimal.mp4");
+
+    let video = mp4.tracks().get(&1).unwrap();
+    let data = video.codec_private_data().unwrap();
+    let mut avcc_box = Vec::new();
+    BoxHeader::new(BoxType::AvcCBox, 8 + data.len() as u64)
+        .write(&mut avcc_box)
+        .unwrap();
+    avcc_box.extend_from_slice(&data);
+    let mut reader = Cursor::new(avcc_box);
+    let header = BoxHeader::read(&mut reader).unwrap();
+    let avcc = AvcCBox::read_box(&mut reader, header.size).unwrap();
+    assert_eq!(
+        &avcc,
+        &video.trak.mdia.minf.stbl.stsd.avc1.as_ref().unwrap().avcc
+    );
+    assert_eq!(
+        avcc.sequence_parameter_sets[0].bytes,
+        video.sequence_parameter_set().unwrap()
+    );
+
+    // AAC-LC, 48 kHz, mono.
+    let audio = mp4.tracks().get(&2).unwrap();
+    assert_eq!(audio.codec_private_data().unwrap(), vec![0x11, 0x88]);
+}
+
+#[test]
+fn test_fix_fragment_run_offsets() {
+    let config = Mp4Config {
+        major_brand: str::parse("iso6").unwrap(),
+        minor_version: 0,
+        compatible_brands: vec![str::parse("iso6").unwrap()],
+        ..Default::default()
+    };
+    let writer = Mp4FragmentWriter::write_init(
+        Cursor::new(Vec::<u8>::new()),
+        &config,
+        &TrackConfig::from(AacConfig::default()),
+    )
+    .unwrap();
+    let mut data = writer.into_writer().into_inner();
+    let init_size = data.len() as u64;
+
+    // A media segment written as if it were the only one after the init
+    // segment, with absolute offsets.
+    let segment = |sequence_number: u32, samples: &[Vec<u8>]| {
+        let mut moof = MoofBox {
+            mfhd: MfhdBox {
+                sequence_number,
+                ..Default::default()
+            },
+            trafs: vec![TrafBox {
+                tfhd: TfhdBox {
+                    flags: TfhdBox::FLAG_BASE_DATA_OFFSET,
+                    track_id: 1,
+                    base_data_offset: Some(init_size),
+                    ..Default::default()
+                },
+                tfdt: Some(TfdtBox {
+                    version: 1,
+                    flags: 0,
+                    base_media_decode_time: (sequence_number as u64 - 1) * 2048,
+                }),
+                trun: Some(TrunBox {
+                    flags: TrunBox::FLAG_DATA_OFFSET
+                        | TrunBox::FLAG_SAMPLE_DURATION
+                        | TrunBox::FLAG_SAMPLE_SIZE,
+                    sample_count: samples.len() as u32,
+                    data_offset: Some(0),
+                    sample_durations: vec![1024; samples.len()],
+                    sample_sizes: samples.iter().map(|s| s.len() as u32).collect(),
+                    ..Default::default()
+                }),
+                senc: None,
+            }],
+        };
+        let data_offset = moof.box_size() as i32 + 8;
+        moof.trafs[0].trun.as_mut().unwrap().data_offset = Some(data_offset);
+
+        let mut bytes = Vec::new();
+        moof.write_box(&mut bytes).unwrap();
+        let payload = samples.concat();
+        BoxHeader::new(BoxType::MdatBox, 8 + payload.len() as u64)
+            .write(&mut bytes)
+            .unwrap();
+        bytes.extend_from_slice(&payload);
+        bytes
+    };
+    let first_segment = segment(1, &[vec![1; 4], vec![2; 6]]);
+    let second_offset = init_size + first_segment.len() as u64;
+    data.extend(first_segment);
+    data.extend(segment(2, &[vec![3; 5], vec![4; 3]]));
+
+    let size = data.len() as u64;
+    let mut mp4 = Mp4Reader::read_header(Cursor::new(&mut data), size).unwrap();
+    assert_eq!(mp4.sample_count(1).unwrap(), 4);
+    // The second segment's offsets still point into the first one.
+    assert_eq!(
+        mp4.read_sample(1, 3).unwrap().unwrap().bytes,
+        Bytes::from(vec![1, 1, 1, 1, 2])
+    );
+
+    mp4.fix_fragment_run_offsets().unwrap();
+    let expected = [vec![1; 4], vec![2; 6], vec![3; 5], vec![4; 3]];
+    for (i, bytes) in expected.iter().enumerate() {
+        let sample = mp4.read_sample(1, i as u32 + 1).unwrap().unwrap();
+        assert_eq!(sample.bytes, Bytes::from(bytes.clone()));
+    }
+
+    // The fix is written to the file itself.
+    drop(mp4);
+    let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    assert_eq!(
+        mp4.moofs[1].trafs[0].tfhd.base_data_offset,
+        Some(second_offset)
+    );
+    let sample = mp4.read_sample(1, 4).unwrap().unwrap();
+    assert_eq!(sample.bytes, Bytes::from(vec![4; 3]));
+}
+
+#[test]
+fn test_is_sync_sample() {
+    // Without an stss box, every sample is a sync sample.
+    let mp4 = get_reader("tests/samples/minimal.mp4");
+    let audio = mp4.tracks().get(&2).unwrap();
+    assert!(audio.trak.mdia.minf.stbl.stss.is_none());
+    assert!((1..=audio.sample_count()).all(|id| audio.is_sync_sample(id)));
+
+    // In fragments, it comes from the trun sample flags.
+    let config = Mp4Config {
+        major_brand: str::parse("iso6").unwrap(),
+        minor_version: 0,
+        compatible_brands: vec![str::parse("iso6").unwrap()],
+        ..Default::default()
+    };
+    let track_config = TrackConfig {
+        track_type: TrackType::Video,
+        timescale: 1000,
+        language: String::from("und"),
+        volume: FixedPointU8::new(0),
+        media_conf: MediaConfig::AvcConfig(AvcConfig {
+            width: 320,
+            height: 240,
+            seq_param_set: vec![0x67, 0x64, 0x00, 0x1f],
+            pic_param_set: vec![0x68, 0xeb, 0xe3, 0xcb],
+        }),
+        ..Default::default()
+    };
+    let mut writer =
+        Mp4FragmentWriter::write_init(Cursor::new(Vec::<u8>::new()), &config, &track_config)
+            .unwrap();
+    let sync = [true, false, false, true, false];
+    for (i, &is_sync) in sync.iter().enumerate() {
+        if is_sync {
+            if i > 0 {
+                writer.end_segment().unwrap();
+            }
+            writer.start_segment().unwrap();
+        }
+        writer
+            .write_sample(&Mp4Sample {
+                start_time: 0,
+                duration: 40,
+                rendering_offset: 0,
+                is_sync,
+                bytes: Bytes::from(vec![0u8; 4]),
+            })
+            .unwrap();
+    }
+    writer.end_segment().unwrap();
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    let track = mp4.tracks().get(&1).unwrap();
+    assert_eq!(track.sample_count(), 5);
+    let flags: Vec<bool> = (1..=5).map(|id| track.is_sync_sample(id)).collect();
+    assert_eq!(flags, sync);
+}
+
+#[test]
+fn test_write_fragment_index() {
+    let config = Mp4Config {
+        major_brand: str::parse("iso6").unwrap(),
+        minor_version: 0,
+        compatible_brands: vec![str::parse("iso6").unwrap(), str::parse("dash").unwrap()],
+        ..Default::default()
+    };
+    let mut writer = Mp4FragmentWriter::write_init(
+        Cursor::new(Vec::<u8>::new()),
+        &config,
+        &TrackConfig::from(AacConfig::default()),
+    )
+    .unwrap();
+
+    for i in 0..6u8 {
+        if i % 3 == 0 {
+            writer.start_segment().unwrap();
+        }
+        writer
+            .write_sample(&Mp4Sample {
+                start_time: 0,
+                duration: 1024,
+                rendering_offset: 0,
+                is_sync: true,
+                bytes: Bytes::from(vec![i; 10]),
+            })
+            .unwrap();
+        if i == 1 {
+            writer.flush_chunk().unwrap();
+        }
+    }
+    let sidx = writer.write_end().unwrap();
+    assert_eq!(sidx.references.len(), 3);
+    assert_eq!(
+        sidx.references
+            .iter()
+            .map(|r| r.subsegment_duration)
+            .collect::<Vec<u32>>(),
+        vec![2048, 1024, 3072]
+    );
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    assert_eq!(mp4.sidxs, vec![sidx.clone()]);
+
+    // The sidx sits right after the init segment and its references cover
+    // the rest of the file, each starting at a segment or chunk.
+    let index = mp4.segment_index();
+    assert_eq!(index.len(), 3);
+    let init_size = mp4.ftyp.box_size() + mp4.moov.box_size();
+    assert_eq!(index[0].offset, init_size + sidx.box_size());
+    let mut offset = index[0].offset;
+    for subsegment in index.iter() {
+        assert_eq!(subsegment.offset, offset);
+        let header = mp4.raw_box_reader(subsegment.offset, 8).unwrap();
+        assert!(&header[4..] == b"styp" || &header[4..] == b"moof");
+        offset += subsegment.size as u64;
+    }
+    assert_eq!(offset, size);
+    assert_eq!(index[2].start_time, 3 * 1024);
+
+    assert_eq!(mp4.sample_count(1).unwrap(), 6);
+    for sample_id in 1..=6u32 {
+        let sample = mp4.read_sample(1, sample_id).unwrap().unwrap();
+        assert_eq!(sample.bytes, Bytes::from(vec![sample_id as u8 - 1; 10]));
+    }
+}
+
+#[test]
+fn test_write_fragment_index_segments() {
+    let config = Mp4Config {
+        major_brand: str::parse("iso6").unwrap(),
+        minor_version: 0,
+        compatible_brands: vec![str::parse("iso6").unwrap()],
+        ..Default::default()
+    };
+    let fragment_config = FragmentConfig {
+        index_segments: true,
+        ..Default::default()
+    };
+    let mut writer = Mp4FragmentWriter::write_init_with(
+        Cursor::new(Vec::<u8>::new()),
+        &config,
+        &TrackConfig::from(AacConfig::default()),
+        &fragment_config,
+    )
+    .unwrap();
+
+    let mut sidxs = Vec::new();
+    for i in 0..6u8 {
+        if i % 3 == 0 {
+            writer.start_segment().unwrap();
+        }
+        writer
+            .write_sample(&Mp4Sample {
+                start_time: 0,
+                duration: 1024,
+                rendering_offset: 0,
+                is_sync: true,
+                bytes: Bytes::from(vec![i; 10]),
+            })
+            .unwrap();
+        if i % 3 == 1 {
+            writer.flush_chunk().unwrap();
+        }
+        if i % 3 == 2 {
+            sidxs.push(writer.end_segment().unwrap());
+        }
+    }
+    assert_eq!(sidxs.len(), 2);
+    assert_eq!(sidxs[1].earliest_presentation_time, 3 * 1024);
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    assert_eq!(mp4.sidxs, sidxs);
+
+    // Each segment is styp, sidx and its two chunks, which the sidx indexes.
+    let index = mp4.segment_index();
+    assert_eq!(index.len(), 4);
+    let mut offset = mp4.ftyp.box_size() + mp4.moov.box_size();
+    for (i, subsegment) in index.iter().enumerate() {
+        if i % 2 == 0 {
+            let header = mp4.raw_box_reader(offset, 8).unwrap();
+            assert_eq!(&header[4..], b"styp");
+            let styp_size = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+            offset += styp_size as u64 + sidxs[i / 2].box_size();
+        }
+        assert_eq!(subsegment.offset, offset);
+        let header = mp4.raw_box_reader(subsegment.offset, 8).unwrap();
+        assert_eq!(&header[4..], b"moof");
+        offset += subsegment.size as u64;
+    }
+    assert_eq!(offset, size);
+    assert_eq!(
+        index.iter().map(|s| s.start_time).collect::<Vec<u64>>(),
+        vec![0, 2048, 3072, 5120]
+    );
+
+    assert_eq!(mp4.sample_count(1).unwrap(), 6);
+    for sample_id in 1..=6u32 {
+        let sample = mp4.read_sample(1, sample_id).unwrap().unwrap();
+        assert_eq!(sample.bytes, Bytes::from(vec![sample_id as u8 - 1; 10]));
+    }
+}
+
+#[test]
+fn test_write_fragment_index_overflow() {
+    let mut writer = Mp4FragmentWriter::write_init(
+        Cursor::new(Vec::<u8>::new()),
+        &Mp4Config::default(),
+        &TrackConfig::from(AacConfig::default()),
+    )
+    .unwrap();
+
+    // A chunk longer than a sidx subsegment_duration can hold.
+    for _ in 0..2 {
+        writer
+            .write_sample(&Mp4Sample {
+                start_time: 0,
+                duration: u32::MAX,
+                rendering_offset: 0,
+                is_sync: true,
+                bytes: Bytes::from(vec![0; 10]),
+            })
+            .unwrap();
+    }
+    assert!(matches!(writer.flush_chunk(), Err(Error::InvalidData(_))));
+}
+
+#[cfg(feature = "cenc")]
+#[test]
+fn test_read_cenc_file() {
+    // minimal_init.mp4 and minimal_fragment.m4s, protected with the cenc
+    // scheme by a separate AES implementation: the video sample in the first
+    // fragment with a subsample per NAL unit, and again whole in a second.
+    let kid: [u8; 16] = core::array::from_fn(|i| 0x10 + i as u8);
+    let key: [u8; 16] = core::array::from_fn(|i| i as u8 * 0x11);
+
+    let mp4 = get_reader("tests/samples/minimal_init.mp4");
+    let f = File::open("tests/samples/minimal_fragment.m4s").unwrap();
+    let size = f.metadata().unwrap().len();
+    let mut fragment = mp4.read_fragment_header(BufReader::new(f), size).unwrap();
+    let clear = fragment.read_sample(1, 1).unwrap().unwrap().bytes;
+
+    let mut mp4 = get_reader("tests/samples/minimal_cenc.mp4");
+    let track = mp4.tracks().get(&1).unwrap();
+    assert!(track.is_encrypted());
+    assert_eq!(track.media_type().unwrap(), MediaType::H264);
+    let subsamples = |traf_idx: usize| {
+        let senc = track.trafs[traf_idx].senc.as_ref().unwrap();
+        senc.entries(8).unwrap()[0].subsamples.len()
+    };
+    assert_eq!(subsamples(0), 2);
+    assert_eq!(subsamples(1), 0);
+
+    let encrypted = mp4.read_sample(1, 1).unwrap().unwrap().bytes;
+    assert_ne!(encrypted, clear);
+    // The NAL unit lengths and headers stay in the clear.
+    assert_eq!(encrypted[..5], clear[..5]);
+
+    mp4.set_decryption_keys(HashMap::from([(kid, key)]));
+    for sample_id in [1, 2] {
+        let sample = mp4.read_sample(1, sample_id).unwrap().unwrap();
+        assert_eq!(sample.bytes, clear);
+        let mut buf = Vec::new();
+        mp4.read_sample_into(1, sample_id, &mut buf).unwrap();
+        assert_eq!(buf, clear);
+    }
+}
+
+#[cfg(feature = "cenc")]
+#[test]
+fn test_read_decrypted_samples() {
+    // NIST SP 800-38A, F.5.1: CTR-AES128.
+    let key: [u8; 16] = [
+        0x2b, 0x7e, 0x15, 0x16, 0x28, 0xae, 0xd2, 0xa6, 0xab, 0xf7, 0x15, 0x88, 0x09, 0xcf, 0x4f,
+        0x3c,
+    ];
+    let iv: Vec<u8> = (0xf0..=0xff).collect();
+    let plaintext = [
+        0x6b, 0xc1, 0xbe, 0xe2, 0x2e, 0x40, 0x9f, 0x96, 0xe9, 0x3d, 0x7e, 0x11, 0x73, 0x93, 0x17,
+        0x2a, 0xae, 0x2d, 0x8a, 0x57, 0x1e, 0x03, 0xac, 0x9c, 0x9e, 0xb7, 0x6f, 0xac, 0x45, 0xaf,
+        0x8e, 0x51,
+    ];
+    let ciphertext = [
+        0x87, 0x4d, 0x61, 0x91, 0xb6, 0x20, 0xe3, 0x26, 0x1b, 0xef, 0x68, 0x64, 0x99, 0x0d, 0xb6,
+        0xce, 0x98, 0x06, 0xf6, 0x6b, 0x79, 0x70, 0xfd, 0xff, 0x86, 0x17, 0x18, 0x7b, 0xb9, 0xff,
+        0xfd, 0xff,
+    ];
+    let kid = [0x42; 16];
+
+    // The first sample has a clear 5 byte header; the protected bytes of
+    // the second one are split in two subsamples sharing one key stream.
+    let clear_header = [0xFF, 0xF1, 0x50, 0x80, 0x00];
+    let encrypted_samples = [
+        [&clear_header[..], &ciphertext[..]].concat(),
+        [
+            &[0xAA][..],
+            &ciphertext[..10],
+            &[0xBB, 0xBB][..],
+            &ciphertext[10..],
+        ]
+        .concat(),
+    ];
+    let clear_samples = [
+        [&clear_header[..], &plaintext[..]].concat(),
+        [
+            &[0xAA][..],
+            &plaintext[..10],
+            &[0xBB, 0xBB][..],
+            &plaintext[10..],
+        ]
+        .concat(),
+    ];
+    let entries = vec![
+        SencEntry {
+            iv: iv.clone(),
+            subsamples: vec![SencSubsample {
+                bytes_of_clear_data: 5,
+                bytes_of_protected_data: 32,
+            }],
+        },
+        SencEntry {
+            iv: iv.clone(),
+            subsamples: vec![
+                SencSubsample {
+                    bytes_of_clear_data: 1,
+                    bytes_of_protected_data: 10,
+                },
+                SencSubsample {
+                    bytes_of_clear_data: 2,
+                    bytes_of_protected_data: 22,
+                },
+            ],
+        },
+    ];
+
+    let config = Mp4Config {
+        major_brand: str::parse("iso6").unwrap(),
+        minor_version: 0,
+        compatible_brands: vec![str::parse("iso6").unwrap()],
+        deterministic: true,
+        ..Default::default()
+    };
+    let mut writer = Mp4FragmentWriter::write_init(
+        Cursor::new(Vec::<u8>::new()),
+        &config,
+        &TrackConfig::from(AacConfig::default()),
+    )
+    .unwrap();
+    writer.start_segment().unwrap();
+    for bytes in encrypted_samples.iter() {
+        writer
+            .write_sample(&Mp4Sample {
+                start_time: 0,
+                duration: 1024,
+                rendering_offset: 0,
+                is_sync: true,
+                bytes: Bytes::from(bytes.clone()),
+            })
+            .unwrap();
+    }
+    writer.end_segment().unwrap();
+    let data = writer.into_writer().into_inner();
+    let mp4 = Mp4Reader::read_header(Cursor::new(data.clone()), data.len() as u64).unwrap();
+
+    // Turn it into a protected file: enca with sinf/schi/tenc, and senc.
+    let tenc = TencBox {
+        version: 0,
+        flags: 0,
+        default_crypt_byte_block: 0,
+        default_skip_byte_block: 0,
+        default_is_protected: 1,
+        default_per_sample_iv_size: 16,
+        default_kid: kid,
+        default_constant_iv: None,
+    };
+    let mut schi = Vec::new();
+    BoxHeader::new(BoxType::SchiBox, 8 + tenc.box_size())
+        .write(&mut schi)
+        .unwrap();
+    tenc.write_box(&mut schi).unwrap();
+    let mut moov = mp4.moov.clone();
+    moov.traks[0]
+        .mdia
+        .minf
+        .stbl
+        .stsd
+        .mp4a
+        .as_mut()
+        .unwrap()
+        .sinf = Some(SinfBox {
+        frma: FrmaBox {
+            original_format: str::parse("mp4a").unwrap(),
+        },
+        schm: Some(SchmBox {
+            version: 0,
+            flags: 0,
+            scheme_type: str::parse("cenc").unwrap(),
+            scheme_version: 0x0001_0000,
+            scheme_uri: None,
+        }),
+        schi: Some(schi),
+    });
+    let mut moof = mp4.moofs[0].clone();
+    moof.trafs[0].senc = Some(SencBox::new(&entries));
+    let data_offset = moof.box_size() as i32 + 8;
+    moof.trafs[0].trun.as_mut().unwrap().data_offset = Some(data_offset);
+
+    let mut data = Vec::new();
+    mp4.ftyp.write_box(&mut data).unwrap();
+    moov.write_box(&mut data).unwrap();
+    moof.write_box(&mut data).unwrap();
+    let payload = encrypted_samples.concat();
+    BoxHeader::new(BoxType::MdatBox, 8 + payload.len() as u64)
+        .write(&mut data)
+        .unwrap();
+    data.extend_from_slice(&payload);
+
+    let size = data.len() as u64;
+    let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    let track = mp4.tracks().get(&1).unwrap();
+    assert!(track.is_encrypted());
+    assert_eq!(
+        track.trafs[0].senc.as_ref().unwrap().entries(16).unwrap(),
+        entries
+    );
+
+    // Without keys, samples are read as stored.
+    let sample = mp4.read_sample(1, 1).unwrap().unwrap();
+    assert_eq!(sample.bytes, Bytes::from(encrypted_samples[0].clone()));
+
+    mp4.set_decryption_keys(HashMap::from([([0x00; 16], key)]));
+    assert!(matches!(
+        mp4.read_sample(1, 1),
+        Err(Error::DecryptionKeyNotFound(1, k)) if k == kid
+    ));
+
+    mp4.set_decryption_keys(HashMap::from([(kid, key)]));
+    for (i, bytes) in clear_samples.iter().enumerate() {
+        let sample = mp4.read_sample(1, i as u32 + 1).unwrap().unwrap();
+        assert_eq!(sample.bytes, Bytes::from(bytes.clone()));
+    }
+}
+
+#[test]
+fn test_write_opus_track() {
+    let config = Mp4Config {
+        compatible_brands: vec![str::parse("isom").unwrap(), str::parse("Opus").unwrap()],
+        ..Default::default()
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+
+    // 5.1 with channel mapping family 1.
+    let opus_conf = OpusConfig {
+        output_channel_count: 6,
+        pre_skip: 312,
+        input_sample_rate: 44100,
+        output_gain: -128,
+        channel_mapping_family: 1,
+        stream_count: 4,
+        coupled_count: 2,
+        channel_mapping: vec![0, 4, 1, 2, 3, 5],
+    };
+    writer
+        .add_track(&TrackConfig::from(opus_conf.clone()))
+        .unwrap();
+    for i in 0..3u64 {
+        let sample = Mp4Sample {
+            start_time: i * 960,
+            duration: 960,
+            rendering_offset: 0,
+            is_sync: true,
+            bytes: Bytes::from(vec![i as u8; 8]),
+        };
+        writer.write_sample(1, &sample).unwrap();
+    }
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+
+    let track = mp4.tracks().get(&1).unwrap();
+    assert_eq!(track.track_type().unwrap(), TrackType::Audio);
+    assert_eq!(track.media_type().unwrap(), MediaType::Opus);
+    assert_eq!(track.box_type().unwrap(), BoxType::OpusBox.into());
+    assert_eq!(track.timescale(), 48000);
+    assert_eq!(track.opus_config().unwrap(), opus_conf);
+    let opus = track.trak.mdia.minf.stbl.stsd.opus.as_ref().unwrap();
+    assert_eq!(opus.channelcount, 6);
+    assert_eq!(opus.samplerate.value(), 48000);
+    assert_eq!(mp4.guess_mime_type().unwrap(), "audio/mp4; codecs=\"Opus\"");
+
+    let sample = mp4.read_sample(1, 3).unwrap().unwrap();
+    assert_eq!(sample.bytes, Bytes::from(vec![2u8; 8]));
+}
+
+#[test]
+fn test_keyframe_interval() {
+    let config = Mp4Config {
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        ..Default::default()
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    writer
+        .add_track(&TrackConfig::from(AvcConfig {
+            width: 320,
+            height: 240,
+            seq_param_set: vec![0x67, 0x64, 0x00, 0x0D],
+            pic_param_set: vec![0x68, 0xEB, 0xE3, 0xCB],
+        }))
+        .unwrap();
+
+    // 25 fps with a keyframe every 25 samples (1, 26, 51 and 76), plus one
+    // every 50 samples at the end (126).
+    for i in 0..150 {
+        let sample = Mp4Sample {
+            start_time: i * 40,
+            duration: 40,
+            rendering_offset: 0,
+            is_sync: (i < 100 && i % 25 == 0) || i == 125,
+            bytes: Bytes::from(vec![i as u8]),
+        };
+        writer.write_sample(1, &sample).unwrap();
+    }
+    writer.write_end().unwrap();
+
+    let mut data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data.clone()), size).unwrap();
+
+    let track = mp4.tracks().get(&1).unwrap();
+    assert_eq!(track.keyframe_interval(), (31.25, 50));
+    assert_eq!(
+        track.keyframe_interval_duration().unwrap(),
+        (Duration::from_millis(1250), Duration::from_secs(2))
+    );
+    assert_eq!(track.average_gop_size(), 30.0);
+
+    // mdhd's timescale follows its version, flags and two times.
+    let mdhd = data.windows(4).position(|w| w == b"mdhd").unwrap();
+    data[mdhd + 16..mdhd + 20].copy_from_slice(&0u32.to_be_bytes());
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    assert!(matches!(
+        mp4.tracks().get(&1).unwrap().keyframe_interval_duration(),
+        Err(Error::InvalidData("track timescale is zero"))
+    ));
+
+    // Without stss every sample is a sync sample.
+    let mp4 = get_reader("tests/samples/minimal.mp4");
+    assert_eq!(mp4.tracks().get(&2).unwrap().keyframe_interval(), (1.0, 1));
+}
+
+#[test]
+fn test_write_media_header_per_track_type() {
+    let config = Mp4Config {
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        ..Default::default()
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    writer
+        .add_track(&TrackConfig::from(Vp9Config {
+            width: 320,
+            height: 240,
+        }))
+        .unwrap();
+    writer
+        .add_track(&TrackConfig::from(AacConfig::default()))
+        .unwrap();
+    writer.add_track(&TrackConfig::from(TtxtConfig {})).unwrap();
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+
+    let minf = |track_id: u32| &mp4.tracks().get(&track_id).unwrap().trak.mdia.minf;
+    let headers = |track_id: u32| {
+        let minf = minf(track_id);
+        (
+            minf.vmhd.is_some(),
+            minf.smhd.is_some(),
+            minf.hmhd.is_some(),
+            minf.nmhd.is_some(),
+        )
+    };
+    assert_eq!(headers(1), (true, false, false, false));
+    assert_eq!(headers(2), (false, true, false, false));
+    assert_eq!(headers(3), (false, false, false, true));
+    assert_eq!(minf(3).nmhd, Some(NmhdBox::default()));
+}
+
+#[test]
+fn test_dump_elementary_stream() {
+    let mut mp4 = get_reader("tests/samples/minimal.mp4");
+
+    let mut adts = Vec::new();
+    mp4.dump_elementary_stream(2, &mut adts).unwrap();
+    let mut offset = 0;
+    for sample_id in 1..=3 {
+        let sample = mp4.read_sample(2, sample_id).unwrap().unwrap();
+        let header = &adts[offset..offset + 7];
+        assert_eq!(header[0], 0xFF);
+        assert_eq!(header[1] & 0xF0, 0xF0);
+        // AAC-LC (profile 1), 48 kHz (index 3), mono.
+        assert_eq!(header[2] >> 6, 1);
+        assert_eq!((header[2] >> 2) & 0xF, 3);
+        assert_eq!(((header[2] & 0x1) << 2) | (header[3] >> 6), 1);
+        let frame_size = ((header[3] as usize & 0x3) << 11)
+            | ((header[4] as usize) << 3)
+            | (header[5] as usize >> 5);
+        assert_eq!(frame_size, 7 + sample.bytes.len());
+        assert_eq!(&adts[offset + 7..offset + frame_size], &sample.bytes[..]);
+        offset += frame_size;
+    }
+    assert_eq!(offset, adts.len());
+
+    let mut annexb = Vec::new();
+    mp4.dump_elementary_stream(1, &mut annexb).unwrap();
+    let sps = mp4.tracks()[&1].sequence_parameter_set().unwrap().to_vec();
+    assert_eq!(&annexb[..4], &[0, 0, 0, 1]);
+    assert_eq!(&annexb[4..4 + sps.len()], &sps[..]);
+
+    assert!(matches!(
+        mp4.dump_elementary_stream(3, &mut Vec::new()),
+        Err(Error::TrakNotFound(3))
+    ));
+}
+
+#[test]
+fn test_write_av1_track() {
+    let config = Mp4Config {
+        compatible_brands: vec![str::parse("isom").unwrap(), str::parse("av01").unwrap()],
+        ..Default::default()
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+
+    // Main profile, level 4.0, 8-bit 4:2:0, 1920x1080.
+    let sequence_header = vec![
+        0x0A, 0x0B, 0x00, 0x00, 0x00, 0x42, 0xAB, 0xBF, 0xC3, 0x73, 0xFF, 0xE6, 0x02,
+    ];
+    writer
+        .add_track(&TrackConfig::from(Av1Config {
+            width: 1920,
+            height: 1080,
+            config_obus: sequence_header.clone(),
+        }))
+        .unwrap();
+    let sample = Mp4Sample {
+        start_time: 0,
+        duration: 40,
+        rendering_offset: 0,
+        is_sync: true,
+        bytes: Bytes::from_static(&[0x12, 0x00]),
+    };
+    writer.write_sample(1, &sample).unwrap();
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+
+    let track = mp4.tracks().get(&1).unwrap();
+    assert_eq!(track.media_type().unwrap(), MediaType::AV1);
+    assert_eq!(track.box_type().unwrap(), BoxType::Av01Box.into());
+    assert_eq!(track.width(), 1920);
+    assert_eq!(track.height(), 1080);
+    assert!(track.trak.mdia.minf.vmhd.is_some());
+
+    let av1c = &track.trak.mdia.minf.stbl.stsd.av01.as_ref().unwrap().av1c;
+    assert_eq!(av1c.seq_profile, 0);
+    assert_eq!(av1c.seq_level_idx_0, 8);
+    assert_eq!(av1c.config_obus, sequence_header);
+
+    let codec_private = track.codec_private_data().unwrap();
+    assert_eq!(&codec_private[..4], &[0x81, 0x08, 0x0C, 0x00]);
+    assert_eq!(&codec_private[4..], &sequence_header[..]);
+    assert_eq!(
+        mp4.guess_mime_type().unwrap(),
+        "video/mp4; codecs=\"av01.0.08M.08\""
+    );
+}
+
+#[test]
+fn test_write_ac3_track() {
+    let config = Mp4Config {
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        ..Default::default()
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+
+    // 5.1 (3/2 + LFE) at 48 kHz and 448 kbit/s.
+    let ac3_conf = Ac3Config {
+        fscod: 0,
+        bsid: 8,
+        bsmod: 0,
+        acmod: 7,
+        lfeon: true,
+        bit_rate_code: 16,
+    };
+    writer
+        .add_track(&TrackConfig::from(ac3_conf.clone()))
+        .unwrap();
+    let sample = Mp4Sample {
+        start_time: 0,
+        duration: 1536,
+        rendering_offset: 0,
+        is_sync: true,
+        bytes: Bytes::from_static(&[0x0B, 0x77]),
+    };
+    writer.write_sample(1, &sample).unwrap();
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+
+    let track = mp4.tracks().get(&1).unwrap();
+    assert_eq!(track.media_type().unwrap(), MediaType::AC3);
+    assert_eq!(track.box_type().unwrap(), BoxType::Ac3Box.into());
+    assert_eq!(track.timescale(), 48000);
+    assert_eq!(track.channel_config().unwrap(), ChannelConfig::FiveOne);
+    assert_eq!(track.ac3_config().unwrap(), ac3_conf);
+    assert_eq!(track.codec_string().unwrap(), "ac-3");
+}
+
+#[test]
+fn test_box_offsets() {
+    let mp4 = get_reader("tests/samples/minimal.mp4");
+    assert_eq!(
+        mp4.box_offsets(),
+        &[
+            (BoxType::FtypBox, 0, 32),
+            (BoxType::MoovBox, 32, 1273),
+            (BoxType::FreeBox, 1305, 8),
+            (BoxType::MdatBox, 1313, 1278),
+        ]
+    );
+
+    // The offsets address the boxes themselves.
+    let (_, offset, size) = mp4.box_offsets()[1];
+    let mut f = File::open("tests/samples/minimal.mp4").unwrap();
+    f.seek(SeekFrom::Start(offset)).unwrap();
+    let header = BoxHeader::read(&mut f).unwrap();
+    assert_eq!(header.name, BoxType::MoovBox);
+    assert_eq!(header.size, size);
+}
+
+#[test]
+fn test_read_eac3_track() {
+    let config = Mp4Config {
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        ..Default::default()
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+
+    let eac3 = Eac3Box {
+        data_reference_index: 1,
+        dec3: Dec3Box {
+            data_rate: 768,
+            substreams: vec![Ec3Substream {
+                fscod: 0,
+                bsid: 16,
+                asvc: false,
+                bsmod: 0,
+                acmod: 7,
+                lfeon: true,
+                num_dep_sub: 1,
+                chan_loc: 0x002,
+            }],
+        },
+        ..Default::default()
+    };
+    let mut sample_entry = Vec::new();
+    eac3.write_box(&mut sample_entry).unwrap();
+    writer
+        .add_track(&TrackConfig::from(MediaConfig::RawSampleEntryConfig(
+            RawSampleEntryConfig {
+                track_type: TrackType::Audio,
+                timescale: 48000,
+                width: 0,
+                height: 0,
+                sample_entry,
+            },
+        )))
+        .unwrap();
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+
+    let track = mp4.tracks().get(&1).unwrap();
+    assert_eq!(track.media_type().unwrap(), MediaType::EAC3);
+    assert_eq!(track.box_type().unwrap(), BoxType::Eac3Box.into());
+    assert_eq!(track.trak.mdia.minf.stbl.stsd.eac3, Some(eac3));
+    assert_eq!(track.codec_string().unwrap(), "ec-3");
+}
+
+#[test]
+fn test_write_display_size() {
+    let config = Mp4Config {
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        ..Default::default()
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+
+    // 1440x1080 coded, displayed at 1920x1080.
+    let mut track_conf = TrackConfig::from(AvcConfig {
+        width: 1440,
+        height: 1080,
+        seq_param_set: vec![0x67, 0x64, 0x00, 0x28],
+        pic_param_set: vec![0x68, 0xEB, 0xE3, 0xCB],
+    });
+    track_conf.display_size = Some((1920, 1080));
+    writer.add_track(&track_conf).unwrap();
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+
+    let track = mp4.tracks().get(&1).unwrap();
+    assert_eq!(track.trak.tkhd.width.value(), 1920);
+    assert_eq!(track.trak.tkhd.height.value(), 1080);
+    let avc1 = track.trak.mdia.minf.stbl.stsd.avc1.as_ref().unwrap();
+    assert_eq!((avc1.width, avc1.height), (1440, 1080));
+    assert_eq!((track.width(), track.height()), (1440, 1080));
+}
+
+#[test]
+fn test_is_streamable() {
+    let faststart = get_reader("tests/samples/minimal.mp4");
+    assert!(faststart.is_streamable());
+    assert!(faststart.validate().is_empty());
+
+    // The writer appends the moov after the mdat.
+    let config = Mp4Config {
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        ..Default::default()
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    writer
+        .add_track(&TrackConfig::from(MediaConfig::AacConfig(
+            AacConfig::default(),
+        )))
+        .unwrap();
+    writer
+        .write_sample(
+            1,
+            &Mp4Sample {
+                start_time: 0,
+                duration: 1024,
+                rendering_offset: 0,
+                is_sync: true,
+                bytes: Bytes::from_static(&[0x21, 0x10, 0x04]),
+            },
+        )
+        .unwrap();
+    writer.write_end().unwrap();
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+
+    assert!(!mp4.is_streamable());
+    let &(_, mdat, _) = mp4
+        .box_offsets()
+        .iter()
+        .find(|b| b.0 == BoxType::MdatBox)
+        .unwrap();
+    let &(_, moov, _) = mp4
+        .box_offsets()
+        .iter()
+        .find(|b| b.0 == BoxType::MoovBox)
+        .unwrap();
+    assert!(mdat < moov);
+    assert_eq!(mp4.validate(), vec![Warning::MoovAfterMdat(moov, mdat)]);
+}
+
+#[test]
+fn test_sample_to_chunk_map() {
+    let config = Mp4Config {
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        ..Default::default()
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    let mut track_config = TrackConfig::from(AacConfig::default());
+    track_config.timescale = 48000;
+    writer.add_track(&track_config).unwrap();
+    for i in 1..=12u8 {
+        let sample = Mp4Sample {
+            start_time: (i as u64 - 1) * 1024,
+            duration: 1024,
+            rendering_offset: 0,
+            is_sync: true,
+            bytes: Bytes::from(vec![i; i as usize]),
+        };
+        writer.write_sample(1, &sample).unwrap();
+        if i % 5 == 0 {
+            writer.flush_chunk(1).unwrap();
+        }
+    }
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    let track = mp4.tracks().get(&1).unwrap();
+    let stbl = &track.trak.mdia.minf.stbl;
+    let stco = &stbl.stco.as_ref().unwrap().entries;
+
+    let map = track.sample_to_chunk_map().unwrap();
+    assert_eq!(map.len(), 12);
+
+    // Expand the stsc runs by hand: each entry applies up to the next one's
+    // first_chunk, and the last one up to the final chunk.
+    let mut expected = Vec::new();
+    for (i, entry) in stbl.stsc.entries.iter().enumerate() {
+        let next_chunk = stbl
+            .stsc
+            .entries
+            .get(i + 1)
+            .map_or(stco.len() as u32 + 1, |next| next.first_chunk);
+        for chunk_id in entry.first_chunk..next_chunk {
+            for _ in 0..entry.samples_per_chunk {
+                expected.push((chunk_id, stco[chunk_id as usize - 1] as u64));
+            }
+        }
+    }
+    expected.truncate(12);
+    assert_eq!(map, expected);
+
+    // Chunks hold samples 1-5, 6-10 and 11-12.
+    let chunk_ids: Vec<u32> = map.iter().map(|&(chunk_id, _)| chunk_id).collect();
+    assert_eq!(chunk_ids, [1, 1, 1, 1, 1, 2, 2, 2, 2, 2, 3, 3]);
+    for (i, &(_, chunk_offset)) in map.iter().enumerate() {
+        let sample_id = i as u32 + 1;
+        if i == 0 || map[i - 1].0 != map[i].0 {
+            assert_eq!(track.sample_offset(sample_id).unwrap(), chunk_offset);
+        }
+    }
+}
+
+#[test]
+fn test_read_avc3_track() {
+    let mut data = fs::read("tests/samples/minimal.mp4").unwrap();
+    let mut moov = get_reader("tests/samples/minimal.mp4").moov;
+    let avc1 = moov.traks[0].mdia.minf.stbl.stsd.avc1.as_mut().unwrap();
+    assert_eq!(avc1.get_type(), BoxType::Avc1Box);
+    avc1.in_band_parameter_sets = true;
+
+    // Only the sample entry type changes, so the moov keeps its size.
+    let mut buf = Vec::new();
+    moov.write_box(&mut buf).unwrap();
+    data.splice(32..32 + buf.len(), buf);
+
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    let track = mp4.tracks().get(&1).unwrap();
+    assert_eq!(track.box_type().unwrap(), str::parse("avc3").unwrap());
+    assert_eq!(track.media_type().unwrap(), MediaType::H264);
+    assert_eq!(track.width(), 320);
+    assert!(!track.sequence_parameter_set().unwrap().is_empty());
+
+    let mut buf = Vec::new();
+    mp4.moov.write_box(&mut buf).unwrap();
+    assert!(buf.windows(4).any(|w| w == b"avc3"));
+    assert!(!buf.windows(4).any(|w| w == b"avc1"));
+}
+
+#[test]
+fn test_write_free_after_ftyp() {
+    let config = Mp4Config {
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        free_size: 1024,
+        ..Default::default()
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    writer
+        .add_track(&TrackConfig::from(AacConfig::default()))
+        .unwrap();
+    writer
+        .write_sample(
+            1,
+            &Mp4Sample {
+                start_time: 0,
+                duration: 1024,
+                rendering_offset: 0,
+                is_sync: true,
+                bytes: Bytes::from_static(&[0x21, 0x10, 0x04]),
+            },
+        )
+        .unwrap();
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    let boxes: Vec<BoxType> = mp4.box_offsets().iter().map(|&(name, _, _)| name).collect();
+    assert_eq!(
+        boxes,
+        [
+            BoxType::FtypBox,
+            BoxType::FreeBox,
+            BoxType::WideBox,
+            BoxType::MdatBox,
+            BoxType::MoovBox
+        ]
+    );
+    assert_eq!(mp4.box_offsets()[1].2, 1024);
+    let sample = mp4.read_sample(1, 1).unwrap().unwrap();
+    assert_eq!(sample.bytes, Bytes::from_static(&[0x21, 0x10, 0x04]));
+
+    let config = Mp4Config {
+        free_size: 4,
+        ..config
+    };
+    assert!(Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).is_err());
+}
+
+#[test]
+fn test_colour_info() {
+    let config = Mp4Config {
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        ..Default::default()
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    writer
+        .add_track(&TrackConfig::from(MediaConfig::Vp9Config(Vp9Config {
+            width: 1920,
+            height: 1080,
+        })))
+        .unwrap();
+    writer.write_end().unwrap();
+
+    let mut data = writer.into_writer().into_inner();
+    let mp4 = Mp4Reader::read_header(Cursor::new(data.clone()), data.len() as u64).unwrap();
+    assert_eq!(mp4.tracks().get(&1).unwrap().colour_info(), None);
+
+    // The writer puts the moov last, so it can be replaced in place.
+    let colr = ColrBox {
+        colour_primaries: 9,
+        transfer_characteristics: 16,
+        matrix_coefficients: 9,
+        full_range_flag: true,
+        ..Default::default()
+    };
+    let mut moov = mp4.moov.clone();
+    moov.traks[0]
+        .mdia
+        .minf
+        .stbl
+        .stsd
+        .vp09
+        .as_mut()
+        .unwrap()
+        .colr = Some(colr.clone());
+    let &(_, moov_offset, _) = mp4.box_offsets().last().unwrap();
+    data.truncate(moov_offset as usize);
+    moov.write_box(&mut data).unwrap();
+
+    let mp4 = Mp4Reader::read_header(Cursor::new(data.clone()), data.len() as u64).unwrap();
+    let track = mp4.tracks().get(&1).unwrap();
+    assert_eq!(track.colour_info(), Some(&colr));
+    assert_eq!(track.width(), 1920);
+}
+
+#[test]
+fn test_stsc_past_last_chunk() {
+    let config = Mp4Config {
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        ..Default::default()
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    let mut track_config = TrackConfig::from(AacConfig::default());
+    track_config.timescale = 48000;
+    writer.add_track(&track_config).unwrap();
+    for i in 1..=6u8 {
+        let sample = Mp4Sample {
+            start_time: (i as u64 - 1) * 1024,
+            duration: 1024,
+            rendering_offset: 0,
+            is_sync: true,
+            bytes: Bytes::from(vec![i; 4]),
+        };
+        writer.write_sample(1, &sample).unwrap();
+        if i % 2 == 0 {
+            writer.flush_chunk(1).unwrap();
+        }
+    }
+    writer.write_end().unwrap();
+
+    // Drop the last chunk from stco, as a truncated file might, while stsc
+    // still describes samples 5 and 6 as being in chunk 3.
+    let mut data = writer.into_writer().into_inner();
+    let mp4 = Mp4Reader::read_header(Cursor::new(data.clone()), data.len() as u64).unwrap();
+    let mut moov = mp4.moov.clone();
+    let stbl = &mut moov.traks[0].mdia.minf.stbl;
+    assert_eq!(stbl.stsc.entries.len(), 1);
+    assert_eq!(stbl.stsc.entries[0].samples_per_chunk, 2);
+    stbl.stco.as_mut().unwrap().entries.pop();
+    let &(_, moov_offset, _) = mp4.box_offsets().last().unwrap();
+    data.truncate(moov_offset as usize);
+    moov.write_box(&mut data).unwrap();
+
+    let size = data.len() as u64;
+    let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    for sample_id in 1..=4 {
+        let sample = mp4.read_sample(1, sample_id).unwrap().unwrap();
+        assert_eq!(sample.bytes, Bytes::from(vec![sample_id as u8; 4]));
+    }
+    for sample_id in 5..=6 {
+        assert!(matches!(
+            mp4.read_sample(1, sample_id),
+            Err(Error::ChunkOutOfRange(1, id, 3, 2)) if id == sample_id
+        ));
+    }
+}
+
+#[test]
+fn test_pixel_aspect_ratio() {
+    let write = |track_conf: &TrackConfig| {
+        let config = Mp4Config {
+            compatible_brands: vec![str::parse("isom").unwrap()],
+            ..Default::default()
+        };
+        let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+        writer.add_track(track_conf).unwrap();
+        writer.write_end().unwrap();
+
+        let data = writer.into_writer().into_inner();
+        let size = data.len() as u64;
+        Mp4Reader::read_header(Cursor::new(data), size).unwrap()
+    };
+
+    let mut track_conf = TrackConfig::from(Vp9Config {
+        width: 1920,
+        height: 1080,
+    });
+    track_conf.pixel_aspect_ratio = Some((4, 3));
+    let src = write(&track_conf);
+    let src_track = src.tracks().get(&1).unwrap();
+    assert_eq!(src_track.pixel_aspect_ratio(), Some((4, 3)));
+    assert_eq!(src_track.display_dimensions_corrected(), (2560, 1080));
+
+    // Remuxing carries the pasp over, as mp4copy does.
+    let mut remux_conf = TrackConfig::from(Vp9Config {
+        width: src_track.width(),
+        height: src_track.height(),
+    });
+    remux_conf.pixel_aspect_ratio = src_track.pixel_aspect_ratio();
+    let dst = write(&remux_conf);
+    let dst_track = dst.tracks().get(&1).unwrap();
+    assert_eq!(
+        dst_track.trak.mdia.minf.stbl.stsd.vp09,
+        src_track.trak.mdia.minf.stbl.stsd.vp09
+    );
+
+    let mp4 = get_reader("tests/samples/minimal.mp4");
+    let track = mp4.tracks().get(&1).unwrap();
+    assert_eq!(track.pixel_aspect_ratio(), None);
+    assert_eq!(track.display_dimensions_corrected(), (320, 240));
+}
+
+#[test]
+fn test_codec_string() {
+    // AVC High profile, level 1.3, and AAC-LC.
+    let mp4 = get_reader("tests/samples/minimal.mp4");
+    assert_eq!(mp4.tracks()[&1].codec_string().unwrap(), "avc1.64000d");
+    assert_eq!(mp4.tracks()[&2].codec_string().unwrap(), "mp4a.40.2");
+
+    let config = Mp4Config {
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        ..Default::default()
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    writer
+        .add_track(&TrackConfig::from(HevcConfig {
+            width: 3840,
+            height: 2160,
+            hdr: None,
+            ..Default::default()
+        }))
+        .unwrap();
+    writer.write_end().unwrap();
+
+    // HEVC Main 10, main tier, level 4.1, progressive source.
+    let mut data = writer.into_writer().into_inner();
+    let mp4 = Mp4Reader::read_header(Cursor::new(data.clone()), data.len() as u64).unwrap();
+    let mut moov = mp4.moov.clone();
+    let hvcc = &mut moov.traks[0]
+        .mdia
+        .minf
+        .stbl
+        .stsd
+        .hev1
+        .as_mut()
+        .unwrap()
+        .hvcc;
+    hvcc.general_profile_idc = 2;
+    hvcc.general_profile_compatibility_flags = 0x2000_0000;
+    hvcc.general_level_idc = 123;
+    hvcc.general_constraint_indicator_flag = 0xB000_0000_0000;
+    let &(_, moov_offset, _) = mp4.box_offsets().last().unwrap();
+    data.truncate(moov_offset as usize);
+    moov.write_box(&mut data).unwrap();
+
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    assert_eq!(mp4.tracks()[&1].codec_string().unwrap(), "hev1.2.4.L123.B0");
+
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    writer
+        .add_track(&TrackConfig::from(Vp9Config {
+            width: 320,
+            height: 240,
+        }))
+        .unwrap();
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    assert_eq!(mp4.tracks()[&1].codec_string().unwrap(), "vp09.00.31.08");
+}
+
+#[test]
+fn test_first_and_last_sample_time() {
+    let mp4 = get_reader("tests/samples/minimal.mp4");
+    let timescale = mp4.moov.mvhd.timescale;
+    let audio = mp4.tracks().get(&2).unwrap();
+    // The edit list skips 1024 ticks of encoder priming.
+    assert_eq!(audio.presentation_offset(timescale), -1024);
+    assert_eq!(audio.first_sample_time(timescale).unwrap(), 0);
+    assert_eq!(audio.last_sample_time(timescale).unwrap(), 1024);
+
+    // With B-frames the last sample in decode order isn't the last presented.
+    let config = Mp4Config {
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        ..Default::default()
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    writer
+        .add_track(&TrackConfig::from(AvcConfig {
+            width: 320,
+            height: 240,
+            seq_param_set: vec![0x67, 0x64, 0x00, 0x0D],
+            pic_param_set: vec![0x68, 0xEB, 0xE3, 0xCB],
+        }))
+        .unwrap();
+    // Decode order I P B with presentation order I B P.
+    for (i, rendering_offset) in [1000, 2000, 0].iter().enumerate() {
+        let sample = Mp4Sample {
+            start_time: i as u64 * 1000,
+            duration: 1000,
+            rendering_offset: *rendering_offset,
+            is_sync: i == 0,
+            bytes: Bytes::from_static(&[0, 0, 0, 1, 0x65]),
+        };
+        writer.write_sample(1, &sample).unwrap();
+    }
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    let video = mp4.tracks().get(&1).unwrap();
+    assert_eq!(video.first_sample_time(1000).unwrap(), 1000);
+    assert_eq!(video.last_sample_time(1000).unwrap(), 3000);
+}
+
+#[test]
+fn test_write_fragment_init_mvex() {
+    let config = Mp4Config {
+        major_brand: str::parse("iso6").unwrap(),
+        minor_version: 0,
+        compatible_brands: vec![str::parse("iso6").unwrap()],
+        ..Default::default()
+    };
+    let fragment_config = FragmentConfig {
+        track_id: 3,
+        fragment_duration: Some(5000),
+        default_sample_duration: 1024,
+        default_sample_size: 10,
+        default_sample_flags: 0x0101_0000,
+        index_segments: false,
+    };
+    let mut writer = Mp4FragmentWriter::write_init_with(
+        Cursor::new(Vec::<u8>::new()),
+        &config,
+        &TrackConfig::from(AacConfig::default()),
+        &fragment_config,
+    )
+    .unwrap();
+    writer.start_segment().unwrap();
+    writer
+        .write_sample(&Mp4Sample {
+            start_time: 0,
+            duration: 1024,
+            rendering_offset: 0,
+            is_sync: true,
+            bytes: Bytes::from(vec![7; 10]),
+        })
+        .unwrap();
+    writer.end_segment().unwrap();
+    let data = writer.into_writer().into_inner();
+
+    let size = data.len() as u64;
+    let mut mp4 = Mp4Reader::read_header(Cursor::new(data.clone()), size).unwrap();
+    let track = mp4.tracks().get(&3).unwrap();
+    assert_eq!(track.default_sample_duration, 1024);
+    assert_eq!(track.default_sample_flags, 0x0101_0000);
+    let sample = mp4.read_sample(3, 1).unwrap().unwrap();
+    assert_eq!(sample.bytes, Bytes::from(vec![7; 10]));
+
+    // The init segment on its own.
+    let init_size = mp4.ftyp.box_size() + mp4.moov.box_size();
+    let init = data[..init_size as usize].to_vec();
+    let mp4 = Mp4Reader::read_header(Cursor::new(init), init_size).unwrap();
+    let mvex = mp4.moov.mvex.as_ref().unwrap();
+    assert_eq!(
+        mvex.trexs,
+        vec![TrexBox {
+            version: 0,
+            flags: 0,
+            track_id: 3,
+            default_sample_description_index: 1,
+            default_sample_duration: 1024,
+            default_sample_size: 10,
+            default_sample_flags: 0x0101_0000,
+        }]
+    );
+    assert_eq!(mvex.mehd.as_ref().unwrap().fragment_duration, 5000);
+    assert_eq!(mp4.moov.mvhd.next_track_id, 4);
+    assert!(mp4.tracks().contains_key(&3));
+
+    let fragment_config = FragmentConfig {
+        track_id: 0,
+        ..Default::default()
+    };
+    assert!(Mp4FragmentWriter::write_init_with(
+        Cursor::new(Vec::<u8>::new()),
+        &config,
+        &TrackConfig::from(AacConfig::default()),
+        &fragment_config,
+    )
+    .is_err());
+}
+
+#[test]
+fn test_samples_iterator() {
+    let mut mp4 = get_reader("tests/samples/minimal.mp4");
+    let mut expected = Vec::new();
+    for sample_id in 1..=mp4.sample_count(2).unwrap() {
+        expected.push(mp4.read_sample(2, sample_id).unwrap().unwrap());
+    }
+
+    let samples: Vec<Mp4Sample> = mp4.samples(2).unwrap().collect::<Result<_, _>>().unwrap();
+    assert_eq!(samples.len(), 3);
+    assert_eq!(samples, expected);
+
+    let mut samples = mp4.samples(1).unwrap();
+    assert!(samples.next().unwrap().is_ok());
+    assert!(samples.next().is_none());
+    assert!(samples.next().is_none());
+
+    assert!(matches!(mp4.samples(3), Err(Error::TrakNotFound(3))));
+}
+
+#[test]
+fn test_stsd_trailing_padding() {
+    let mp4 = get_reader("tests/samples/minimal.mp4");
+    let stsd = &mp4.tracks()[&1].trak.mdia.minf.stbl.stsd;
+    let mut data = Vec::new();
+    stsd.write_box(&mut data).unwrap();
+
+    // Zero padding, or a header too small to be an entry, ends the entries.
+    for tail in [&[0u8; 12][..], &[0, 0, 0, 4, 0, 0, 0, 0]] {
+        let mut padded = [&data[..], tail].concat();
+        let size = padded.len() as u32;
+        padded[..4].copy_from_slice(&size.to_be_bytes());
+
+        let mut reader = Cursor::new(padded);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        let read = StsdBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(read.entry_count, 1);
+        assert_eq!(read.avc1, stsd.avc1);
+    }
+}
+
+#[test]
+fn test_stsd_sample_entry_past_end() {
+    // An unknown sample entry claiming more bytes than the stsd has left.
+    let mut data = Vec::new();
+    data.extend_from_slice(&40u32.to_be_bytes());
+    data.extend_from_slice(b"stsd");
+    data.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1]);
+    data.extend_from_slice(&40u32.to_be_bytes());
+    data.extend_from_slice(b"xyz1");
+    data.resize(40, 0);
+
+    let mut reader = Cursor::new(data);
+    let header = BoxHeader::read(&mut reader).unwrap();
+    assert!(matches!(
+        StsdBox::read_box(&mut reader, header.size),
+        Err(Error::InvalidData(
+            "stsd sample entry extends past the end of the stsd box"
+        ))
+    ));
+}
+
+#[test]
+fn test_stsd_entry_count_mismatch() {
+    let mut data = fs::read("tests/samples/minimal.mp4").unwrap();
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data.clone()), size).unwrap();
+    for track in mp4.tracks().values() {
+        assert_eq!(track.trak.mdia.minf.stbl.stsd.entry_count, 1);
+    }
+
+    assert_eq!(mp4.sample_entry_size(1).unwrap(), 1);
+    assert!(matches!(
+        mp4.sample_entry_size(3),
+        Err(Error::TrakNotFound(3))
+    ));
+
+    // entry_count follows the stsd header and its version and flags.
+    let stsd = data.windows(4).position(|w| w == b"stsd").unwrap();
+    data[stsd + 8..stsd + 12].copy_from_slice(&2u32.to_be_bytes());
+    assert!(matches!(
+        Mp4Reader::read_header(Cursor::new(data), size),
+        Err(Error::InvalidData(
+            "stsd entry_count does not match the number of sample entries"
+        ))
+    ));
+}
+
+#[test]
+fn test_sample_id_for_time() {
+    // Three 1024-tick AAC frames at 48 kHz.
+    let mut mp4 = get_reader("tests/samples/minimal.mp4");
+    let audio = mp4.tracks().get(&2).unwrap();
+    let frame = Duration::from_nanos(1024 * 1_000_000_000 / 48000);
+    assert_eq!(audio.sample_id_for_time(Duration::ZERO), Some(1));
+    assert_eq!(audio.sample_id_for_time(frame * 3 / 2), Some(2));
+    assert_eq!(audio.sample_id_for_time(frame * 5 / 2), Some(3));
+    assert_eq!(audio.sample_id_for_time(Duration::from_secs(1)), None);
+
+    let sample = mp4.sample_at_time(2, frame * 3 / 2).unwrap().unwrap();
+    assert_eq!(sample, mp4.read_sample(2, 2).unwrap().unwrap());
+    assert!(mp4
+        .sample_at_time(2, Duration::from_secs(1))
+        .unwrap()
+        .is_none());
+    assert!(mp4.sample_at_time(9, Duration::ZERO).is_err());
+
+    // Decode order I P B I P B with presentation order I B P I B P.
+    let config = Mp4Config {
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        ..Default::default()
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    let mut track_config = TrackConfig::from(AvcConfig {
+        width: 320,
+        height: 240,
+        seq_param_set: vec![0x67, 0x64, 0x00, 0x0D],
+        pic_param_set: vec![0x68, 0xEB, 0xE3, 0xCB],
+    });
+    track_config.timescale = 1000;
+    writer.add_track(&track_config).unwrap();
+    for (i, rendering_offset) in [1000, 2000, 0, 1000, 2000, 0].iter().enumerate() {
+        let sample = Mp4Sample {
+            start_time: i as u64 * 1000,
+            duration: 1000,
+            rendering_offset: *rendering_offset,
+            is_sync: i % 3 == 0,
+            bytes: Bytes::from(vec![i as u8; 4]),
+        };
+        writer.write_sample(1, &sample).unwrap();
+    }
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    let video = mp4.tracks().get(&1).unwrap();
+    let ms = Duration::from_millis;
+    assert_eq!(video.sample_id_for_time(ms(4500)), Some(5));
+    assert_eq!(video.sync_sample_id_for_time(ms(4500)), Some(4));
+    assert_eq!(video.sync_sample_id_for_time(ms(2999)), Some(1));
+    assert_eq!(video.sync_sample_id_for_time(ms(6000)), None);
+
+    assert_eq!(video.sample_id_for_presentation_time(ms(500)), None);
+    assert_eq!(video.sample_id_for_presentation_time(ms(1000)), Some(1));
+    assert_eq!(video.sample_id_for_presentation_time(ms(2000)), Some(3));
+    assert_eq!(video.sample_id_for_presentation_time(ms(3500)), Some(2));
+    assert_eq!(video.sample_id_for_presentation_time(ms(6500)), Some(5));
+
+    // Fragmented tracks are searched through their truns.
+    let mut writer = Mp4FragmentWriter::write_init(
+        Cursor::new(Vec::<u8>::new()),
+        &config,
+        &TrackConfig::from(AacConfig::default()),
+    )
+    .unwrap();
+    for i in 0..4u8 {
+        if i % 2 == 0 {
+            writer.start_segment().unwrap();
+        }
+        writer
+            .write_sample(&Mp4Sample {
+                start_time: 0,
+                duration: 1000,
+                rendering_offset: 0,
+                is_sync: true,
+                bytes: Bytes::from(vec![i; 4]),
+            })
+            .unwrap();
+    }
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    let track = mp4.tracks().get(&1).unwrap();
+    let to_time = |ticks: u64| Duration::from_secs(ticks) / track.timescale();
+    assert_eq!(track.sample_id_for_time(Duration::ZERO), Some(1));
+    assert_eq!(track.sample_id_for_time(to_time(2500)), Some(3));
+    assert_eq!(track.sample_id_for_time(to_time(3999)), Some(4));
+    assert_eq!(track.sample_id_for_time(to_time(4000)), None);
+}
+
+#[test]
+fn test_duration_ticks() {
+    let mp4 = get_reader("tests/samples/minimal.mp4");
+    assert_eq!(mp4.movie_duration_ticks(), mp4.moov.mvhd.duration);
+    assert_eq!(mp4.movie_duration_ticks(), 62);
+    for track in mp4.tracks().values() {
+        assert_eq!(track.media_duration_ticks(), track.trak.mdia.mdhd.duration);
+    }
+    assert_eq!(mp4.tracks()[&2].media_duration_ticks(), 2944);
+}
+
+#[test]
+fn test_sync_samples() {
+    let mp4 = get_reader("tests/samples/minimal.mp4");
+    // Without stss every sample is a sync sample.
+    let video = mp4.tracks().get(&1).unwrap();
+    assert!(video.trak.mdia.minf.stbl.stss.is_none());
+    assert_eq!(
+        video.sync_samples(),
+        (1..=video.sample_count()).collect::<Vec<u32>>()
+    );
+    let audio = mp4.tracks().get(&2).unwrap();
+    assert!(audio.trak.mdia.minf.stbl.stss.is_none());
+    assert_eq!(audio.sync_samples(), vec![1, 2, 3]);
+
+    let config = Mp4Config {
+        major_brand: str::parse("iso6").unwrap(),
+        minor_version: 0,
+        compatible_brands: vec![str::parse("iso6").unwrap()],
+        ..Default::default()
+    };
+    let mut writer = Mp4FragmentWriter::write_init(
+        Cursor::new(Vec::<u8>::new()),
+        &config,
+        &TrackConfig::from(AvcConfig {
+            width: 320,
+            height: 240,
+            seq_param_set: vec![0x67, 0x64, 0x00, 0x0D],
+            pic_param_set: vec![0x68, 0xEB, 0xE3, 0xCB],
+        }),
+    )
+    .unwrap();
+    for i in 0..6u8 {
+        if i % 3 == 0 {
+            writer.start_segment().unwrap();
+        }
+        writer
+            .write_sample(&Mp4Sample {
+                start_time: 0,
+                duration: 40,
+                rendering_offset: 0,
+                is_sync: i % 3 == 0,
+                bytes: Bytes::from(vec![i; 4]),
+            })
+            .unwrap();
+    }
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    assert_eq!(mp4.tracks()[&1].sync_samples(), vec![1, 4]);
+}
+
+#[test]
+fn test_write_vmhd_flags() {
+    let config = Mp4Config {
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        ..Default::default()
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    writer
+        .add_track(&TrackConfig::from(AvcConfig {
+            width: 320,
+            height: 240,
+            seq_param_set: vec![0x67, 0x64, 0x00, 0x0D],
+            pic_param_set: vec![0x68, 0xEB, 0xE3, 0xCB],
+        }))
+        .unwrap();
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    let vmhd = mp4.tracks()[&1].trak.mdia.minf.vmhd.as_ref().unwrap();
+    assert_eq!(vmhd.flags, 1);
+    assert_eq!(vmhd.flags, VmhdBox::FLAG_NO_LEAN_AHEAD);
+}
+
+#[test]
+fn test_guess_mime_type() {
+    let mp4 = get_reader("tests/samples/minimal.mp4");
+    assert_eq!(
+        mp4.guess_mime_type().unwrap(),
+        "video/mp4; codecs=\"avc1.64000d,mp4a.40.2\""
+    );
+}
+
+#[test]
+fn test_read_sample_annexb() {
+    let sps = vec![0x67, 0x64, 0x00, 0x0D];
+    let pps = vec![0x68, 0xEB, 0xE3, 0xCB];
+    let config = Mp4Config {
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        ..Default::default()
+    };
+
+    // Two NAL units in a sync sample, then one in a non-sync sample, with
+    // 1, 2 and 4 byte length prefixes.
+    for length_size in [1usize, 2, 4] {
+        let prefixed = |nals: &[&[u8]]| {
+            let mut bytes = Vec::new();
+            for nal in nals {
+                bytes.extend_from_slice(&(nal.len() as u32).to_be_bytes()[4 - length_size..]);
+                bytes.extend_from_slice(nal);
+            }
+            Bytes::from(bytes)
+        };
+        let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+        writer
+            .add_track(&TrackConfig::from(AvcConfig {
+                width: 320,
+                height: 240,
+                seq_param_set: sps.clone(),
+                pic_param_set: pps.clone(),
+            }))
+            .unwrap();
+        for (nals, is_sync) in [
+            (&[&[0x06, 0x05][..], &[0x65, 0x88, 0x84]][..], true),
+            (&[&[0x41, 0x9A][..]][..], false),
+        ] {
+            writer
+                .write_sample(
+                    1,
+                    &Mp4Sample {
+                        start_time: 0,
+                        duration: 40,
+                        rendering_offset: 0,
+                        is_sync,
+                        bytes: prefixed(nals),
+                    },
+                )
+                .unwrap();
+        }
+        writer.write_end().unwrap();
+
+        let mut data = writer.into_writer().into_inner();
+        let mp4 = Mp4Reader::read_header(Cursor::new(data.clone()), data.len() as u64).unwrap();
+        let mut moov = mp4.moov.clone();
+        let avc1 = moov.traks[0].mdia.minf.stbl.stsd.avc1.as_mut().unwrap();
+        avc1.avcc.length_size_minus_one = length_size as u8 - 1;
+        let &(_, moov_offset, _) = mp4.box_offsets().last().unwrap();
+        data.truncate(moov_offset as usize);
+        moov.write_box(&mut data).unwrap();
+
+        let size = data.len() as u64;
+        let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+        let sample = mp4.read_sample_annexb(1, 1).unwrap().unwrap();
+        assert!(sample.is_sync);
+        assert_eq!(sample.bytes.len(), 4 * 4 + 4 + 4 + 2 + 3);
+        assert_eq!(
+            sample.bytes,
+            [
+                &[0, 0, 0, 1][..],
+                &sps,
+                &[0, 0, 0, 1],
+                &pps,
+                &[0, 0, 0, 1, 0x06, 0x05],
+                &[0, 0, 0, 1, 0x65, 0x88, 0x84],
+            ]
+            .concat()
+        );
+
+        let sample = mp4.read_sample_annexb(1, 2).unwrap().unwrap();
+        assert_eq!(sample.bytes, Bytes::from_static(&[0, 0, 0, 1, 0x41, 0x9A]));
+        assert!(mp4.read_sample_annexb(1, 3).unwrap().is_none());
+    }
+
+    // Annex B doesn't apply to audio.
+    let mut mp4 = get_reader("tests/samples/minimal.mp4");
+    assert!(matches!(
+        mp4.read_sample_annexb(2, 1),
+        Err(Error::InvalidData(_))
+    ));
+}
+
+#[test]
+fn test_write_self_contained_dref() {
+    let config = Mp4Config {
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        ..Default::default()
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    writer
+        .add_track(&TrackConfig::from(AvcConfig {
+            width: 320,
+            height: 240,
+            seq_param_set: vec![0x67, 0x64, 0x00, 0x0D],
+            pic_param_set: vec![0x68, 0xEB, 0xE3, 0xCB],
+        }))
+        .unwrap();
+    writer
+        .add_track(&TrackConfig::from(AacConfig::default()))
+        .unwrap();
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    for track_id in [1, 2] {
+        let track = &mp4.tracks()[&track_id];
+        let dref = &track.trak.mdia.minf.dinf.dref;
+        assert_eq!(dref.entry_count, 1);
+        let url = dref.url.as_ref().unwrap();
+        assert_eq!(url.flags, 0x000001);
+        assert!(url.location.is_empty());
+        assert_eq!(track.data_location(), None);
+    }
+}
+
+#[test]
+fn test_write_dref_entry_count() {
+    let roundtrip = |dinf: &DinfBox| {
+        let mut data = Vec::new();
+        dinf.write_box(&mut data).unwrap();
+        assert_eq!(data.len() as u64, dinf.box_size());
+        let mut reader = Cursor::new(data);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        DinfBox::read_box(&mut reader, header.size).unwrap()
+    };
+
+    let dinf = DinfBox::default();
+    assert_eq!(roundtrip(&dinf), dinf);
+
+    // The count written is that of the entries written.
+    let mut empty = DinfBox::default();
+    empty.dref.url = None;
+    assert_eq!(roundtrip(&empty).dref.entry_count, 0);
+    let mut stale = DinfBox::default();
+    stale.dref.entry_count = 3;
+    assert_eq!(roundtrip(&stale), dinf);
+}
+
+#[test]
+fn test_read_sample_into() {
+    let mut mp4 = get_reader("tests/samples/minimal.mp4");
+    let mut expected = get_reader("tests/samples/minimal.mp4");
+    let mut buf = Vec::new();
+    for track_id in [1, 2] {
+        let sample_count = mp4.sample_count(track_id).unwrap();
+        for sample_id in 1..=sample_count {
+            let info = mp4
+                .read_sample_into(track_id, sample_id, &mut buf)
+                .unwrap()
+                .unwrap();
+            let sample = expected.read_sample(track_id, sample_id).unwrap().unwrap();
+            assert_eq!(
+                info,
+                SampleInfo {
+                    start_time: sample.start_time,
+                    duration: sample.duration,
+                    rendering_offset: sample.rendering_offset,
+                    is_sync: sample.is_sync,
+                }
+            );
+            assert_eq!(buf, sample.bytes);
+        }
+        assert!(mp4
+            .read_sample_into(track_id, sample_count + 1, &mut buf)
+            .unwrap()
+            .is_none());
+    }
+
+    // Samples sliced from a cached chunk are copied into the buffer too.
+    let sample = mp4.read_sample(2, 1).unwrap().unwrap();
+    mp4.read_sample_into(2, 2, &mut buf).unwrap().unwrap();
+    assert_eq!(buf, expected.read_sample(2, 2).unwrap().unwrap().bytes);
+    mp4.read_sample_into(2, 1, &mut buf).unwrap().unwrap();
+    assert_eq!(buf, sample.bytes);
+}
+
+#[test]
+fn test_sample_time_many_stts_entries() {
+    let config = Mp4Config {
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        ..Default::default()
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    writer
+        .add_track(&TrackConfig::from(AacConfig::default()))
+        .unwrap();
+    let durations: Vec<u32> = (0..100).map(|i| 1024 + i % 3).collect();
+    let mut start_times = Vec::new();
+    let mut start_time = 0;
+    for &duration in &durations {
+        start_times.push(start_time);
+        writer
+            .write_sample(
+                1,
+                &Mp4Sample {
+                    start_time,
+                    duration,
+                    rendering_offset: 0,
+                    is_sync: true,
+                    bytes: Bytes::from_static(&[0; 4]),
+                },
+            )
+            .unwrap();
+        start_time += duration as u64;
+    }
+    writer.write_end().unwrap();
+
+    // Empty stts entries don't change the timing.
+    let mut data = writer.into_writer().into_inner();
+    let mp4 = Mp4Reader::read_header(Cursor::new(data.clone()), data.len() as u64).unwrap();
+    let mut moov = mp4.moov.clone();
+    let stts = &mut moov.traks[0].mdia.minf.stbl.stts;
+    assert_eq!(stts.entries.len(), 100);
+    let empty = SttsEntry {
+        sample_count: 0,
+        sample_delta: 7,
+    };
+    stts.entries.insert(50, empty.clone());
+    stts.entries.insert(0, empty.clone());
+    stts.entries.push(empty);
+    let &(_, moov_offset, _) = mp4.box_offsets().last().unwrap();
+    data.truncate(moov_offset as usize);
+    moov.write_box(&mut data).unwrap();
+
+    let size = data.len() as u64;
+    let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    for (i, (&start_time, &duration)) in start_times.iter().zip(&durations).enumerate() {
+        let sample = mp4.read_sample(1, i as u32 + 1).unwrap().unwrap();
+        assert_eq!((sample.start_time, sample.duration), (start_time, duration));
+    }
+    assert!(mp4.read_sample(1, 101).unwrap().is_none());
+}
+
+#[test]
+fn test_parameter_sets() {
+    let config = Mp4Config {
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        ..Default::default()
+    };
+    let sps = vec![0x67, 0x64, 0x00, 0x0D];
+    let pps = vec![0x68, 0xEB, 0xE3, 0xCB];
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    writer
+        .add_track(&TrackConfig::from(AvcConfig {
+            width: 320,
+            height: 240,
+            seq_param_set: sps.clone(),
+            pic_param_set: pps.clone(),
+        }))
+        .unwrap();
+    writer
+        .add_track(&TrackConfig::from(AacConfig::default()))
+        .unwrap();
+    writer.write_end().unwrap();
+
+    let mut data = writer.into_writer().into_inner();
+    let mp4 = Mp4Reader::read_header(Cursor::new(data.clone()), data.len() as u64).unwrap();
+    let track = &mp4.tracks()[&1];
+    assert_eq!(track.sequence_parameter_sets().unwrap(), vec![&sps[..]]);
+    assert_eq!(track.picture_parameter_sets().unwrap(), vec![&pps[..]]);
+
+    let second_pps = vec![0x68, 0xCE, 0x38, 0x80];
+    let mut moov = mp4.moov.clone();
+    let avcc = &mut moov.traks[0]
+        .mdia
+        .minf
+        .stbl
+        .stsd
+        .avc1
+        .as_mut()
+        .unwrap()
+        .avcc;
+    let mut nal = avcc.picture_parameter_sets[0].clone();
+    nal.bytes = second_pps.clone();
+    avcc.picture_parameter_sets.push(nal);
+    let &(_, moov_offset, _) = mp4.box_offsets().last().unwrap();
+    data.truncate(moov_offset as usize);
+    moov.write_box(&mut data).unwrap();
+
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    let track = &mp4.tracks()[&1];
+    assert_eq!(track.sequence_parameter_sets().unwrap(), vec![&sps[..]]);
+    assert_eq!(
+        track.picture_parameter_sets().unwrap(),
+        vec![&pps[..], &second_pps[..]]
+    );
+    assert_eq!(track.picture_parameter_set().unwrap(), &pps[..]);
+
+    assert!(matches!(
+        mp4.tracks()[&2].picture_parameter_sets(),
+        Err(Error::BoxInStblNotFound(2, BoxType::Avc1Box))
+    ));
+}
+
+#[test]
+fn test_composition_offset() {
+    let config = Mp4Config {
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        ..Default::default()
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    writer
+        .add_track(&TrackConfig::from(AvcConfig {
+            width: 320,
+            height: 240,
+            seq_param_set: vec![0x67, 0x64, 0x00, 0x0D],
+            pic_param_set: vec![0x68, 0xEB, 0xE3, 0xCB],
+        }))
+        .unwrap();
+    // Decode order I P B with presentation order I B P.
+    let rendering_offsets = [1000, 2000, 0];
+    for (i, &rendering_offset) in rendering_offsets.iter().enumerate() {
+        let sample = Mp4Sample {
+            start_time: i as u64 * 1000,
+            duration: 1000,
+            rendering_offset,
+            is_sync: i == 0,
+            bytes: Bytes::from_static(&[0, 0, 0, 1, 0x65]),
+        };
+        writer.write_sample(1, &sample).unwrap();
+    }
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    let video = mp4.tracks().get(&1).unwrap();
+    assert!(video.trak.mdia.minf.stbl.ctts.is_some());
+    let offsets: Vec<i32> = (1..=3).map(|id| video.composition_offset(id)).collect();
+    assert_eq!(offsets, rendering_offsets);
+    let times: Vec<u64> = (1..=3)
+        .map(|id| video.presentation_time(id).unwrap())
+        .collect();
+    assert_eq!(times, [1000, 3000, 2000]);
+    assert!(video.presentation_time(4).is_err());
+
+    for sample_id in 1..=3 {
+        let time = mp4.tracks()[&1].presentation_time(sample_id).unwrap();
+        let sample = mp4.read_sample(1, sample_id).unwrap().unwrap();
+        assert_eq!(sample.presentation_time(), time as i64);
+    }
+}
+
+#[test]
+fn test_hevc_parameter_sets() {
+    let config = Mp4Config {
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        ..Default::default()
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    writer
+        .add_track(&TrackConfig::from(HevcConfig {
+            width: 1920,
+            height: 1080,
+            hdr: None,
+            ..Default::default()
+        }))
+        .unwrap();
+    writer
+        .add_track(&TrackConfig::from(AacConfig::default()))
+        .unwrap();
+    writer.write_end().unwrap();
+
+    let mut data = writer.into_writer().into_inner();
+    let mp4 = Mp4Reader::read_header(Cursor::new(data.clone()), data.len() as u64).unwrap();
+    assert!(mp4.tracks()[&1].hevc_vps().unwrap().is_empty());
+
+    let vps = vec![0x40, 0x01, 0x0C, 0x01];
+    let sps = vec![0x42, 0x01, 0x01, 0x01];
+    let pps = [vec![0x44, 0x01, 0xC1, 0x72], vec![0x44, 0x01, 0xC1, 0x73]];
+    let array = |nal_unit_type: u8, nalus: &[&Vec<u8>]| HvcCArray {
+        completeness: true,
+        nal_unit_type,
+        nalus: nalus
+            .iter()
+            .map(|data| HvcCArrayNalu {
+                size: data.len() as u16,
+                data: data.to_vec(),
+            })
+            .collect(),
+    };
+    let mut moov = mp4.moov.clone();
+    let hvcc = &mut moov.traks[0]
+        .mdia
+        .minf
+        .stbl
+        .stsd
+        .hev1
+        .as_mut()
+        .unwrap()
+        .hvcc;
+    hvcc.arrays = vec![
+        array(32, &[&vps]),
+        array(33, &[&sps]),
+        array(34, &[&pps[0], &pps[1]]),
+        // SEI
+        array(39, &[&vec![0x4E, 0x01, 0x05]]),
+    ];
+    let &(_, moov_offset, _) = mp4.box_offsets().last().unwrap();
+    data.truncate(moov_offset as usize);
+    moov.write_box(&mut data).unwrap();
+
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    let track = &mp4.tracks()[&1];
+    assert_eq!(track.hevc_vps().unwrap(), vec![&vps[..]]);
+    assert_eq!(track.hevc_sps().unwrap(), vec![&sps[..]]);
+    assert_eq!(track.hevc_pps().unwrap(), vec![&pps[0][..], &pps[1][..]]);
+
+    assert!(matches!(
+        mp4.tracks()[&2].hevc_sps(),
+        Err(Error::BoxInStblNotFound(2, BoxType::Hev1Box))
+    ));
+}
+
+#[test]
+fn test_write_audio_without_ctts_and_stss() {
+    let config = Mp4Config {
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        ..Default::default()
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    writer
+        .add_track(&TrackConfig::from(AvcConfig {
+            width: 320,
+            height: 240,
+            seq_param_set: vec![0x67, 0x64, 0x00, 0x0D],
+            pic_param_set: vec![0x68, 0xEB, 0xE3, 0xCB],
+        }))
+        .unwrap();
+    writer
+        .add_track(&TrackConfig::from(AacConfig::default()))
+        .unwrap();
+    // Odd sync flags and rendering offsets are ignored for audio only.
+    for i in 0..4u64 {
+        for track_id in [1, 2] {
+            let sample = Mp4Sample {
+                start_time: i * 1000,
+                duration: 1000,
+                rendering_offset: (i % 2) as i32 * 500,
+                is_sync: i % 2 == 0,
+                bytes: Bytes::from_static(&[0, 0, 0, 1, 0x65]),
+            };
+            writer.write_sample(track_id, &sample).unwrap();
+        }
+    }
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    let video = &mp4.tracks()[&1].trak.mdia.minf.stbl;
+    assert!(video.ctts.is_some());
+    assert_eq!(video.stss.as_ref().unwrap().entries, [1, 3]);
+    let audio = &mp4.tracks()[&2].trak.mdia.minf.stbl;
+    assert!(audio.ctts.is_none());
+    assert!(audio.stss.is_none());
+
+    for sample_id in 1..=4 {
+        let sample = mp4.read_sample(2, sample_id).unwrap().unwrap();
+        assert!(sample.is_sync);
+        assert_eq!(sample.rendering_offset, 0);
+    }
+}
+
+#[test]
+fn test_rotation() {
+    let write = |rotation: u16| {
+        let config = Mp4Config {
+            compatible_brands: vec![str::parse("isom").unwrap()],
+            ..Default::default()
+        };
+        let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+        let mut track_config = TrackConfig::from(AvcConfig {
+            width: 1920,
+            height: 1080,
+            seq_param_set: vec![0x67, 0x64, 0x00, 0x0D],
+            pic_param_set: vec![0x68, 0xEB, 0xE3, 0xCB],
+        });
+        track_config.rotation = rotation;
+        writer.add_track(&track_config)?;
+        writer.write_end()?;
+        let data = writer.into_writer().into_inner();
+        let size = data.len() as u64;
+        Mp4Reader::read_header(Cursor::new(data), size)
+    };
+
+    let mp4 = write(0).unwrap();
+    let track = &mp4.tracks()[&1];
+    assert_eq!(track.rotation(), 0);
+    assert_eq!(track.display_dimensions_rotated(), (1920, 1080));
+
+    for rotation in [90, 270] {
+        let mp4 = write(rotation).unwrap();
+        let track = &mp4.tracks()[&1];
+        assert_eq!(track.rotation(), rotation);
+        assert_eq!((track.width(), track.height()), (1920, 1080));
+        assert_eq!(track.display_dimensions_rotated(), (1080, 1920));
+    }
+
+    let mp4 = write(180).unwrap();
+    let track = &mp4.tracks()[&1];
+    assert_eq!(track.rotation(), 180);
+    assert_eq!(track.display_dimensions_rotated(), (1920, 1080));
+
+    assert!(matches!(write(30), Err(Error::InvalidData(_))));
+}
+
+#[test]
+fn test_data_reference_index() {
+    let mp4 = get_reader("tests/samples/minimal.mp4");
+    for track_id in [1, 2] {
+        let track = &mp4.tracks()[&track_id];
+        assert_eq!(track.trak.mdia.minf.dinf.dref.entry_count, 1);
+        for sample_id in 1..=track.sample_count() {
+            assert_eq!(track.data_reference_index(sample_id).unwrap(), 1);
+        }
+        for sample_id in [0, track.sample_count() + 1] {
+            assert!(matches!(
+                track.data_reference_index(sample_id),
+                Err(Error::EntryInStblNotFound(id, BoxType::StszBox, _)) if id == track_id
+            ));
+        }
+    }
+}
+
+#[test]
+fn test_write_fragment_data_offsets() {
+    let config = Mp4Config {
+        major_brand: str::parse("iso6").unwrap(),
+        minor_version: 0,
+        compatible_brands: vec![str::parse("iso6").unwrap()],
+        ..Default::default()
+    };
+    let mut writer = Mp4FragmentWriter::write_init(
+        Cursor::new(Vec::<u8>::new()),
+        &config,
+        &TrackConfig::from(AacConfig::default()),
+    )
+    .unwrap();
+    for segment in 0..2u8 {
+        writer.start_segment().unwrap();
+        for i in 0..3u8 {
+            writer
+                .write_sample(&Mp4Sample {
+                    start_time: 0,
+                    duration: 1024,
+                    rendering_offset: 0,
+                    is_sync: true,
+                    bytes: Bytes::from(vec![segment, i, 0xAA, 0xBB]),
+                })
+                .unwrap();
+        }
+    }
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data.clone()), size).unwrap();
+    let trexs = &mp4.moov.mvex.as_ref().unwrap().trexs;
+    assert_eq!(trexs.len(), 1);
+    assert_eq!(trexs[0].track_id, 1);
+
+    // Each trun's data_offset, relative to its moof, lands on the first byte
+    // of the mdat payload that follows.
+    let mdat_offsets: Vec<u64> = mp4
+        .box_offsets()
+        .iter()
+        .filter(|&&(box_type, _, _)| box_type == BoxType::MdatBox)
+        .map(|&(_, offset, _)| offset)
+        .collect();
+    let track = &mp4.tracks()[&1];
+    assert_eq!(track.moof_offsets.len(), 2);
+    for ((traf, &moof_offset), &mdat_offset) in track
+        .trafs
+        .iter()
+        .zip(&track.moof_offsets)
+        .zip(&mdat_offsets)
+    {
+        assert_ne!(traf.tfhd.flags & TfhdBox::FLAG_DEFAULT_BASE_IS_MOOF, 0);
+        assert_eq!(traf.tfhd.base_data_offset, None);
+        let data_offset = traf.trun.as_ref().unwrap().data_offset.unwrap();
+        assert_eq!(moof_offset + data_offset as u64, mdat_offset + 8);
+    }
+
+    let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    for sample_id in 1..=6u32 {
+        let sample = mp4.read_sample(1, sample_id).unwrap().unwrap();
+        let (segment, i) = ((sample_id - 1) / 3, (sample_id - 1) % 3);
+        assert_eq!(sample.start_time, (sample_id as u64 - 1) * 1024);
+        assert_eq!(
+            sample.bytes,
+            Bytes::from(vec![segment as u8, i as u8, 0xAA, 0xBB])
+        );
+    }
+}
+
+#[test]
+fn test_write_fragmented_tracks() {
+    let config = Mp4Config {
+        major_brand: str::parse("iso6").unwrap(),
+        minor_version: 0,
+        compatible_brands: vec![str::parse("iso6").unwrap()],
+        ..Default::default()
+    };
+    let sample = |duration: u32, is_sync: bool, bytes: Vec<u8>| Mp4Sample {
+        start_time: 0,
+        duration,
+        rendering_offset: 0,
+        is_sync,
+        bytes: Bytes::from(bytes),
+    };
+
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    writer
+        .add_track(&TrackConfig::from(AvcConfig {
+            width: 320,
+            height: 240,
+            seq_param_set: vec![0x67, 0x64, 0x00, 0x0D],
+            pic_param_set: vec![0x68, 0xEB, 0xE3, 0xCB],
+        }))
+        .unwrap();
+    writer
+        .add_track(&TrackConfig::from(AacConfig::default()))
+        .unwrap();
+    assert!(matches!(
+        writer.write_fragment_sample(1, &sample(40, true, vec![0])),
+        Err(Error::InvalidData("fragmented output was not started"))
+    ));
+    writer.write_fragment_start().unwrap();
+    assert!(writer.write_sample(1, &sample(40, true, vec![0])).is_err());
+    assert!(matches!(
+        writer.write_fragment_sample(3, &sample(40, true, vec![0])),
+        Err(Error::TrakNotFound(3))
+    ));
+
+    // Two fragments of two video and three audio samples each.
+    for fragment in 0..2u8 {
+        for i in 0..2u8 {
+            writer
+                .write_fragment_sample(1, &sample(40, i == 0, vec![1, fragment, i]))
+                .unwrap();
+        }
+        for i in 0..3u8 {
+            writer
+                .write_fragment_sample(2, &sample(1024, true, vec![2, fragment, i, 0xAA]))
+                .unwrap();
+        }
+        if fragment == 0 {
+            writer.write_fragment_end().unwrap();
+        }
+    }
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    let trexs = &mp4.moov.mvex.as_ref().unwrap().trexs;
+    assert_eq!(
+        trexs.iter().map(|trex| trex.track_id).collect::<Vec<u32>>(),
+        vec![1, 2]
+    );
+    assert_eq!(mp4.moov.mvhd.next_track_id, 3);
+    assert_eq!(mp4.moofs.len(), 2);
+    for (i, moof) in mp4.moofs.iter().enumerate() {
+        assert_eq!(moof.mfhd.sequence_number, i as u32 + 1);
+        assert_eq!(moof.trafs.len(), 2);
+    }
+
+    for (track_id, count, duration) in [(1, 4, 40), (2, 6, 1024)] {
+        assert_eq!(mp4.sample_count(track_id).unwrap(), count);
+        let per_fragment = count / 2;
+        for sample_id in 1..=count {
+            let sample = mp4.read_sample(track_id, sample_id).unwrap().unwrap();
+            let (fragment, i) = (
+                (sample_id - 1) / per_fragment,
+                (sample_id - 1) % per_fragment,
+            );
+            assert_eq!(sample.start_time, (sample_id as u64 - 1) * duration);
+            assert_eq!(
+                &sample.bytes[..3],
+                &[track_id as u8, fragment as u8, i as u8]
+            );
+        }
+    }
+
+    // Samples can't be written ahead of the init segment.
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    writer
+        .add_track(&TrackConfig::from(AacConfig::default()))
+        .unwrap();
+    writer
+        .write_sample(1, &sample(1024, true, vec![0]))
+        .unwrap();
+    assert!(matches!(
+        writer.write_fragment_start(),
+        Err(Error::InvalidData(
+            "samples were written before fragmented output was started"
+        ))
+    ));
+}
+
+#[test]
+fn test_dolby_vision_av1() {
+    let mut header = Mp4Header::new_empty(&Mp4Config::default());
+    let track_id = header
+        .add_track(&TrackConfig::from(Av1Config {
+            width: 1920,
+            height: 1080,
+            config_obus: Vec::new(),
+        }))
+        .unwrap();
+
+    // Profile 10 is normally stored as dvvC; the box type read is kept.
+    let dvcc = DvccBox {
+        dvvc: false,
+        dv_version_major: 1,
+        dv_version_minor: 0,
+        dv_profile: 10,
+        dv_level: 9,
+        rpu_present: true,
+        el_present: false,
+        bl_present: true,
+        dv_bl_signal_compatibility_id: 0,
+    };
+    let stsd = &mut header.trak_mut(track_id).unwrap().mdia.minf.stbl.stsd;
+    let av01 = stsd.av01.as_mut().unwrap();
+    av01.dolby_vision_only = true;
+    av01.dvcc = Some(dvcc.clone());
+
+    let mut data = Vec::new();
+    let size = header.write(&mut data).unwrap();
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    let track = mp4.tracks().get(&track_id).unwrap();
+    assert_eq!(track.media_type().unwrap(), MediaType::AV1);
+    assert_eq!(track.box_type().unwrap(), BoxType::Dav1Box.into());
+    assert_eq!(track.dolby_vision_info(), Some(&dvcc));
+
+    let mut stsd = Vec::new();
+    track.trak.mdia.minf.stbl.stsd.write_box(&mut stsd).unwrap();
+    assert!(stsd.windows(4).any(|w| w == b"dvcC"));
+    assert!(!stsd.windows(4).any(|w| w == b"dvvC"));
+}
+
+#[test]
+fn test_hevc_write_read() {
+    let vps = vec![
+        0x40, 0x01, 0x0C, 0x01, 0xFF, 0xFF, 0x01, 0x60, 0x00, 0x00, 0x03, 0x00, 0x90, 0x00, 0x00,
+        0x03, 0x00, 0x00, 0x03, 0x00, 0x78, 0x95, 0x98, 0x09,
+    ];
+    let sps = vec![
+        0x42, 0x01, 0x01, 0x01, 0x60, 0x00, 0x00, 0x03, 0x00, 0x90, 0x00, 0x00, 0x03, 0x00, 0x00,
+        0x03, 0x00, 0x78, 0xA0, 0x03, 0xC0, 0x80, 0x10, 0xE5, 0x96, 0x56, 0x69, 0x24, 0xCA, 0xE0,
+        0x10, 0x00, 0x00, 0x03, 0x00, 0x10, 0x00, 0x00, 0x03, 0x01, 0xE0, 0x80,
+    ];
+    let pps = vec![0x44, 0x01, 0xC1, 0x72, 0xB4, 0x62, 0x40];
+
+    let config = Mp4Config {
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        ..Default::default()
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    writer
+        .add_track(&TrackConfig::from(HevcConfig {
+            width: 1920,
+            height: 1080,
+            hdr: None,
+            video_param_set: vps.clone(),
+            seq_param_set: sps.clone(),
+            pic_param_set: pps.clone(),
+        }))
+        .unwrap();
+    // An IDR slice with a 4-byte length prefix.
+    let slice = [0x26, 0x01, 0xAF, 0x09, 0x40];
+    let mut bytes = (slice.len() as u32).to_be_bytes().to_vec();
+    bytes.extend_from_slice(&slice);
+    writer
+        .write_sample(
+            1,
+            &Mp4Sample {
+                start_time: 0,
+                duration: 40,
+                rendering_offset: 0,
+                is_sync: true,
+                bytes: Bytes::from(bytes),
+            },
+        )
+        .unwrap();
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    let track = &mp4.tracks()[&1];
+    assert_eq!(track.media_type().unwrap(), MediaType::H265);
+    assert_eq!(track.hevc_profile().unwrap(), 1);
+    assert_eq!((track.width(), track.height()), (1920, 1080));
+    assert_eq!(track.codec_string().unwrap(), "hev1.1.6.L120.90");
+    assert_eq!(track.hevc_vps().unwrap(), vec![&vps[..]]);
+    assert_eq!(track.hevc_sps().unwrap(), vec![&sps[..]]);
+    assert_eq!(track.hevc_pps().unwrap(), vec![&pps[..]]);
+
+    let sample = mp4.read_sample_annexb(1, 1).unwrap().unwrap();
+    let start_code = [0, 0, 0, 1];
+    assert_eq!(
+        sample.bytes,
+        [
+            &start_code[..],
+            &vps,
+            &start_code,
+            &sps,
+            &start_code,
+            &pps,
+            &start_code,
+            &slice
+        ]
+        .concat()
+    );
+}
+
+#[test]
+fn test_read_fragment_decode_times() {
+    let mp4 = get_reader("tests/samples/minimal_init.mp4");
+
+    let moof = |tfdt: Option<u64>, sample_durations: Option<Vec<u32>>| {
+        let mut tfhd = TfhdBox {
+            flags: TfhdBox::FLAG_DEFAULT_BASE_IS_MOOF,
+            track_id: 1,
+            ..Default::default()
+        };
+        let mut trun = TrunBox {
+            flags: TrunBox::FLAG_DATA_OFFSET | TrunBox::FLAG_SAMPLE_SIZE,
+            sample_count: 2,
+            sample_sizes: vec![1, 1],
+            data_offset: Some(0),
+            ..Default::default()
+        };
+        match sample_durations {
+            Some(durations) => {
+                trun.flags |= TrunBox::FLAG_SAMPLE_DURATION;
+                trun.sample_durations = durations;
+            }
+            None => {
+                tfhd.flags |= TfhdBox::FLAG_DEFAULT_SAMPLE_DURATION;
+                tfhd.default_sample_duration = Some(512);
+            }
+        }
+        let mut moof = MoofBox::default();
+        moof.trafs.push(TrafBox {
+            tfhd,
+            tfdt: tfdt.map(|base_media_decode_time| TfdtBox {
+                version: 1,
+                flags: 0,
+                base_media_decode_time,
+            }),
+            trun: Some(trun),
+            senc: None,
+        });
+        let data_offset = (moof.box_size() + 8) as i32;
+        moof.trafs[0].trun.as_mut().unwrap().data_offset = Some(data_offset);
+
+        let mut data = Vec::new();
+        moof.write_box(&mut data).unwrap();
+        BoxHeader::new(BoxType::MdatBox, 8 + 2)
+            .write(&mut data)
+            .unwrap();
+        data.extend_from_slice(&[0xAA, 0xBB]);
+        data
+    };
+
+    // Without tfdt, each fragment continues where the previous one ended;
+    // a tfdt resets the decode time.
+    let data = [
+        moof(None, None),
+        moof(None, Some(vec![100, 200])),
+        moof(Some(5000), None),
+    ]
+    .concat();
+    let size = data.len() as u64;
+    let mut fragment = mp4.read_fragment_header(Cursor::new(data), size).unwrap();
+    let times: Vec<(u64, u32)> = (1..=6)
+        .map(|sample_id| {
+            let sample = fragment.read_sample(1, sample_id).unwrap().unwrap();
+            (sample.start_time, sample.duration)
+        })
+        .collect();
+    assert_eq!(
+        times,
+        [
+            (0, 512),
+            (512, 512),
+            (1024, 100),
+            (1124, 200),
+            (5000, 512),
+            (5512, 512)
+        ]
+    );
+}
+
+#[test]
+fn test_write_metadata_handler_name() {
+    let config = Mp4Config {
+        major_brand: str::parse("M4A ").unwrap(),
+        compatible_brands: vec![str::parse("M4A ").unwrap(), str::parse("isom").unwrap()],
+        deterministic: true,
+        ..Default::default()
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    writer
+        .add_track(&TrackConfig::from(AacConfig::default()))
+        .unwrap();
+    writer.set_metadata_handler_name("Metadata Handler");
+    writer
+        .set_cover_art(b"\xff\xd8\xff\xe0".to_vec(), DataType::Image)
+        .unwrap();
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let mp4 = Mp4Reader::read_header(Cursor::new(data.clone()), data.len() as u64).unwrap();
+    let meta = mp4.moov.udta.as_ref().unwrap().meta.as_ref().unwrap();
+    let MetaBox::Mdir { name, ilst } = meta else {
+        panic!("expected an mdir handler, got {:?}", meta);
+    };
+    assert_eq!(name, "Metadata Handler");
+    assert!(ilst.is_some());
+    assert_eq!(mp4.metadata().poster(), Some(&b"\xff\xd8\xff\xe0"[..]));
+
+    // The hdlr box carries the mdir handler type followed by the name.
+    let mut expected = b"mdir".to_vec();
+    expected.extend([0; 12]);
+    expected.extend(b"Metadata Handler\0");
+    assert!(data.windows(expected.len()).any(|w| w == expected));
+}
+
+#[test]
+fn test_read_fragment_per_sample_durations() {
+    let mut mp4 = get_reader("tests/samples/minimal_init.mp4");
+    let trex_duration = 20;
+    mp4.moov.mvex.as_mut().unwrap().trexs[0].default_sample_duration = trex_duration;
+
+    // One moof with two trafs for the same track: the first with per-sample
+    // durations, the second falling back to the trex default.
+    let traf = |sample_durations: Option<Vec<u32>>| {
+        let mut trun = TrunBox {
+            flags: TrunBox::FLAG_DATA_OFFSET | TrunBox::FLAG_SAMPLE_SIZE,
+            sample_count: 3,
+            sample_sizes: vec![1, 1, 1],
+            data_offset: Some(0),
+            ..Default::default()
+        };
+        if let Some(durations) = sample_durations {
+            trun.flags |= TrunBox::FLAG_SAMPLE_DURATION;
+            trun.sample_durations = durations;
+        }
+        TrafBox {
+            tfhd: TfhdBox {
+                flags: TfhdBox::FLAG_DEFAULT_BASE_IS_MOOF,
+                track_id: 1,
+                ..Default::default()
+            },
+            tfdt: None,
+            trun: Some(trun),
+            senc: None,
+        }
+    };
+    let mut moof = MoofBox::default();
+    moof.trafs.push(traf(Some(vec![100, 300, 50])));
+    moof.trafs.push(traf(None));
+    let payload_offset = (moof.box_size() + 8) as i32;
+    moof.trafs[0].trun.as_mut().unwrap().data_offset = Some(payload_offset);
+    moof.trafs[1].trun.as_mut().unwrap().data_offset = Some(payload_offset + 3);
+
+    let mut data = Vec::new();
+    moof.write_box(&mut data).unwrap();
+    BoxHeader::new(BoxType::MdatBox, 8 + 6)
+        .write(&mut data)
+        .unwrap();
+    data.extend_from_slice(&[1, 2, 3, 4, 5, 6]);
+
+    let size = data.len() as u64;
+    let mut fragment = mp4.read_fragment_header(Cursor::new(data), size).unwrap();
+    let times: Vec<(u64, u32, u8)> = (1..=6)
+        .map(|sample_id| {
+            let sample = fragment.read_sample(1, sample_id).unwrap().unwrap();
+            (sample.start_time, sample.duration, sample.bytes[0])
+        })
+        .collect();
+    let d = trex_duration as u64;
+    assert_eq!(
+        times,
+        [
+            (0, 100, 1),
+            (100, 300, 2),
+            (400, 50, 3),
+            (450, trex_duration, 4),
+            (450 + d, trex_duration, 5),
+            (450 + 2 * d, trex_duration, 6),
+        ]
+    );
+}
+
+#[test]
+fn test_read_fragment_sample_time_overflow() {
+    let mp4 = get_reader("tests/samples/minimal_init.mp4");
+
+    // The second sample starts past u64::MAX.
+    let mut moof = MoofBox::default();
+    moof.trafs.push(TrafBox {
+        tfhd: TfhdBox {
+            flags: TfhdBox::FLAG_DEFAULT_BASE_IS_MOOF,
+            track_id: 1,
+            ..Default::default()
+        },
+        tfdt: Some(TfdtBox {
+            version: 1,
+            flags: 0,
+            base_media_decode_time: u64::MAX - 50,
+        }),
+        trun: Some(TrunBox {
+            flags: TrunBox::FLAG_DATA_OFFSET
+                | TrunBox::FLAG_SAMPLE_SIZE
+                | TrunBox::FLAG_SAMPLE_DURATION,
+            sample_count: 2,
+            sample_sizes: vec![1, 1],
+            sample_durations: vec![100, 100],
+            data_offset: Some(0),
+            ..Default::default()
+        }),
+        senc: None,
+    });
+    let data_offset = (moof.box_size() + 8) as i32;
+    moof.trafs[0].trun.as_mut().unwrap().data_offset = Some(data_offset);
+
+    let mut data = Vec::new();
+    moof.write_box(&mut data).unwrap();
+    BoxHeader::new(BoxType::MdatBox, 8 + 2)
+        .write(&mut data)
+        .unwrap();
+    data.extend_from_slice(&[1, 2]);
+
+    let size = data.len() as u64;
+    let mut fragment = mp4.read_fragment_header(Cursor::new(data), size).unwrap();
+    let sample = fragment.read_sample(1, 1).unwrap().unwrap();
+    assert_eq!(sample.start_time, u64::MAX - 50);
+    assert!(matches!(
+        fragment.read_sample(1, 2),
+        Err(Error::InvalidData(_))
+    ));
+    let mut buf = Vec::new();
+    assert!(matches!(
+        fragment.read_sample_into(1, 2, &mut buf),
+        Err(Error::InvalidData(_))
+    ));
+}
+
+#[test]
+fn test_track_sample_offset() {
+    let mp4 = get_reader("tests/samples/minimal.mp4");
+    let track = &mp4.tracks()[&2];
+    // Samples 2 and 3 share the second chunk.
+    assert_eq!(track.sample_offset(1).unwrap(), 1321);
+    assert_eq!(track.sample_offset(2).unwrap(), 2251);
+    assert_eq!(track.sample_offset(3).unwrap(), 2431);
+    assert_eq!(mp4.tracks()[&1].sample_offset(1).unwrap(), 1500);
+    assert!(track.sample_offset(4).is_err());
+
+    let mp4 = get_reader("tests/samples/minimal_init.mp4");
+    let moof = || {
+        let mut moof = MoofBox::default();
+        moof.trafs.push(TrafBox {
+            tfhd: TfhdBox {
+                flags: TfhdBox::FLAG_DEFAULT_BASE_IS_MOOF,
+                track_id: 1,
+                ..Default::default()
+            },
+            tfdt: None,
+            trun: Some(TrunBox {
+                flags: TrunBox::FLAG_DATA_OFFSET | TrunBox::FLAG_SAMPLE_SIZE,
+                sample_count: 2,
+                sample_sizes: vec![2, 3],
+                data_offset: Some(0),
+                ..Default::default()
+            }),
+            senc: None,
+        });
+        let data_offset = (moof.box_size() + 8) as i32;
+        moof.trafs[0].trun.as_mut().unwrap().data_offset = Some(data_offset);
+
+        let mut data = Vec::new();
+        moof.write_box(&mut data).unwrap();
+        BoxHeader::new(BoxType::MdatBox, 8 + 5)
+            .write(&mut data)
+            .unwrap();
+        data.extend_from_slice(&[0; 5]);
+        (data, data_offset as u64)
+    };
+    let (first, payload_offset) = moof();
+    let (second, _) = moof();
+    let second_offset = first.len() as u64;
+    let data = [first, second].concat();
+    let size = data.len() as u64;
+    let fragment = mp4.read_fragment_header(Cursor::new(data), size).unwrap();
+    let track = &fragment.tracks()[&1];
+    assert_eq!(track.sample_offset(1).unwrap(), payload_offset);
+    assert_eq!(track.sample_offset(2).unwrap(), payload_offset + 2);
+    assert_eq!(
+        track.sample_offset(3).unwrap(),
+        second_offset + payload_offset
+    );
+    assert_eq!(
+        track.sample_offset(4).unwrap(),
+        second_offset + payload_offset + 2
+    );
+    assert!(track.sample_offset(5).is_err());
+}
+
+#[test]
+fn test_read_fragment_sample_within_traf() {
+    let mp4 = get_reader("tests/samples/minimal_init.mp4");
+
+    // Two trafs in one moof: the first addressed by a trun data_offset from
+    // the moof, the second by an absolute tfhd base_data_offset alone.
+    let mut moof = MoofBox::default();
+    moof.trafs.push(TrafBox {
+        tfhd: TfhdBox {
+            flags: TfhdBox::FLAG_DEFAULT_BASE_IS_MOOF,
+            track_id: 1,
+            ..Default::default()
+        },
+        tfdt: None,
+        trun: Some(TrunBox {
+            flags: TrunBox::FLAG_DATA_OFFSET | TrunBox::FLAG_SAMPLE_SIZE,
+            sample_count: 2,
+            sample_sizes: vec![2, 3],
+            data_offset: Some(0),
+            ..Default::default()
+        }),
+        senc: None,
+    });
+    moof.trafs.push(TrafBox {
+        tfhd: TfhdBox {
+            flags: TfhdBox::FLAG_BASE_DATA_OFFSET,
+            track_id: 1,
+            base_data_offset: Some(0),
+            ..Default::default()
+        },
+        tfdt: None,
+        trun: Some(TrunBox {
+            flags: TrunBox::FLAG_SAMPLE_SIZE,
+            sample_count: 2,
+            sample_sizes: vec![1, 4],
+            ..Default::default()
+        }),
+        senc: None,
+    });
+    let payload_offset = moof.box_size() + 8;
+    moof.trafs[0].trun.as_mut().unwrap().data_offset = Some(payload_offset as i32);
+    moof.trafs[1].tfhd.base_data_offset = Some(payload_offset + 5);
+
+    let mut data = Vec::new();
+    moof.write_box(&mut data).unwrap();
+    BoxHeader::new(BoxType::MdatBox, 8 + 10)
+        .write(&mut data)
+        .unwrap();
+    data.extend_from_slice(&[1, 1, 2, 2, 2, 3, 4, 4, 4, 4]);
+
+    let size = data.len() as u64;
+    let mut fragment = mp4.read_fragment_header(Cursor::new(data), size).unwrap();
+    let expected: [&[u8]; 4] = [&[1, 1], &[2, 2, 2], &[3], &[4, 4, 4, 4]];
+    for (i, bytes) in expected.iter().enumerate() {
+        let sample = fragment.read_sample(1, i as u32 + 1).unwrap().unwrap();
+        assert_eq!(sample.bytes, Bytes::copy_from_slice(bytes));
+    }
+    let track = &fragment.tracks()[&1];
+    assert_eq!(track.sample_offset(2).unwrap(), payload_offset + 2);
+    assert_eq!(track.sample_offset(4).unwrap(), payload_offset + 6);
+}
+
+#[test]
+fn test_read_fragment_implicit_base_offsets() {
+    let mp4 = get_reader("tests/samples/minimal_init.mp4");
+
+    // Without default-base-is-moof or a base_data_offset, only the first traf
+    // is relative to the moof; later ones follow the previous traf's data.
+    let traf = |data_offset: i32, sample_sizes: Vec<u32>| TrafBox {
+        tfhd: TfhdBox {
+            flags: 0,
+            track_id: 1,
+            ..Default::default()
+        },
+        tfdt: None,
+        trun: Some(TrunBox {
+            flags: TrunBox::FLAG_DATA_OFFSET | TrunBox::FLAG_SAMPLE_SIZE,
+            sample_count: sample_sizes.len() as u32,
+            sample_sizes,
+            data_offset: Some(data_offset),
+            ..Default::default()
+        }),
+        senc: None,
+    };
+    let mut moof = MoofBox::default();
+    moof.trafs.push(traf(0, vec![2, 3]));
+    moof.trafs.push(traf(1, vec![1]));
+    moof.trafs.push(traf(0, vec![4]));
+    let payload_offset = moof.box_size() + 8;
+    moof.trafs[0].trun.as_mut().unwrap().data_offset = Some(payload_offset as i32);
+
+    let mut data = Vec::new();
+    moof.write_box(&mut data).unwrap();
+    BoxHeader::new(BoxType::MdatBox, 8 + 11)
+        .write(&mut data)
+        .unwrap();
+    data.extend_from_slice(&[1, 1, 2, 2, 2, 0, 3, 4, 4, 4, 4]);
+
+    let size = data.len() as u64;
+    let mut fragment = mp4.read_fragment_header(Cursor::new(data), size).unwrap();
+    let expected: [&[u8]; 4] = [&[1, 1], &[2, 2, 2], &[3], &[4, 4, 4, 4]];
+    for (i, bytes) in expected.iter().enumerate() {
+        let sample = fragment.read_sample(1, i as u32 + 1).unwrap().unwrap();
+        assert_eq!(sample.bytes, Bytes::copy_from_slice(bytes));
+    }
+    let track = &fragment.tracks()[&1];
+    let offsets: Vec<u64> = (1..=4).map(|id| track.sample_offset(id).unwrap()).collect();
+    assert_eq!(offsets, [0, 2, 6, 7].map(|offset| payload_offset + offset));
+    assert_eq!(track.sample_offsets().unwrap(), offsets);
+}
+
+#[test]
+fn test_read_empty_sample() {
+    let config = Mp4Config {
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        deterministic: true,
+        ..Default::default()
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    writer.add_track(&TrackConfig::from(TtxtConfig {})).unwrap();
+    let cues = [b"\x00\x02Hi".to_vec(), Vec::new(), b"\x00\x03Bye".to_vec()];
+    for (i, cue) in cues.iter().enumerate() {
+        let sample = Mp4Sample {
+            start_time: i as u64 * 1000,
+            duration: 1000,
+            rendering_offset: 0,
+            is_sync: true,
+            bytes: Bytes::from(cue.clone()),
+        };
+        writer.write_sample(1, &sample).unwrap();
+    }
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    let sample = mp4.read_sample(1, 2).unwrap().unwrap();
+    assert!(sample.bytes.is_empty());
+    assert_eq!(sample.start_time, 1000);
+    assert_eq!(sample.duration, 1000);
+    assert!(mp4.read_sample(1, 4).unwrap().is_none());
+
+    let mut buf = vec![0xFF; 4];
+    assert!(mp4.read_sample_into(1, 2, &mut buf).unwrap().is_some());
+    assert!(buf.is_empty());
+
+    assert_eq!(mp4.read_text_sample(1, 1).unwrap().unwrap(), "Hi");
+    assert_eq!(mp4.read_text_sample(1, 2).unwrap().unwrap(), "");
+    assert_eq!(mp4.read_text_sample(1, 3).unwrap().unwrap(), "Bye");
+}
+
+#[test]
+fn test_write_end_faststart() {
+    let write = |free_size: u32| {
+        let config = Mp4Config {
+            compatible_brands: vec![str::parse("isom").unwrap()],
+            deterministic: true,
+            free_size,
+            ..Default::default()
+        };
+        let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+        writer
+            .add_track(&TrackConfig::from(AacConfig::default()))
+            .unwrap();
+        writer.add_track(&TrackConfig::from(TtxtConfig {})).unwrap();
+        for i in 0..50u8 {
+            let sample = Mp4Sample {
+                start_time: i as u64 * 1024,
+                duration: 1024,
+                rendering_offset: 0,
+                is_sync: true,
+                bytes: Bytes::from(vec![i; 100 + i as usize]),
+            };
+            writer.write_sample(1, &sample).unwrap();
+        }
+        for i in 0..3u8 {
+            let sample = Mp4Sample {
+                start_time: i as u64 * 1000,
+                duration: 1000,
+                rendering_offset: 0,
+                is_sync: true,
+                bytes: Bytes::from(vec![0, 1, b'a' + i]),
+            };
+            writer.write_sample(2, &sample).unwrap();
+        }
+        writer.write_end_faststart().unwrap();
+        writer.into_writer().into_inner()
+    };
+    let check = |data: Vec<u8>| {
+        let size = data.len() as u64;
+        let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+        assert!(mp4.is_streamable());
+        let last = mp4.box_offsets().last().unwrap();
+        assert_eq!(last.0, BoxType::MdatBox);
+        assert_eq!(last.1 + last.2, size);
+        for i in 0..50u8 {
+            let sample = mp4.read_sample(1, i as u32 + 1).unwrap().unwrap();
+            assert_eq!(sample.bytes, Bytes::from(vec![i; 100 + i as usize]));
+        }
+        for i in 0..3u8 {
+            let text = mp4.read_text_sample(2, i as u32 + 1).unwrap().unwrap();
+            assert_eq!(text, ((b'a' + i) as char).to_string());
+        }
+        mp4
+    };
+
+    // Without a free box, the mdat is moved after the moov.
+    let mp4 = check(write(0));
+    let types: Vec<BoxType> = mp4.box_offsets().iter().map(|b| b.0).collect();
+    assert_eq!(
+        types,
+        [
+            BoxType::FtypBox,
+            BoxType::MoovBox,
+            BoxType::WideBox,
+            BoxType::MdatBox
+        ]
+    );
+    let moov_size = mp4.moov.box_size();
+
+    // A large enough free box takes the moov in place, leaving the rest free.
+    let data = write(moov_size as u32 + 100);
+    let mp4 = check(data);
+    let boxes: Vec<(BoxType, u64)> = mp4.box_offsets().iter().map(|b| (b.0, b.2)).collect();
+    assert_eq!(
+        boxes[1..3],
+        [(BoxType::MoovBox, moov_size), (BoxType::FreeBox, 100)]
+    );
+
+    // Too little left over for a free box header: the mdat moves to fit one.
+    let mp4 = check(write(moov_size as u32 + 3));
+    let boxes: Vec<(BoxType, u64)> = mp4.box_offsets().iter().map(|b| (b.0, b.2)).collect();
+    assert_eq!(
+        boxes[1..3],
+        [(BoxType::MoovBox, moov_size), (BoxType::FreeBox, 11)]
+    );
+}
+
+#[test]
+fn test_write_btrt() {
+    let config = Mp4Config {
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        deterministic: true,
+        ..Default::default()
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    let mut track_config = TrackConfig::from(AvcConfig {
+        width: 320,
+        height: 240,
+        seq_param_set: vec![0x67, 0x64, 0x00, 0x0D],
+        pic_param_set: vec![0x68, 0xEB, 0xE3, 0xCB],
+    });
+    track_config.timescale = 30;
+    writer.add_track(&track_config).unwrap();
+    // One second of 100 byte frames, then one of 300 byte frames.
+    for i in 0..60u64 {
+        let sample = Mp4Sample {
+            start_time: i,
+            duration: 1,
+            rendering_offset: 0,
+            is_sync: i % 30 == 0,
+            bytes: Bytes::from(vec![0u8; if i < 30 { 100 } else { 300 }]),
+        };
+        writer.write_sample(1, &sample).unwrap();
+    }
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    let track = mp4.tracks().get(&1).unwrap();
+    let btrt = track
+        .trak
+        .mdia
+        .minf
+        .stbl
+        .stsd
+        .avc1
+        .as_ref()
+        .unwrap()
+        .btrt
+        .as_ref()
+        .unwrap();
+    // 12000 bytes over two seconds, peaking at 9000 bytes in the second.
+    assert_eq!(btrt.avg_bitrate, 48000);
+    assert_eq!(btrt.max_bitrate, 72000);
+    assert_eq!(btrt.buffer_size_db, 300);
+    assert_eq!(track.bitrate(), 48000);
+    assert_eq!(track.max_bitrate(), Some(72000));
+}
+
+/// A file that keeps only small writes, such as box headers and the moov,
+/// and reads everything else back as zeros, to write files larger than
+/// memory.
+#[derive(Default)]
+struct SparseFile {
+    pos: u64,
+    len: u64,
+    writes: Vec<(u64, Vec<u8>)>,
+}
+
+impl std::io::Write for SparseFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if buf.len() <= 1024 * 1024 {
+            self.writes.push((self.pos, buf.to_vec()));
+        }
+        self.pos += buf.len() as u64;
+        self.len = self.len.max(self.pos);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Read for SparseFile {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = (buf.len() as u64).min(self.len.saturating_sub(self.pos)) as usize;
+        let (start, end) = (self.pos, self.pos + len as u64);
+        buf[..len].fill(0);
+        for (offset, data) in &self.writes {
+            let data_end = offset + data.len() as u64;
+            if *offset < end && data_end > start {
+                let from = start.max(*offset);
+                let to = end.min(data_end);
+                buf[(from - start) as usize..(to - start) as usize]
+                    .copy_from_slice(&data[(from - offset) as usize..(to - offset) as usize]);
+            }
+        }
+        self.pos = end;
+        Ok(len)
+    }
+}
+
+impl Seek for SparseFile {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.pos = match pos {
+            SeekFrom::Start(pos) => pos,
+            SeekFrom::End(delta) => self.len.checked_add_signed(delta).unwrap(),
+            SeekFrom::Current(delta) => self.pos.checked_add_signed(delta).unwrap(),
+        };
+        Ok(self.pos)
+    }
+}
+
+#[test]
+fn test_write_co64_past_4gib() {
+    let config = Mp4Config {
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        deterministic: true,
+        ..Default::default()
+    };
+    let mut writer = Mp4Writer::write_start(SparseFile::default(), &config).unwrap();
+    let mut track_config = TrackConfig::from(AvcConfig {
+        width: 320,
+        height: 240,
+        seq_param_set: vec![0x67, 0x64, 0x00, 0x0D],
+        pic_param_set: vec![0x68, 0xEB, 0xE3, 0xCB],
+    });
+    track_config.timescale = 1;
+    writer.add_track(&track_config).unwrap();
+    // A second track whose chunks all stay below 4 GiB.
+    writer
+        .add_track(&TrackConfig::from(AacConfig::default()))
+        .unwrap();
+    writer
+        .write_sample(
+            2,
+            &Mp4Sample {
+                start_time: 0,
+                duration: 1024,
+                rendering_offset: 0,
+                is_sync: true,
+                bytes: Bytes::from_static(&[0x21, 0x10]),
+            },
+        )
+        .unwrap();
+    writer.flush_chunk(2).unwrap();
+
+    // One second, and so one chunk, per 64 MiB sample.
+    let bytes = Bytes::from(vec![0u8; 64 << 20]);
+    for i in 0..66 {
+        let sample = Mp4Sample {
+            start_time: i,
+            duration: 1,
+            rendering_offset: 0,
+            is_sync: true,
+            bytes: bytes.clone(),
+        };
+        writer.write_sample(1, &sample).unwrap();
+    }
+    writer.write_end().unwrap();
+
+    let mut file = writer.into_writer();
+    file.seek(SeekFrom::Start(0)).unwrap();
+    let size = file.len;
+    assert!(size > u32::MAX as u64);
+    let mp4 = Mp4Reader::read_header(file, size).unwrap();
+
+    // The mdat header took over the wide box in front of it.
+    let boxes = mp4.box_offsets();
+    assert_eq!(boxes[1].0, BoxType::MdatBox);
+    assert_eq!(boxes[1].1, boxes[0].2);
+    assert!(boxes[1].2 > u32::MAX as u64);
+    assert_eq!(boxes[2].0, BoxType::MoovBox);
+    assert_eq!(boxes[2].1, boxes[1].1 + boxes[1].2);
+
+    let stbl = &mp4.tracks()[&1].trak.mdia.minf.stbl;
+    assert!(stbl.stco.is_none());
+    let co64 = stbl.co64.as_ref().unwrap();
+    assert_eq!(co64.entries.len(), 66);
+    assert!(*co64.entries.last().unwrap() > u32::MAX as u64);
+    for (i, offsets) in co64.entries.windows(2).enumerate() {
+        assert_eq!(offsets[1] - offsets[0], 64 << 20, "chunk {}", i + 2);
+    }
+
+    let stbl = &mp4.tracks()[&2].trak.mdia.minf.stbl;
+    assert!(stbl.co64.is_none());
+    assert_eq!(stbl.stco.as_ref().unwrap().entries.len(), 1);
+}
+
+#[test]
+fn test_sample_index() {
+    let mut mp4 = get_reader("tests/samples/minimal.mp4");
+    for track_id in [1, 2] {
+        let index = mp4.sample_index(track_id).unwrap();
+        assert_eq!(index.len() as u32, mp4.sample_count(track_id).unwrap());
+        for entry in index {
+            let sample = mp4.read_sample(track_id, entry.id).unwrap().unwrap();
+            assert_eq!(entry.dts, sample.start_time);
+            assert_eq!(
+                entry.pts as i64,
+                sample.start_time as i64 + sample.rendering_offset as i64
+            );
+            assert_eq!(entry.size as usize, sample.bytes.len());
+            assert_eq!(entry.is_sync, sample.is_sync);
+            assert_eq!(entry.offset, mp4.sample_offset(track_id, entry.id).unwrap());
+        }
+    }
+
+    let index = mp4.sample_index(2).unwrap();
+    assert_eq!(
+        index.iter().map(|entry| entry.offset).collect::<Vec<_>>(),
+        [1321, 2251, 2431]
+    );
+    assert!(matches!(mp4.sample_index(3), Err(Error::TrakNotFound(3))));
+}
+
+#[test]
+fn test_writer_checkpoint() {
+    let write = |checkpoints: &[u32], sample_count: u32| {
+        let config = Mp4Config {
+            compatible_brands: vec![str::parse("isom").unwrap()],
+            deterministic: true,
+            ..Default::default()
+        };
+        let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+        writer
+            .add_track(&TrackConfig::from(AacConfig::default()))
+            .unwrap();
+        for i in 0..sample_count {
+            if checkpoints.contains(&i) {
+                writer.checkpoint().unwrap();
+            }
+            let sample = Mp4Sample {
+                start_time: i as u64 * 1024,
+                duration: 1024,
+                rendering_offset: 0,
+                is_sync: true,
+                bytes: Bytes::from(vec![i as u8; 10 + i as usize]),
+            };
+            writer.write_sample(1, &sample).unwrap();
+        }
+        writer
+    };
+    let read = |data: Vec<u8>, sample_count: u32| {
+        let size = data.len() as u64;
+        let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+        assert_eq!(mp4.sample_count(1).unwrap(), sample_count);
+        for i in 0..sample_count {
+            let sample = mp4.read_sample(1, i + 1).unwrap().unwrap();
+            assert_eq!(sample.start_time, i as u64 * 1024);
+            assert_eq!(sample.bytes, Bytes::from(vec![i as u8; 10 + i as usize]));
+        }
+        mp4.box_offsets().iter().map(|b| b.0).collect::<Vec<_>>()
+    };
+
+    // Stopping without write_end leaves the samples up to the checkpoint.
+    let writer = write(&[10], 30);
+    let boxes = read(writer.into_writer().into_inner(), 10);
+    assert_eq!(
+        boxes,
+        [
+            BoxType::FtypBox,
+            BoxType::WideBox,
+            BoxType::MdatBox,
+            BoxType::MoovBox,
+            BoxType::WideBox,
+            BoxType::MdatBox
+        ]
+    );
+
+    // Finishing frees the checkpoint moovs and describes every sample.
+    let mut writer = write(&[10, 20], 30);
+    writer.write_end().unwrap();
+    let boxes = read(writer.into_writer().into_inner(), 30);
+    assert_eq!(
+        boxes,
+        [
+            BoxType::FtypBox,
+            BoxType::WideBox,
+            BoxType::MdatBox,
+            BoxType::FreeBox,
+            BoxType::WideBox,
+            BoxType::MdatBox,
+            BoxType::FreeBox,
+            BoxType::WideBox,
+            BoxType::MdatBox,
+            BoxType::MoovBox
+        ]
+    );
+
+    let mut writer = write(&[10], 30);
+    assert!(matches!(
+        writer.write_end_faststart(),
+        Err(Error::InvalidData(_))
+    ));
+}